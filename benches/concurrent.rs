@@ -0,0 +1,86 @@
+use fm_index::suffix_array::{DiskSampler, NullSampler};
+use fm_index::{BackwardSearchIndex, FMIndex};
+
+use criterion::{criterion_group, criterion_main};
+use criterion::{BenchmarkId, Criterion, Throughput};
+
+mod common;
+
+const THREADS: [usize; 5] = [1, 2, 4, 8, 16];
+
+// Runs `query` on every pattern from each of `threads` threads sharing one index.
+fn run<F: Fn(&str) + Sync>(threads: usize, patterns: &[String], query: F) {
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                for pattern in patterns {
+                    query(pattern);
+                }
+            });
+        }
+    });
+}
+
+// Throughput is counted over all threads, so that it grows linearly with the threads
+// as long as queries do not contend for shared state.
+pub fn bench_count(c: &mut Criterion) {
+    let mut group = c.benchmark_group("concurrent_count");
+    let (text, converter) = common::binary_text_set(50000, 0.5);
+    let patterns = common::binary_patterns(10);
+    let index = FMIndex::new(text, converter, NullSampler::new());
+    for &threads in THREADS.iter() {
+        group.throughput(Throughput::Elements((threads * patterns.len()) as u64));
+        group.bench_with_input(
+            BenchmarkId::new("FMIndex", threads),
+            &threads,
+            |b, &threads| {
+                b.iter(|| {
+                    run(threads, &patterns, |pattern| {
+                        index.search_backward(pattern).count();
+                    })
+                })
+            },
+        );
+    }
+}
+
+// Locating with a disk-backed suffix array locks its cache, which is split into shards
+// to let the threads proceed in parallel.
+pub fn bench_locate_disk(c: &mut Criterion) {
+    let mut group = c.benchmark_group("concurrent_locate_disk");
+    let patterns = common::binary_patterns(10);
+    for &shards in [1, 16].iter() {
+        let path = std::env::temp_dir().join(format!(
+            "fm-index-bench-{}-{}.sa",
+            std::process::id(),
+            shards
+        ));
+        let (text, converter) = common::binary_text_set(50000, 0.5);
+        let index = FMIndex::new(
+            text,
+            converter,
+            DiskSampler::new(&path)
+                .level(2)
+                .cache_blocks(64)
+                .shards(shards),
+        );
+        for &threads in THREADS.iter() {
+            group.throughput(Throughput::Elements((threads * patterns.len()) as u64));
+            group.bench_with_input(
+                BenchmarkId::new(format!("shards={}", shards), threads),
+                &threads,
+                |b, &threads| {
+                    b.iter(|| {
+                        run(threads, &patterns, |pattern| {
+                            index.search_backward(pattern).locate();
+                        })
+                    })
+                },
+            );
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
+
+criterion_group!(benches, bench_count, bench_locate_disk);
+criterion_main!(benches);
@@ -0,0 +1,61 @@
+use std::env;
+use std::fs::{self, File};
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, UInt64Array};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use fm_index::converter::RangeConverter;
+use fm_index::export::{self, MatchBatch};
+use fm_index::suffix_array::SuffixOrderSampler;
+use fm_index::FMIndexMultiPieces;
+use parquet::arrow::ArrowWriter;
+
+fn schema() -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new("pattern_id", DataType::UInt64, false),
+        Field::new("position", DataType::UInt64, false),
+        Field::new("piece_id", DataType::UInt64, false),
+    ]))
+}
+
+// The columns are moved into the arrays without copying.
+fn to_record_batch(schema: Arc<Schema>, batch: MatchBatch) -> RecordBatch {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(UInt64Array::from(batch.pattern_ids)),
+        Arc::new(UInt64Array::from(batch.positions)),
+        Arc::new(UInt64Array::from(batch.piece_ids)),
+    ];
+    RecordBatch::try_new(schema, columns).unwrap()
+}
+
+/// Usage: fm-index-arrow <TEXT> <OUTPUT.parquet> <PATTERN>...
+///
+/// Lines of TEXT are indexed as pieces.
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.len() < 3 {
+        eprintln!("usage: fm-index-arrow <TEXT> <OUTPUT.parquet> <PATTERN>...");
+        std::process::exit(2);
+    }
+    let text = fs::read_to_string(&args[0])
+        .unwrap()
+        .trim_end_matches('\n')
+        .replace('\n', "\0")
+        .into_bytes();
+    let index = FMIndexMultiPieces::new(
+        text,
+        RangeConverter::new(b' ', b'~'),
+        SuffixOrderSampler::new().level(2),
+    );
+
+    let schema = schema();
+    let file = File::create(&args[1]).unwrap();
+    let mut writer = ArrowWriter::try_new(file, schema.clone(), None).unwrap();
+    for batch in export::match_batches(&index, &args[2..], 8192) {
+        writer
+            .write(&to_record_batch(schema.clone(), batch))
+            .unwrap();
+    }
+    writer.close().unwrap();
+}
@@ -0,0 +1,135 @@
+//! A command line tool which indexes files as pieces of a multi-piece index and queries it.
+//!
+//! ```sh
+//! cargo run --example fm-index-cli --features cli -- build corpus.idx src/ README.md
+//! cargo run --example fm-index-cli --features cli -- count corpus.idx "fn main"
+//! cargo run --example fm-index-cli --features cli -- locate corpus.idx "fn main"
+//! cargo run --example fm-index-cli --features cli -- prefix corpus.idx "//!"
+//! ```
+use fm_index::converter::RangeConverter;
+use fm_index::suffix_array::{SuffixOrderSampledArray, SuffixOrderSampler};
+use fm_index::{BackwardSearchIndex, FMIndexMultiPieces, Versioned, VersionedFormat};
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::process;
+
+type Index = FMIndexMultiPieces<u8, RangeConverter<u8>, SuffixOrderSampledArray>;
+
+// The index persisted with the paths of the files, in the order of pieces,
+// tagged by `Versioned` so that a file written by another version of the crate is rejected.
+#[derive(Serialize, Deserialize)]
+struct Corpus {
+    paths: Vec<PathBuf>,
+    index: Index,
+}
+
+// Only the current layout is read, as for the multi-piece index.
+impl VersionedFormat for Corpus {}
+
+const USAGE: &str = "usage:
+    fm-index-cli build <index> <file or directory>...
+    fm-index-cli count <index> <pattern>
+    fm-index-cli locate <index> <pattern>
+    fm-index-cli prefix <index> <pattern>";
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let result = match args.iter().map(|s| s.as_str()).collect::<Vec<_>>()[..] {
+        ["build", index, ref inputs @ ..] if !inputs.is_empty() => build(index, inputs),
+        ["count", index, pattern] => count(index, pattern),
+        ["locate", index, pattern] => locate(index, pattern),
+        ["prefix", index, pattern] => prefix(index, pattern),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+// Collects the files under `path` in sorted order.
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    if path.is_dir() {
+        let mut entries = fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for entry in entries {
+            collect_files(&entry, files)?;
+        }
+    } else {
+        files.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+fn build(index_path: &str, inputs: &[&str]) -> Result<(), Box<dyn Error>> {
+    let mut files = vec![];
+    for input in inputs {
+        collect_files(Path::new(input), &mut files)?;
+    }
+    let mut text = vec![];
+    let mut paths = vec![];
+    for file in files {
+        let content = fs::read(&file)?;
+        // pieces are delimited by zero and must not be empty,
+        // and 0xff, which never occurs in UTF-8, is left for the separator code
+        if content.is_empty() || content.iter().any(|&c| c == 0 || c == 0xff) {
+            eprintln!("skipping {}: empty or binary", file.display());
+            continue;
+        }
+        text.extend_from_slice(&content);
+        text.push(0);
+        paths.push(file);
+    }
+    if paths.is_empty() {
+        return Err("no files to index".into());
+    }
+    let index = FMIndexMultiPieces::new(
+        text,
+        RangeConverter::new(1, 254),
+        SuffixOrderSampler::new().level(4),
+    );
+    eprintln!("indexed {} files in {} bytes", paths.len(), index.size());
+    let writer = BufWriter::new(File::create(index_path)?);
+    serde_cbor::to_writer(writer, &Versioned::new(Corpus { paths, index }))?;
+    Ok(())
+}
+
+fn load(index_path: &str) -> Result<Corpus, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(index_path)?);
+    let corpus: Versioned<Corpus> = serde_cbor::from_reader(reader)?;
+    Ok(corpus.into_inner())
+}
+
+fn count(index_path: &str, pattern: &str) -> Result<(), Box<dyn Error>> {
+    let corpus = load(index_path)?;
+    println!("{}", corpus.index.search_backward(pattern).count());
+    Ok(())
+}
+
+fn locate(index_path: &str, pattern: &str) -> Result<(), Box<dyn Error>> {
+    let corpus = load(index_path)?;
+    let mut positions = corpus.index.search_backward(pattern).locate_in_pieces();
+    positions.sort();
+    for p in positions {
+        let path = &corpus.paths[u64::from(p.piece) as usize];
+        println!("{}:{}", path.display(), p.offset);
+    }
+    Ok(())
+}
+
+fn prefix(index_path: &str, pattern: &str) -> Result<(), Box<dyn Error>> {
+    let corpus = load(index_path)?;
+    for id in corpus.index.pieces_with_prefix(pattern) {
+        println!("{}", corpus.paths[u64::from(id) as usize].display());
+    }
+    Ok(())
+}
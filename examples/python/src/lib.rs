@@ -0,0 +1,132 @@
+use fm_index::converter::RangeConverter;
+use fm_index::suffix_array::{SuffixOrderSampledArray, SuffixOrderSampler};
+use fm_index::{BackwardSearchIndex, IndexWithPieces};
+use numpy::{IntoPyArray, PyArray1};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+type Index = fm_index::FMIndex<u8, RangeConverter<u8>, SuffixOrderSampledArray>;
+type MultiPieces = fm_index::FMIndexMultiPieces<u8, RangeConverter<u8>, SuffixOrderSampledArray>;
+
+fn check_text(text: &[u8], level: usize) -> PyResult<()> {
+    if text.contains(&0) {
+        return Err(PyValueError::new_err("text must not contain zero bytes"));
+    }
+    check_level(text.len(), level)
+}
+
+// The sampling level must satisfy 2^level < n, where n includes the terminator.
+fn check_level(len: usize, level: usize) -> PyResult<()> {
+    if level >= 64 || 1u64 << level > len as u64 {
+        return Err(PyValueError::new_err(
+            "2 ** level must be less than the text length + 1",
+        ));
+    }
+    Ok(())
+}
+
+fn sampler(level: usize) -> SuffixOrderSampler {
+    SuffixOrderSampler::new().level(level)
+}
+
+/// An FM-index over a byte string.
+#[pyclass(name = "FMIndex", frozen)]
+struct PyFMIndex {
+    index: Index,
+}
+
+#[pymethods]
+impl PyFMIndex {
+    #[new]
+    #[pyo3(signature = (text, level = 2))]
+    fn new(py: Python<'_>, text: Vec<u8>, level: usize) -> PyResult<Self> {
+        check_text(&text, level)?;
+        let index =
+            py.allow_threads(|| Index::new(text, RangeConverter::new(1, 255), sampler(level)));
+        Ok(PyFMIndex { index })
+    }
+
+    fn __len__(&self) -> usize {
+        self.index.len() as usize
+    }
+
+    fn count(&self, py: Python<'_>, pattern: &[u8]) -> u64 {
+        py.allow_threads(|| self.index.search_backward(pattern).count())
+    }
+
+    /// Returns the positions of `pattern` as a numpy array.
+    fn locate<'py>(&self, py: Python<'py>, pattern: &[u8]) -> &'py PyArray1<u64> {
+        py.allow_threads(|| self.index.search_backward(pattern).locate())
+            .into_pyarray(py)
+    }
+}
+
+/// An FM-index over byte strings (pieces), each of which is terminated by zero in the indexed text.
+#[pyclass(name = "FMIndexMultiPieces", frozen)]
+struct PyFMIndexMultiPieces {
+    index: MultiPieces,
+}
+
+#[pymethods]
+impl PyFMIndexMultiPieces {
+    #[new]
+    #[pyo3(signature = (pieces, level = 2))]
+    fn new(py: Python<'_>, pieces: Vec<Vec<u8>>, level: usize) -> PyResult<Self> {
+        if pieces.iter().any(|piece| piece.is_empty()) {
+            return Err(PyValueError::new_err("pieces must not be empty"));
+        }
+        for piece in &pieces {
+            check_text(piece, 0)?;
+        }
+        let text = pieces.join(&0);
+        check_level(text.len(), level)?;
+        let index = py.allow_threads(|| {
+            MultiPieces::new(text, RangeConverter::new(1, 255), sampler(level))
+        });
+        Ok(PyFMIndexMultiPieces { index })
+    }
+
+    fn __len__(&self) -> usize {
+        self.index.pieces_count() as usize
+    }
+
+    fn count(&self, py: Python<'_>, pattern: &[u8]) -> u64 {
+        py.allow_threads(|| self.index.search_backward(pattern).count())
+    }
+
+    /// Returns the positions of `pattern` in the text joined by zeros as a numpy array.
+    fn locate<'py>(&self, py: Python<'py>, pattern: &[u8]) -> &'py PyArray1<u64> {
+        py.allow_threads(|| self.index.search_backward(pattern).locate())
+            .into_pyarray(py)
+    }
+
+    /// Returns the positions of `pattern` and the IDs of the pieces containing them as two numpy arrays.
+    fn locate_with_pieces<'py>(
+        &self,
+        py: Python<'py>,
+        pattern: &[u8],
+    ) -> (&'py PyArray1<u64>, &'py PyArray1<u64>) {
+        let (positions, ids) = py.allow_threads(|| {
+            let positions = self.index.search_backward(pattern).locate();
+            let ids = positions
+                .iter()
+                .map(|&p| u64::from(self.index.piece_id_at(p)))
+                .collect::<Vec<_>>();
+            (positions, ids)
+        });
+        (positions.into_pyarray(py), ids.into_pyarray(py))
+    }
+
+    /// Returns the ID of the piece equal to `piece`, if any.
+    fn find_piece(&self, py: Python<'_>, piece: &[u8]) -> Option<u64> {
+        py.allow_threads(|| self.index.contains_piece(piece).map(u64::from))
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "fm_index")]
+fn fm_index_py(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PyFMIndex>()?;
+    m.add_class::<PyFMIndexMultiPieces>()?;
+    Ok(())
+}
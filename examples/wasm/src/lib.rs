@@ -0,0 +1,45 @@
+use fm_index::converter::RangeConverter;
+use fm_index::suffix_array::{SuffixOrderSampledArray, SuffixOrderSampler};
+use fm_index::{BackwardSearchIndex, FMIndex};
+use wasm_bindgen::prelude::*;
+
+type Index = FMIndex<u8, RangeConverter<u8>, SuffixOrderSampledArray>;
+
+#[wasm_bindgen]
+pub struct SearchIndex {
+    index: Index,
+}
+
+#[wasm_bindgen]
+impl SearchIndex {
+    /// Builds an index over printable ASCII text in the browser.
+    #[wasm_bindgen(constructor)]
+    pub fn new(text: &str) -> SearchIndex {
+        let index = FMIndex::new(
+            text.as_bytes().to_vec(),
+            RangeConverter::new(b' ', b'~'),
+            SuffixOrderSampler::new().level(2),
+        );
+        SearchIndex { index }
+    }
+
+    /// Loads an index built ahead of time and serialized with bincode.
+    pub fn load(bytes: &[u8]) -> Result<SearchIndex, JsValue> {
+        let index = bincode::deserialize(bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(SearchIndex { index })
+    }
+
+    pub fn count(&self, pattern: &str) -> f64 {
+        self.index.search_backward(pattern).count() as f64
+    }
+
+    /// Returns the positions of `pattern` as a `Float64Array`.
+    pub fn locate(&self, pattern: &str) -> Vec<f64> {
+        self.index
+            .search_backward(pattern)
+            .locate()
+            .into_iter()
+            .map(|p| p as f64)
+            .collect()
+    }
+}
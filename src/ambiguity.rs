@@ -0,0 +1,67 @@
+use std::borrow::Cow;
+use std::fmt;
+
+/// A mapping from a pattern character to the set of index characters it matches,
+/// used by [`crate::BackwardSearchIndex::search_ambiguous`].
+///
+/// It is implemented for closures returning a `Vec`.
+pub trait Ambiguity<T: Clone> {
+    /// Returns the distinct characters matched by `c`.
+    fn expand(&self, c: T) -> Cow<'_, [T]>;
+}
+
+impl<T, F> Ambiguity<T> for F
+where
+    T: Clone,
+    F: Fn(T) -> Vec<T>,
+{
+    fn expand(&self, c: T) -> Cow<'_, [T]> {
+        Cow::Owned(self(c))
+    }
+}
+
+/// IUPAC nucleotide codes over the bases `A`, `C`, `G` and `T`.
+///
+/// Codes are case-insensitive and expand to upper-case bases, e.g. `R` to `A` and `G`, and `N` to all bases.
+/// `U` is read as `T`, and other characters match only themselves.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Iupac;
+
+impl Ambiguity<u8> for Iupac {
+    fn expand(&self, c: u8) -> Cow<'_, [u8]> {
+        let bases: &'static [u8] = match c.to_ascii_uppercase() {
+            b'A' => b"A",
+            b'C' => b"C",
+            b'G' => b"G",
+            b'T' | b'U' => b"T",
+            b'R' => b"AG",
+            b'Y' => b"CT",
+            b'S' => b"CG",
+            b'W' => b"AT",
+            b'K' => b"GT",
+            b'M' => b"AC",
+            b'B' => b"CGT",
+            b'D' => b"AGT",
+            b'H' => b"ACT",
+            b'V' => b"ACG",
+            b'N' => b"ACGT",
+            _ => return Cow::Owned(vec![c]),
+        };
+        Cow::Borrowed(bases)
+    }
+}
+
+/// An error returned when an ambiguous search, or a search across pieces, has more matching branches
+/// than its limit allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BranchLimitExceeded {
+    pub limit: usize,
+}
+
+impl fmt::Display for BranchLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "search exceeded the limit of {} branches", self.limit)
+    }
+}
+
+impl std::error::Error for BranchLimitExceeded {}
@@ -0,0 +1,242 @@
+//! Searches anchored at the start or the end of the text, or of pieces of [`crate::FMIndexMultiPieces`].
+//!
+//! [`crate::BackwardSearchIndex::search_prefix`], [`crate::BackwardSearchIndex::search_suffix`]
+//! and [`crate::BackwardSearchIndex::search_exact`] return an [`AnchoredSearch`] whose anchors are part of its type,
+//! so that only refinements keeping the anchors compile:
+//!
+//! - a [`Suffix`] search is refined by prepending characters, i.e. `search_suffix(p).search_backward(q)`
+//!   is the same as `search_suffix(q + p)`, and takes _O(|q|)_ steps;
+//! - a [`Prefix`] search is refined by appending characters, i.e. `search_prefix(p).search_forward(q)`
+//!   is the same as `search_prefix(p + q)`, which searches the whole pattern again;
+//! - either of them becomes an [`Exact`] search by anchoring the other end, which cannot be refined.
+//!
+//! Occurrences of anchored searches are located at the start of the pattern, like unanchored searches,
+//! though those of searches anchored at the start are found at the terminator (or separator) preceding the pattern.
+//!
+//! ```
+//! use fm_index::converter::RangeConverter;
+//! use fm_index::suffix_array::SuffixOrderSampler;
+//! use fm_index::{BackwardSearchIndex, FMIndexMultiPieces};
+//!
+//! let text = b"banana\0bandana\0cabana".to_vec();
+//! let index = FMIndexMultiPieces::new(text, RangeConverter::new(b'a', b'z'), SuffixOrderSampler::new());
+//! assert_eq!(index.search_prefix("ban").count(), 2);
+//! assert_eq!(index.search_prefix("ban").search_forward("d").count(), 1);
+//! assert_eq!(index.search_suffix("ana").count(), 3);
+//! assert_eq!(index.search_suffix("ana").search_backward("b").count(), 1);
+//! let exact = index.search_suffix("ana").search_backward("ban").anchor_start();
+//! assert_eq!(exact.pattern(), b"banana");
+//! assert_eq!(exact.count(), 1);
+//! ```
+
+use crate::character::Character;
+use crate::search::{BackwardSearchIndex, Match, SaInterval, Search};
+use crate::suffix_array::IndexWithSA;
+
+use std::marker::PhantomData;
+
+/// The anchor of a search for a prefix of the text.
+#[derive(Clone, Copy, Debug)]
+pub struct Prefix;
+
+/// The anchor of a search for a suffix of the text.
+#[derive(Clone, Copy, Debug)]
+pub struct Suffix;
+
+/// The anchors of a search for the whole text.
+#[derive(Clone, Copy, Debug)]
+pub struct Exact;
+
+/// A search for a pattern anchored by `A`, i.e. [`Prefix`], [`Suffix`] or [`Exact`].
+///
+/// It is a search for the pattern with the terminator on its anchored sides,
+/// which is available by [`AnchoredSearch::as_search`], though its refinements are no longer anchored.
+pub struct AnchoredSearch<'a, I, A>
+where
+    I: BackwardSearchIndex,
+{
+    search: Search<'a, I>,
+    _anchor: PhantomData<A>,
+}
+
+impl<'a, I, A> AnchoredSearch<'a, I, A>
+where
+    I: BackwardSearchIndex,
+    I::T: Character,
+{
+    pub(crate) fn new(search: Search<'a, I>) -> Self {
+        AnchoredSearch {
+            search,
+            _anchor: PhantomData,
+        }
+    }
+
+    pub fn get_range(&self) -> SaInterval {
+        self.search.get_range()
+    }
+
+    pub fn count(&self) -> u64 {
+        self.search.count()
+    }
+
+    /// Returns the pattern without the terminators of the anchors.
+    pub fn pattern(&self) -> Vec<I::T> {
+        let mut pattern = self.search.pattern();
+        if pattern.last().is_some_and(|&c| c.into() == 0) {
+            pattern.pop();
+        }
+        if pattern.first().is_some_and(|&c| c.into() == 0) {
+            pattern.remove(0);
+        }
+        pattern
+    }
+
+    /// Returns the search for the pattern with the terminators of the anchors.
+    pub fn as_search(&self) -> &Search<'a, I> {
+        &self.search
+    }
+
+    pub fn into_search(self) -> Search<'a, I> {
+        self.search
+    }
+
+    /// Iterates over the occurrences like [`Search::iter_matches`],
+    /// i.e. of the pattern with the terminators of the anchors.
+    pub fn iter_matches(&self) -> impl Iterator<Item = Match<'a, I>> + '_ {
+        self.search.iter_matches()
+    }
+}
+
+impl<'a, I, A> AnchoredSearch<'a, I, A>
+where
+    I: BackwardSearchIndex + IndexWithSA,
+    I::T: Character,
+{
+    // Locates the occurrences after the terminators they are found at.
+    fn locate_after_terminator(&self) -> Vec<u64> {
+        let index = self.search.index();
+        self.search
+            .locate()
+            .into_iter()
+            .map(|p| index.position_after_terminator(p))
+            .collect()
+    }
+}
+
+impl<'a, I> AnchoredSearch<'a, I, Prefix>
+where
+    I: BackwardSearchIndex + IndexWithSA,
+    I::T: Character,
+{
+    /// Lists the start positions of all occurrences, i.e. those of the text or the pieces.
+    pub fn locate(&self) -> Vec<u64> {
+        self.locate_after_terminator()
+    }
+}
+
+impl<'a, I> AnchoredSearch<'a, I, Suffix>
+where
+    I: BackwardSearchIndex + IndexWithSA,
+    I::T: Character,
+{
+    /// Lists the positions of all occurrences like [`Search::locate`].
+    pub fn locate(&self) -> Vec<u64> {
+        self.search.locate()
+    }
+}
+
+impl<'a, I> AnchoredSearch<'a, I, Exact>
+where
+    I: BackwardSearchIndex + IndexWithSA,
+    I::T: Character,
+{
+    /// Lists the start positions of all occurrences, i.e. those of the text or the pieces.
+    pub fn locate(&self) -> Vec<u64> {
+        self.locate_after_terminator()
+    }
+}
+
+impl<'a, I> AnchoredSearch<'a, I, Prefix>
+where
+    I: BackwardSearchIndex,
+    I::T: Character,
+{
+    /// Appends `pattern` to the pattern, searching the whole pattern again.
+    pub fn search_forward<K: AsRef<[I::T]>>(&self, pattern: K) -> Self {
+        let mut whole = self.pattern();
+        whole.extend_from_slice(pattern.as_ref());
+        self.search.index().search_prefix(whole)
+    }
+
+    /// Anchors the pattern at the end as well, searching the whole pattern again.
+    pub fn anchor_end(&self) -> AnchoredSearch<'a, I, Exact> {
+        self.search.index().search_exact(self.pattern())
+    }
+}
+
+impl<'a, I> AnchoredSearch<'a, I, Suffix>
+where
+    I: BackwardSearchIndex,
+    I::T: Character,
+{
+    /// Prepends `pattern` to the pattern.
+    pub fn search_backward<K: AsRef<[I::T]>>(&self, pattern: K) -> Self {
+        AnchoredSearch::new(self.search.search_backward(pattern))
+    }
+
+    /// Anchors the pattern at the start as well.
+    pub fn anchor_start(&self) -> AnchoredSearch<'a, I, Exact> {
+        AnchoredSearch::new(self.search.search_backward([I::T::from_u64(0)]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::converter::RangeConverter;
+    use crate::search::BackwardSearchIndex;
+    use crate::suffix_array::SuffixOrderSampler;
+    use crate::{FMIndex, FMIndexMultiPieces};
+
+    #[test]
+    fn test_refinements() {
+        let index = FMIndex::new(
+            b"mississippi".to_vec(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new(),
+        );
+        let prefix = index.search_prefix("mis").search_forward("si");
+        assert_eq!(prefix.pattern(), b"missi");
+        assert_eq!(prefix.locate(), vec![0]);
+        assert_eq!(prefix.search_forward("x").count(), 0);
+        assert_eq!(prefix.anchor_end().count(), 0);
+        assert_eq!(
+            prefix.search_forward("ssippi").anchor_end().pattern(),
+            b"mississippi"
+        );
+
+        let suffix = index.search_suffix("pi").search_backward("sip");
+        assert_eq!(suffix.pattern(), b"sippi");
+        assert_eq!(suffix.locate(), vec![6]);
+        assert_eq!(suffix.anchor_start().count(), 0);
+        let exact = suffix.search_backward("missis").anchor_start();
+        assert_eq!(exact.locate(), vec![0]);
+        assert_eq!(
+            exact.get_range(),
+            index.search_exact("mississippi").get_range()
+        );
+
+        let index = FMIndexMultiPieces::new(
+            b"ab\0b\0ab".to_vec(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new(),
+        );
+        assert_eq!(index.search_suffix("b").search_backward("a").count(), 2);
+        assert_eq!(index.search_suffix("b").anchor_start().count(), 1);
+        assert_eq!(index.search_prefix("a").anchor_end().count(), 0);
+        assert_eq!(index.search_prefix("").search_forward("ab").count(), 2);
+        let mut starts = index.search_prefix("a").locate();
+        starts.sort();
+        assert_eq!(starts, vec![0, 5]);
+        assert_eq!(index.search_exact("b").locate(), vec![3]);
+    }
+}
@@ -0,0 +1,304 @@
+use crate::character::Character;
+use crate::converter::{Converter, IndexWithConverter};
+use crate::estimate::IndexEstimate;
+use crate::heap_size::HeapSize;
+use crate::piece::{IndexWithPieces, PieceId};
+use crate::sais;
+use crate::search::SaInterval;
+use crate::stats::IndexStats;
+use crate::suffix_array::{ArraySampler, IndexWithSA, PartialArray};
+use crate::{BackwardIterableIndex, FMIndex, ForwardIterableIndex, RLFMIndex};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The trade-off between query speed and index size for [`auto_build`].
+///
+/// The run-length index is chosen when its estimated size is at most the given fraction
+/// of that of the plain index, since its queries are a few times slower.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Preference {
+    /// Chooses the run-length index whenever it is smaller.
+    Space,
+    /// Chooses the run-length index if it is at most half the size, which is the default.
+    #[default]
+    Balanced,
+    /// Chooses the run-length index only if it is at most a tenth of the size, e.g. for highly repetitive texts.
+    Speed,
+    /// Chooses the run-length index if it is at most the given fraction of the size.
+    SizeRatio(f64),
+}
+
+impl Preference {
+    fn size_ratio(self) -> f64 {
+        match self {
+            Preference::Space => 1.0,
+            Preference::Balanced => 0.5,
+            Preference::Speed => 0.1,
+            Preference::SizeRatio(ratio) => ratio,
+        }
+    }
+}
+
+/// An index whose backend is chosen by [`auto_build`] from the runs of the BWT of the text.
+///
+/// It implements the same traits as both backends, so it is searched like either of them.
+// Boxing the plain index would add an indirection to every query.
+#[allow(clippy::large_enum_variant)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AutoIndex<T, C, S> {
+    Plain(FMIndex<T, C, S>),
+    RunLength(RLFMIndex<T, C, S>),
+}
+
+/// Builds an [`FMIndex`] or an [`RLFMIndex`] over `text`, whichever suits `preference`.
+///
+/// The number of runs _r_ of the BWT is counted from the suffix array before the index is built,
+/// which takes _O(n)_ time on top of the construction,
+/// and the sizes of both backends are compared by [`IndexEstimate`].
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::SuffixOrderSampler;
+/// use fm_index::{auto_build, AutoIndex, BackwardSearchIndex, Preference};
+///
+/// let text = b"abracadabra".repeat(100);
+/// let index = auto_build(text, RangeConverter::new(b'a', b'z'), SuffixOrderSampler::new(), Preference::Space);
+/// assert!(matches!(index, AutoIndex::RunLength(_)));
+/// assert_eq!(index.search_backward("cad").count(), 100);
+/// ```
+pub fn auto_build<T, C, S, B>(
+    mut text: Vec<T>,
+    converter: C,
+    sampler: B,
+    preference: Preference,
+) -> AutoIndex<T, C, S>
+where
+    T: Character,
+    C: Converter<T>,
+    B: ArraySampler<S>,
+{
+    if !text.last().is_some_and(|c| c.is_zero()) {
+        text.push(T::zero());
+    }
+    let sa = sais::sais(&text, &converter);
+    let n = text.len();
+    let bw = |i: usize| text[(sa[i] as usize + n - 1) % n];
+    let runs = 1 + (1..n).filter(|&i| bw(i) != bw(i - 1)).count() as u64;
+    let estimate = IndexEstimate::for_text_stats(n as u64, converter.len(), runs, 0);
+    let plain = estimate.fm_index - estimate.suffix_array;
+    let run_length = estimate.rlfm_index - estimate.suffix_array;
+    if run_length as f64 <= plain as f64 * preference.size_ratio() {
+        AutoIndex::RunLength(RLFMIndex::from_suffix_array(text, sa, converter, sampler))
+    } else {
+        AutoIndex::Plain(FMIndex::from_suffix_array(text, sa, converter, sampler))
+    }
+}
+
+impl<T, C, S> AutoIndex<T, C, S> {
+    pub fn is_run_length(&self) -> bool {
+        matches!(self, AutoIndex::RunLength(_))
+    }
+}
+
+impl<T, C> AutoIndex<T, C, ()> {
+    pub fn size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + match self {
+                AutoIndex::Plain(index) => index.heap_size(),
+                AutoIndex::RunLength(index) => index.heap_size(),
+            }
+    }
+}
+
+impl<T, C, S> AutoIndex<T, C, S>
+where
+    S: PartialArray,
+{
+    pub fn size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + match self {
+                AutoIndex::Plain(index) => index.heap_size(),
+                AutoIndex::RunLength(index) => index.heap_size(),
+            }
+    }
+}
+
+// Calls a method of the backend.
+macro_rules! delegate {
+    ($self:ident, $index:ident => $e:expr) => {
+        match $self {
+            AutoIndex::Plain($index) => $e,
+            AutoIndex::RunLength($index) => $e,
+        }
+    };
+}
+
+impl<T, C> AutoIndex<T, C, ()>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    /// Summarizes the backend, whose size excludes the enum itself.
+    pub fn stats(&self) -> IndexStats {
+        delegate!(self, index => index.stats())
+    }
+}
+
+impl<T, C, S> AutoIndex<T, C, S>
+where
+    T: Character,
+    C: Converter<T>,
+    S: PartialArray,
+{
+    /// Summarizes the backend, whose size excludes the enum itself.
+    pub fn stats(&self) -> IndexStats {
+        delegate!(self, index => index.stats())
+    }
+}
+
+impl<T, C, S> BackwardIterableIndex for AutoIndex<T, C, S>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    type T = T;
+
+    fn get_l(&self, i: u64) -> T {
+        delegate!(self, index => index.get_l(i))
+    }
+
+    fn lf_map(&self, i: u64) -> u64 {
+        delegate!(self, index => index.lf_map(i))
+    }
+
+    fn lf_map2(&self, c: T, i: u64) -> u64 {
+        delegate!(self, index => index.lf_map2(c, i))
+    }
+
+    fn len(&self) -> u64 {
+        delegate!(self, index => BackwardIterableIndex::len(index))
+    }
+
+    fn char_range(&self, c: T) -> SaInterval {
+        delegate!(self, index => index.char_range(c))
+    }
+
+    fn may_contain(&self, pattern: &[T]) -> bool {
+        delegate!(self, index => index.may_contain(pattern))
+    }
+}
+
+impl<T, C, S> ForwardIterableIndex for AutoIndex<T, C, S>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    type T = T;
+
+    fn get_f(&self, i: u64) -> T {
+        delegate!(self, index => index.get_f(i))
+    }
+
+    fn fl_map(&self, i: u64) -> u64 {
+        delegate!(self, index => index.fl_map(i))
+    }
+
+    fn fl_map2(&self, c: T, i: u64) -> u64 {
+        delegate!(self, index => index.fl_map2(c, i))
+    }
+
+    fn len(&self) -> u64 {
+        delegate!(self, index => ForwardIterableIndex::len(index))
+    }
+}
+
+impl<T, C, S> IndexWithSA for AutoIndex<T, C, S>
+where
+    T: Character,
+    C: Converter<T>,
+    S: PartialArray,
+{
+    fn get_sa(&self, i: u64) -> u64 {
+        delegate!(self, index => index.get_sa(i))
+    }
+
+    fn get_sa_with_budget(&self, i: u64, budget: &mut u64) -> Option<u64> {
+        delegate!(self, index => index.get_sa_with_budget(i, budget))
+    }
+}
+
+impl<T, C, S> IndexWithConverter<T> for AutoIndex<T, C, S>
+where
+    C: Converter<T>,
+{
+    type C = C;
+
+    fn get_converter(&self) -> &C {
+        delegate!(self, index => index.get_converter())
+    }
+}
+
+impl<T, C, S> IndexWithPieces for AutoIndex<T, C, S> {
+    fn pieces_count(&self) -> u64 {
+        1
+    }
+
+    fn piece_id(&self, _i: u64) -> PieceId {
+        PieceId::from(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::search::BackwardSearchIndex;
+    use crate::suffix_array::SuffixOrderSampler;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_auto_build() {
+        let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+        let random = (0..200)
+            .map(|_| rng.gen_range(b'a', b'z'))
+            .collect::<Vec<_>>();
+        let repetitive = b"abcab".repeat(100);
+        for (text, preference, run_length) in [
+            (random, Preference::Space, false),
+            (repetitive.clone(), Preference::Space, true),
+            (repetitive, Preference::SizeRatio(0.0), false),
+        ] {
+            let index = auto_build(
+                text.clone(),
+                RangeConverter::new(b'a', b'z'),
+                SuffixOrderSampler::new().level(2),
+                preference,
+            );
+            assert_eq!(index.is_run_length(), run_length);
+            let plain = FMIndex::new(
+                text.clone(),
+                RangeConverter::new(b'a', b'z'),
+                SuffixOrderSampler::new().level(2),
+            );
+            if !run_length {
+                assert_eq!(
+                    index.size(),
+                    std::mem::size_of_val(&index) + plain.heap_size()
+                );
+            }
+            for pattern in &["a", "ab", "cab", "bca", "xyz"] {
+                let mut res = index.search_backward(pattern).locate();
+                let mut expected = plain.search_backward(pattern).locate();
+                res.sort();
+                expected.sort();
+                assert_eq!(res, expected, "pattern {:?}", pattern);
+            }
+            assert_eq!(
+                index.search_backward("a").iter_forward(0).take(3).count(),
+                3
+            );
+        }
+    }
+}
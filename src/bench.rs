@@ -0,0 +1,213 @@
+//! Measuring queries on a user's own corpus and patterns.
+//!
+//! The results help to choose an index type and a sampling level for a workload.
+//!
+//! ```
+//! use fm_index::bench::{self, Workload};
+//! use fm_index::converter::RangeConverter;
+//! use fm_index::suffix_array::SuffixOrderSampler;
+//! use fm_index::FMIndex;
+//!
+//! let index = FMIndex::new(
+//!     b"mississippi".to_vec(),
+//!     RangeConverter::new(b'a', b'z'),
+//!     SuffixOrderSampler::new().level(2),
+//! );
+//! let workload = Workload::new(vec!["ssi", "i", "pp", "x"]).rounds(10);
+//! let profile = bench::profile(&index, &workload);
+//! assert_eq!(profile.queries, 40);
+//! assert_eq!(profile.occurrences, 70);
+//! println!("{}", profile);
+//! ```
+
+use crate::search::BackwardSearchIndex;
+use crate::suffix_array::IndexWithSA;
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// A set of patterns to be searched, run `rounds` times.
+pub struct Workload<K> {
+    patterns: Vec<K>,
+    rounds: usize,
+}
+
+impl<K> Workload<K> {
+    pub fn new(patterns: Vec<K>) -> Self {
+        Workload {
+            patterns,
+            rounds: 1,
+        }
+    }
+
+    pub fn rounds(mut self, rounds: usize) -> Self {
+        self.rounds = rounds;
+        self
+    }
+}
+
+/// Latency statistics of a phase of queries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Latency {
+    pub total: Duration,
+    pub mean: Duration,
+    pub min: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl Latency {
+    fn from_samples(mut samples: Vec<Duration>) -> Self {
+        if samples.is_empty() {
+            return Latency::default();
+        }
+        samples.sort();
+        let percentile = |p: usize| samples[(samples.len() - 1) * p / 100];
+        let total = samples.iter().sum::<Duration>();
+        Latency {
+            total,
+            mean: total / samples.len() as u32,
+            min: samples[0],
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: samples[samples.len() - 1],
+        }
+    }
+}
+
+impl fmt::Display for Latency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "mean {:?}, p50 {:?}, p90 {:?}, p99 {:?}, max {:?}",
+            self.mean, self.p50, self.p90, self.p99, self.max
+        )
+    }
+}
+
+/// The result of [`profile`] or [`profile_count`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Profile {
+    /// The number of queries run.
+    pub queries: usize,
+    /// The total number of occurrences found.
+    pub occurrences: u64,
+    /// The latency of backward search, which is all of a count query.
+    pub search: Latency,
+    /// The latency of locating the occurrences after the search, if measured.
+    pub locate: Option<Latency>,
+    /// The latency of whole queries.
+    pub total: Latency,
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} queries, {} occurrences",
+            self.queries, self.occurrences
+        )?;
+        writeln!(f, "search: {}", self.search)?;
+        if let Some(locate) = &self.locate {
+            writeln!(f, "locate: {}", locate)?;
+        }
+        write!(f, "total:  {}", self.total)
+    }
+}
+
+/// Measures counting the occurrences of each pattern of `workload`.
+pub fn profile_count<I, K>(index: &I, workload: &Workload<K>) -> Profile
+where
+    I: BackwardSearchIndex,
+    K: AsRef<[I::T]>,
+{
+    let mut search = vec![];
+    let mut occurrences = 0;
+    for _ in 0..workload.rounds {
+        for pattern in &workload.patterns {
+            let start = Instant::now();
+            occurrences += index.search_backward(pattern).count();
+            search.push(start.elapsed());
+        }
+    }
+    let search = Latency::from_samples(search);
+    Profile {
+        queries: workload.rounds * workload.patterns.len(),
+        occurrences,
+        search,
+        locate: None,
+        total: search,
+    }
+}
+
+/// Measures locating the occurrences of each pattern of `workload`,
+/// broken down into backward search and locating.
+pub fn profile<I, K>(index: &I, workload: &Workload<K>) -> Profile
+where
+    I: BackwardSearchIndex + IndexWithSA,
+    K: AsRef<[I::T]>,
+{
+    let mut search = vec![];
+    let mut locate = vec![];
+    let mut total = vec![];
+    let mut occurrences = 0;
+    for _ in 0..workload.rounds {
+        for pattern in &workload.patterns {
+            let start = Instant::now();
+            let result = index.search_backward(pattern);
+            let searched = Instant::now();
+            occurrences += result.locate().len() as u64;
+            let end = Instant::now();
+            search.push(searched - start);
+            locate.push(end - searched);
+            total.push(end - start);
+        }
+    }
+    Profile {
+        queries: workload.rounds * workload.patterns.len(),
+        occurrences,
+        search: Latency::from_samples(search),
+        locate: Some(Latency::from_samples(locate)),
+        total: Latency::from_samples(total),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::suffix_array::NullSampler;
+    use crate::RLFMIndex;
+
+    #[test]
+    fn test_latency() {
+        let samples = (1..=100).rev().map(Duration::from_millis).collect();
+        let latency = Latency::from_samples(samples);
+        assert_eq!(latency.min, Duration::from_millis(1));
+        assert_eq!(latency.p50, Duration::from_millis(50));
+        assert_eq!(latency.p90, Duration::from_millis(90));
+        assert_eq!(latency.p99, Duration::from_millis(99));
+        assert_eq!(latency.max, Duration::from_millis(100));
+        assert_eq!(latency.total, Duration::from_millis(5050));
+        assert_eq!(latency.mean, Duration::from_micros(50500));
+        assert_eq!(Latency::from_samples(vec![]), Latency::default());
+    }
+
+    #[test]
+    fn test_profile_count() {
+        let index = RLFMIndex::new(
+            b"mississippi".to_vec(),
+            RangeConverter::new(b'a', b'z'),
+            NullSampler::new(),
+        );
+        let profile = profile_count(&index, &Workload::new(vec!["ssi", "s"]).rounds(3));
+        assert_eq!(profile.queries, 6);
+        assert_eq!(profile.occurrences, 18);
+        assert_eq!(profile.locate, None);
+        assert!(profile.search.min <= profile.search.p50);
+        assert!(profile.search.p99 <= profile.search.max);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::character::Character;
+use crate::converter::IndexWithConverter;
+use crate::search::{BackwardSearchIndex, Search};
+use crate::suffix_array::IndexWithSA;
+
+/// A pair of indexes built over a text and its reverse.
+///
+/// Backward search on the reverse index is equivalent to forward search on the original text,
+/// which allows suffix-anchored queries such as [`BidirectionalIndex::ends_with`].
+pub struct BidirectionalIndex<I> {
+    forward: I,
+    reverse: I,
+}
+
+impl<T, I> BidirectionalIndex<I>
+where
+    T: Character,
+    I: BackwardSearchIndex<T = T>,
+{
+    /// Builds indexes over `text` and its reverse with `build`.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::NullSampler;
+    /// use fm_index::{BidirectionalIndex, FMIndex};
+    ///
+    /// let text = b"mississippi".to_vec();
+    /// let index = BidirectionalIndex::new(text, |text| {
+    ///     FMIndex::new(text, RangeConverter::new(b'a', b'z'), NullSampler::new())
+    /// });
+    /// assert!(index.starts_with("miss"));
+    /// assert!(index.ends_with("ppi"));
+    /// assert!(!index.ends_with("miss"));
+    /// ```
+    pub fn new<F>(mut text: Vec<T>, build: F) -> Self
+    where
+        F: Fn(Vec<T>) -> I,
+    {
+        if text.last().is_some_and(|c| c.is_zero()) {
+            text.pop();
+        }
+        let mut reversed = text.clone();
+        reversed.reverse();
+        BidirectionalIndex {
+            forward: build(text),
+            reverse: build(reversed),
+        }
+    }
+
+    pub fn forward(&self) -> &I {
+        &self.forward
+    }
+
+    pub fn reverse(&self) -> &I {
+        &self.reverse
+    }
+
+    pub fn search_backward<K>(&self, pattern: K) -> Search<'_, I>
+    where
+        K: AsRef<[T]>,
+    {
+        self.forward.search_backward(pattern)
+    }
+
+    /// Searches for `pattern` in the reverse index.
+    ///
+    /// The resulting search is a search on the reversed text: chaining `search_backward` on it
+    /// with a reversed pattern extends the match to the right, and its positions refer to the reversed text.
+    /// Use [`BidirectionalIndex::locate_forward`] to get positions in the original text.
+    pub fn search_forward<K>(&self, pattern: K) -> Search<'_, I>
+    where
+        K: AsRef<[T]>,
+    {
+        self.reverse.search_backward(reversed(pattern))
+    }
+
+    /// Returns true if the text starts with `pattern`.
+    pub fn starts_with<K>(&self, pattern: K) -> bool
+    where
+        K: AsRef<[T]>,
+    {
+        // The character preceding the first one is the terminator.
+        let mut anchored = vec![T::zero()];
+        anchored.extend_from_slice(pattern.as_ref());
+        self.forward.search_backward(anchored).count() > 0
+    }
+
+    /// Returns true if the text ends with `pattern`.
+    pub fn ends_with<K>(&self, pattern: K) -> bool
+    where
+        K: AsRef<[T]>,
+    {
+        let mut anchored = reversed(pattern);
+        anchored.insert(0, T::zero());
+        self.reverse.search_backward(anchored).count() > 0
+    }
+}
+
+impl<T, I> BidirectionalIndex<I>
+where
+    T: Character,
+    I: BackwardSearchIndex<T = T> + IndexWithConverter<T>,
+{
+    /// Counts the occurrences of each character following `pattern` in the original text,
+    /// i.e. its right extensions by one character, with a left extension on the reverse index.
+    pub fn right_extension_counts<K>(&self, pattern: K) -> Vec<(T, u64)>
+    where
+        K: AsRef<[T]>,
+    {
+        self.search_forward(pattern).left_extension_counts()
+    }
+}
+
+impl<T, I> BidirectionalIndex<I>
+where
+    T: Character,
+    I: BackwardSearchIndex<T = T> + IndexWithSA,
+{
+    /// Lists the positions of `pattern` in the original text using the reverse index.
+    pub fn locate_forward<K>(&self, pattern: K) -> Vec<u64>
+    where
+        K: AsRef<[T]>,
+    {
+        let m = pattern.as_ref().len() as u64;
+        // excluding the terminator
+        let n = self.reverse.len() - 1;
+        self.search_forward(pattern)
+            .locate()
+            .into_iter()
+            .map(|p| n - p - m)
+            .collect()
+    }
+}
+
+fn reversed<T: Copy, K: AsRef<[T]>>(pattern: K) -> Vec<T> {
+    pattern.as_ref().iter().rev().copied().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::suffix_array::{NullSampler, SuffixOrderSampler};
+    use crate::{FMIndex, RLFMIndex};
+
+    #[test]
+    fn test_anchored() {
+        let text = "mississippi".to_string().into_bytes();
+        let index = BidirectionalIndex::new(text, |text| {
+            RLFMIndex::new(text, RangeConverter::new(b'a', b'z'), NullSampler::new())
+        });
+        for (pattern, starts, ends) in [
+            ("m", true, false),
+            ("mississippi", true, true),
+            ("i", false, true),
+            ("ppi", false, true),
+            ("issi", false, false),
+            ("", true, true),
+            ("z", false, false),
+        ] {
+            assert_eq!(
+                index.starts_with(pattern),
+                starts,
+                "starts_with({:?})",
+                pattern
+            );
+            assert_eq!(index.ends_with(pattern), ends, "ends_with({:?})", pattern);
+        }
+    }
+
+    #[test]
+    fn test_right_extension_counts() {
+        let text = "mississippi".to_string().into_bytes();
+        let index = BidirectionalIndex::new(text, |text| {
+            FMIndex::new(text, RangeConverter::new(b'a', b'z'), NullSampler::new())
+        });
+        assert_eq!(
+            index.right_extension_counts("i"),
+            vec![(b'p', 1), (b's', 2)]
+        );
+        assert_eq!(
+            index.search_backward("i").left_extension_counts(),
+            vec![(b'm', 1), (b'p', 1), (b's', 2)]
+        );
+        assert_eq!(index.right_extension_counts("ss"), vec![(b'i', 2)]);
+        assert_eq!(index.right_extension_counts("ppi"), vec![]);
+        assert_eq!(index.right_extension_counts("x"), vec![]);
+    }
+
+    #[test]
+    fn test_locate_forward() {
+        let text = "mississippi\0".to_string().into_bytes();
+        let index = BidirectionalIndex::new(text, |text| {
+            FMIndex::new(
+                text,
+                RangeConverter::new(b'a', b'z'),
+                SuffixOrderSampler::new().level(1),
+            )
+        });
+        for pattern in &["m", "i", "iss", "ssi", "ppi", "pps"] {
+            let mut expected = index.search_backward(pattern).locate();
+            expected.sort();
+            let mut actual = index.locate_forward(pattern);
+            actual.sort();
+            assert_eq!(actual, expected, "pattern {:?}", pattern);
+        }
+    }
+}
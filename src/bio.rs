@@ -0,0 +1,186 @@
+//! Readers of FASTA and FASTQ files into texts of multi-piece indexes, enabled by the `bio-io` feature.
+//!
+//! Each record becomes a piece of [`crate::FMIndexMultiPieces`], so that piece IDs are record numbers,
+//! and the names of records are kept beside the text to be looked up by piece IDs.
+//!
+//! ```
+//! use fm_index::bio;
+//! use fm_index::converter::RangeConverter;
+//! use fm_index::suffix_array::SuffixOrderSampler;
+//! use fm_index::{BackwardSearchIndex, FMIndexMultiPieces, IndexWithPieces};
+//!
+//! let fasta = ">chr1 first\nACGT\nACGA\n>chr2\nTTACG\n";
+//! let records = bio::read_fasta(fasta.as_bytes()).unwrap();
+//! let index = FMIndexMultiPieces::new(records.text.clone(), RangeConverter::new(b'A', b'T'), SuffixOrderSampler::new());
+//! let search = index.search_backward("TACG");
+//! assert_eq!(search.count(), 2);
+//! let piece = search.iter_matches().map(|m| m.piece_id()).max().unwrap();
+//! assert_eq!(records.name(piece), "chr2");
+//! ```
+
+use crate::piece::PieceId;
+
+use std::io::{self, BufRead};
+
+/// The sequences of records as the text of a multi-piece index, and their names.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Records {
+    /// The sequences delimited by zero, in the order of records.
+    pub text: Vec<u8>,
+    /// The name of each record, i.e. the first word of its header.
+    pub names: Vec<String>,
+}
+
+impl Records {
+    /// Returns the name of the record of a piece.
+    pub fn name(&self, id: PieceId) -> &str {
+        &self.names[u64::from(id) as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    fn push(&mut self, name: String, sequence: &[u8]) -> io::Result<()> {
+        if sequence.is_empty() {
+            return Err(invalid(format!("record {} has an empty sequence", name)));
+        }
+        if sequence.contains(&0) {
+            return Err(invalid(format!("record {} contains a zero byte", name)));
+        }
+        self.text.extend_from_slice(sequence);
+        self.text.push(0);
+        self.names.push(name);
+        Ok(())
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Reads a line without the line terminator, or returns `None` at the end of the input.
+fn read_line<R: BufRead>(reader: &mut R, line: &mut Vec<u8>) -> io::Result<Option<()>> {
+    line.clear();
+    if reader.read_until(b'\n', line)? == 0 {
+        return Ok(None);
+    }
+    while matches!(line.last(), Some(b'\n') | Some(b'\r')) {
+        line.pop();
+    }
+    Ok(Some(()))
+}
+
+fn record_name(header: &[u8]) -> String {
+    let name = header
+        .split(|c| c.is_ascii_whitespace())
+        .next()
+        .unwrap_or(b"");
+    String::from_utf8_lossy(name).into_owned()
+}
+
+/// Reads the records of a FASTA file, whose sequences may span several lines.
+///
+/// Whitespace in sequences is removed, and other bytes are kept as they are.
+/// Returns an error of kind `InvalidData` for a sequence before the first header
+/// or a record without a sequence.
+pub fn read_fasta<R: BufRead>(mut reader: R) -> io::Result<Records> {
+    let mut records = Records::default();
+    let mut name: Option<String> = None;
+    let mut sequence = vec![];
+    let mut line = vec![];
+    while read_line(&mut reader, &mut line)?.is_some() {
+        if let Some(header) = line.strip_prefix(b">") {
+            if let Some(name) = name.take() {
+                records.push(name, &sequence)?;
+            }
+            name = Some(record_name(header));
+            sequence.clear();
+        } else if name.is_some() {
+            sequence.extend(line.iter().filter(|c| !c.is_ascii_whitespace()));
+        } else if line.iter().any(|c| !c.is_ascii_whitespace()) {
+            return Err(invalid("a sequence precedes the first header".to_string()));
+        }
+    }
+    if let Some(name) = name {
+        records.push(name, &sequence)?;
+    }
+    Ok(records)
+}
+
+/// Reads the records of a FASTQ file of four lines per record, discarding the qualities.
+///
+/// Returns an error of kind `InvalidData` for a malformed record,
+/// e.g. one whose quality line differs in length from its sequence.
+pub fn read_fastq<R: BufRead>(mut reader: R) -> io::Result<Records> {
+    let mut records = Records::default();
+    let (mut header, mut sequence, mut plus, mut quality) = (vec![], vec![], vec![], vec![]);
+    while read_line(&mut reader, &mut header)?.is_some() {
+        if header.is_empty() {
+            continue;
+        }
+        let name = match header.strip_prefix(b"@") {
+            Some(header) => record_name(header),
+            None => return Err(invalid("a record does not start with '@'".to_string())),
+        };
+        let complete = read_line(&mut reader, &mut sequence)?.is_some()
+            && read_line(&mut reader, &mut plus)?.is_some()
+            && read_line(&mut reader, &mut quality)?.is_some();
+        if !complete || !plus.starts_with(b"+") {
+            return Err(invalid(format!(
+                "record {} is truncated or malformed",
+                name
+            )));
+        }
+        if quality.len() != sequence.len() {
+            return Err(invalid(format!(
+                "record {} has qualities of a different length from its sequence",
+                name
+            )));
+        }
+        records.push(name, &sequence)?;
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_fasta() {
+        let fasta = "\n>a desc\r\nAC GT\r\nNN\n>b\nT\n\n>c\nGG";
+        let records = read_fasta(fasta.as_bytes()).unwrap();
+        assert_eq!(records.text, b"ACGTNN\0T\0GG\0".to_vec());
+        assert_eq!(records.names, vec!["a", "b", "c"]);
+        assert_eq!(records.name(PieceId::from(1)), "b");
+
+        assert!(read_fasta("".as_bytes()).unwrap().is_empty());
+        for fasta in &["ACGT\n>a\nA", ">a\n>b\nA", ">a\nA\n>b\n"] {
+            let err = read_fasta(fasta.as_bytes()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", fasta);
+        }
+    }
+
+    #[test]
+    fn test_read_fastq() {
+        let fastq = "@r1 x\nACGT\n+\nIIII\n@r2\nTT\n+r2\nII\n";
+        let records = read_fastq(fastq.as_bytes()).unwrap();
+        assert_eq!(records.text, b"ACGT\0TT\0".to_vec());
+        assert_eq!(records.names, vec!["r1", "r2"]);
+
+        for fastq in &[
+            "r1\nA\n+\nI\n",
+            "@r1\nAC\n+\nI\n",
+            "@r1\nA\n+\n",
+            "@r1\nA\n-\nI\n",
+        ] {
+            let err = read_fastq(fastq.as_bytes()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", fastq);
+        }
+    }
+}
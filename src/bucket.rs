@@ -0,0 +1,167 @@
+use crate::succinct::{BitVector, RankSelect};
+use crate::util;
+
+use fid::BitArray;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A non-decreasing array of size _O(σ)_ which stores the number of characters
+/// smaller than a given character (_σ_: number of characters).
+pub trait BucketArray {
+    fn get(&self, c: u64) -> u64;
+    fn len(&self) -> u64;
+    fn size(&self) -> usize;
+
+    /// Returns the greatest `c` such that `get(c) <= i`.
+    fn search(&self, i: u64) -> u64 {
+        // invariant: c exists in [s, e)
+        let mut s = 0;
+        let mut e = self.len();
+        while e - s > 1 {
+            let m = s + (e - s) / 2;
+            if self.get(m) <= i {
+                s = m;
+            } else {
+                e = m;
+            }
+        }
+        s
+    }
+}
+
+impl BucketArray for Vec<u64> {
+    fn get(&self, c: u64) -> u64 {
+        self[c as usize]
+    }
+
+    fn len(&self) -> u64 {
+        Vec::len(self) as u64
+    }
+
+    fn size(&self) -> usize {
+        Vec::len(self) * std::mem::size_of::<u64>()
+    }
+}
+
+/// A bucket array represented with Elias-Fano encoding.
+///
+/// It takes _σ (2 + log(n / σ))_ bits instead of _64σ_ bits,
+/// and each access costs a select query instead of a memory load.
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EliasFanoBucketArray {
+    high: BitVector,
+    low: BitArray,
+    low_width: usize,
+    len: u64,
+}
+
+impl EliasFanoBucketArray {
+    pub fn new(cs: &[u64]) -> Self {
+        let len = cs.len() as u64;
+        let universe = cs.last().map_or(0, |&c| c + 1);
+        let low_width = if universe > len {
+            util::log2(universe / len) as usize
+        } else {
+            0
+        };
+
+        let mut high = BitVector::default();
+        let mut low = BitArray::with_word_size(low_width, cs.len());
+        let mut p = 0;
+        for (i, &c) in cs.iter().enumerate() {
+            debug_assert!(i == 0 || cs[i - 1] <= c, "cs must be non-decreasing");
+            let h = (c >> low_width) + i as u64;
+            while p < h {
+                high.push(false);
+                p += 1;
+            }
+            high.push(true);
+            p += 1;
+            if low_width > 0 {
+                low.set_word(i, low_width, c & ((1 << low_width) - 1));
+            }
+        }
+
+        EliasFanoBucketArray {
+            high,
+            low,
+            low_width,
+            len,
+        }
+    }
+}
+
+impl EliasFanoBucketArray {
+    /// Returns the number of elements less than `x`.
+    ///
+    /// It takes a select query and a scan over the elements sharing the high bits of `x`.
+    pub(crate) fn rank(&self, x: u64) -> u64 {
+        let h = x >> self.low_width;
+        let zeros = self.high.len() - self.len;
+        if h > zeros {
+            return self.len;
+        }
+        let mut p = if h == 0 {
+            0
+        } else {
+            self.high.select0(h - 1) + 1
+        };
+        let mut k = p - h;
+        let low = x & ((1 << self.low_width) - 1);
+        while k < self.len
+            && self.high.get(p)
+            && self.low.get_word(k as usize, self.low_width) < low
+        {
+            k += 1;
+            p += 1;
+        }
+        k
+    }
+}
+
+impl BucketArray for EliasFanoBucketArray {
+    fn get(&self, c: u64) -> u64 {
+        debug_assert!(c < self.len);
+        let h = self.high.select1(c) - c;
+        (h << self.low_width) | self.low.get_word(c as usize, self.low_width)
+    }
+
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.high.size() + self.low.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elias_fano() {
+        let cases: Vec<Vec<u64>> = vec![
+            vec![0],
+            vec![0, 1, 5, 6, 8],
+            vec![0, 0, 0, 3, 3, 3, 3],
+            vec![0, 1, 1, 1, 100, 1000, 1000, 12345],
+            (0..300).map(|i| i * i).collect(),
+        ];
+        for cs in cases {
+            let ef = EliasFanoBucketArray::new(&cs);
+            assert_eq!(ef.len(), cs.len() as u64);
+            for (c, &expected) in cs.iter().enumerate() {
+                assert_eq!(ef.get(c as u64), expected, "cs = {:?}, c = {}", cs, c);
+            }
+            for i in 0..=*cs.last().unwrap() {
+                assert_eq!(ef.search(i), cs.search(i), "cs = {:?}, i = {}", cs, i);
+            }
+            for i in 0..=*cs.last().unwrap() + 2 {
+                let expected = cs.partition_point(|&c| c < i) as u64;
+                assert_eq!(ef.rank(i), expected, "cs = {:?}, i = {}", cs, i);
+            }
+        }
+    }
+}
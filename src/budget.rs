@@ -0,0 +1,166 @@
+/// Limits on the results of a single query, e.g. per request of a multi-tenant server,
+/// so that one heavy query cannot exhaust memory.
+///
+/// Limits are unset by default. [`crate::Search::locate_within`] and [`crate::Search::iter_matches_within`]
+/// stop at the first limit reached and return a [`PartialResult`] telling which one it was.
+/// The number of occurrences is known before they are visited, so results are never allocated beyond the limits.
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::SuffixOrderSampler;
+/// use fm_index::{BackwardSearchIndex, BudgetLimit, FMIndex, QueryBudget};
+///
+/// let index = FMIndex::new(b"abracadabra".repeat(10), RangeConverter::new(b'a', b'z'), SuffixOrderSampler::new());
+/// let search = index.search_backward("abra");
+/// let budget = QueryBudget::new().max_positions(100).max_bytes(64);
+/// let partial = search.locate_within(&budget);
+/// assert_eq!(partial.result.len(), 8);
+/// assert_eq!(partial.exhausted, Some(BudgetLimit::Bytes));
+/// assert!(search.locate_within(&QueryBudget::new().max_positions(100)).is_complete());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueryBudget {
+    max_positions: Option<u64>,
+    max_matches: Option<u64>,
+    max_bytes: Option<usize>,
+}
+
+/// The limit of a [`QueryBudget`] that cut a query short.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BudgetLimit {
+    /// The number of positions resolved with the suffix array.
+    Positions,
+    /// The number of occurrences iterated.
+    Matches,
+    /// The number of bytes of the result buffer.
+    Bytes,
+}
+
+/// The result of a query under a [`QueryBudget`], which holds the results up to the limit
+/// if the query is cut short.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartialResult<R> {
+    pub result: R,
+    /// The limit reached, or `None` if the result is complete.
+    pub exhausted: Option<BudgetLimit>,
+}
+
+impl<R> PartialResult<R> {
+    pub fn is_complete(&self) -> bool {
+        self.exhausted.is_none()
+    }
+}
+
+impl QueryBudget {
+    /// Creates a budget without limits.
+    pub fn new() -> Self {
+        QueryBudget::default()
+    }
+
+    pub fn max_positions(mut self, max_positions: u64) -> Self {
+        self.max_positions = Some(max_positions);
+        self
+    }
+
+    pub fn max_matches(mut self, max_matches: u64) -> Self {
+        self.max_matches = Some(max_matches);
+        self
+    }
+
+    /// Limits the size of the buffer holding the results.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    // Returns how many of `n` results are admitted under `limits`, where each result takes `item_bytes` bytes,
+    // and the tightest limit if it admits fewer than `n`.
+    pub(crate) fn admit(
+        &self,
+        n: u64,
+        item_bytes: usize,
+        limits: &[BudgetLimit],
+    ) -> (u64, Option<BudgetLimit>) {
+        let mut admitted = n;
+        let mut exhausted = None;
+        for &limit in limits {
+            let max = match limit {
+                BudgetLimit::Positions => self.max_positions,
+                BudgetLimit::Matches => self.max_matches,
+                BudgetLimit::Bytes => self.max_bytes.map(|b| (b / item_bytes) as u64),
+            };
+            if let Some(max) = max {
+                if max < admitted {
+                    admitted = max;
+                    exhausted = Some(limit);
+                }
+            }
+        }
+        (admitted, exhausted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::search::BackwardSearchIndex;
+    use crate::suffix_array::SuffixOrderSampler;
+    use crate::FMIndex;
+
+    #[test]
+    fn test_budget() {
+        let index = FMIndex::new(
+            b"mississippi".repeat(20),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(2),
+        );
+        let search = index.search_backward("ssi");
+        let all = search.locate();
+        assert_eq!(all.len(), 40);
+
+        let unlimited = search.locate_within(&QueryBudget::new());
+        assert_eq!(
+            unlimited,
+            PartialResult {
+                result: all.clone(),
+                exhausted: None
+            }
+        );
+
+        for (budget, n, exhausted) in [
+            (QueryBudget::new().max_positions(40), 40, None),
+            (
+                QueryBudget::new().max_positions(10).max_matches(20),
+                10,
+                Some(BudgetLimit::Positions),
+            ),
+            (
+                QueryBudget::new().max_positions(20).max_matches(10),
+                10,
+                Some(BudgetLimit::Matches),
+            ),
+            (
+                QueryBudget::new().max_matches(30).max_bytes(100),
+                12,
+                Some(BudgetLimit::Bytes),
+            ),
+            (QueryBudget::new().max_bytes(7), 0, Some(BudgetLimit::Bytes)),
+        ] {
+            let partial = search.locate_within(&budget);
+            assert_eq!(partial.result, all[..n], "{:?}", budget);
+            assert_eq!(partial.exhausted, exhausted, "{:?}", budget);
+        }
+
+        let budget = QueryBudget::new().max_matches(5).max_bytes(0);
+        let partial = search.iter_matches_within(&budget);
+        assert_eq!(partial.exhausted, Some(BudgetLimit::Matches));
+        assert_eq!(
+            partial.result.map(|m| m.locate()).collect::<Vec<_>>(),
+            all[..5]
+        );
+        assert!(search
+            .iter_matches_within(&QueryBudget::new().max_matches(40))
+            .is_complete());
+    }
+}
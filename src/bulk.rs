@@ -0,0 +1,325 @@
+//! Running counts or locates for a stream of patterns and writing the results to a sink,
+//! for bulk workloads such as pattern files larger than memory.
+//!
+//! Patterns are read in batches of [`BulkOptions::batch_size`], searched by [`crate::QuerySession`]
+//! on [`BulkOptions::threads`] threads, and written in the order of the input,
+//! so memory is bounded by a batch regardless of the number of patterns.
+//! Wrap the sink in an [`std::io::BufWriter`] when it is a file or a socket.
+//!
+//! ```
+//! use fm_index::bulk::{self, BulkOptions, OutputFormat};
+//! use fm_index::converter::RangeConverter;
+//! use fm_index::suffix_array::SuffixOrderSampler;
+//! use fm_index::FMIndex;
+//!
+//! let index = FMIndex::new(b"mississippi".to_vec(), RangeConverter::new(b'a', b'z'), SuffixOrderSampler::new());
+//! let patterns = "ssi\npp\nx\n".as_bytes();
+//! let mut out = vec![];
+//! let n = bulk::count_stream(&index, patterns, &mut out, &BulkOptions::new()).unwrap();
+//! assert_eq!(n, 3);
+//! assert_eq!(String::from_utf8(out).unwrap(), "0\t2\n1\t1\n2\t0\n");
+//!
+//! let mut out = vec![];
+//! let options = BulkOptions::new().output(OutputFormat::JsonLines);
+//! bulk::locate_stream(&index, "ssi\n".as_bytes(), &mut out, &options).unwrap();
+//! assert_eq!(String::from_utf8(out).unwrap(), "{\"id\":0,\"count\":2,\"positions\":[2,5]}\n");
+//! ```
+
+use crate::search::{BackwardSearchIndex, Search};
+use crate::session::QuerySession;
+use crate::suffix_array::IndexWithSA;
+
+use std::io::{self, BufRead, Read, Write};
+
+/// How patterns are delimited in the input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatternFormat {
+    /// One pattern per line, terminated by `\n` or `\r\n`.
+    Lines,
+    /// Each pattern is preceded by its length as a little-endian `u32`, so it may contain any byte.
+    LengthPrefixed,
+}
+
+/// How results are written to the sink, one line per pattern with the pattern's index in the input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Tab-separated `id`, `count` and, for locates, the positions separated by commas.
+    Tsv,
+    /// A JSON object per line with the keys `id`, `count` and, for locates, `positions`.
+    JsonLines,
+}
+
+/// The options of [`count_stream`] and [`locate_stream`].
+#[derive(Clone, Debug)]
+pub struct BulkOptions {
+    input: PatternFormat,
+    output: OutputFormat,
+    batch_size: usize,
+    threads: usize,
+}
+
+impl Default for BulkOptions {
+    fn default() -> Self {
+        BulkOptions {
+            input: PatternFormat::Lines,
+            output: OutputFormat::Tsv,
+            batch_size: 1024,
+            threads: 1,
+        }
+    }
+}
+
+impl BulkOptions {
+    /// Creates options reading lines and writing TSV.
+    pub fn new() -> Self {
+        BulkOptions::default()
+    }
+
+    pub fn input(mut self, input: PatternFormat) -> Self {
+        self.input = input;
+        self
+    }
+
+    pub fn output(mut self, output: OutputFormat) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Sets the number of patterns read at a time, which is 1024 by default.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch_size must be positive");
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Sets the number of threads searching a batch, which is 1 by default.
+    pub fn threads(mut self, threads: usize) -> Self {
+        assert!(threads > 0, "threads must be positive");
+        self.threads = threads;
+        self
+    }
+}
+
+/// Counts the occurrences of each pattern read from `input` and writes them to `output`.
+///
+/// Returns the number of patterns, or the first I/O error, after which the output is incomplete.
+pub fn count_stream<I, R, W>(
+    index: &I,
+    input: R,
+    mut output: W,
+    options: &BulkOptions,
+) -> io::Result<u64>
+where
+    I: BackwardSearchIndex<T = u8> + Sync,
+    R: BufRead,
+    W: Write,
+{
+    run(
+        index,
+        input,
+        options,
+        |search| search.count(),
+        |id, count| write_result(&mut output, options.output, id, *count, None),
+    )
+}
+
+/// Locates the occurrences of each pattern read from `input` and writes their positions,
+/// sorted in ascending order, to `output`.
+///
+/// Returns the number of patterns, or the first I/O error, after which the output is incomplete.
+pub fn locate_stream<I, R, W>(
+    index: &I,
+    input: R,
+    mut output: W,
+    options: &BulkOptions,
+) -> io::Result<u64>
+where
+    I: BackwardSearchIndex<T = u8> + IndexWithSA + Sync,
+    R: BufRead,
+    W: Write,
+{
+    let locate = |search: Search<'_, I>| {
+        let mut positions = search.locate();
+        positions.sort_unstable();
+        positions
+    };
+    run(index, input, options, locate, |id, positions| {
+        write_result(
+            &mut output,
+            options.output,
+            id,
+            positions.len() as u64,
+            Some(positions),
+        )
+    })
+}
+
+fn run<'a, I, R, F, T, G>(
+    index: &'a I,
+    mut input: R,
+    options: &BulkOptions,
+    f: F,
+    mut write: G,
+) -> io::Result<u64>
+where
+    I: BackwardSearchIndex<T = u8> + Sync,
+    R: BufRead,
+    F: Fn(Search<'a, I>) -> T + Sync + Copy,
+    T: Send,
+    G: FnMut(u64, &T) -> io::Result<()>,
+{
+    let mut id = 0;
+    loop {
+        let mut batch = Vec::with_capacity(options.batch_size);
+        while batch.len() < options.batch_size {
+            match read_pattern(&mut input, options.input)? {
+                Some(pattern) => batch.push(pattern),
+                None => break,
+            }
+        }
+        if batch.is_empty() {
+            return Ok(id);
+        }
+        let done = batch.len() < options.batch_size;
+        // the whole batch is searched at once by the session
+        let results = QuerySession::new(index, batch.iter(), f)
+            .batch_size(batch.len())
+            .threads(options.threads)
+            .collect::<Vec<_>>();
+        for result in &results {
+            write(id, result)?;
+            id += 1;
+        }
+        if done {
+            return Ok(id);
+        }
+    }
+}
+
+fn read_pattern<R: BufRead>(input: &mut R, format: PatternFormat) -> io::Result<Option<Vec<u8>>> {
+    match format {
+        PatternFormat::Lines => {
+            let mut line = vec![];
+            if input.read_until(b'\n', &mut line)? == 0 {
+                return Ok(None);
+            }
+            if line.last() == Some(&b'\n') {
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+            }
+            Ok(Some(line))
+        }
+        PatternFormat::LengthPrefixed => {
+            let mut len = [0; 4];
+            if input.fill_buf()?.is_empty() {
+                return Ok(None);
+            }
+            input.read_exact(&mut len)?;
+            // the length is untrusted, so the pattern grows with the bytes actually read
+            let len = u64::from(u32::from_le_bytes(len));
+            let mut pattern = vec![];
+            input.by_ref().take(len).read_to_end(&mut pattern)?;
+            if (pattern.len() as u64) < len {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            Ok(Some(pattern))
+        }
+    }
+}
+
+fn write_result<W: Write>(
+    output: &mut W,
+    format: OutputFormat,
+    id: u64,
+    count: u64,
+    positions: Option<&Vec<u64>>,
+) -> io::Result<()> {
+    let join = |positions: &Vec<u64>| {
+        positions
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    match (format, positions) {
+        (OutputFormat::Tsv, None) => writeln!(output, "{}\t{}", id, count),
+        (OutputFormat::Tsv, Some(positions)) => {
+            writeln!(output, "{}\t{}\t{}", id, count, join(positions))
+        }
+        (OutputFormat::JsonLines, None) => {
+            writeln!(output, "{{\"id\":{},\"count\":{}}}", id, count)
+        }
+        (OutputFormat::JsonLines, Some(positions)) => writeln!(
+            output,
+            "{{\"id\":{},\"count\":{},\"positions\":[{}]}}",
+            id,
+            count,
+            join(positions)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::suffix_array::SuffixOrderSampler;
+    use crate::FMIndex;
+
+    #[test]
+    fn test_bulk() {
+        let index = FMIndex::new(
+            b"mississippi".to_vec(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(1),
+        );
+        let patterns = ["ssi", "i", "", "pp", "x", "issi", "s"];
+
+        let mut input = vec![];
+        for pattern in &patterns {
+            input.extend_from_slice(&(pattern.len() as u32).to_le_bytes());
+            input.extend_from_slice(pattern.as_bytes());
+        }
+        let options = BulkOptions::new()
+            .input(PatternFormat::LengthPrefixed)
+            .batch_size(2)
+            .threads(2);
+        let mut out = vec![];
+        assert_eq!(
+            count_stream(&index, &input[..], &mut out, &options).unwrap(),
+            7
+        );
+        let expected = patterns
+            .iter()
+            .enumerate()
+            .map(|(id, p)| format!("{}\t{}\n", id, index.search_backward(p).count()))
+            .collect::<String>();
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+
+        let lines = "ssi\r\ni\n\npp";
+        let mut out = vec![];
+        let options = BulkOptions::new().batch_size(3);
+        assert_eq!(
+            locate_stream(&index, lines.as_bytes(), &mut out, &options).unwrap(),
+            4
+        );
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "0\t2\t2,5\n1\t4\t1,4,7,10\n2\t12\t0,1,2,3,4,5,6,7,8,9,10,11\n3\t1\t8\n"
+        );
+
+        // truncated in the middle of a pattern
+        let mut out = vec![];
+        let options = BulkOptions::new().input(PatternFormat::LengthPrefixed);
+        let err = count_stream(&index, &input[..input.len() - 1], &mut out, &options).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        // a huge length is not allocated up front
+        let mut out = vec![];
+        let input = [&u32::MAX.to_le_bytes()[..], b"ssi"].concat();
+        let err = count_stream(&index, &input[..], &mut out, &options).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
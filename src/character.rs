@@ -1,7 +1,29 @@
+use crate::converter::RangeConverter;
+
 use num_traits::Num;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
 
+/// A character of indexed texts, which is an unsigned integer of up to 64 bits.
+///
+/// Symbols of wider or custom types are indexed as their codes in a [`SymbolEncoding`], e.g. [`DenseAlphabet`].
 pub trait Character: Into<u64> + Copy + Clone + Num + Ord + std::fmt::Debug {
+    /// Converts `n` into a character, truncating the bits beyond the width of the type.
     fn from_u64(n: u64) -> Self;
+
+    /// Converts `n` into a character, or returns an error if it does not fit in the type.
+    fn try_from_u64(n: u64) -> Result<Self, CharacterOverflow> {
+        let c = Self::from_u64(n);
+        if c.into() == n {
+            Ok(c)
+        } else {
+            Err(CharacterOverflow {
+                value: n,
+                max: Self::from_u64(u64::MAX).into(),
+            })
+        }
+    }
 }
 
 macro_rules! impl_character {
@@ -18,3 +40,258 @@ impl_character!(u64);
 impl_character!(u32);
 impl_character!(u16);
 impl_character!(u8);
+
+/// An error returned by [`Character::try_from_u64`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CharacterOverflow {
+    /// The value which does not fit in the character type.
+    pub value: u64,
+    /// The largest value of the character type.
+    pub max: u64,
+}
+
+impl fmt::Display for CharacterOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} exceeds the largest character {}",
+            self.value, self.max
+        )
+    }
+}
+
+impl std::error::Error for CharacterOverflow {}
+
+/// A mapping of symbols of any type, e.g. `u128` or composite symbols, to a dense alphabet of characters.
+///
+/// Symbols are mapped to codes in `1..=alphabet_size()`, as zero is reserved for the terminator,
+/// and an index is built over the codes of a text and searched with the codes of patterns.
+pub trait SymbolEncoding<S> {
+    type Code: Character;
+
+    /// Returns the code of `symbol`, or `None` if it is not in the alphabet.
+    fn encode(&self, symbol: &S) -> Option<Self::Code>;
+
+    /// Returns the symbol of `code`, or `None` if it is zero or out of the alphabet.
+    ///
+    /// The symbol is returned by value, so that an encoding may compute it from the code.
+    fn decode(&self, code: Self::Code) -> Option<S>;
+
+    /// Returns the number of symbols, excluding the terminator.
+    fn alphabet_size(&self) -> u64;
+
+    /// Encodes a text, or returns an error at the first symbol not in the alphabet.
+    fn encode_text(&self, symbols: &[S]) -> Result<Vec<Self::Code>, InvalidSymbol> {
+        symbols
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                self.encode(s)
+                    .ok_or(InvalidSymbol::Unknown { position: i as u64 })
+            })
+            .collect()
+    }
+
+    /// Encodes a pattern, or returns `None` if it contains a symbol not in the alphabet,
+    /// in which case it occurs nowhere in the text.
+    fn encode_pattern(&self, symbols: &[S]) -> Option<Vec<Self::Code>> {
+        symbols.iter().map(|s| self.encode(s)).collect()
+    }
+
+    /// Returns a converter of the codes into the characters stored in indexes.
+    fn converter(&self) -> RangeConverter<Self::Code> {
+        RangeConverter::new(
+            Self::Code::from_u64(1),
+            Self::Code::from_u64(self.alphabet_size().max(1)),
+        )
+    }
+}
+
+/// A [`SymbolEncoding`] of the symbols of a fixed alphabet, numbered in their order.
+///
+/// The order of codes follows that of symbols, so suffixes of the codes are sorted as those of the symbols.
+/// The alphabet must fit in the code type `K`, which is checked on construction;
+/// [`crate::FMIndexMultiPieces`] takes one more code for the separator, so `K` must hold `len() + 1` there.
+///
+/// ```
+/// use fm_index::suffix_array::SuffixOrderSampler;
+/// use fm_index::{BackwardSearchIndex, DenseAlphabet, FMIndex, SymbolEncoding};
+///
+/// let base = 1u128 << 100;
+/// let text = [base, base + 7, base, base + 7, 3];
+/// let alphabet = DenseAlphabet::<u128, u16>::new(text.iter().copied()).unwrap();
+/// let index = FMIndex::new(alphabet.encode_text(&text).unwrap(), alphabet.converter(), SuffixOrderSampler::new());
+///
+/// let pattern = alphabet.encode_pattern(&[base, base + 7]).unwrap();
+/// let mut positions = index.search_backward(pattern).locate();
+/// positions.sort();
+/// assert_eq!(positions, vec![0, 2]);
+/// assert_eq!(alphabet.encode_pattern(&[base + 1]), None);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DenseAlphabet<S, K> {
+    // sorted and deduplicated
+    symbols: Vec<S>,
+    _k: std::marker::PhantomData<K>,
+}
+
+impl<S, K> DenseAlphabet<S, K>
+where
+    S: Ord,
+    K: Character,
+{
+    /// Builds the alphabet of the distinct `symbols`,
+    /// or returns an error if they are too many to be coded by `K`.
+    pub fn new<I: IntoIterator<Item = S>>(symbols: I) -> Result<Self, InvalidSymbol> {
+        let mut symbols = symbols.into_iter().collect::<Vec<_>>();
+        symbols.sort_unstable();
+        symbols.dedup();
+        if let Err(CharacterOverflow { value, max }) = K::try_from_u64(symbols.len() as u64) {
+            return Err(InvalidSymbol::AlphabetTooLarge { size: value, max });
+        }
+        Ok(DenseAlphabet {
+            symbols,
+            _k: std::marker::PhantomData,
+        })
+    }
+
+    /// Returns the symbols in the order of their codes.
+    pub fn symbols(&self) -> &[S] {
+        &self.symbols
+    }
+
+    /// Returns the number of symbols, which is also the largest code.
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Returns true if the alphabet has no symbols, so that only empty texts are encoded.
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+}
+
+impl<S, K> SymbolEncoding<S> for DenseAlphabet<S, K>
+where
+    S: Ord + Clone,
+    K: Character,
+{
+    type Code = K;
+
+    fn encode(&self, symbol: &S) -> Option<K> {
+        let i = self.symbols.binary_search(symbol).ok()?;
+        Some(K::from_u64(i as u64 + 1))
+    }
+
+    fn decode(&self, code: K) -> Option<S> {
+        let i = code.into().checked_sub(1)?;
+        self.symbols.get(i as usize).cloned()
+    }
+
+    fn alphabet_size(&self) -> u64 {
+        self.symbols.len() as u64
+    }
+}
+
+/// An error returned by [`SymbolEncoding`] and [`DenseAlphabet`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidSymbol {
+    /// The symbol at `position` is not in the alphabet.
+    Unknown { position: u64 },
+    /// The alphabet has more than `max` symbols, the largest code.
+    AlphabetTooLarge { size: u64, max: u64 },
+}
+
+impl fmt::Display for InvalidSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvalidSymbol::Unknown { position } => {
+                write!(f, "symbol at {} is not in the alphabet", position)
+            }
+            InvalidSymbol::AlphabetTooLarge { size, max } => write!(
+                f,
+                "alphabet of {} symbols exceeds the largest code {}",
+                size, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InvalidSymbol {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::BackwardSearchIndex;
+    use crate::suffix_array::SuffixOrderSampler;
+    use crate::{FMIndex, FMIndexMultiPieces, PieceId};
+
+    #[test]
+    fn test_try_from_u64() {
+        assert_eq!(u8::try_from_u64(255), Ok(255));
+        assert_eq!(
+            u8::try_from_u64(256),
+            Err(CharacterOverflow {
+                value: 256,
+                max: 255
+            })
+        );
+        assert_eq!(u16::try_from_u64(1 << 16).unwrap_err().max, 65535);
+        assert_eq!(u64::try_from_u64(u64::MAX), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn test_dense_alphabet() {
+        let symbols = (0..20u128).map(|i| (i % 5) << 90).collect::<Vec<_>>();
+        let alphabet = DenseAlphabet::<u128, u8>::new(symbols.clone()).unwrap();
+        assert_eq!(alphabet.len(), 5);
+        let codes = alphabet.encode_text(&symbols).unwrap();
+        assert!(codes.iter().all(|&c| (1..=5).contains(&c)));
+        // codes keep the order of symbols
+        assert!(alphabet.symbols().windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(alphabet.decode(codes[3]), Some(3 << 90));
+        assert_eq!(alphabet.decode(0), None);
+        assert_eq!(alphabet.decode(6), None);
+        assert_eq!(
+            alphabet.encode_text(&[0, 1, 2]),
+            Err(InvalidSymbol::Unknown { position: 1 })
+        );
+
+        let index = FMIndex::new(codes, alphabet.converter(), SuffixOrderSampler::new());
+        let pattern = alphabet.encode_pattern(&[3 << 90, 4 << 90]).unwrap();
+        let search = index.search_backward(pattern);
+        let mut positions = search.locate();
+        positions.sort();
+        assert_eq!(positions, vec![3, 8, 13, 18]);
+        // iterators yield codes to be decoded
+        for m in search.iter_matches() {
+            let code = m.iter_forward().nth(1).unwrap();
+            assert_eq!(alphabet.decode(code), Some(4 << 90));
+        }
+
+        // pieces are delimited by zero, which is never a code
+        let small = DenseAlphabet::<u128, u8>::new([7, 9]).unwrap();
+        let text = [&[7, 9][..], &[9, 7, 7]]
+            .iter()
+            .map(|p| small.encode_text(p).unwrap())
+            .collect::<Vec<_>>()
+            .join(&0);
+        let index = FMIndexMultiPieces::new(text, small.converter(), SuffixOrderSampler::new());
+        let search = index.search_backward(small.encode_pattern(&[9, 7]).unwrap());
+        assert_eq!(search.count(), 1);
+        assert_eq!(index.piece_len(PieceId::from(1)), 3);
+    }
+
+    #[test]
+    fn test_alphabet_too_large() {
+        assert_eq!(
+            DenseAlphabet::<u32, u8>::new(0..256).unwrap_err(),
+            InvalidSymbol::AlphabetTooLarge {
+                size: 256,
+                max: 255
+            }
+        );
+        assert!(DenseAlphabet::<u32, u8>::new(0..255).is_ok());
+    }
+}
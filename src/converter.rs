@@ -1,14 +1,22 @@
 use crate::character::Character;
+use crate::util::strict_assert;
 
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 pub trait Converter<T> {
     fn convert(&self, c: T) -> T;
     fn convert_inv(&self, c: T) -> T;
     fn len(&self) -> u64;
+
+    /// Returns whether `c` can be converted, i.e. the sentinel or a character of the alphabet.
+    fn contains(&self, _c: T) -> bool {
+        true
+    }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct RangeConverter<T> {
     min: T,
     max: T,
@@ -19,7 +27,7 @@ where
     T: Character,
 {
     pub fn new(min: T, max: T) -> Self {
-        debug_assert!(!T::is_zero(&min), "min should not be zero");
+        strict_assert!(!T::is_zero(&min), "min should not be zero");
         RangeConverter { min, max }
     }
 }
@@ -48,8 +56,13 @@ where
         // [min, max] + sentinel
         (self.max - self.min).into() + 2
     }
+
+    fn contains(&self, c: T) -> bool {
+        c.is_zero() || (self.min <= c && c <= self.max)
+    }
 }
 
+#[derive(Clone, PartialEq)]
 pub struct IdConverter {
     size: u64,
 }
@@ -60,7 +73,10 @@ impl IdConverter {
     }
 }
 
-impl<T> Converter<T> for IdConverter {
+impl<T> Converter<T> for IdConverter
+where
+    T: Character,
+{
     fn convert(&self, c: T) -> T {
         c
     }
@@ -70,9 +86,137 @@ impl<T> Converter<T> for IdConverter {
     fn len(&self) -> u64 {
         self.size
     }
+
+    fn contains(&self, c: T) -> bool {
+        c.into() < self.size
+    }
 }
 
 pub trait IndexWithConverter<T> {
     type C: Converter<T>;
     fn get_converter(&self) -> &Self::C;
 }
+
+/// A mapping of characters applied to both texts and patterns.
+///
+/// The sentinel (zero) must be mapped to itself.
+pub trait Normalizer<T> {
+    fn normalize(&self, c: T) -> T;
+}
+
+impl<T, F> Normalizer<T> for F
+where
+    F: Fn(T) -> T,
+{
+    fn normalize(&self, c: T) -> T {
+        self(c)
+    }
+}
+
+/// A normalizer which maps ASCII upper case letters to lower case ones.
+#[derive(Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AsciiLowercase;
+
+impl Normalizer<u8> for AsciiLowercase {
+    fn normalize(&self, c: u8) -> u8 {
+        c.to_ascii_lowercase()
+    }
+}
+
+impl Normalizer<u32> for AsciiLowercase {
+    fn normalize(&self, c: u32) -> u32 {
+        if (u32::from(b'A')..=u32::from(b'Z')).contains(&c) {
+            c + u32::from(b'a' - b'A')
+        } else {
+            c
+        }
+    }
+}
+
+/// A converter which normalizes characters before converting them.
+///
+/// Since the converter is stored in the index, the same normalization is applied
+/// at construction and at query time.
+/// Iterators return normalized characters.
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NormalizedConverter<C, N> {
+    converter: C,
+    normalizer: N,
+}
+
+impl<C, N> NormalizedConverter<C, N> {
+    pub fn new(converter: C, normalizer: N) -> Self {
+        NormalizedConverter {
+            converter,
+            normalizer,
+        }
+    }
+}
+
+impl<T, C, N> Converter<T> for NormalizedConverter<C, N>
+where
+    C: Converter<T>,
+    N: Normalizer<T>,
+{
+    fn convert(&self, c: T) -> T {
+        self.converter.convert(self.normalizer.normalize(c))
+    }
+
+    fn convert_inv(&self, c: T) -> T {
+        self.converter.convert_inv(c)
+    }
+
+    fn len(&self) -> u64 {
+        self.converter.len()
+    }
+
+    fn contains(&self, c: T) -> bool {
+        self.converter.contains(self.normalizer.normalize(c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::BackwardSearchIndex;
+    use crate::suffix_array::SuffixOrderSampler;
+    use crate::FMIndex;
+
+    #[test]
+    fn test_normalized_converter() {
+        let text = "Mississippi MISSISSIPPI".to_string().into_bytes();
+        let converter = NormalizedConverter::new(RangeConverter::new(b' ', b'z'), AsciiLowercase);
+        let index = FMIndex::new(text, converter, SuffixOrderSampler::new().level(1));
+
+        let mut res = index.search_backward("ISS").locate();
+        res.sort();
+        assert_eq!(res, vec![1, 4, 13, 16]);
+        assert_eq!(index.search_backward("mIsSiSsIpPi").count(), 2);
+        assert_eq!(
+            index
+                .search_backward("pi m")
+                .iter_forward(0)
+                .take(6)
+                .collect::<Vec<_>>(),
+            b"pi mis".to_owned()
+        );
+    }
+
+    #[test]
+    fn test_closure_normalizer() {
+        // ignore the difference between 'u' and 'v'
+        let text = "IVLIVS CAESAR".to_string().into_bytes();
+        let converter = NormalizedConverter::new(RangeConverter::new(b' ', b'Z'), |c: u8| {
+            if c == b'V' {
+                b'U'
+            } else {
+                c
+            }
+        });
+        let index = FMIndex::new(text, converter, SuffixOrderSampler::new().level(1));
+        assert_eq!(index.search_backward("IULIUS").count(), 1);
+        assert_eq!(index.search_backward("IVLIVS").count(), 1);
+    }
+}
@@ -0,0 +1,194 @@
+//! Readers of JSON-lines and CSV document dumps into texts of multi-piece indexes, enabled by the `corpus-io` feature.
+//!
+//! The text of each document is read from a designated field or column and becomes a piece of
+//! [`crate::FMIndexMultiPieces`].
+//! Documents with empty texts are skipped, so the row of each piece is kept as its payload,
+//! which can be attached to the index as keys by [`crate::FMIndexMultiPieces::with_keys`].
+//!
+//! ```
+//! use fm_index::converter::RangeConverter;
+//! use fm_index::corpus;
+//! use fm_index::suffix_array::SuffixOrderSampler;
+//! use fm_index::{BackwardSearchIndex, FMIndexMultiPieces, IndexWithPieces};
+//!
+//! let jsonl = r#"{"id": 1, "body": "red apple"}
+//! {"id": 2, "body": ""}
+//! {"id": 3, "body": "green apple pie"}
+//! "#;
+//! let documents = corpus::read_jsonl(jsonl.as_bytes(), "body").unwrap();
+//! assert_eq!(documents.rows, vec![0, 2]);
+//! let index = FMIndexMultiPieces::new(documents.text, RangeConverter::new(b' ', b'~'), SuffixOrderSampler::new())
+//!     .with_keys(documents.rows);
+//! let search = index.search_backward("pie");
+//! let piece = search.iter_matches().next().unwrap().piece_id();
+//! assert_eq!(index.key_by_piece_id(piece), Some(2));
+//! ```
+
+use std::io::{self, BufRead};
+
+/// The texts of documents as the text of a multi-piece index, and their rows.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Documents {
+    /// The texts of documents delimited by zero.
+    pub text: Vec<u8>,
+    /// The 0-origin row of each piece in the input, not counting the CSV header.
+    pub rows: Vec<u64>,
+}
+
+impl Documents {
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    fn push(&mut self, row: u64, text: &str) -> io::Result<()> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        if text.contains('\0') {
+            return Err(invalid(format!(
+                "the text of row {} contains a zero byte",
+                row
+            )));
+        }
+        self.text.extend_from_slice(text.as_bytes());
+        self.text.push(0);
+        self.rows.push(row);
+        Ok(())
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reads JSON objects, one per line, and takes the string of `field` from each.
+///
+/// Blank lines are skipped but counted as rows.
+/// Returns an error of kind `InvalidData` for a line which is not an object with a string `field`.
+pub fn read_jsonl<R: BufRead>(reader: R, field: &str) -> io::Result<Documents> {
+    let mut documents = Documents::default();
+    for (row, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: serde_json::Value =
+            serde_json::from_str(&line).map_err(|e| invalid(format!("row {}: {}", row, e)))?;
+        match value.get(field).and_then(|v| v.as_str()) {
+            Some(text) => documents.push(row as u64, text)?,
+            None => {
+                return Err(invalid(format!(
+                    "row {} has no string field {:?}",
+                    row, field
+                )));
+            }
+        }
+    }
+    Ok(documents)
+}
+
+/// Reads a CSV file with a header row and takes the field of `column` from each record.
+///
+/// Fields may be quoted with `"`, in which case they may contain commas, newlines and doubled quotes.
+/// Returns an error of kind `InvalidData` if the header has no `column` or a record is too short.
+pub fn read_csv<R: BufRead>(mut reader: R, column: &str) -> io::Result<Documents> {
+    let mut documents = Documents::default();
+    let header = match read_record(&mut reader)? {
+        Some(header) => header,
+        None => return Ok(documents),
+    };
+    let i = header
+        .iter()
+        .position(|name| name == column)
+        .ok_or_else(|| invalid(format!("the header has no column {:?}", column)))?;
+    let mut row = 0;
+    while let Some(record) = read_record(&mut reader)? {
+        match record.get(i) {
+            Some(text) => documents.push(row, text)?,
+            None => return Err(invalid(format!("row {} has no column {:?}", row, column))),
+        }
+        row += 1;
+    }
+    Ok(documents)
+}
+
+// Reads the fields of a record, which may span lines within quotes.
+fn read_record<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<String>>> {
+    let mut line = String::new();
+    loop {
+        if reader.read_line(&mut line)? == 0 {
+            if line.is_empty() {
+                return Ok(None);
+            }
+            break;
+        }
+        // a record ends at a newline outside of quotes
+        if line.matches('"').count().is_multiple_of(2) {
+            break;
+        }
+    }
+    while line.ends_with('\n') || line.ends_with('\r') {
+        line.pop();
+    }
+
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            (c, _) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(invalid("a quoted field is not closed".to_string()));
+    }
+    fields.push(field);
+    Ok(Some(fields))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_jsonl() {
+        let jsonl = "{\"t\": \"a\\nb\"}\n\n{\"t\": \"c\", \"u\": 1}\n{\"t\": \"\"}\n{\"t\": \"d\"}";
+        let documents = read_jsonl(jsonl.as_bytes(), "t").unwrap();
+        assert_eq!(documents.text, b"a\nb\0c\0d\0".to_vec());
+        assert_eq!(documents.rows, vec![0, 2, 4]);
+
+        for jsonl in &["{\"t\": 1}", "{\"u\": \"a\"}", "{\"t\": \"a\"", "[\"a\"]"] {
+            let err = read_jsonl(jsonl.as_bytes(), "t").unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", jsonl);
+        }
+    }
+
+    #[test]
+    fn test_read_csv() {
+        let csv =
+            "id,text\r\n1,plain\r\n2,\"quoted, with \"\"quotes\"\"\nand a newline\"\n3,\n4,last";
+        let documents = read_csv(csv.as_bytes(), "text").unwrap();
+        assert_eq!(
+            documents.text,
+            b"plain\0quoted, with \"quotes\"\nand a newline\0last\0".to_vec()
+        );
+        assert_eq!(documents.rows, vec![0, 1, 3]);
+        assert!(read_csv("".as_bytes(), "text").unwrap().is_empty());
+
+        for csv in &["id,body\n1,a", "id,text\n1", "id,text\n1,\"a"] {
+            let err = read_csv(csv.as_bytes(), "text").unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", csv);
+        }
+    }
+}
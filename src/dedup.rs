@@ -0,0 +1,131 @@
+use crate::character::Character;
+use crate::converter::Converter;
+use crate::iter::ForwardIterableIndex;
+use crate::multi_pieces::FMIndexMultiPieces;
+use crate::piece::{IndexWithPieces, PieceId};
+use crate::search::BackwardSearchIndex;
+
+/// A group of identical pieces found by [`FMIndexMultiPieces::duplicates`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateCluster {
+    /// The identical pieces in ascending order of IDs.
+    pub pieces: Vec<PieceId>,
+    /// The other pieces which contain the pieces as a proper substring, in ascending order of IDs.
+    pub contained_in: Vec<PieceId>,
+}
+
+impl<T, C, S> FMIndexMultiPieces<T, C, S>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    /// Finds pieces which are duplicates of or contained in other pieces.
+    ///
+    /// Each cluster consists of identical pieces, and is returned only if it has more than one piece
+    /// or its pieces occur in other pieces.
+    /// The rows of the pieces enclosed by separators are adjacent when the pieces are identical,
+    /// so each distinct piece is extracted and searched only once.
+    /// Finding the containing pieces walks LF-mapping back from each occurrence,
+    /// which can be slow for short pieces occurring in many long pieces.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::NullSampler;
+    /// use fm_index::{FMIndexMultiPieces, PieceId};
+    ///
+    /// let text = b"banana\0apple\0nan\0banana".to_vec();
+    /// let index = FMIndexMultiPieces::new(text, RangeConverter::new(b'a', b'z'), NullSampler::new());
+    /// let clusters = index.duplicates();
+    /// assert_eq!(clusters.len(), 2);
+    /// assert_eq!(clusters[0].pieces, vec![PieceId::from(0), PieceId::from(3)]);
+    /// assert_eq!(clusters[1].pieces, vec![PieceId::from(2)]);
+    /// assert_eq!(clusters[1].contained_in, vec![PieceId::from(0), PieceId::from(3)]);
+    /// ```
+    pub fn duplicates(&self) -> Vec<DuplicateCluster> {
+        let zero = [T::zero()];
+        let rows = self.boundary().separator_rows();
+        let mut clusters = vec![];
+        // separator rows are in the order of the pieces following them
+        let mut i = rows.start;
+        while i < rows.end {
+            let piece = self
+                .iter_forward(i)
+                .skip(1)
+                .take_while(|c| !c.is_zero())
+                .collect::<Vec<_>>();
+            let e = self
+                .search_backward(zero)
+                .search_backward(&piece)
+                .search_backward(zero)
+                .get_range()
+                .end;
+            debug_assert!(i < e);
+            let mut pieces = (i..e).map(|r| self.piece_id(r)).collect::<Vec<_>>();
+            pieces.sort();
+
+            let mut contained_in = vec![];
+            if !piece.is_empty() {
+                for r in self.search_backward(&piece).get_range().rows() {
+                    let id = self.piece_id(r);
+                    if pieces.binary_search(&id).is_err() {
+                        contained_in.push(id);
+                    }
+                }
+            }
+            contained_in.sort();
+            contained_in.dedup();
+
+            if pieces.len() > 1 || !contained_in.is_empty() {
+                clusters.push(DuplicateCluster {
+                    pieces,
+                    contained_in,
+                });
+            }
+            i = e;
+        }
+        clusters.sort_by(|a, b| a.pieces[0].cmp(&b.pieces[0]));
+        clusters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::suffix_array::NullSampler;
+
+    #[test]
+    fn test_duplicates() {
+        let pieces = ["ab", "xabx", "ab", "cd", "b", "cd", "ab", "abc"];
+        let text = pieces.join("\0").into_bytes();
+        let index =
+            FMIndexMultiPieces::new(text, RangeConverter::new(b'a', b'z'), NullSampler::new());
+
+        let mut expected = vec![];
+        for (i, p) in pieces.iter().enumerate() {
+            if pieces[..i].contains(p) {
+                continue;
+            }
+            let same = (0..pieces.len())
+                .filter(|&j| pieces[j] == *p)
+                .map(|j| PieceId::from(j as u64))
+                .collect::<Vec<_>>();
+            let contained_in = (0..pieces.len())
+                .filter(|&j| pieces[j] != *p && pieces[j].contains(p))
+                .map(|j| PieceId::from(j as u64))
+                .collect::<Vec<_>>();
+            if same.len() > 1 || !contained_in.is_empty() {
+                expected.push(DuplicateCluster {
+                    pieces: same,
+                    contained_in,
+                });
+            }
+        }
+        assert_eq!(index.duplicates(), expected);
+
+        let text = b"abc\0def".to_vec();
+        let index =
+            FMIndexMultiPieces::new(text, RangeConverter::new(b'a', b'z'), NullSampler::new());
+        assert_eq!(index.duplicates(), vec![]);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::iter::BackwardIterableIndex;
+
+use std::fmt;
+
+/// The components that differ between two indexes of the same type, returned by their `diff` methods.
+///
+/// It is meant for checking that different construction paths,
+/// e.g. a deserialized index and a freshly built one, produce identical indexes.
+/// Indexes are equal by `==` if and only if no component differs.
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::SuffixOrderSampler;
+/// use fm_index::FMIndex;
+///
+/// let converter = RangeConverter::new(b'a', b'z');
+/// let a = FMIndex::new(b"mississippi".to_vec(), converter.clone(), SuffixOrderSampler::new().level(1));
+/// let b = FMIndex::new(b"mississippi".to_vec(), converter.clone(), SuffixOrderSampler::new().level(2));
+/// let diff = a.diff(&b);
+/// assert_eq!(diff.components, vec!["suffix_array"]);
+/// assert!(a != b);
+///
+/// let c = FMIndex::new(b"misissippi".to_vec(), converter, SuffixOrderSampler::new().level(1));
+/// assert_eq!(a.diff(&c).components, vec!["bwt", "cs", "suffix_array"]);
+/// assert!(a.diff(&c).first_bwt_mismatch.is_some());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IndexDiff {
+    /// The names of the differing components, e.g. `"bwt"` or `"suffix_array"`, in the order of comparison.
+    pub components: Vec<&'static str>,
+    /// The first row whose characters differ in the BWTs, or the length of the shorter one if it is a prefix of the other.
+    pub first_bwt_mismatch: Option<u64>,
+}
+
+impl IndexDiff {
+    pub fn is_identical(&self) -> bool {
+        self.components.is_empty()
+    }
+
+    pub(crate) fn check(&mut self, component: &'static str, differs: bool) {
+        if differs {
+            self.components.push(component);
+        }
+    }
+
+    // Compares the BWTs by characters if their structures differ, which may also be due to layouts.
+    pub(crate) fn check_bwt<I>(&mut self, a: &I, b: &I, structure_differs: bool)
+    where
+        I: BackwardIterableIndex,
+        I::T: PartialEq,
+    {
+        if !structure_differs {
+            return;
+        }
+        let n = a.len().min(b.len());
+        self.first_bwt_mismatch = (0..n)
+            .find(|&i| a.get_l(i) != b.get_l(i))
+            .or(if a.len() != b.len() { Some(n) } else { None });
+        self.check("bwt", self.first_bwt_mismatch.is_some());
+    }
+}
+
+impl fmt::Display for IndexDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_identical() {
+            return write!(f, "identical");
+        }
+        write!(f, "differ in {}", self.components.join(", "))?;
+        if let Some(i) = self.first_bwt_mismatch {
+            write!(f, " (the BWTs diverge at row {})", i)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::converter::RangeConverter;
+    use crate::suffix_array::{Scratch, SuffixOrderSampler};
+    use crate::{FMIndex, FMIndexMultiPieces, HybridFMIndex, RLFMIndex};
+
+    #[test]
+    fn test_diff() {
+        let text = b"abracadabra mississippi".to_vec();
+        let converter = RangeConverter::new(b' ', b'z');
+        let sampler = || SuffixOrderSampler::new().level(2);
+
+        let a = FMIndex::new(text.clone(), converter.clone(), sampler());
+        let b = FMIndex::new_with_scratch(
+            text.clone(),
+            converter.clone(),
+            sampler(),
+            &mut Scratch::new(),
+        );
+        assert!(a == b);
+        assert!(a.diff(&b).is_identical());
+        assert_eq!(a.diff(&b).to_string(), "identical");
+        let mut other = text.clone();
+        other[5] = b'x';
+        let b = FMIndex::new(other.clone(), converter.clone(), sampler());
+        let diff = a.diff(&b);
+        assert_eq!(diff.components, vec!["bwt", "cs", "suffix_array"]);
+        assert!(diff.first_bwt_mismatch.is_some());
+        let b = FMIndex::new(text[..10].to_vec(), converter.clone(), sampler());
+        assert_eq!(a.diff(&b).first_bwt_mismatch.map(|i| i <= 11), Some(true));
+
+        let a = RLFMIndex::new(text.clone(), converter.clone(), sampler());
+        let b = RLFMIndex::new(text.clone(), converter.clone(), SuffixOrderSampler::new());
+        assert_eq!(a.diff(&b).components, vec!["suffix_array"]);
+        assert!(a != b);
+
+        let a = HybridFMIndex::new_with_block_level(text.clone(), converter.clone(), sampler(), 2);
+        let b = HybridFMIndex::new_with_block_level(text.clone(), converter.clone(), sampler(), 3);
+        let diff = a.diff(&b);
+        assert_eq!(diff.components, vec!["level", "blocks"]);
+        assert_eq!(diff.first_bwt_mismatch, None);
+        assert_eq!(diff.to_string(), "differ in level, blocks");
+
+        let text = b"apple\0banana\0cherry".to_vec();
+        let converter = RangeConverter::new(b'a', b'z');
+        let a = FMIndexMultiPieces::new(text.clone(), converter.clone(), sampler());
+        let b = FMIndexMultiPieces::new(text, converter, sampler()).with_keys(vec![3, 1, 2]);
+        assert_eq!(a.diff(&b).components, vec!["keys"]);
+    }
+}
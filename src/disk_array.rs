@@ -0,0 +1,431 @@
+use crate::suffix_array::{ArraySampler, PartialArray};
+use crate::util::strict_assert;
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const WORD: usize = std::mem::size_of::<u64>();
+
+/// A suffix-order sampled suffix array stored in a file, built by [`DiskSampler`].
+///
+/// Samples are read in blocks, and recently used blocks are kept in an LRU cache,
+/// so that locating pays a bounded amount of memory and IO while the rest of the index stays in memory.
+/// The file consists of the samples as little-endian 64-bit integers.
+///
+/// The cache is the only state shared by queries of an index, and is locked while a block is looked up.
+/// It can be split into shards with their own file handles by [`DiskSampledArray::open_sharded`],
+/// so that concurrent readers mostly lock different shards.
+///
+/// # Panics
+///
+/// [`PartialArray::get`] panics if reading the file fails, e.g. if it has been removed.
+pub struct DiskSampledArray {
+    path: PathBuf,
+    level: usize,
+    len: u64,
+    samples: u64,
+    block_size: usize,
+    // block `b` is cached in `shards[b % shards.len()]`
+    shards: Vec<Mutex<BlockCache>>,
+}
+
+struct BlockCache {
+    file: File,
+    capacity: usize,
+    // block number -> (time of the last use, samples)
+    blocks: HashMap<u64, (u64, Vec<u64>)>,
+    clock: u64,
+    reads: u64,
+}
+
+impl BlockCache {
+    fn get(&mut self, block: u64, block_size: usize, samples: u64) -> &[u64] {
+        self.clock += 1;
+        let clock = self.clock;
+        if !self.blocks.contains_key(&block) {
+            if self.blocks.len() >= self.capacity {
+                let lru = *self
+                    .blocks
+                    .iter()
+                    .min_by_key(|(_, (used, _))| *used)
+                    .unwrap()
+                    .0;
+                self.blocks.remove(&lru);
+            }
+            let start = block * block_size as u64;
+            let n = (samples - start).min(block_size as u64) as usize;
+            let mut buf = vec![0; n * WORD];
+            self.file
+                .seek(SeekFrom::Start(start * WORD as u64))
+                .and_then(|_| self.file.read_exact(&mut buf))
+                .expect("failed to read suffix array samples");
+            let words = buf
+                .chunks_exact(WORD)
+                .map(|w| u64::from_le_bytes(w.try_into().unwrap()))
+                .collect();
+            self.reads += 1;
+            self.blocks.insert(block, (clock, words));
+        }
+        let entry = self.blocks.get_mut(&block).unwrap();
+        entry.0 = clock;
+        &entry.1
+    }
+}
+
+impl DiskSampledArray {
+    /// Opens samples written by [`DiskSampler`] for a suffix array of length `len`.
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        level: usize,
+        len: u64,
+        block_size: usize,
+        cache_blocks: usize,
+    ) -> std::io::Result<Self> {
+        Self::open_sharded(path, level, len, block_size, cache_blocks, 1)
+    }
+
+    /// Opens samples like [`DiskSampledArray::open`] with the cache split into `shards`,
+    /// each of which keeps `cache_blocks / shards` blocks (rounded up) and its own file handle.
+    ///
+    /// Returns an error of kind [`std::io::ErrorKind::InvalidInput`] if `block_size`, `cache_blocks` or `shards` is zero.
+    pub fn open_sharded<P: AsRef<Path>>(
+        path: P,
+        level: usize,
+        len: u64,
+        block_size: usize,
+        cache_blocks: usize,
+        shards: usize,
+    ) -> std::io::Result<Self> {
+        for (name, value) in [
+            ("block_size", block_size),
+            ("cache_blocks", cache_blocks),
+            ("shards", shards),
+        ] {
+            if value == 0 {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("{} must be positive", name),
+                ));
+            }
+        }
+        let path = path.as_ref().to_path_buf();
+        let samples = ((len - 1) >> level) + 1;
+        let shards = (0..shards)
+            .map(|_| {
+                let file = File::open(&path)?;
+                if file.metadata()?.len() != samples * WORD as u64 {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "the file size does not match the number of samples",
+                    ));
+                }
+                Ok(Mutex::new(BlockCache {
+                    file,
+                    capacity: cache_blocks.div_ceil(shards),
+                    blocks: HashMap::new(),
+                    clock: 0,
+                    reads: 0,
+                }))
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        Ok(DiskSampledArray {
+            path,
+            level,
+            len,
+            samples,
+            block_size,
+            shards,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the number of blocks read from the file so far.
+    pub fn reads(&self) -> u64 {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().reads)
+            .sum()
+    }
+
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+}
+
+impl PartialArray for DiskSampledArray {
+    fn get(&self, i: u64) -> Option<u64> {
+        strict_assert!(i < self.len);
+        if i & ((1 << self.level) - 1) != 0 {
+            return None;
+        }
+        let k = i >> self.level;
+        let block_size = self.block_size as u64;
+        let b = k / block_size;
+        let mut cache = self.shards[(b % self.shards.len() as u64) as usize]
+            .lock()
+            .unwrap();
+        let block = cache.get(b, self.block_size, self.samples);
+        Some(block[(k % block_size) as usize])
+    }
+
+    /// Returns the size in memory, which is bounded by the capacity of the cache.
+    fn size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self
+                .shards
+                .iter()
+                .map(|shard| {
+                    let cache = shard.lock().unwrap();
+                    cache
+                        .blocks
+                        .values()
+                        .map(|(_, block)| block.len() * WORD)
+                        .sum::<usize>()
+                })
+                .sum::<usize>()
+    }
+
+    fn sample_level(&self) -> Option<usize> {
+        Some(self.level)
+    }
+}
+
+/// A sampler which writes every `2^level`-th suffix array entry in suffix order to a file.
+///
+/// The file is created or truncated by [`ArraySampler::sample`], which panics if writing fails.
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::DiskSampler;
+/// use fm_index::{BackwardSearchIndex, FMIndex};
+///
+/// let path = std::env::temp_dir().join(format!("fm-index-doc-{}.sa", std::process::id()));
+/// let index = FMIndex::new(
+///     b"mississippi".to_vec(),
+///     RangeConverter::new(b'a', b'z'),
+///     DiskSampler::new(&path).level(1).cache_blocks(2),
+/// );
+/// let mut positions = index.search_backward("ssi").locate();
+/// positions.sort();
+/// assert_eq!(positions, vec![2, 5]);
+/// std::fs::remove_file(&path).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct DiskSampler {
+    path: PathBuf,
+    level: usize,
+    block_size: usize,
+    cache_blocks: usize,
+    shards: usize,
+}
+
+impl DiskSampler {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        DiskSampler {
+            path: path.as_ref().to_path_buf(),
+            level: 0,
+            block_size: 512,
+            cache_blocks: 64,
+            shards: 1,
+        }
+    }
+
+    pub fn level(mut self, level: usize) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets the number of samples read at a time, which is 512 by default.
+    ///
+    /// Panics if `block_size` is zero.
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        assert!(block_size > 0, "block_size must be positive");
+        self.block_size = block_size;
+        self
+    }
+
+    /// Sets the number of blocks kept in memory, which is 64 by default.
+    ///
+    /// Panics if `cache_blocks` is zero.
+    pub fn cache_blocks(mut self, cache_blocks: usize) -> Self {
+        assert!(cache_blocks > 0, "cache_blocks must be positive");
+        self.cache_blocks = cache_blocks;
+        self
+    }
+
+    /// Sets the number of shards of the cache, which is 1 by default.
+    /// See [`DiskSampledArray::open_sharded`].
+    ///
+    /// Panics if `shards` is zero.
+    pub fn shards(mut self, shards: usize) -> Self {
+        assert!(shards > 0, "shards must be positive");
+        self.shards = shards;
+        self
+    }
+
+    fn write(&self, sa: &[u64]) -> std::io::Result<()> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&self.path)?;
+        let mut writer = BufWriter::new(file);
+        for &p in sa.iter().step_by(1 << self.level) {
+            writer.write_all(&p.to_le_bytes())?;
+        }
+        writer.flush()
+    }
+}
+
+impl ArraySampler<DiskSampledArray> for DiskSampler {
+    fn sample(&self, sa: Vec<u64>) -> DiskSampledArray {
+        self.sample_slice(&sa)
+    }
+
+    fn sample_slice(&self, sa: &[u64]) -> DiskSampledArray {
+        let n = sa.len();
+        debug_assert!(n > 0);
+        debug_assert!(
+            n > (1 << self.level),
+            "sampling level L must satisfy 2^L < text_len (L = {}, text_len = {})",
+            self.level,
+            n,
+        );
+        self.write(sa)
+            .and_then(|_| {
+                DiskSampledArray::open_sharded(
+                    &self.path,
+                    self.level,
+                    n as u64,
+                    self.block_size,
+                    self.cache_blocks,
+                    self.shards,
+                )
+            })
+            .unwrap_or_else(|e| {
+                panic!(
+                    "failed to write suffix array samples to {}: {}",
+                    self.path.display(),
+                    e
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::suffix_array::{IndexWithSA, SuffixOrderSampler};
+    use crate::{BackwardSearchIndex, FMIndex};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_disk_sampled_array() {
+        let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+        let text = (0..300)
+            .map(|_| rng.gen_range(b'a', b'e'))
+            .collect::<Vec<_>>();
+        let path = std::env::temp_dir().join(format!("fm-index-test-{}.sa", std::process::id()));
+        let converter = RangeConverter::new(b'a', b'z');
+        let expected = FMIndex::new(
+            text.clone(),
+            converter.clone(),
+            SuffixOrderSampler::new().level(2),
+        );
+        let index = FMIndex::new(
+            text,
+            converter,
+            DiskSampler::new(&path)
+                .level(2)
+                .block_size(8)
+                .cache_blocks(3),
+        );
+        for i in 0..index.len() {
+            assert_eq!(index.get_sa(i), expected.get_sa(i), "i = {}", i);
+        }
+        for pattern in &["a", "bc", "dab", "cdda"] {
+            let mut actual = index.search_backward(pattern).locate();
+            let mut expected = expected.search_backward(pattern).locate();
+            actual.sort();
+            expected.sort();
+            assert_eq!(actual, expected);
+        }
+
+        let samples = DiskSampledArray::open(&path, 2, index.len(), 8, 3).unwrap();
+        assert_eq!(samples.size(), std::mem::size_of::<DiskSampledArray>());
+        assert_eq!(samples.get(1), None);
+        assert_eq!(samples.get(0), Some(expected.get_sa(0)));
+        assert_eq!(samples.get(4), Some(expected.get_sa(4)));
+        assert_eq!(samples.reads(), 1);
+        for block in 1..4 {
+            samples.get(block * 32);
+        }
+        assert_eq!(samples.reads(), 4);
+        assert!(samples.size() <= std::mem::size_of::<DiskSampledArray>() + 3 * 8 * WORD);
+        samples.get(0);
+        assert_eq!(samples.reads(), 5);
+        assert!(DiskSampledArray::open(&path, 1, index.len(), 8, 3).is_err());
+        for (block_size, cache_blocks) in [(0, 3), (8, 0)] {
+            let err = DiskSampledArray::open(&path, 2, index.len(), block_size, cache_blocks);
+            assert_eq!(
+                err.err().map(|e| e.kind()),
+                Some(std::io::ErrorKind::InvalidInput)
+            );
+        }
+
+        let err = DiskSampledArray::open_sharded(&path, 2, index.len(), 8, 3, 0);
+        assert_eq!(
+            err.err().map(|e| e.kind()),
+            Some(std::io::ErrorKind::InvalidInput)
+        );
+        let samples = DiskSampledArray::open_sharded(&path, 2, index.len(), 8, 3, 2).unwrap();
+        assert_eq!(samples.shards(), 2);
+        let n = index.len();
+        std::thread::scope(|scope| {
+            for t in 0..4 {
+                let samples = &samples;
+                let expected = &expected;
+                scope.spawn(move || {
+                    for i in (t..n).step_by(3) {
+                        let expected = if i % 4 == 0 {
+                            Some(expected.get_sa(i))
+                        } else {
+                            None
+                        };
+                        assert_eq!(samples.get(i), expected);
+                    }
+                });
+            }
+        });
+        assert!(samples.size() <= std::mem::size_of::<DiskSampledArray>() + 2 * 2 * 8 * WORD);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "block_size must be positive")]
+    fn test_disk_sampler_zero_block_size() {
+        DiskSampler::new("unused.sa").block_size(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "cache_blocks must be positive")]
+    fn test_disk_sampler_zero_cache_blocks() {
+        DiskSampler::new("unused.sa").cache_blocks(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "shards must be positive")]
+    fn test_disk_sampler_zero_shards() {
+        DiskSampler::new("unused.sa").shards(0);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::converter::IndexWithConverter;
+use crate::iter::{BackwardIterableIndex, ForwardIterableIndex, PieceIterator};
+use crate::search::Match;
+
+use std::fmt;
+
+/// A view of an occurrence in a byte text with the characters around it, returned by [`Match::display`].
+///
+/// The context extends up to the given number of characters on each side but stops at the boundaries
+/// of the piece (or the text) containing the occurrence.
+/// A context cut short of the boundary is marked with an ellipsis, which is `"…"` by default,
+/// and the occurrence itself can be enclosed in markers.
+/// Bytes are rendered as UTF-8, replacing invalid sequences, e.g. those cut by the context.
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::NullSampler;
+/// use fm_index::{BackwardSearchIndex, FMIndexMultiPieces};
+///
+/// let text = b"the quick brown fox\0jumps over the lazy dog".to_vec();
+/// let index = FMIndexMultiPieces::new(text, RangeConverter::new(b' ', b'z'), NullSampler::new());
+/// let search = index.search_backward("the");
+/// let lines = search
+///     .iter_matches()
+///     .map(|m| m.display(5, 6).markers("[", "]").to_string())
+///     .collect::<Vec<_>>();
+/// assert_eq!(lines, vec!["…over [the] lazy …", "[the] quick…"]);
+/// ```
+pub struct DisplayMatch<'m, 'a, I> {
+    m: &'m Match<'a, I>,
+    before: usize,
+    after: usize,
+    ellipsis: &'m str,
+    markers: (&'m str, &'m str),
+}
+
+impl<'a, I> Match<'a, I>
+where
+    I: BackwardIterableIndex<T = u8> + ForwardIterableIndex<T = u8> + IndexWithConverter<u8>,
+{
+    /// Returns a view rendering the occurrence with up to `before` and `after` characters around it.
+    pub fn display(&self, before: usize, after: usize) -> DisplayMatch<'_, 'a, I> {
+        DisplayMatch {
+            m: self,
+            before,
+            after,
+            ellipsis: "…",
+            markers: ("", ""),
+        }
+    }
+}
+
+impl<'m, 'a, I> DisplayMatch<'m, 'a, I> {
+    /// Sets the marker of a context cut short of the boundary of the piece.
+    pub fn ellipsis(mut self, ellipsis: &'m str) -> Self {
+        self.ellipsis = ellipsis;
+        self
+    }
+
+    /// Sets the markers enclosing the occurrence, which are empty by default.
+    pub fn markers(mut self, open: &'m str, close: &'m str) -> Self {
+        self.markers = (open, close);
+        self
+    }
+}
+
+impl<I> fmt::Display for DisplayMatch<'_, '_, I>
+where
+    I: BackwardIterableIndex<T = u8> + ForwardIterableIndex<T = u8> + IndexWithConverter<u8>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // one more character than needed tells whether the context is cut short
+        let mut before = PieceIterator::new(self.m.iter_backward())
+            .take(self.before + 1)
+            .collect::<Vec<_>>();
+        let cut_before = before.len() > self.before;
+        before.truncate(self.before);
+        before.reverse();
+
+        let mut forward = self.m.iter_forward();
+        let occurrence = (&mut forward)
+            .take(self.m.pattern_len() as usize)
+            .collect::<Vec<_>>();
+        let mut after = PieceIterator::new(forward)
+            .take(self.after + 1)
+            .collect::<Vec<_>>();
+        let cut_after = after.len() > self.after;
+        after.truncate(self.after);
+
+        if cut_before {
+            f.write_str(self.ellipsis)?;
+        }
+        write!(
+            f,
+            "{}{}{}{}{}",
+            String::from_utf8_lossy(&before),
+            self.markers.0,
+            String::from_utf8_lossy(&occurrence),
+            self.markers.1,
+            String::from_utf8_lossy(&after)
+        )?;
+        if cut_after {
+            f.write_str(self.ellipsis)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::converter::RangeConverter;
+    use crate::search::BackwardSearchIndex;
+    use crate::suffix_array::{NullSampler, SuffixOrderSampler};
+    use crate::{FMIndex, FMIndexMultiPieces};
+
+    #[test]
+    fn test_display_match() {
+        let index = FMIndex::new(
+            b"abracadabra".to_vec(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new(),
+        );
+        let search = index.search_backward("cad");
+        let m = search.iter_matches().next().unwrap();
+        assert_eq!(m.display(2, 2).to_string(), "…racadab…");
+        assert_eq!(m.display(4, 5).to_string(), "abracadabra");
+        assert_eq!(m.display(10, 10).ellipsis("...").to_string(), "abracadabra");
+        assert_eq!(
+            m.display(0, 1)
+                .ellipsis("...")
+                .markers("<", ">")
+                .to_string(),
+            "...<cad>a..."
+        );
+
+        let index = FMIndexMultiPieces::new(
+            "naïve\0café".as_bytes().to_vec(),
+            RangeConverter::new(b'a', 0xff),
+            NullSampler::new(),
+        );
+        let search = index.search_backward("f");
+        let m = search.iter_matches().next().unwrap();
+        assert_eq!(m.display(10, 10).to_string(), "café");
+        // the context cuts "é" in the middle
+        assert_eq!(m.display(1, 1).to_string(), "…af\u{fffd}…");
+    }
+}
@@ -0,0 +1,217 @@
+use crate::character::Character;
+use crate::converter::Converter;
+use crate::iter::BackwardIterableIndex;
+use crate::multi_pieces::FMIndexMultiPieces;
+use crate::piece::{IndexWithPieces, PieceId};
+use crate::search::SaInterval;
+use crate::util;
+use crate::wavelet_matrix::WaveletMatrix;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The IDs of the pieces of all suffixes of a multi-piece text in suffix order,
+/// stored in a wavelet matrix, built by [`FMIndexMultiPieces::document_array`].
+///
+/// It costs _n log k_ bits for a text of length _n_ with _k_ pieces,
+/// and answers queries on the pieces of the occurrences in a range of rows
+/// (e.g. [`crate::Search::get_range`]) without walking every match:
+/// distinct pieces and their frequencies in _O(d log k)_ time for _d_ distinct pieces,
+/// and counting, selecting and quantiles in _O(log k)_ time.
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::NullSampler;
+/// use fm_index::{BackwardSearchIndex, FMIndexMultiPieces, PieceId};
+///
+/// let text = b"banana\0apple\0ananas".to_vec();
+/// let index = FMIndexMultiPieces::new(text, RangeConverter::new(b'a', b'z'), NullSampler::new());
+/// let da = index.document_array();
+/// let range = index.search_backward("an").get_range();
+/// assert_eq!(da.count_distinct(range), 2);
+/// assert_eq!(
+///     da.frequencies(range),
+///     vec![(PieceId::from(0), 2), (PieceId::from(2), 2)]
+/// );
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DocumentArray {
+    wm: WaveletMatrix,
+    pieces_count: u64,
+}
+
+impl<T, C, S> FMIndexMultiPieces<T, C, S>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    /// Builds the [`DocumentArray`] of this index by walking the whole text backward with LF-mapping.
+    pub fn document_array(&self) -> DocumentArray {
+        let n = self.len();
+        let k = self.pieces_count();
+        let mut ids = vec![0u64; n as usize];
+        // the terminator of row 0 follows the last piece
+        let mut i = 0;
+        let mut id = k - 1;
+        ids[0] = id;
+        for _ in 1..n {
+            i = self.lf_map(i);
+            if let Some(next) = self.boundary().piece_after(i) {
+                let next = u64::from(next);
+                ids[i as usize] = next;
+                id = next.saturating_sub(1);
+            } else {
+                ids[i as usize] = id;
+            }
+        }
+        let bits = if k > 1 { util::log2(k - 1) + 1 } else { 1 };
+        DocumentArray {
+            wm: WaveletMatrix::new_with_size(ids, bits),
+            pieces_count: k,
+        }
+    }
+}
+
+impl DocumentArray {
+    pub fn len(&self) -> u64 {
+        self.wm.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.wm.len() == 0
+    }
+
+    pub fn pieces_count(&self) -> u64 {
+        self.pieces_count
+    }
+
+    /// Returns the ID of the piece containing the suffix of the `i`-th row,
+    /// which agrees with [`IndexWithPieces::piece_id`].
+    pub fn piece_id(&self, i: u64) -> PieceId {
+        PieceId::from(self.wm.access::<u64>(i))
+    }
+
+    /// Returns the distinct pieces in `range` in ascending order of IDs,
+    /// with the numbers of rows in each of them.
+    pub fn frequencies(&self, range: SaInterval) -> Vec<(PieceId, u64)> {
+        self.wm
+            .distinct(range.start, range.end)
+            .into_iter()
+            .map(|(id, count)| (PieceId::from(id), count))
+            .collect()
+    }
+
+    /// Counts the distinct pieces in `range`.
+    pub fn count_distinct(&self, range: SaInterval) -> u64 {
+        self.wm.distinct(range.start, range.end).len() as u64
+    }
+
+    /// Counts the rows of `piece` in `range`.
+    pub fn count_in_piece(&self, range: SaInterval, piece: PieceId) -> u64 {
+        let id = u64::from(piece);
+        self.wm.rank(id, range.end) - self.wm.rank(id, range.start)
+    }
+
+    /// Counts the rows in `range` whose pieces have IDs in `[first, last)`.
+    pub fn count_in_pieces(&self, range: SaInterval, first: PieceId, last: PieceId) -> u64 {
+        let (s, e) = (range.start, range.end);
+        let (first, last) = (u64::from(first), u64::from(last));
+        if first >= last {
+            return 0;
+        }
+        self.wm.count_less(s, e, last) - self.wm.count_less(s, e, first)
+    }
+
+    /// Returns the rows of `piece` in `range` in ascending order,
+    /// to locate the occurrences in a piece without visiting the others.
+    pub fn rows_in_piece(
+        &self,
+        range: SaInterval,
+        piece: PieceId,
+    ) -> impl Iterator<Item = u64> + '_ {
+        let id = u64::from(piece);
+        let start = self.wm.rank(id, range.start);
+        let end = self.wm.rank(id, range.end);
+        (start..end).map(move |r| self.wm.select(id, r))
+    }
+
+    /// Returns the `k`-th smallest piece ID (from zero) of the rows in `range`.
+    pub fn quantile(&self, range: SaInterval, k: u64) -> PieceId {
+        assert!(
+            k < range.len(),
+            "k must be less than the length of the range"
+        );
+        PieceId::from(self.wm.quantile(range.start, range.end, k))
+    }
+
+    pub fn size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.wm.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::search::BackwardSearchIndex;
+    use crate::suffix_array::NullSampler;
+
+    #[test]
+    fn test_document_array() {
+        let pieces = ["mississippi", "ppi", "issi", "sip", "pi", "mis", "ssiss"];
+        let index = FMIndexMultiPieces::new(
+            pieces.join("\0").into_bytes(),
+            RangeConverter::new(b'a', b'z'),
+            NullSampler::new(),
+        );
+        let da = index.document_array();
+        assert_eq!(da.len(), index.len());
+        assert_eq!(da.pieces_count(), pieces.len() as u64);
+        for i in 0..index.len() {
+            assert_eq!(da.piece_id(i), index.piece_id(i), "row {}", i);
+        }
+
+        for pattern in &["i", "ss", "pi", "x", "\0m", ""] {
+            let range = index.search_backward(pattern).get_range();
+            let mut ids = range.rows().map(|i| index.piece_id(i)).collect::<Vec<_>>();
+            ids.sort();
+            let mut frequencies: Vec<(PieceId, u64)> = vec![];
+            for &id in &ids {
+                match frequencies.last_mut() {
+                    Some((last, count)) if *last == id => *count += 1,
+                    _ => frequencies.push((id, 1)),
+                }
+            }
+            assert_eq!(da.frequencies(range), frequencies);
+            assert_eq!(da.count_distinct(range), frequencies.len() as u64);
+            for (k, &id) in ids.iter().enumerate() {
+                assert_eq!(da.quantile(range, k as u64), id);
+            }
+            for id in 0..pieces.len() as u64 {
+                let id = PieceId::from(id);
+                let rows = range
+                    .rows()
+                    .filter(|&i| index.piece_id(i) == id)
+                    .collect::<Vec<_>>();
+                assert_eq!(da.count_in_piece(range, id), rows.len() as u64);
+                assert_eq!(da.rows_in_piece(range, id).collect::<Vec<_>>(), rows);
+            }
+            let (first, last) = (PieceId::from(2), PieceId::from(5));
+            assert_eq!(
+                da.count_in_pieces(range, first, last),
+                ids.iter().filter(|&&id| first <= id && id < last).count() as u64
+            );
+        }
+
+        let index = FMIndexMultiPieces::new(
+            b"abc".to_vec(),
+            RangeConverter::new(b'a', b'z'),
+            NullSampler::new(),
+        );
+        let da = index.document_array();
+        assert_eq!(
+            da.frequencies(index.search_backward("").get_range()),
+            vec![(PieceId::from(0), 5)]
+        );
+    }
+}
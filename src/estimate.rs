@@ -0,0 +1,127 @@
+use crate::util;
+
+const WORD: u64 = std::mem::size_of::<u64>() as u64;
+
+/// Predicted sizes in bytes of the index types for a text, as returned by their `size` methods,
+/// for choosing an index type and a sampling level before building one.
+///
+/// The suffix array is assumed to be sampled by [`crate::suffix_array::SuffixOrderSampler`].
+/// Bit vectors are estimated without compression, so the sizes are upper bounds up to small constants;
+/// compressible bit vectors, such as those of a repetitive text, can make indexes smaller.
+///
+/// ```
+/// use fm_index::IndexEstimate;
+///
+/// // a 1 GB DNA text (4 bases and the terminator) with 100 M runs in the BWT
+/// let estimate = IndexEstimate::for_text_stats(1 << 30, 5, 100_000_000, 5);
+/// assert!(estimate.rlfm_index < estimate.fm_index);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndexEstimate {
+    /// The size of [`crate::FMIndex`].
+    pub fm_index: u64,
+    /// The size of [`crate::RLFMIndex`].
+    pub rlfm_index: u64,
+    /// The size of the suffix array samples, included in the sizes above.
+    pub suffix_array: u64,
+    len: u64,
+    sigma: u64,
+}
+
+impl IndexEstimate {
+    /// Estimates the sizes for a text of length `n` including the terminator,
+    /// `sigma` characters including the terminator (i.e. the `len` of the converter),
+    /// `runs` runs of equal characters in its BWT, and suffix array samples at every `2^level` rows.
+    ///
+    /// `runs` is at most `n`; for a text without repetitions it is close to `n`.
+    pub fn for_text_stats(n: u64, sigma: u64, runs: u64, level: usize) -> Self {
+        assert!(n > 0, "n must be positive");
+        assert!(sigma > 1, "sigma must be greater than 1");
+        let runs = runs.min(n);
+        let suffix_array = bits_to_bytes(((n - 1) >> level) + 1, util::word_size(n) as u64);
+        let cs = sigma * WORD;
+        IndexEstimate {
+            fm_index: wavelet_matrix(n, sigma) + cs + suffix_array,
+            rlfm_index: wavelet_matrix(runs, sigma) + 2 * bit_vector(n) + cs + suffix_array,
+            suffix_array,
+            len: n,
+            sigma,
+        }
+    }
+
+    /// Returns the size of [`crate::FMIndexMultiPieces`] for a text of `pieces` pieces.
+    ///
+    /// The separators are counted in `n` of [`IndexEstimate::for_text_stats`].
+    pub fn multi_pieces(&self, pieces: u64) -> u64 {
+        let (n, sigma) = (self.len, self.sigma + 1);
+        let starts = if pieces > 0 {
+            bits_to_bytes(pieces, 2 + util::log2((n / pieces).max(1)))
+        } else {
+            0
+        };
+        wavelet_matrix(n, sigma) + sigma * WORD + pieces * WORD + starts + self.suffix_array
+    }
+}
+
+fn bits_to_bytes(count: u64, width: u64) -> u64 {
+    (count * width).div_ceil(64) * WORD
+}
+
+// An uncompressed bit vector with the rank and select directories of the fid crate.
+fn bit_vector(n: u64) -> u64 {
+    let bits = bits_to_bytes(n, 1);
+    let small_blocks = bits_to_bytes(n.div_ceil(64), 7);
+    let large_blocks = 2 * n.div_ceil(1024) * WORD;
+    let select = 2 * n.div_ceil(4096) * WORD;
+    bits + small_blocks + large_blocks + select
+}
+
+fn wavelet_matrix(n: u64, sigma: u64) -> u64 {
+    let levels = util::log2(sigma - 1) + 1;
+    levels * (bit_vector(n) + WORD)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::suffix_array::SuffixOrderSampler;
+    use crate::{FMIndex, FMIndexMultiPieces, RLFMIndex};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn assert_close(estimate: u64, actual: usize) {
+        let actual = actual as u64;
+        assert!(
+            actual * 3 / 4 <= estimate && estimate <= actual * 4 / 3,
+            "estimate = {}, actual = {}",
+            estimate,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_estimate() {
+        let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+        let n = 1 << 16;
+        let text = (0..n)
+            .map(|i| {
+                if i % 1000 == 999 {
+                    0
+                } else {
+                    rng.gen_range(b'a', b'z' + 1)
+                }
+            })
+            .collect::<Vec<_>>();
+        let converter = RangeConverter::new(b'a', b'z');
+        let sampler = SuffixOrderSampler::new().level(3);
+
+        let rlfmi = RLFMIndex::new(text.clone(), converter.clone(), sampler.clone());
+        let estimate = IndexEstimate::for_text_stats(n + 1, 27, rlfmi.runs(), 3);
+        assert_close(estimate.rlfm_index, rlfmi.size());
+        let fmi = FMIndex::new(text.clone(), converter.clone(), sampler.clone());
+        assert_close(estimate.fm_index, fmi.size());
+        let pieces = FMIndexMultiPieces::new(text, converter, sampler);
+        assert_close(estimate.multi_pieces(n / 1000 + 1), pieces.size());
+    }
+}
@@ -0,0 +1,175 @@
+//! Streaming match results as columns, for loading them into columnar formats such as Arrow.
+//!
+//! [`match_batches`] locates the occurrences of a sequence of patterns and yields them
+//! in batches of up to a fixed number of rows.
+//! Each column of a [`MatchBatch`] is a contiguous vector of `u64`,
+//! which is the layout of an Arrow `UInt64Array` without nulls and can be wrapped without copying
+//! (e.g. `UInt64Array::from(batch.positions)`).
+//! `examples/arrow` shows how to write the batches to a Parquet file.
+//!
+//! ```
+//! use fm_index::converter::RangeConverter;
+//! use fm_index::export;
+//! use fm_index::suffix_array::SuffixOrderSampler;
+//! use fm_index::FMIndexMultiPieces;
+//!
+//! let text = b"apple\0banana\0cherry".to_vec();
+//! let index = FMIndexMultiPieces::new(text, RangeConverter::new(b'a', b'z'), SuffixOrderSampler::new());
+//! let batches = export::match_batches(&index, vec!["an", "e"], 2).collect::<Vec<_>>();
+//! assert_eq!(batches.len(), 2);
+//! assert_eq!(batches[0].pattern_ids, vec![0, 0]);
+//! assert_eq!(batches[1].pattern_ids, vec![1, 1]);
+//! assert_eq!(batches.iter().map(|b| b.len()).sum::<usize>(), 4);
+//! ```
+
+use crate::piece::IndexWithPieces;
+use crate::search::{BackwardSearchIndex, SaInterval};
+use crate::suffix_array::IndexWithSA;
+
+/// A batch of occurrences as columns of the same length.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MatchBatch {
+    /// The index of the pattern in the input sequence.
+    pub pattern_ids: Vec<u64>,
+    /// The position of the occurrence in the text.
+    pub positions: Vec<u64>,
+    /// The ID of the piece containing the occurrence, which is 0 for a single text.
+    pub piece_ids: Vec<u64>,
+}
+
+impl MatchBatch {
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+}
+
+/// An iterator over batches of occurrences, returned by [`match_batches`].
+pub struct MatchBatches<'a, I, P> {
+    index: &'a I,
+    patterns: P,
+    batch_size: usize,
+    next_pattern_id: u64,
+    // the pattern being located and its remaining rows
+    current: Option<(u64, SaInterval)>,
+}
+
+/// Locates the occurrences of each of `patterns` in order, yielding batches of up to `batch_size` rows.
+///
+/// Patterns are searched lazily as batches are pulled,
+/// and the occurrences of a pattern may span several batches.
+/// Empty batches are never yielded.
+pub fn match_batches<I, P>(
+    index: &I,
+    patterns: P,
+    batch_size: usize,
+) -> MatchBatches<'_, I, P::IntoIter>
+where
+    I: BackwardSearchIndex + IndexWithSA + IndexWithPieces,
+    P: IntoIterator,
+    P::Item: AsRef<[I::T]>,
+{
+    assert!(batch_size > 0, "batch_size must be positive");
+    MatchBatches {
+        index,
+        patterns: patterns.into_iter(),
+        batch_size,
+        next_pattern_id: 0,
+        current: None,
+    }
+}
+
+impl<'a, I, P> Iterator for MatchBatches<'a, I, P>
+where
+    I: BackwardSearchIndex + IndexWithSA + IndexWithPieces,
+    P: Iterator,
+    P::Item: AsRef<[I::T]>,
+{
+    type Item = MatchBatch;
+
+    fn next(&mut self) -> Option<MatchBatch> {
+        let mut batch = MatchBatch::default();
+        while batch.len() < self.batch_size {
+            let (pattern_id, rows) = match self.current.take() {
+                Some(current) => current,
+                None => match self.patterns.next() {
+                    Some(pattern) => {
+                        let id = self.next_pattern_id;
+                        self.next_pattern_id += 1;
+                        (id, self.index.search_backward(pattern).get_range())
+                    }
+                    None => break,
+                },
+            };
+            let n = rows.len().min((self.batch_size - batch.len()) as u64);
+            for i in rows.start..rows.start + n {
+                batch.pattern_ids.push(pattern_id);
+                batch.positions.push(self.index.get_sa(i));
+                batch.piece_ids.push(self.index.piece_id(i).into());
+            }
+            if n < rows.len() {
+                self.current = Some((pattern_id, SaInterval::new(rows.start + n, rows.end)));
+            }
+        }
+        if batch.is_empty() {
+            None
+        } else {
+            Some(batch)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::suffix_array::SuffixOrderSampler;
+    use crate::{FMIndex, FMIndexMultiPieces};
+
+    #[test]
+    fn test_match_batches() {
+        let index = FMIndex::new(
+            b"mississippi".to_vec(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(1),
+        );
+        let patterns = ["x", "ssi", "i", "", "p"];
+        for batch_size in 1..20 {
+            let batches = match_batches(&index, &patterns, batch_size).collect::<Vec<_>>();
+            assert!(batches
+                .iter()
+                .all(|b| !b.is_empty() && b.len() <= batch_size));
+            assert!(batches[..batches.len() - 1]
+                .iter()
+                .all(|b| b.len() == batch_size));
+            let mut rows = batches
+                .iter()
+                .flat_map(|b| {
+                    (0..b.len()).map(move |k| (b.pattern_ids[k], b.positions[k], b.piece_ids[k]))
+                })
+                .collect::<Vec<_>>();
+            rows.sort();
+            let mut expected = vec![];
+            for (id, pattern) in patterns.iter().enumerate() {
+                for p in index.search_backward(pattern).locate() {
+                    expected.push((id as u64, p, 0));
+                }
+            }
+            expected.sort();
+            assert_eq!(rows, expected);
+        }
+
+        let index = FMIndexMultiPieces::new(
+            b"ab\0ba\0ab".to_vec(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new(),
+        );
+        let batch = match_batches(&index, &["ba"], 10).next().unwrap();
+        assert_eq!(batch.pattern_ids, vec![0]);
+        assert_eq!(batch.positions, vec![3]);
+        assert_eq!(batch.piece_ids, vec![1]);
+    }
+}
@@ -0,0 +1,245 @@
+//! A C ABI for byte-text indexes, enabled by the `ffi` feature.
+//!
+//! The generic API cannot be exported as it is, so this module fixes the index type to
+//! [`FmIndex`], an opaque [`FMIndex`] over bytes with a suffix-order sampled suffix array.
+//! A C header can be generated with `cbindgen --config cbindgen.toml --crate fm-index`,
+//! and a shared library built with `cargo rustc --release --features ffi --crate-type cdylib`.
+//!
+//! Functions returning a pointer return null on failure, e.g. on an invalid text or serialized index,
+//! and functions returning a number return [`FM_INDEX_ERROR`].
+//! Panics never cross the boundary.
+//! Indexes are serialized as CBOR in the format of [`crate::Versioned`].
+//!
+//! This is the only module allowed to use `unsafe` code, which is confined to dereferencing
+//! the pointers passed by callers under the conditions in the `# Safety` sections.
+#![allow(unsafe_code)]
+
+use crate::converter::RangeConverter;
+use crate::search::BackwardSearchIndex;
+use crate::suffix_array::{SuffixOrderSampledArray, SuffixOrderSampler};
+use crate::{FMIndex, Versioned};
+
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+/// The value returned on failure by functions returning a number.
+pub const FM_INDEX_ERROR: u64 = u64::MAX;
+
+/// An index over a byte text which does not contain zero, opaque to C.
+pub struct FmIndex(FMIndex<u8, RangeConverter<u8>, SuffixOrderSampledArray>);
+
+// Returns an empty slice for a null pointer so that empty patterns can be passed as (NULL, 0).
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> &'a [u8] {
+    if data.is_null() || len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(data, len)
+    }
+}
+
+// Runs `f`, returning `FM_INDEX_ERROR` if it panics.
+fn catch_number<F: FnOnce() -> u64>(f: F) -> u64 {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(FM_INDEX_ERROR)
+}
+
+fn into_raw(
+    index: Option<FMIndex<u8, RangeConverter<u8>, SuffixOrderSampledArray>>,
+) -> *mut FmIndex {
+    index.map_or(ptr::null_mut(), |index| {
+        Box::into_raw(Box::new(FmIndex(index)))
+    })
+}
+
+/// Builds an index over `text[0..len]`, keeping every `2^level`-th suffix array entry.
+///
+/// Returns null if the text is empty or contains zero, or if `2^level` is not less than `len + 1`.
+///
+/// # Safety
+///
+/// `text` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fm_index_new(text: *const u8, len: usize, level: u32) -> *mut FmIndex {
+    let text = bytes(text, len);
+    if text.is_empty() || text.contains(&0) || level >= 64 || 1u64 << level > len as u64 {
+        return ptr::null_mut();
+    }
+    let text = text.to_vec();
+    into_raw(
+        panic::catch_unwind(|| {
+            FMIndex::new(
+                text,
+                RangeConverter::new(1, 255),
+                SuffixOrderSampler::new().level(level as usize),
+            )
+        })
+        .ok(),
+    )
+}
+
+/// Frees an index. Null is ignored.
+///
+/// # Safety
+///
+/// `index` must be null or returned by this module and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn fm_index_free(index: *mut FmIndex) {
+    if !index.is_null() {
+        drop(Box::from_raw(index));
+    }
+}
+
+/// Returns the length of the indexed text including the terminator.
+///
+/// # Safety
+///
+/// `index` must be a valid index.
+#[no_mangle]
+pub unsafe extern "C" fn fm_index_len(index: *const FmIndex) -> u64 {
+    let index = &(*index).0;
+    catch_number(|| index.len())
+}
+
+/// Counts the occurrences of `pattern[0..len]`.
+///
+/// # Safety
+///
+/// `index` must be a valid index, and `pattern` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fm_index_count(
+    index: *const FmIndex,
+    pattern: *const u8,
+    len: usize,
+) -> u64 {
+    let index = &(*index).0;
+    let pattern = bytes(pattern, len);
+    catch_number(|| index.search_backward(pattern).count())
+}
+
+/// Writes the positions of `pattern[0..len]` into `out[0..capacity]`
+/// and returns the number of occurrences, or [`FM_INDEX_ERROR`] on failure.
+///
+/// If it exceeds `capacity`, only the first `capacity` positions (in the order of [`crate::Search::locate`])
+/// are written; call it again with a larger buffer to get all of them.
+///
+/// # Safety
+///
+/// `index` must be a valid index, `pattern` must point to `len` readable bytes,
+/// and `out` must point to `capacity` writable `u64`s.
+#[no_mangle]
+pub unsafe extern "C" fn fm_index_locate(
+    index: *const FmIndex,
+    pattern: *const u8,
+    len: usize,
+    out: *mut u64,
+    capacity: usize,
+) -> u64 {
+    let index = &(*index).0;
+    let pattern = bytes(pattern, len);
+    catch_number(|| {
+        let search = index.search_backward(pattern);
+        let count = search.count();
+        let n = (count as usize).min(capacity);
+        if n > 0 {
+            let out = slice::from_raw_parts_mut(out, n);
+            for (o, m) in out.iter_mut().zip(search.iter_matches()) {
+                *o = m.locate();
+            }
+        }
+        count
+    })
+}
+
+/// Serializes an index with its format version and returns a buffer of `*out_len` bytes,
+/// which must be freed by [`fm_index_bytes_free`].
+///
+/// # Safety
+///
+/// `index` must be a valid index and `out_len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn fm_index_serialize(index: *const FmIndex, out_len: *mut usize) -> *mut u8 {
+    let index = AssertUnwindSafe(&(*index).0);
+    match panic::catch_unwind(|| serde_cbor::to_vec(&Versioned::new(*index))) {
+        Ok(Ok(buf)) => {
+            let buf = buf.into_boxed_slice();
+            *out_len = buf.len();
+            Box::into_raw(buf) as *mut u8
+        }
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Frees a buffer returned by [`fm_index_serialize`].
+///
+/// # Safety
+///
+/// `data` and `len` must be the buffer and length returned by [`fm_index_serialize`], or `data` must be null.
+#[no_mangle]
+pub unsafe extern "C" fn fm_index_bytes_free(data: *mut u8, len: usize) {
+    if !data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, len)));
+    }
+}
+
+/// Restores an index serialized by [`fm_index_serialize`], or returns null if `data` is not one
+/// or was written in another format version.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fm_index_deserialize(data: *const u8, len: usize) -> *mut FmIndex {
+    let data = bytes(data, len);
+    into_raw(
+        panic::catch_unwind(|| serde_cbor::from_slice(data).ok().map(Versioned::into_inner))
+            .ok()
+            .flatten(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffi() {
+        let text = b"mississippi";
+        unsafe {
+            let index = fm_index_new(text.as_ptr(), text.len(), 1);
+            assert!(!index.is_null());
+            assert_eq!(fm_index_len(index), 12);
+            assert_eq!(fm_index_count(index, b"ssi".as_ptr(), 3), 2);
+            assert_eq!(fm_index_count(index, ptr::null(), 0), 12);
+
+            let mut out = [0u64; 4];
+            assert_eq!(
+                fm_index_locate(index, b"i".as_ptr(), 1, out.as_mut_ptr(), 2),
+                4
+            );
+            assert_eq!(out, [10, 7, 0, 0]);
+            assert_eq!(
+                fm_index_locate(index, b"i".as_ptr(), 1, out.as_mut_ptr(), 4),
+                4
+            );
+            assert_eq!(out, [10, 7, 4, 1]);
+
+            let mut len = 0;
+            let buf = fm_index_serialize(index, &mut len);
+            assert!(!buf.is_null());
+            let restored = fm_index_deserialize(buf, len);
+            assert!(!restored.is_null());
+            assert_eq!(fm_index_count(restored, b"ssi".as_ptr(), 3), 2);
+            assert!(fm_index_deserialize(buf, len / 2).is_null());
+            // an index serialized without its version is rejected
+            let raw = serde_cbor::to_vec(&(*index).0).unwrap();
+            assert!(fm_index_deserialize(raw.as_ptr(), raw.len()).is_null());
+            fm_index_bytes_free(buf, len);
+            fm_index_free(restored);
+            fm_index_free(index);
+
+            assert!(fm_index_new(b"a\0b".as_ptr(), 3, 0).is_null());
+            assert!(fm_index_new(ptr::null(), 0, 0).is_null());
+            assert!(fm_index_new(text.as_ptr(), text.len(), 4).is_null());
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use crate::character::Character;
+use crate::converter::Converter;
+use crate::iter::BackwardIterableIndex;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A set of the q-grams of a text, represented as a bitmap of their hash values.
+///
+/// It answers whether a pattern may occur in the text in _O(m)_ time without touching the index.
+/// False positives occur (as in a Bloom filter with one hash function) but false negatives do not,
+/// so an index needs to be searched only if [`QGramFilter::may_contain`] returns true.
+///
+/// Characters are hashed by their codes in a converter, so patterns are matched as the index matches them,
+/// e.g. case-insensitively with [`crate::converter::NormalizedConverter`].
+/// Indexes store a filter built by `with_qgram_filter` and consult it on every backward search.
+///
+/// ```
+/// use fm_index::converter::{IndexWithConverter, RangeConverter};
+/// use fm_index::suffix_array::NullSampler;
+/// use fm_index::{BackwardSearchIndex, FMIndex};
+///
+/// let text = b"mississippi".to_vec();
+/// let index = FMIndex::new(text, RangeConverter::new(b'a', b'z'), NullSampler::new())
+///     .with_qgram_filter(3, 1 << 10);
+///
+/// let filter = index.qgram_filter().unwrap();
+/// assert!(filter.may_contain("ssi", index.get_converter()));
+/// assert_eq!(index.search_backward("ssi").count(), 2);
+/// // rejected by the filter without rank queries
+/// assert_eq!(index.search_backward("ssp").count(), 0);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct QGramFilter {
+    q: usize,
+    mask: u64,
+    bits: Vec<u64>,
+}
+
+impl QGramFilter {
+    /// Builds a filter of the `q`-grams of `text`, converted by `converter`,
+    /// with a bitmap of `bits` bits, which is rounded up to a power of two.
+    pub fn new<T, C>(text: &[T], converter: &C, q: usize, bits: usize) -> Self
+    where
+        T: Character,
+        C: Converter<T>,
+    {
+        let codes = text
+            .iter()
+            .map(|&c| converter.convert(c).into())
+            .collect::<Vec<_>>();
+        let mut filter = Self::empty(q, bits);
+        for gram in codes.windows(q) {
+            filter.insert(gram);
+        }
+        filter
+    }
+
+    /// Builds a filter of the `q`-grams of the cyclic sequence of `codes`,
+    /// so that grams wrapping around the terminator are included.
+    pub(crate) fn from_cyclic_codes(codes: &[u64], q: usize, bits: usize) -> Self {
+        let mut filter = Self::empty(q, bits);
+        let mut codes = codes.to_vec();
+        let wrap = (q - 1).min(codes.len());
+        codes.extend_from_within(..wrap);
+        for gram in codes.windows(q) {
+            filter.insert(gram);
+        }
+        filter
+    }
+
+    fn empty(q: usize, bits: usize) -> Self {
+        assert!(q > 0, "q must be positive");
+        let bits = bits.max(64).next_power_of_two();
+        QGramFilter {
+            q,
+            mask: bits as u64 - 1,
+            bits: vec![0; bits / 64],
+        }
+    }
+
+    fn insert(&mut self, gram: &[u64]) {
+        let h = self.hash(gram);
+        self.bits[(h / 64) as usize] |= 1 << (h % 64);
+    }
+
+    pub fn q(&self) -> usize {
+        self.q
+    }
+
+    /// Returns false if `pattern` certainly does not occur in the text,
+    /// e.g. it contains a character not contained by `converter`.
+    ///
+    /// `converter` must be the one the filter was built with.
+    /// Patterns shorter than `q` consisting of contained characters cannot be rejected.
+    pub fn may_contain<T, C, K>(&self, pattern: K, converter: &C) -> bool
+    where
+        T: Character,
+        C: Converter<T>,
+        K: AsRef<[T]>,
+    {
+        self.may_contain_codes(pattern.as_ref().iter().map(|&c| {
+            if converter.contains(c) {
+                Some(converter.convert(c).into())
+            } else {
+                None
+            }
+        }))
+    }
+
+    /// Returns false if the codes certainly do not occur in the text, or any of them is `None`.
+    pub(crate) fn may_contain_codes<I>(&self, codes: I) -> bool
+    where
+        I: IntoIterator<Item = Option<u64>>,
+    {
+        let codes = match codes.into_iter().collect::<Option<Vec<_>>>() {
+            Some(codes) => codes,
+            None => return false,
+        };
+        codes.windows(self.q).all(|gram| {
+            let h = self.hash(gram);
+            self.bits[(h / 64) as usize] & (1 << (h % 64)) != 0
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.bits.len() * std::mem::size_of::<u64>()
+    }
+
+    fn hash(&self, gram: &[u64]) -> u64 {
+        let mut h = 0u64;
+        for &c in gram {
+            h = (h.rotate_left(5) ^ c).wrapping_mul(0x517c_c1b7_2722_0a95);
+        }
+        (h >> 32 ^ h) & self.mask
+    }
+}
+
+/// Reads a rotation of the text of `index` as codes, walking LF-mapping over the whole index once.
+pub(crate) fn cyclic_text_codes<I, F>(index: &I, code: F) -> Vec<u64>
+where
+    I: BackwardIterableIndex,
+    F: Fn(I::T) -> u64,
+{
+    let n = index.len();
+    let mut codes = Vec::with_capacity(n as usize);
+    let mut i = 0;
+    for _ in 0..n {
+        codes.push(code(index.get_l(i)));
+        i = index.lf_map(i);
+    }
+    codes.reverse();
+    codes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::{AsciiLowercase, IdConverter, NormalizedConverter, RangeConverter};
+    use crate::search::BackwardSearchIndex;
+    use crate::suffix_array::NullSampler;
+    use crate::FMIndex;
+
+    #[test]
+    fn test_no_false_negatives() {
+        let text = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua.".to_string().into_bytes();
+        let converter = IdConverter::new(256);
+        let filter = QGramFilter::new(&text, &converter, 3, 1 << 12);
+        for i in 0..text.len() {
+            for j in i..text.len().min(i + 10) {
+                assert!(filter.may_contain(&text[i..j], &converter));
+            }
+        }
+        assert!(filter.may_contain("do", &converter));
+        assert!(!filter.may_contain("xyzzy", &converter));
+        assert!(!filter.may_contain("dolorx", &converter));
+    }
+
+    #[test]
+    fn test_normalized_converter() {
+        let text = b"Mississippi River".to_vec();
+        let converter = NormalizedConverter::new(RangeConverter::new(b' ', b'z'), AsciiLowercase);
+        let filter = QGramFilter::new(&text, &converter, 3, 1 << 10);
+        assert!(filter.may_contain("MISSISSIPPI rIVER", &converter));
+        assert!(!filter.may_contain("{", &converter));
+
+        let index = FMIndex::new(text, converter, NullSampler::new()).with_qgram_filter(3, 1 << 10);
+        for pattern in ["missi", "SSIPPI", "pi r", "\0mis", "ver\0", "er\0m"] {
+            let expected = FMIndex::new(
+                b"Mississippi River".to_vec(),
+                NormalizedConverter::new(RangeConverter::new(b' ', b'z'), AsciiLowercase),
+                NullSampler::new(),
+            )
+            .search_backward(pattern)
+            .count();
+            assert!(expected > 0, "{}", pattern);
+            assert_eq!(
+                index.search_backward(pattern).count(),
+                expected,
+                "{}",
+                pattern
+            );
+        }
+        assert_eq!(index.search_backward("sissy").count(), 0);
+    }
+}
@@ -1,19 +1,36 @@
+use crate::bucket::{BucketArray, EliasFanoBucketArray};
 use crate::character::Character;
 use crate::converter::{Converter, IndexWithConverter};
+use crate::diff::IndexDiff;
+use crate::filter::{self, QGramFilter};
+use crate::piece::{IndexWithPieces, PieceId};
 use crate::sais;
-use crate::suffix_array::{ArraySampler, IndexWithSA, PartialArray};
-use crate::util;
+use crate::search::SaInterval;
+use crate::stats::{self, IndexStats, SuffixArrayStats};
+use crate::suffix_array::{
+    self, ArraySampler, IndexWithExtrema, IndexWithISA, IndexWithSA, InvalidText, PartialArray,
+    PartialExtremaArray, PartialInverseArray, Scratch,
+};
+use crate::text::Text;
+use crate::util::{self, strict_assert};
 use crate::wavelet_matrix::WaveletMatrix;
 use crate::{BackwardIterableIndex, ForwardIterableIndex};
 
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-
-#[derive(Serialize, Deserialize)]
-pub struct FMIndex<T, C, S> {
-    bw: WaveletMatrix,
-    cs: Vec<u64>,
+use std::sync::Arc;
+
+#[derive(PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FMIndex<T, C, S, B = Vec<u64>> {
+    // shared by indexes created with `resample`
+    bw: Arc<WaveletMatrix>,
+    cs: B,
     converter: C,
     suffix_array: S,
+    // q-grams of the text added by `with_qgram_filter`
+    #[cfg_attr(feature = "serde", serde(default))]
+    qgram_filter: Option<QGramFilter>,
     _t: std::marker::PhantomData<T>,
 }
 
@@ -27,58 +44,326 @@ where
         if !text[text.len() - 1].is_zero() {
             text.push(T::zero());
         }
+        let sa = sais::sais(&text, &converter);
+        Self::from_suffix_array(text, sa, converter, sampler)
+    }
+
+    /// Builds an index like [`FMIndex::new`], or returns an error if `text` cannot be indexed,
+    /// e.g. it contains a character out of the range of `converter`.
+    pub fn try_new<B: ArraySampler<S>>(
+        mut text: Vec<T>,
+        converter: C,
+        sampler: B,
+    ) -> Result<Self, InvalidText> {
+        if text.last().is_some_and(|c| !c.is_zero()) {
+            text.push(T::zero());
+        }
+        let sa = suffix_array::try_build_suffix_array(&text, &converter)?;
+        Ok(Self::from_suffix_array(text, sa, converter, sampler))
+    }
+
+    /// Builds an index like [`FMIndex::new`], computing the suffix array in the buffer of `scratch`.
+    pub fn new_with_scratch<B: ArraySampler<S>>(
+        mut text: Vec<T>,
+        converter: C,
+        sampler: B,
+        scratch: &mut Scratch,
+    ) -> Self {
+        if !text[text.len() - 1].is_zero() {
+            text.push(T::zero());
+        }
+        let sa = scratch.build(&text, &converter);
+        Self::build(&text, sa, converter, sampler)
+    }
+
+    /// Builds an index from `text` and its suffix array computed elsewhere, skipping SA-IS.
+    ///
+    /// `text` must end with zero, and `sa` must be the suffix array of the whole `text` including it.
+    /// The suffix array is not verified; use [`crate::suffix_array::validate`] to check it beforehand.
+    pub fn from_suffix_array<B: ArraySampler<S>>(
+        text: Vec<T>,
+        sa: Vec<u64>,
+        converter: C,
+        sampler: B,
+    ) -> Self {
+        Self::build(&text, &sa, converter, sampler)
+    }
+
+    /// Builds an index from any [`Text`], such as [`crate::PackedText`],
+    /// without copying it into a vector of characters.
+    ///
+    /// `text` must end with zero.
+    pub fn from_text<X, B>(text: &X, converter: C, sampler: B) -> Self
+    where
+        X: Text<T = T> + ?Sized,
+        B: ArraySampler<S>,
+    {
+        let sa = sais::sais(text, &converter);
+        Self::build(text, &sa, converter, sampler)
+    }
+
+    fn build<X, B>(text: &X, sa: &[u64], converter: C, sampler: B) -> Self
+    where
+        X: Text<T = T> + ?Sized,
+        B: ArraySampler<S>,
+    {
         let n = text.len();
+        assert!(
+            n > 0 && text.get(n - 1).is_zero(),
+            "text must end with zero"
+        );
+        assert_eq!(sa.len(), n, "sa must be as long as text");
+        suffix_array::assert_text_len(n as u64);
 
-        let cs = sais::get_bucket_start_pos(&sais::count_chars(&text, &converter));
-        let sa = sais::sais(&text, &converter);
+        let cs = sais::get_bucket_start_pos(&sais::count_chars(text, &converter));
 
         let mut bw = vec![T::zero(); n];
         for i in 0..n {
             let k = sa[i] as usize;
             if k > 0 {
-                bw[i] = converter.convert(text[k - 1]);
+                bw[i] = converter.convert(text.get(k - 1));
             }
         }
-        let bw = WaveletMatrix::new_with_size(bw, util::log2(converter.len() - 1) + 1);
+        let bw = Arc::new(WaveletMatrix::new_with_size(
+            bw,
+            util::log2(converter.len() - 1) + 1,
+        ));
 
         FMIndex {
             cs,
             bw,
             converter,
+            suffix_array: sampler.sample_slice(sa),
+            qgram_filter: None,
+            _t: std::marker::PhantomData::<T>,
+        }
+    }
+
+    /// Replaces the array of bucket positions with its Elias-Fano representation.
+    ///
+    /// This reduces the _O(σ)_ term of the index size for large alphabets
+    /// at the cost of slower LF-mapping.
+    pub fn compress_cs(self) -> FMIndex<T, C, S, EliasFanoBucketArray> {
+        FMIndex {
+            cs: EliasFanoBucketArray::new(&self.cs),
+            bw: self.bw,
+            converter: self.converter,
+            suffix_array: self.suffix_array,
+            qgram_filter: self.qgram_filter,
+            _t: self._t,
+        }
+    }
+}
+
+impl<T, C, S, B> FMIndex<T, C, S, B>
+where
+    T: Character,
+    C: Converter<T> + Clone,
+    B: BucketArray + Clone,
+{
+    /// Creates an index with a suffix array sampled by `sampler`, without the text.
+    ///
+    /// The whole suffix array is recovered by walking LF-mapping over the text once,
+    /// which takes _O(n log σ)_ time and _n_ words of temporary memory.
+    /// The new index shares the BWT with this one.
+    pub fn resample<S2, B2: ArraySampler<S2>>(&self, sampler: B2) -> FMIndex<T, C, S2, B> {
+        let n = self.len();
+        let mut sa = vec![0; n as usize];
+        // Row 0 is the suffix consisting of the terminator.
+        let mut i = 0;
+        for p in (0..n).rev() {
+            sa[i as usize] = p;
+            i = self.lf_map(i);
+        }
+        FMIndex {
+            bw: Arc::clone(&self.bw),
+            cs: self.cs.clone(),
+            converter: self.converter.clone(),
             suffix_array: sampler.sample(sa),
+            qgram_filter: self.qgram_filter.clone(),
+            _t: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T, C> FMIndex<T, C, ()>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    /// Builds an index like [`FMIndex::from_bwt`], or returns an error if `bw` does not contain
+    /// exactly one zero or contains a character out of the range of `converter`.
+    pub fn try_from_bwt(bw: Vec<T>, converter: C) -> Result<Self, InvalidText> {
+        let mut zero = None;
+        for (i, &c) in bw.iter().enumerate() {
+            if !converter.contains(c) || converter.convert(c).into() >= converter.len() {
+                return Err(InvalidText::OutOfRange { position: i as u64 });
+            }
+            if c.is_zero() && zero.replace(i).is_some() {
+                return Err(InvalidText::ExtraTerminator { position: i as u64 });
+            }
+        }
+        match zero {
+            Some(_) => Ok(Self::from_bwt(bw, converter)),
+            None if bw.is_empty() => Err(InvalidText::Empty),
+            None => Err(InvalidText::NotTerminated),
+        }
+    }
+
+    /// Builds an index which supports counting from a Burrows-Wheeler transform computed elsewhere.
+    ///
+    /// `bw` consists of original characters and contains the terminator (zero) exactly once.
+    /// This is checked only in debug builds or with the `release-asserts` feature, and panics if violated;
+    /// use [`FMIndex::try_from_bwt`] to get an error instead.
+    pub fn from_bwt(bw: Vec<T>, converter: C) -> Self {
+        strict_assert!(
+            bw.iter().filter(|c| c.is_zero()).count() == 1,
+            "bw must contain exactly one zero"
+        );
+        let cs = sais::get_bucket_start_pos(&sais::count_chars(&bw, &converter));
+        let bw = bw.into_iter().map(|c| converter.convert(c)).collect();
+        let bw = Arc::new(WaveletMatrix::new_with_size(
+            bw,
+            util::log2(converter.len() - 1) + 1,
+        ));
+
+        FMIndex {
+            cs,
+            bw,
+            converter,
+            suffix_array: (),
+            qgram_filter: None,
             _t: std::marker::PhantomData::<T>,
         }
     }
+}
 
+impl<T, C, S, B> FMIndex<T, C, S, B> {
     pub fn len(&self) -> u64 {
         self.bw.len()
     }
 }
 
-impl<T, C> FMIndex<T, C, ()> {
+impl<T, C, B> FMIndex<T, C, (), B>
+where
+    B: BucketArray,
+{
     pub fn size(&self) -> usize {
         std::mem::size_of::<Self>()
             + self.bw.size()
-            + self.cs.len() * std::mem::size_of::<Vec<u64>>()
+            + self.cs.size()
+            + self.qgram_filter.as_ref().map_or(0, |filter| filter.size())
     }
 }
 
-impl<T, C, S> FMIndex<T, C, S>
+impl<T, C, S, B> FMIndex<T, C, S, B>
 where
     S: PartialArray,
+    B: BucketArray,
 {
     pub fn size(&self) -> usize {
         std::mem::size_of::<Self>()
             + self.bw.size()
-            + self.cs.len() * std::mem::size_of::<Vec<u64>>()
+            + self.cs.size()
+            + self.qgram_filter.as_ref().map_or(0, |filter| filter.size())
             + self.suffix_array.size()
     }
 }
 
-impl<T, C, S> BackwardIterableIndex for FMIndex<T, C, S>
+impl<T, C, S, B> FMIndex<T, C, S, B>
+where
+    T: Character,
+    C: Converter<T>,
+    B: BucketArray,
+{
+    fn collect_stats<X: SuffixArrayStats>(&self, suffix_array: &X) -> IndexStats {
+        let n = self.len();
+        let runs = stats::count_runs(n, |i| self.bw.access::<u64>(i));
+        let mut components = vec![("bwt", self.bw.size()), ("cs", self.cs.size())];
+        if let Some(filter) = &self.qgram_filter {
+            components.push(("qgram_filter", filter.size()));
+        }
+        IndexStats::new(n, self.converter.len(), 1, runs).with_components(
+            std::mem::size_of::<Self>(),
+            components,
+            suffix_array,
+        )
+    }
+}
+
+impl<T, C, B> FMIndex<T, C, (), B>
+where
+    T: Character,
+    C: Converter<T>,
+    B: BucketArray,
+{
+    /// Summarizes the index, counting the runs of the BWT.
+    pub fn stats(&self) -> IndexStats {
+        self.collect_stats(&())
+    }
+}
+
+impl<T, C, S, B> FMIndex<T, C, S, B>
 where
     T: Character,
     C: Converter<T>,
+    B: BucketArray,
+    S: PartialArray,
+{
+    /// Summarizes the index, counting the runs of the BWT.
+    pub fn stats(&self) -> IndexStats {
+        self.collect_stats(&self.suffix_array)
+    }
+}
+
+impl<T, C, S, B> FMIndex<T, C, S, B>
+where
+    T: Character,
+    C: Converter<T> + PartialEq,
+    S: PartialEq,
+    B: BucketArray + PartialEq,
+{
+    /// Compares each component of this index with those of `other`.
+    pub fn diff(&self, other: &Self) -> IndexDiff {
+        let mut diff = IndexDiff::default();
+        diff.check_bwt(self, other, self.bw != other.bw);
+        diff.check("cs", self.cs != other.cs);
+        diff.check("converter", self.converter != other.converter);
+        diff.check("suffix_array", self.suffix_array != other.suffix_array);
+        diff.check("qgram_filter", self.qgram_filter != other.qgram_filter);
+        diff
+    }
+}
+
+impl<T, C, S, B> FMIndex<T, C, S, B>
+where
+    T: Character,
+    C: Converter<T>,
+    B: BucketArray,
+{
+    /// Builds a [`QGramFilter`] of the `q`-grams of the text with a bitmap of `bits` bits and stores it with the index,
+    /// so that backward searches for patterns containing a q-gram absent from the text return nothing without rank queries.
+    ///
+    /// The filter hashes the characters converted by the converter of the index, as they are stored,
+    /// so it never rejects a pattern that the index matches.
+    /// The text is read by walking LF-mapping over the whole index once, which takes _n_ words of temporary memory.
+    pub fn with_qgram_filter(mut self, q: usize, bits: usize) -> Self {
+        let codes = filter::cyclic_text_codes(&self, |c| c.into());
+        self.qgram_filter = Some(QGramFilter::from_cyclic_codes(&codes, q, bits));
+        self
+    }
+
+    /// Returns the filter added by [`FMIndex::with_qgram_filter`], if any.
+    pub fn qgram_filter(&self) -> Option<&QGramFilter> {
+        self.qgram_filter.as_ref()
+    }
+}
+
+impl<T, C, S, B> BackwardIterableIndex for FMIndex<T, C, S, B>
+where
+    T: Character,
+    C: Converter<T>,
+    B: BucketArray,
 {
     type T = T;
 
@@ -88,50 +373,63 @@ where
 
     fn lf_map(&self, i: u64) -> u64 {
         let c = self.get_l(i);
-        self.cs[c.into() as usize] + self.bw.rank(c, i)
+        self.cs.get(c.into()) + self.bw.rank(c, i)
     }
 
     fn lf_map2(&self, c: T, i: u64) -> u64 {
+        if !self.converter.contains(c) {
+            return 0;
+        }
         let c = self.converter.convert(c);
-        self.cs[c.into() as usize] + self.bw.rank(c, i)
+        self.cs.get(c.into()) + self.bw.rank(c, i)
+    }
+
+    fn char_range(&self, c: T) -> SaInterval {
+        if !self.converter.contains(c) {
+            return SaInterval::default();
+        }
+        let c = self.converter.convert(c).into();
+        let e = if c + 1 < self.cs.len() {
+            self.cs.get(c + 1)
+        } else {
+            self.bw.len()
+        };
+        SaInterval::new(self.cs.get(c), e)
     }
 
     fn len(&self) -> u64 {
         self.bw.len()
     }
+
+    fn may_contain(&self, pattern: &[T]) -> bool {
+        match &self.qgram_filter {
+            Some(filter) => filter.may_contain(pattern, &self.converter),
+            None => true,
+        }
+    }
 }
 
-impl<T, C, S> ForwardIterableIndex for FMIndex<T, C, S>
+impl<T, C, S, B> ForwardIterableIndex for FMIndex<T, C, S, B>
 where
     T: Character,
     C: Converter<T>,
+    B: BucketArray,
 {
     type T = T;
     fn get_f(&self, i: u64) -> Self::T {
         // binary search to find c s.t. cs[c] <= i < cs[c+1]
         // <=> c is the greatest index s.t. cs[c] <= i
-        // invariant: c exists in [s, e)
-        let mut s = 0;
-        let mut e = self.cs.len();
-        while e - s > 1 {
-            let m = s + (e - s) / 2;
-            if self.cs[m] <= i {
-                s = m;
-            } else {
-                e = m;
-            }
-        }
-        T::from_u64(s as u64)
+        T::from_u64(self.cs.search(i))
     }
 
     fn fl_map(&self, i: u64) -> u64 {
         let c = self.get_f(i);
-        self.bw.select(c, i - self.cs[c.into() as usize])
+        self.bw.select(c, i - self.cs.get(c.into()))
     }
 
     fn fl_map2(&self, c: Self::T, i: u64) -> u64 {
         let c = self.converter.convert(c);
-        self.bw.select(c, i - self.cs[c.into() as usize])
+        self.bw.select(c, i - self.cs.get(c.into()))
     }
 
     fn len(&self) -> u64 {
@@ -139,29 +437,64 @@ where
     }
 }
 
-impl<T, C, S> IndexWithSA for FMIndex<T, C, S>
+impl<T, C, S, B> IndexWithSA for FMIndex<T, C, S, B>
 where
     T: Character,
     C: Converter<T>,
     S: PartialArray,
+    B: BucketArray,
 {
-    fn get_sa(&self, mut i: u64) -> u64 {
-        let mut steps = 0;
-        loop {
-            match self.suffix_array.get(i) {
-                Some(sa) => {
-                    return (sa + steps) % self.bw.len();
-                }
-                None => {
-                    i = self.lf_map(i);
-                    steps += 1;
-                }
-            }
-        }
+    fn get_sa(&self, i: u64) -> u64 {
+        suffix_array::resolve_sa(
+            i,
+            self.bw.len(),
+            None,
+            |k| self.suffix_array.get(k),
+            |k| self.lf_map(k),
+        )
+        .unwrap()
+    }
+
+    fn get_sa_with_budget(&self, i: u64, budget: &mut u64) -> Option<u64> {
+        suffix_array::resolve_sa(
+            i,
+            self.bw.len(),
+            Some(budget),
+            |k| self.suffix_array.get(k),
+            |k| self.lf_map(k),
+        )
+    }
+}
+
+impl<T, C, S, B> IndexWithISA for FMIndex<T, C, S, B>
+where
+    T: Character,
+    C: Converter<T>,
+    S: PartialInverseArray,
+    B: BucketArray,
+{
+    fn get_isa(&self, p: u64) -> u64 {
+        suffix_array::get_isa(self, &self.suffix_array, p)
+    }
+}
+
+impl<T, C, S, B> IndexWithExtrema for FMIndex<T, C, S, B>
+where
+    T: Character,
+    C: Converter<T>,
+    S: PartialExtremaArray,
+    B: BucketArray,
+{
+    fn min_sa(&self, interval: SaInterval) -> u64 {
+        suffix_array::get_extrema(self, &self.suffix_array, interval).0
+    }
+
+    fn max_sa(&self, interval: SaInterval) -> u64 {
+        suffix_array::get_extrema(self, &self.suffix_array, interval).1
     }
 }
 
-impl<T, C, S> IndexWithConverter<T> for FMIndex<T, C, S>
+impl<T, C, S, B> IndexWithConverter<T> for FMIndex<T, C, S, B>
 where
     C: Converter<T>,
 {
@@ -172,12 +505,23 @@ where
     }
 }
 
+impl<T, C, S, B> IndexWithPieces for FMIndex<T, C, S, B> {
+    fn pieces_count(&self) -> u64 {
+        1
+    }
+
+    fn piece_id(&self, _i: u64) -> PieceId {
+        PieceId::from(0)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::converter::RangeConverter;
     use crate::search::BackwardSearchIndex;
-    use crate::suffix_array::{NullSampler, SuffixOrderSampler};
+    use crate::suffix_array::{InvalidText, NullSampler, StepLimitExceeded, SuffixOrderSampler};
+    use crate::OccurrenceOutOfRange;
 
     #[test]
     fn test_small() {
@@ -262,6 +606,172 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_resample() {
+        let text = "The quick brown fox jumps over the lazy dog"
+            .to_string()
+            .into_bytes();
+        let converter = RangeConverter::new(b' ', b'z');
+        let sparse = FMIndex::new(
+            text.clone(),
+            converter.clone(),
+            SuffixOrderSampler::new().level(3),
+        );
+        let dense = sparse.resample(SuffixOrderSampler::new().level(0));
+        assert!(Arc::ptr_eq(&sparse.bw, &dense.bw));
+        let counting = FMIndex::new(text.clone(), converter.clone(), NullSampler::new());
+        let compressed = counting
+            .compress_cs()
+            .resample(SuffixOrderSampler::new().level(1));
+        for pattern in &["o", "the", "T", " ", "dog"] {
+            let expected = sparse.search_backward(pattern).locate();
+            assert_eq!(dense.search_backward(pattern).locate(), expected);
+            assert_eq!(compressed.search_backward(pattern).locate(), expected);
+        }
+    }
+
+    #[test]
+    fn test_from_suffix_array() {
+        let text = "mississippi\0".to_string().into_bytes();
+        let converter = RangeConverter::new(b'a', b'z');
+        let sa = sais::sais(&text, &converter);
+        let bw = sa
+            .iter()
+            .map(|&k| text[(k as usize + text.len() - 1) % text.len()])
+            .collect::<Vec<_>>();
+
+        let expected = FMIndex::new(text.clone(), converter.clone(), NullSampler::new());
+        let from_sa = FMIndex::from_suffix_array(
+            text,
+            sa,
+            converter.clone(),
+            SuffixOrderSampler::new().level(1),
+        );
+        let from_bwt = FMIndex::from_bwt(bw, converter);
+        for pattern in &["i", "ssi", "ppi", "m", "x"] {
+            let count = expected.search_backward(pattern).count();
+            assert_eq!(from_sa.search_backward(pattern).count(), count);
+            assert_eq!(from_bwt.search_backward(pattern).count(), count);
+        }
+        let mut res = from_sa.search_backward("issi").locate();
+        res.sort();
+        assert_eq!(res, vec![1, 4]);
+    }
+
+    #[test]
+    fn test_try_new() {
+        let converter = RangeConverter::new(b'a', b'z');
+        let index =
+            FMIndex::try_new(b"banana".to_vec(), converter.clone(), NullSampler::new()).unwrap();
+        assert_eq!(index.search_backward("ana").count(), 2);
+        assert_eq!(
+            FMIndex::<u8, _, ()>::try_new(
+                b"baNana".to_vec(),
+                converter.clone(),
+                NullSampler::new()
+            )
+            .err(),
+            Some(InvalidText::OutOfRange { position: 2 })
+        );
+        assert_eq!(
+            FMIndex::<u8, _, ()>::try_new(vec![], converter.clone(), NullSampler::new()).err(),
+            Some(InvalidText::Empty)
+        );
+
+        let bw = b"annb\0aa".to_vec();
+        let index = FMIndex::try_from_bwt(bw, converter.clone()).unwrap();
+        assert_eq!(index.search_backward("ana").count(), 2);
+        assert_eq!(
+            FMIndex::try_from_bwt(b"an\0b\0a".to_vec(), converter.clone()).err(),
+            Some(InvalidText::ExtraTerminator { position: 4 })
+        );
+        assert_eq!(
+            FMIndex::try_from_bwt(b"annb".to_vec(), converter).err(),
+            Some(InvalidText::NotTerminated)
+        );
+    }
+
+    #[test]
+    fn test_try_iter() {
+        let index = FMIndex::new(
+            b"banana".to_vec(),
+            RangeConverter::new(b'a', b'z'),
+            NullSampler::new(),
+        );
+        let search = index.search_backward("na");
+        assert_eq!(search.try_iter_forward(1).unwrap().next(), Some(b'n'));
+        assert_eq!(search.try_iter_backward(0).unwrap().next(), Some(b'a'));
+        let err = search.try_iter_forward(2).err().unwrap();
+        assert_eq!(err, OccurrenceOutOfRange { index: 2, count: 2 });
+        assert_eq!(
+            err.to_string(),
+            "occurrence 2 is out of range for 2 occurrences"
+        );
+        assert!(index.search_backward("x").try_iter_backward(0).is_err());
+    }
+
+    #[test]
+    fn test_new_with_scratch() {
+        let converter = RangeConverter::new(b'a', b'z');
+        let mut scratch = Scratch::with_capacity(64);
+        let capacity = scratch.capacity();
+        for text in &["mississippi", "abracadabra", "banana"] {
+            let text = text.to_string().into_bytes();
+            let expected = FMIndex::new(
+                text.clone(),
+                converter.clone(),
+                SuffixOrderSampler::new().level(1),
+            );
+            let index = FMIndex::new_with_scratch(
+                text,
+                converter.clone(),
+                SuffixOrderSampler::new().level(1),
+                &mut scratch,
+            );
+            for i in 0..index.len() {
+                assert_eq!(index.get_sa(i), expected.get_sa(i));
+            }
+            assert_eq!(scratch.capacity(), capacity);
+        }
+        let rlfmi = crate::RLFMIndex::new_with_scratch(
+            b"mississippi".to_vec(),
+            converter,
+            SuffixOrderSampler::new(),
+            &mut scratch,
+        );
+        assert_eq!(rlfmi.search_backward("ssi").count(), 2);
+        assert_eq!(scratch.into_inner().len(), 12);
+    }
+
+    #[test]
+    fn test_compress_cs() {
+        let text = "みんなみんなきれいだな"
+            .chars()
+            .map(|c| c as u32)
+            .collect::<Vec<u32>>();
+        let converter = RangeConverter::new('あ' as u32, 'ん' as u32);
+        let plain = FMIndex::new(text.clone(), converter, SuffixOrderSampler::new().level(2));
+        let compressed = FMIndex::new(
+            text,
+            RangeConverter::new('あ' as u32, 'ん' as u32),
+            SuffixOrderSampler::new().level(2),
+        )
+        .compress_cs();
+        assert!(compressed.size() < plain.size());
+
+        for pattern in &["み", "みん", "な", "きれい", "ぬ"] {
+            let pattern: Vec<u32> = pattern.chars().map(|c| c as u32).collect();
+            let expected = plain.search_backward(&pattern);
+            let actual = compressed.search_backward(&pattern);
+            assert_eq!(actual.count(), expected.count());
+            assert_eq!(actual.locate(), expected.locate());
+        }
+        for i in 0..plain.len() {
+            assert_eq!(compressed.get_f(i), plain.get_f(i));
+            assert_eq!(compressed.fl_map(i), plain.fl_map(i));
+        }
+    }
+
     #[test]
     fn test_lf_map() {
         let text = "mississippi".to_string().into_bytes();
@@ -293,6 +803,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_locate_with_budget() {
+        let text = "mississippi".to_string().into_bytes();
+        let fm_index = FMIndex::new(
+            text,
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(2),
+        );
+        let search = fm_index.search_backward("i");
+        assert_eq!(search.locate_with_budget(u64::MAX), Ok(search.locate()));
+        assert_eq!(
+            search.locate_with_budget(0),
+            Err(StepLimitExceeded { limit: 0 })
+        );
+
+        // The exact number of steps suffices, and one less does not.
+        let mut steps = 0;
+        for k in 0..search.count() {
+            let mut budget = u64::MAX;
+            fm_index.get_sa_with_budget(search.get_range().start + k, &mut budget);
+            steps += u64::MAX - budget;
+        }
+        assert!(steps > 0);
+        assert_eq!(search.locate_with_budget(steps), Ok(search.locate()));
+        assert!(search.locate_with_budget(steps - 1).is_err());
+    }
+
+    #[test]
+    fn test_selectivity_trace() {
+        let text = "mississippi".to_string().into_bytes();
+        let fm_index = FMIndex::new(text, RangeConverter::new(b'a', b'z'), NullSampler::new());
+        let search = fm_index.search_backward("ssi");
+        assert_eq!(search.selectivity_trace(), vec![4, 2, 2]);
+        let search = search.search_backward("mi");
+        assert_eq!(search.selectivity_trace(), vec![4, 2, 2, 2, 1]);
+        let search = search.search_backward("z");
+        assert_eq!(search.selectivity_trace(), vec![4, 2, 2, 2, 1, 0]);
+        assert_eq!(search.selectivity_trace().last(), Some(&search.count()));
+    }
+
     #[test]
     fn test_search_backword() {
         let text = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua.".to_string().into_bytes();
@@ -0,0 +1,111 @@
+use crate::auto::AutoIndex;
+use crate::bucket::{BucketArray, EliasFanoBucketArray};
+use crate::character::Character;
+use crate::converter::Converter;
+use crate::disk_array::DiskSampledArray;
+use crate::doc_array::DocumentArray;
+use crate::filter::QGramFilter;
+use crate::iter::BackwardIterableIndex;
+use crate::lcp::Plcp;
+use crate::pair_table::PairTable;
+use crate::projection::{ProjectedIndex, Projection};
+use crate::suffix_array::{
+    EliasFanoSampledArray, ExtremaSampledArray, IndexWithSA, PartialArray, RegionSampledArray,
+    SuffixOrderSampledArray, TextOrderSampledArray,
+};
+use crate::text::PackedText;
+use crate::text_store::{BlockCodec, TextStore};
+use crate::tokenizer::TokenizedIndex;
+use crate::warm::WarmRegion;
+use crate::{FMIndex, FMIndexMultiPieces, HybridFMIndex, RLFMIndex};
+
+/// The memory owned by a value outside of its inline representation, for accounting the memory of indexes.
+///
+/// The accounting rules are the same as those of the `size` methods, which return
+/// `std::mem::size_of::<Self>() + self.heap_size()`:
+///
+/// - The buffers of all components are counted recursively, by their lengths rather than capacities.
+/// - Components shared by clones, such as the BWT of [`FMIndex`], are counted in full by each owner.
+/// - Borrowed data, converters, tokenizers and projections are not counted.
+/// - Disk-backed arrays count only the blocks cached in memory.
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::SuffixOrderSampler;
+/// use fm_index::{FMIndex, HeapSize};
+///
+/// let index = FMIndex::new(b"mississippi".to_vec(), RangeConverter::new(b'a', b'z'), SuffixOrderSampler::new());
+/// assert_eq!(index.size(), std::mem::size_of_val(&index) + index.heap_size());
+/// ```
+pub trait HeapSize {
+    fn heap_size(&self) -> usize;
+}
+
+impl<T: Character> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.len() * std::mem::size_of::<T>()
+    }
+}
+
+// Implements `HeapSize` by the `size` method of a type, which includes its inline size.
+macro_rules! heap_size_by_size {
+    ([$($g:tt)*] $t:ty) => {
+        impl<$($g)*> HeapSize for $t {
+            fn heap_size(&self) -> usize {
+                self.size() - std::mem::size_of::<Self>()
+            }
+        }
+    };
+}
+
+heap_size_by_size!([T: Character] PackedText<T>);
+heap_size_by_size!([C: BlockCodec] TextStore<C>);
+heap_size_by_size!([T, C, B: BucketArray] FMIndex<T, C, (), B>);
+heap_size_by_size!([T, C, S: PartialArray, B: BucketArray] FMIndex<T, C, S, B>);
+heap_size_by_size!([T, C, B: BucketArray] RLFMIndex<T, C, (), B>);
+heap_size_by_size!([T, C, S: PartialArray, B: BucketArray] RLFMIndex<T, C, S, B>);
+heap_size_by_size!([T, C] AutoIndex<T, C, ()>);
+heap_size_by_size!([T, C, S: PartialArray] AutoIndex<T, C, S>);
+heap_size_by_size!([T, C] HybridFMIndex<T, C, ()>);
+heap_size_by_size!([T, C, S: PartialArray] HybridFMIndex<T, C, S>);
+heap_size_by_size!([T, C] FMIndexMultiPieces<T, C, ()>);
+heap_size_by_size!([T, C, S: PartialArray] FMIndexMultiPieces<T, C, S>);
+heap_size_by_size!([K, S: PartialArray] TokenizedIndex<K, S>);
+heap_size_by_size!(
+    [T: Character, C: Converter<T>, S: PartialArray, P: Projection<T>] ProjectedIndex<T, C, S, P>
+);
+heap_size_by_size!(['a, I: BackwardIterableIndex + IndexWithSA] WarmRegion<'a, I>);
+heap_size_by_size!([] DocumentArray);
+heap_size_by_size!([] QGramFilter);
+heap_size_by_size!([] PairTable);
+heap_size_by_size!([] Plcp);
+heap_size_by_size!([] SuffixOrderSampledArray);
+heap_size_by_size!([] TextOrderSampledArray);
+heap_size_by_size!([S: PartialArray] ExtremaSampledArray<S>);
+heap_size_by_size!([] EliasFanoSampledArray);
+heap_size_by_size!([] RegionSampledArray);
+heap_size_by_size!([] DiskSampledArray);
+heap_size_by_size!([] EliasFanoBucketArray);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::suffix_array::{NullSampler, SuffixOrderSampler};
+
+    #[test]
+    fn test_heap_size() {
+        let text = b"mississippi".to_vec();
+        assert_eq!(text.heap_size(), 11);
+        let converter = RangeConverter::new(b'a', b'z');
+        let index = FMIndex::new(text.clone(), converter.clone(), NullSampler::new());
+        let sampled = FMIndex::new(text.clone(), converter.clone(), SuffixOrderSampler::new());
+        assert!(index.heap_size() > 0);
+        assert!(sampled.heap_size() > index.heap_size());
+        let pieces = FMIndexMultiPieces::new(text, converter, NullSampler::new());
+        assert_eq!(
+            pieces.size(),
+            std::mem::size_of_val(&pieces) + pieces.heap_size()
+        );
+    }
+}
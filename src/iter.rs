@@ -1,19 +1,63 @@
+use crate::character::Character;
 use crate::converter::{Converter, IndexWithConverter};
+use crate::search::SaInterval;
+use crate::util::strict_assert;
 
+use std::iter::FusedIterator;
+
+/// An index that supports LF-mapping, which enables backward search and backward iteration.
 pub trait BackwardIterableIndex: Sized {
     type T: Copy + Clone;
     fn get_l(&self, i: u64) -> Self::T;
     fn lf_map(&self, i: u64) -> u64;
+    /// Returns the number of rows whose last character is less than `c`,
+    /// plus the number of occurrences of `c` in the last column before row `i`.
+    ///
+    /// For a character outside the alphabet of the index it returns 0,
+    /// so that a backward search for a pattern containing it is empty.
     fn lf_map2(&self, c: Self::T, i: u64) -> u64;
     fn len(&self) -> u64;
 
-    fn iter_backward(&self, i: u64) -> BackwardIterator<Self> {
-        debug_assert!(i < self.len());
+    /// Returns the rows `[s, e)` of the suffixes starting with `c`.
+    ///
+    /// It is the first step of a backward search from the whole range.
+    /// Indexes override it to look up the bucket of `c` without rank queries.
+    fn char_range(&self, c: Self::T) -> SaInterval {
+        SaInterval::new(self.lf_map2(c, 0), self.lf_map2(c, self.len()))
+    }
+
+    /// Returns false if `pattern` certainly does not occur in the text, so that a backward search for it
+    /// can return an empty result without rank queries.
+    ///
+    /// Indexes with a [`crate::QGramFilter`] override it to consult the filter; others accept every pattern.
+    fn may_contain(&self, pattern: &[Self::T]) -> bool {
+        let _ = pattern;
+        true
+    }
+
+    /// Returns true if the text consists of the terminator only.
+    fn is_empty(&self) -> bool {
+        self.len() <= 1
+    }
+
+    /// Returns the length of the text without the terminator, which is `len() - 1`
+    /// unless the index stores characters that are not in the text.
+    fn text_len_without_terminator(&self) -> u64 {
+        self.len() - 1
+    }
+
+    /// Returns the position of the character following the terminator (or a separator) located at `p`,
+    /// i.e. the start of the text (or of the piece), where an occurrence anchored at the start begins.
+    fn position_after_terminator(&self, p: u64) -> u64 {
+        (p + 1) % self.len()
+    }
+
+    fn iter_backward(&self, i: u64) -> BackwardIterator<'_, Self> {
+        strict_assert!(i < self.len(), "{} is out of range", i);
         BackwardIterator { index: self, i }
     }
 }
 
-
 pub struct BackwardIterator<'a, I>
 where
     I: BackwardIterableIndex,
@@ -35,6 +79,7 @@ where
     }
 }
 
+/// An index that supports FL-mapping, which enables forward iteration.
 pub trait ForwardIterableIndex: Sized {
     type T: Copy + Clone;
     fn get_f(&self, i: u64) -> Self::T;
@@ -42,8 +87,8 @@ pub trait ForwardIterableIndex: Sized {
     fn fl_map2(&self, c: Self::T, i: u64) -> u64;
     fn len(&self) -> u64;
 
-    fn iter_forward(&self, i: u64) -> ForwardIterator<Self> {
-        debug_assert!(i < self.len());
+    fn iter_forward(&self, i: u64) -> ForwardIterator<'_, Self> {
+        strict_assert!(i < self.len(), "{} is out of range", i);
         ForwardIterator { index: self, i }
     }
 }
@@ -67,4 +112,45 @@ where
         self.i = self.index.fl_map(self.i);
         Some(self.index.get_converter().convert_inv(c))
     }
-}
\ No newline at end of file
+}
+
+/// An iterator that stops at the separator (zero) ending the current piece of a multi-piece text.
+///
+/// The separator itself is not returned.
+pub struct PieceIterator<I> {
+    inner: I,
+    done: bool,
+}
+
+impl<I> PieceIterator<I> {
+    pub(crate) fn new(inner: I) -> Self {
+        PieceIterator { inner, done: false }
+    }
+}
+
+impl<T, I> Iterator for PieceIterator<I>
+where
+    T: Character,
+    I: Iterator<Item = T>,
+{
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.inner.next() {
+            Some(c) if !c.is_zero() => Some(c),
+            _ => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+impl<T, I> FusedIterator for PieceIterator<I>
+where
+    T: Character,
+    I: Iterator<Item = T>,
+{
+}
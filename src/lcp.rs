@@ -0,0 +1,194 @@
+use crate::character::Character;
+use crate::iter::BackwardIterableIndex;
+use crate::succinct::{BitVector, RankSelect};
+use crate::suffix_array::{IndexWithISA, IndexWithSA};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The permuted LCP array of an index over a single text, kept beside the index for
+/// queries on longest common prefixes such as maximal repeats and longest common extensions.
+///
+/// `PLCP[p]` is the length of the longest common prefix of the suffix at `p` and the suffix preceding it
+/// in the suffix array, so `LCP[i] = PLCP[SA[i]]`.
+/// Since `PLCP[p] + p` is non-decreasing, it is stored in _2n_ bits as a unary coded sequence
+/// and each entry is read by a select in constant time.
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::TextOrderSampler;
+/// use fm_index::{FMIndex, Plcp};
+///
+/// let index = FMIndex::new(b"banana".to_vec(), RangeConverter::new(b'a', b'z'), TextOrderSampler::new());
+/// let plcp = Plcp::new(&index);
+/// // suffixes in order: "", "a", "ana", "anana", "banana", "na", "nana"
+/// assert_eq!((0..7).map(|i| plcp.lcp(&index, i)).collect::<Vec<_>>(), vec![0, 0, 1, 3, 0, 0, 2]);
+/// // "anana" and "ana" share "ana"
+/// assert_eq!(plcp.lce(&index, 1, 3), 3);
+/// ```
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Plcp {
+    // PLCP[p] + p zeros in total precede the p-th one
+    bits: BitVector,
+    len: u64,
+}
+
+impl Plcp {
+    /// Builds the array by walking the whole text with LF-mapping and Kasai's algorithm,
+    /// which takes _O(n log σ)_ time and _n_ words and characters of temporary memory.
+    ///
+    /// The suffix array is recovered by the walk, so `index` needs no samples.
+    pub fn new<T, I>(index: &I) -> Self
+    where
+        T: Character,
+        I: BackwardIterableIndex<T = T>,
+    {
+        let n = index.len();
+        let mut sa = vec![0; n as usize];
+        let mut text = vec![T::zero(); n as usize];
+        let mut i = 0;
+        for p in (0..n).rev() {
+            sa[i as usize] = p;
+            text[((p + n - 1) % n) as usize] = index.get_l(i);
+            i = index.lf_map(i);
+        }
+        let mut isa = vec![0; n as usize];
+        for (i, &p) in sa.iter().enumerate() {
+            isa[p as usize] = i as u64;
+        }
+
+        let mut bits = BitVector::default();
+        let mut h = 0;
+        let mut last = 0;
+        for p in 0..n as usize {
+            let i = isa[p] as usize;
+            if i == 0 {
+                h = 0;
+            } else {
+                let q = sa[i - 1] as usize;
+                // the terminator matches nothing
+                while p + h < n as usize
+                    && q + h < n as usize
+                    && text[p + h] == text[q + h]
+                    && !text[p + h].is_zero()
+                {
+                    h += 1;
+                }
+            }
+            let value = h as u64 + p as u64;
+            for _ in last..value {
+                bits.push(false);
+            }
+            bits.push(true);
+            last = value;
+            h = h.saturating_sub(1);
+        }
+        Plcp { bits, len: n }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns `PLCP[p]` for a text position `p`.
+    pub fn get(&self, p: u64) -> u64 {
+        self.bits.select1(p) - 2 * p
+    }
+
+    /// Returns `LCP[i]`, which takes the time of locating the `i`-th row.
+    pub fn lcp<I: IndexWithSA>(&self, index: &I, i: u64) -> u64 {
+        self.get(index.get_sa(i))
+    }
+
+    /// Returns the length of the longest common prefix of the suffixes at text positions `p` and `q`.
+    ///
+    /// This takes the minimum of LCP over the rows between those of the suffixes,
+    /// locating each of them, so it is fast when the suffixes are close in the suffix array.
+    pub fn lce<I: IndexWithSA + IndexWithISA>(&self, index: &I, p: u64, q: u64) -> u64 {
+        if p == q {
+            return self.len - p - 1;
+        }
+        let (i, j) = (index.get_isa(p), index.get_isa(q));
+        (i.min(j) + 1..=i.max(j))
+            .map(|r| self.lcp(index, r))
+            .min()
+            .unwrap()
+    }
+
+    pub fn size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.bits.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::suffix_array::{NullSampler, TextOrderSampler};
+    use crate::{FMIndex, RLFMIndex};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn naive_lce(text: &[u8], p: usize, q: usize) -> u64 {
+        text[p..]
+            .iter()
+            .zip(&text[q..])
+            .take_while(|(a, b)| a == b)
+            .count() as u64
+    }
+
+    #[test]
+    fn test_plcp() {
+        let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+        let text = (0..200)
+            .map(|_| rng.gen_range(b'a', b'd'))
+            .collect::<Vec<_>>();
+        let index = FMIndex::new(
+            text.clone(),
+            RangeConverter::new(b'a', b'z'),
+            TextOrderSampler::new().level(2),
+        );
+        let plcp = Plcp::new(&index);
+        assert_eq!(plcp.len(), text.len() as u64 + 1);
+        assert!(plcp.bits.len() <= 2 * plcp.len());
+
+        let mut sa = (0..text.len()).collect::<Vec<_>>();
+        sa.sort_by_key(|&p| &text[p..]);
+        assert_eq!(plcp.lcp(&index, 0), 0);
+        assert_eq!(plcp.lcp(&index, 1), 0);
+        for i in 1..sa.len() {
+            let expected = naive_lce(&text, sa[i - 1], sa[i]);
+            assert_eq!(plcp.lcp(&index, i as u64 + 1), expected, "row {}", i + 1);
+        }
+        for _ in 0..50 {
+            let p = rng.gen_range(0, text.len());
+            let q = rng.gen_range(0, text.len());
+            assert_eq!(
+                plcp.lce(&index, p as u64, q as u64),
+                naive_lce(&text, p, q),
+                "{} {}",
+                p,
+                q
+            );
+        }
+
+        let null_index = FMIndex::new(
+            text.clone(),
+            RangeConverter::new(b'a', b'z'),
+            NullSampler::new(),
+        );
+        assert!(Plcp::new(&null_index) == plcp);
+
+        let rlfm_index = RLFMIndex::new(
+            text,
+            RangeConverter::new(b'a', b'z'),
+            TextOrderSampler::new().level(2),
+        );
+        assert!(Plcp::new(&rlfm_index) == plcp);
+    }
+}
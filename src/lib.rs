@@ -19,6 +19,21 @@
 //! fm-index = "0.1"
 //! ```
 //!
+//! The `serde` feature (enabled by default) derives `Serialize` and `Deserialize` for indexes.
+//! Disable default features to reduce the code size, e.g. for `wasm32-unknown-unknown`.
+//! The `release-asserts` feature keeps the precondition assertions of public methods, such as the bounds of
+//! iterators and samples, in release builds, so that a violated precondition panics instead of
+//! giving a wrong result. It does not make the crate panic-free, and no feature changes a signature:
+//! only the `try_` variants, i.e. `FMIndex::try_new`, `FMIndex::try_from_bwt`, `RLFMIndex::try_new`,
+//! `Search::try_iter_backward` and `Search::try_iter_forward`, return errors instead of panicking.
+//! The `bio-io` feature adds the `bio` module reading FASTA and FASTQ files into multi-piece texts,
+//! and the `corpus-io` feature adds the `corpus` module reading JSON lines and CSV.
+//! The `rand` feature adds `Search::sample_positions` for sampling occurrences at random.
+//!
+//! The crate denies `unsafe` code. The only exception is the C ABI of the `ffi` feature,
+//! whose functions take raw pointers; rank, select and bit manipulation are implemented in safe code
+//! on top of the backend.
+//!
 //! # Example
 //! ```
 //! use fm_index::converter::RangeConverter;
@@ -93,9 +108,91 @@
 //! - a wavelet matrix that stores the run heads of BWT of the text string
 //! - a succinct bit vector which stores the run lengths of BWT of the text string
 //! - a succinct bit vector which stores the run lengths of BWT of the text string
-//! sorted in alphabetical order of corresponding run heads
+//!   sorted in alphabetical order of corresponding run heads
 //! - an array of size _O(σ)_ (_σ_: number of characters)
-//! which stores the number of characters smaller than a given character in run heads
+//!   which stores the number of characters smaller than a given character in run heads
+//!
+//! ## Hybrid FM-Index
+//!
+//! The BWT is divided into fixed-size blocks, and each block is stored either as a wavelet matrix
+//! or in the same way as the run-length FM-index, whichever is smaller.
+//! It also stores the number of occurrences of each character before each block.
+//!
+//! ## Multi-Piece FM-Index
+//!
+//! An FM-index over a text consisting of pieces (e.g. documents) delimited by zero.
+//! In addition to an FM-index, it stores the ID of the piece following each separator in suffix order,
+//! which gives the piece containing each occurrence, and finds a piece equal to a pattern without walking the text.
+//!
+//! # Extending
+//!
+//! Queries are not tied to a particular index type.
+//! Each capability of [`Search`] is provided for any index that implements the corresponding trait:
+//!
+//! - [`BackwardIterableIndex`] enables backward search, counting and [`Search::iter_backward`]
+//! - [`ForwardIterableIndex`] enables [`Search::iter_forward`]
+//! - [`suffix_array::IndexWithSA`] enables [`Search::locate`]
+//! - [`converter::IndexWithConverter`] restores original characters in iterators
+//!
+//! A new backend therefore needs one implementation of each trait it supports,
+//! and a new query only needs to be written once against these traits.
+//! The same traits serve as the extension point for other crates:
+//! a wrapper type can delegate them to an inner index to get the whole query API.
+//!
+//! ```
+//! use fm_index::converter::{IndexWithConverter, RangeConverter};
+//! use fm_index::suffix_array::{IndexWithSA, SuffixOrderSampledArray, SuffixOrderSampler};
+//! use fm_index::{BackwardIterableIndex, BackwardSearchIndex, FMIndex, Search};
+//!
+//! struct Corpus {
+//!     index: FMIndex<u8, RangeConverter<u8>, SuffixOrderSampledArray>,
+//! }
+//!
+//! impl BackwardIterableIndex for Corpus {
+//!     type T = u8;
+//!     fn get_l(&self, i: u64) -> u8 {
+//!         self.index.get_l(i)
+//!     }
+//!     fn lf_map(&self, i: u64) -> u64 {
+//!         self.index.lf_map(i)
+//!     }
+//!     fn lf_map2(&self, c: u8, i: u64) -> u64 {
+//!         self.index.lf_map2(c, i)
+//!     }
+//!     fn len(&self) -> u64 {
+//!         BackwardIterableIndex::len(&self.index)
+//!     }
+//! }
+//!
+//! impl IndexWithSA for Corpus {
+//!     fn get_sa(&self, i: u64) -> u64 {
+//!         self.index.get_sa(i)
+//!     }
+//! }
+//!
+//! impl IndexWithConverter<u8> for Corpus {
+//!     type C = RangeConverter<u8>;
+//!     fn get_converter(&self) -> &Self::C {
+//!         self.index.get_converter()
+//!     }
+//! }
+//!
+//! fn dolor(corpus: &Corpus) -> Search<'_, Corpus> {
+//!     corpus.search_backward("dolor")
+//! }
+//!
+//! let text = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit".to_vec();
+//! let index = FMIndex::new(
+//!     text,
+//!     RangeConverter::new(b' ', b'~'),
+//!     SuffixOrderSampler::new().level(2),
+//! );
+//! let corpus = Corpus { index };
+//! let search = dolor(&corpus);
+//! assert_eq!(search.count(), 1);
+//! assert_eq!(search.locate(), vec![12]);
+//! assert_eq!(search.iter_backward(0).take(6).collect::<Vec<_>>(), b" muspi".to_owned());
+//! ```
 //!
 //! # Reference
 //!
@@ -107,21 +204,112 @@
 //!
 //! [4] Claude F., Navarro G. (2012). The Wavelet Matrix. In: Calderón-Benavides L., González-Caro C., Chávez E., Ziviani N. (eds) String Processing and Information Retrieval. SPIRE 2012. https://doi.org/10.1007/978-3-642-34109-0_18
 #![allow(clippy::len_without_is_empty)]
+#![deny(unsafe_code)]
 
+pub mod anchor;
+pub mod bench;
+#[cfg(feature = "bio-io")]
+pub mod bio;
+pub mod bucket;
+pub mod bulk;
 pub mod converter;
+#[cfg(feature = "corpus-io")]
+pub mod corpus;
+pub mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod suffix_array;
+pub mod text_store;
+pub mod tokenizer;
+pub mod verify;
 
+mod ambiguity;
+mod auto;
+mod bidirectional;
+mod budget;
 mod character;
+mod dedup;
+mod diff;
+mod disk_array;
+mod display_match;
+mod doc_array;
+mod estimate;
+mod filter;
 mod fm_index;
+mod heap_size;
+mod hybrid;
 mod iter;
+mod lcp;
+mod locator;
+mod matching_stats;
+mod multi_pieces;
+mod pair_table;
+mod pending;
+mod piece;
+mod position;
+mod position_set;
+mod projection;
 mod rlfmi;
 mod sais;
 mod search;
+mod session;
+mod stats;
+mod succinct;
+mod suggest;
+mod text;
+mod text_builder;
 mod util;
+#[cfg(feature = "serde")]
+mod versioned;
+mod visit;
+mod warm;
 mod wavelet_matrix;
 
+pub use crate::ambiguity::{Ambiguity, BranchLimitExceeded, Iupac};
+pub use crate::anchor::AnchoredSearch;
+pub use crate::auto::{auto_build, AutoIndex, Preference};
+pub use crate::bidirectional::BidirectionalIndex;
+pub use crate::budget::{BudgetLimit, PartialResult, QueryBudget};
+pub use crate::character::{
+    Character, CharacterOverflow, DenseAlphabet, InvalidSymbol, SymbolEncoding,
+};
+pub use crate::dedup::DuplicateCluster;
+pub use crate::diff::IndexDiff;
+pub use crate::display_match::DisplayMatch;
+pub use crate::doc_array::DocumentArray;
+pub use crate::estimate::IndexEstimate;
+pub use crate::filter::QGramFilter;
 pub use crate::fm_index::FMIndex;
+pub use crate::heap_size::HeapSize;
+pub use crate::hybrid::HybridFMIndex;
+pub use crate::lcp::Plcp;
+pub use crate::locator::Locator;
+pub use crate::matching_stats::MatchingStatistic;
+pub use crate::multi_pieces::{
+    ConstructionStrategy, FMIndexMultiPieces, PieceScore, PieceStat, SpanningSearch,
+};
+pub use crate::pair_table::PairTable;
+pub use crate::pending::{LiveIndex, PendingBuffer, PendingMerge};
+pub use crate::piece::{
+    hash_piece, validate_multi_piece, IndexWithPieces, MultiPieceDiagnostics, PieceId,
+};
+pub use crate::position::{GlobalPos, PiecePos};
+pub use crate::position_set::{merge_unique, MergeUnique, PositionSet, SetMerge};
+pub use crate::projection::{ProjectedIndex, Projection};
 pub use crate::rlfmi::RLFMIndex;
+pub use crate::stats::IndexStats;
+pub use crate::suggest::Suggestion;
+pub use crate::text::{PackedText, Text};
+pub use crate::text_builder::{PieceOutcome, PieceReport, SkipReason, TextBuilder};
+#[cfg(feature = "serde")]
+pub use crate::versioned::{Versioned, VersionedFormat, FORMAT_VERSION};
+pub use crate::visit::{Visit, VisitStep};
 
-pub use iter::{BackwardIterableIndex, ForwardIterableIndex};
-pub use search::BackwardSearchIndex;
+pub use iter::{
+    BackwardIterableIndex, BackwardIterator, ForwardIterableIndex, ForwardIterator, PieceIterator,
+};
+pub use search::{
+    BackwardSearchIndex, InvalidPattern, Match, OccurrenceOutOfRange, PatternTooLong, SaInterval,
+    Search, SearchState,
+};
+pub use session::QuerySession;
@@ -0,0 +1,105 @@
+use crate::search::{BackwardSearchIndex, SearchState};
+use crate::suffix_array::IndexWithSA;
+
+/// Reusable buffers for answering many locate queries, e.g. one per worker thread.
+///
+/// [`crate::Search::locate`] allocates the pattern and a vector of positions per query.
+/// A locator narrows a [`SearchState`] in place and keeps the positions in a buffer it owns,
+/// so once the buffer has grown to the largest result, queries no longer allocate.
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::SuffixOrderSampler;
+/// use fm_index::{FMIndex, Locator};
+///
+/// let index = FMIndex::new(b"mississippi".to_vec(), RangeConverter::new(b'a', b'z'), SuffixOrderSampler::new());
+/// let mut locator = Locator::new();
+/// let mut positions = locator.locate(&index, "ssi").to_vec();
+/// positions.sort();
+/// assert_eq!(positions, vec![2, 5]);
+/// assert_eq!(locator.locate(&index, "pp"), &[8]);
+/// assert!(locator.locate(&index, "x").is_empty());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Locator {
+    positions: Vec<u64>,
+}
+
+impl Locator {
+    pub fn new() -> Self {
+        Locator::default()
+    }
+
+    /// Creates a locator holding up to `capacity` positions without reallocation.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Locator {
+            positions: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the number of positions the buffer holds without reallocation.
+    pub fn capacity(&self) -> usize {
+        self.positions.capacity()
+    }
+
+    /// Lists the positions of all occurrences of `pattern` in the order of rows,
+    /// which are valid until the next query.
+    pub fn locate<I, K>(&mut self, index: &I, pattern: K) -> &[u64]
+    where
+        I: BackwardSearchIndex + IndexWithSA,
+        K: AsRef<[I::T]>,
+    {
+        let mut state = SearchState::new(index);
+        index.search_in_place(&mut state, pattern);
+        state.locate_into(index, &mut self.positions);
+        &self.positions
+    }
+
+    /// Appends the positions of all occurrences of `pattern` to `results`,
+    /// e.g. to collect those of several queries in a buffer of the caller.
+    ///
+    /// The positions pass through the buffer of the locator, as in [`Locator::locate`].
+    pub fn locate_into<I, K>(&mut self, index: &I, pattern: K, results: &mut Vec<u64>)
+    where
+        I: BackwardSearchIndex + IndexWithSA,
+        K: AsRef<[I::T]>,
+    {
+        results.extend_from_slice(self.locate(index, pattern));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::suffix_array::SuffixOrderSampler;
+    use crate::RLFMIndex;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_locator() {
+        let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+        let text = (0..200)
+            .map(|_| rng.gen_range(b'a', b'd'))
+            .collect::<Vec<_>>();
+        let index = RLFMIndex::new(
+            text,
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(2),
+        );
+        let mut locator = Locator::with_capacity(4);
+        let mut all = vec![];
+        for pattern in &["a", "ab", "cab", "ddd", "abc", ""] {
+            let expected = index.search_backward(pattern).locate();
+            assert_eq!(locator.locate(&index, pattern), &expected[..]);
+            locator.locate_into(&index, pattern, &mut all);
+        }
+        assert!(locator.capacity() > 4);
+        let expected = ["a", "ab", "cab", "ddd", "abc", ""]
+            .iter()
+            .flat_map(|pattern| index.search_backward(pattern).locate())
+            .collect::<Vec<_>>();
+        assert_eq!(all, expected);
+    }
+}
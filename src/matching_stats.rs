@@ -0,0 +1,22 @@
+use crate::search::SaInterval;
+use crate::suffix_array::IndexWithSA;
+
+/// The longest substring of a query starting at a position that occurs in the text,
+/// returned by [`crate::BackwardSearchIndex::matching_statistics`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MatchingStatistic {
+    /// The length of the substring, which is zero if the character at the position does not occur in the text.
+    pub len: u64,
+    /// The rows of the occurrences of the substring.
+    pub range: SaInterval,
+}
+
+impl MatchingStatistic {
+    /// Returns the position of one of the occurrences of the substring, or `None` if it is empty.
+    pub fn locate_one<I: IndexWithSA>(&self, index: &I) -> Option<u64> {
+        if self.len == 0 {
+            return None;
+        }
+        Some(index.get_sa(self.range.start))
+    }
+}
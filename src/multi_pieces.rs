@@ -0,0 +1,2460 @@
+use crate::ambiguity::BranchLimitExceeded;
+use crate::bucket::{BucketArray, EliasFanoBucketArray};
+use crate::character::Character;
+use crate::converter::{Converter, IdConverter, IndexWithConverter};
+use crate::diff::IndexDiff;
+use crate::filter::{self, QGramFilter};
+use crate::iter::{ForwardIterator, PieceIterator};
+use crate::piece::{
+    self, IndexWithPieces, PieceAliases, PieceBoundary, PieceGroups, PieceId, PieceKeys,
+    PieceSamples,
+};
+use crate::position::{GlobalPos, PiecePos};
+use crate::sais;
+use crate::search::{BackwardSearchIndex, Match, Search, SearchState};
+use crate::stats::{self, IndexStats, SuffixArrayStats};
+use crate::suffix_array::{self, ArraySampler, IndexWithSA, PartialArray};
+use crate::text;
+use crate::util::{self, strict_assert};
+use crate::wavelet_matrix::WaveletMatrix;
+use crate::{BackwardIterableIndex, ForwardIterableIndex};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::thread;
+
+// Codes of characters stored in the BWT.
+// Other characters `c` are stored as `converter.convert(c) + 1`.
+const TERMINATOR: u64 = 0;
+const SEPARATOR: u64 = 1;
+
+/// An FM-index over a text consisting of pieces (e.g. documents) delimited by zero.
+///
+/// The text `p_0 \0 p_1 \0 ... p_{k-1} \0` is stored as `\0 p_0 \0 p_1 \0 ... p_{k-1} $`
+/// with a separator before the first piece and a unique terminator `$` after the last one,
+/// so that every piece is enclosed by zeros.
+/// Hence a pattern `"\0" + P`, `P + "\0"` or `"\0" + P + "\0"` matches `P` as a prefix, suffix or whole of a piece.
+///
+/// Positions returned by `locate` refer to the original text.
+/// The separator before the first piece is not in the original text,
+/// so an occurrence starting there is located at 0, the start of the first piece.
+/// Locating walks LF-mapping back to a sampled row or to the separator preceding the piece,
+/// whose position is known, so an occurrence is located within the length of its piece
+/// regardless of the sampling level, which bounds the latency for many short pieces.
+///
+/// There are two ways to find the piece of an occurrence:
+///
+/// - [`IndexWithPieces::piece_id`] walks LF-mapping back to the separator preceding the occurrence
+///   and looks up the ID of the piece following it.
+///   It needs no suffix array, but takes time proportional to the distance from the start of the piece,
+///   unless it is bounded by the samples added by [`FMIndexMultiPieces::with_piece_samples`].
+/// - [`FMIndexMultiPieces::piece_id_at`] takes a position located with the sampled suffix array
+///   and finds the piece by a binary search on the start positions of pieces, stored with Elias-Fano encoding.
+///   It costs _O(k log(n/k))_ bits for _k_ pieces, and is faster for long pieces when the position is needed anyway.
+///
+/// For aggregating the pieces of many occurrences, [`FMIndexMultiPieces::document_array`] builds
+/// a [`crate::DocumentArray`] of _n log k_ bits storing the piece of every row.
+#[derive(PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FMIndexMultiPieces<T, C, S> {
+    bw: WaveletMatrix,
+    cs: Vec<u64>,
+    #[cfg_attr(feature = "serde", serde(rename = "doc"))]
+    boundary: PieceBoundary,
+    // start positions of pieces in the original text
+    starts: EliasFanoBucketArray,
+    // external keys of pieces attached by `with_keys`
+    #[cfg_attr(feature = "serde", serde(default))]
+    keys: Option<PieceKeys>,
+    // original pieces of a deduplicated index built by `new_storage_deduplicated`
+    #[cfg_attr(feature = "serde", serde(default))]
+    aliases: Option<PieceAliases>,
+    // hashes of pieces computed by `with_piece_hashes`
+    #[cfg_attr(feature = "serde", serde(default))]
+    hashes: Option<Vec<u64>>,
+    // pieces of sampled rows added by `with_piece_samples`
+    #[cfg_attr(feature = "serde", serde(default))]
+    piece_samples: Option<PieceSamples>,
+    // groups of pieces assigned by `with_groups`
+    #[cfg_attr(feature = "serde", serde(default))]
+    groups: Option<PieceGroups>,
+    // q-grams of the text added by `with_qgram_filter`
+    #[cfg_attr(feature = "serde", serde(default))]
+    qgram_filter: Option<QGramFilter>,
+    converter: C,
+    suffix_array: S,
+    _t: std::marker::PhantomData<T>,
+}
+
+impl<T, C, S> FMIndexMultiPieces<T, C, S>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    /// Builds an index over `text` whose pieces are delimited by zero.
+    ///
+    /// The last piece may or may not be followed by zero.
+    /// Pieces must not be empty; use [`crate::validate_multi_piece`] to check `text` beforehand.
+    pub fn new<B: ArraySampler<S>>(text: Vec<T>, converter: C, sampler: B) -> Self {
+        let (codes, separators) = Self::encode_pieces(text, &converter);
+        let sa = sais::sais(&codes, &IdConverter::new(converter.len() + 1));
+        Self::from_codes(&codes, separators, sa, converter, sampler)
+    }
+
+    /// Builds an index like [`FMIndexMultiPieces::new`], sorting the suffixes as chosen by `strategy`.
+    ///
+    /// The index is the same for every strategy.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::SuffixOrderSampler;
+    /// use fm_index::{BackwardSearchIndex, ConstructionStrategy, FMIndexMultiPieces};
+    ///
+    /// let text = b"apple\0banana\0apple\0cherry".to_vec();
+    /// let converter = RangeConverter::new(b'a', b'z');
+    /// let strategy = ConstructionStrategy::Segmented { threads: 2 };
+    /// let index = FMIndexMultiPieces::new_with_strategy(text.clone(), converter.clone(), SuffixOrderSampler::new(), strategy);
+    /// assert!(index == FMIndexMultiPieces::new(text, converter, SuffixOrderSampler::new()));
+    /// assert_eq!(index.search_backward("an").count(), 2);
+    /// ```
+    pub fn new_with_strategy<B: ArraySampler<S>>(
+        text: Vec<T>,
+        converter: C,
+        sampler: B,
+        strategy: ConstructionStrategy,
+    ) -> Self
+    where
+        T: Send + Sync,
+    {
+        match strategy {
+            ConstructionStrategy::Monolithic => Self::new(text, converter, sampler),
+            ConstructionStrategy::Segmented { threads } => {
+                assert!(threads > 0, "threads must be positive");
+                let (codes, separators) = Self::encode_pieces(text, &converter);
+                let sa = segmented_suffix_array(&codes, &separators, converter.len() + 1, threads);
+                Self::from_codes(&codes, separators, sa, converter, sampler)
+            }
+        }
+    }
+
+    // Encodes `text` as `SEP p_0 SEP p_1 ... p_{k-1} $` and returns it with the positions of the separators.
+    fn encode_pieces(text: Vec<T>, converter: &C) -> (Vec<T>, Vec<u64>) {
+        let sigma = converter.len() + 1;
+        assert!(
+            T::try_from_u64(sigma - 1).is_ok(),
+            "the converter leaves no room for separators"
+        );
+
+        let mut codes = Vec::with_capacity(text.len() + 2);
+        let mut separators = vec![];
+        codes.push(T::from_u64(SEPARATOR));
+        separators.push(0);
+        for c in text {
+            if c.is_zero() {
+                assert!(
+                    separators.last() != Some(&(codes.len() as u64 - 1)),
+                    "pieces must not be empty"
+                );
+                separators.push(codes.len() as u64);
+                codes.push(T::from_u64(SEPARATOR));
+            } else {
+                codes.push(T::from_u64(converter.convert(c).into() + 1));
+            }
+        }
+        if codes.last().is_some_and(|c| (*c).into() == SEPARATOR) {
+            assert!(codes.len() > 1, "pieces must not be empty");
+            separators.pop();
+            codes.pop();
+        }
+        codes.push(T::from_u64(TERMINATOR));
+        suffix_array::assert_text_len(codes.len() as u64);
+        (codes, separators)
+    }
+
+    fn from_codes<X, B>(
+        codes: &X,
+        separators: Vec<u64>,
+        sa: Vec<u64>,
+        converter: C,
+        sampler: B,
+    ) -> Self
+    where
+        X: text::Text<T = T> + ?Sized,
+        B: ArraySampler<S>,
+    {
+        let (bw, cs, doc) = Self::encode_bwt(codes, &separators, &sa, converter.len() + 1);
+        Self::from_parts(bw, cs, doc, separators, sa, converter, sampler)
+    }
+
+    // Computes the BWT of `codes`, the start rows of characters and the pieces following the separators in the order of rows.
+    fn encode_bwt<X>(
+        codes: &X,
+        separators: &[u64],
+        sa: &[u64],
+        sigma: u64,
+    ) -> (WaveletMatrix, Vec<u64>, Vec<u64>)
+    where
+        X: text::Text<T = T> + ?Sized,
+    {
+        let n = codes.len();
+        let cs = sais::get_bucket_start_pos(&sais::count_chars(codes, &IdConverter::new(sigma)));
+
+        let mut bw = vec![T::zero(); n];
+        let mut doc = Vec::with_capacity(separators.len());
+        for i in 0..n {
+            let k = sa[i] as usize;
+            bw[i] = codes.get((k + n - 1) % n);
+            if codes.get(k).into() == SEPARATOR {
+                // separators are sorted by their positions
+                doc.push(separators.binary_search(&(k as u64)).unwrap() as u64);
+            }
+        }
+        let bw = WaveletMatrix::new_with_size(bw, util::log2(sigma - 1) + 1);
+        (bw, cs, doc)
+    }
+
+    fn from_parts<B: ArraySampler<S>>(
+        bw: WaveletMatrix,
+        cs: Vec<u64>,
+        doc: Vec<u64>,
+        separators: Vec<u64>,
+        sa: Vec<u64>,
+        converter: C,
+        sampler: B,
+    ) -> Self {
+        // The piece following the separator at `p` starts at `p + 1`, which is `p` in the original text.
+        let starts = EliasFanoBucketArray::new(&separators);
+
+        FMIndexMultiPieces {
+            bw,
+            cs,
+            boundary: PieceBoundary::new(doc),
+            starts,
+            keys: None,
+            aliases: None,
+            hashes: None,
+            piece_samples: None,
+            groups: None,
+            qgram_filter: None,
+            converter,
+            suffix_array: sampler.sample(sa),
+            _t: std::marker::PhantomData::<T>,
+        }
+    }
+
+    /// Builds an index over the pieces given by `(offset, length)` spans of `buffer`, in the order of `spans`,
+    /// e.g. for a corpus resident in memory in segmented form.
+    ///
+    /// The text of the pieces delimited by separators is never materialized;
+    /// its characters are read from `buffer` during construction, which takes _O(log k)_ time per access
+    /// for _k_ pieces, so the peak memory is lower than that of [`FMIndexMultiPieces::new`] by the size of the text.
+    /// Spans may overlap or leave gaps, but must not be empty or contain zero.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::SuffixOrderSampler;
+    /// use fm_index::{BackwardSearchIndex, FMIndexMultiPieces};
+    ///
+    /// let buffer = b"[apple][banana][cherry]";
+    /// let spans = [(1, 5), (8, 6), (16, 6)];
+    /// let index = FMIndexMultiPieces::from_spans(buffer, &spans, RangeConverter::new(b'a', b'z'), SuffixOrderSampler::new());
+    /// assert!(index == FMIndexMultiPieces::new(b"apple\0banana\0cherry".to_vec(), RangeConverter::new(b'a', b'z'), SuffixOrderSampler::new()));
+    /// assert_eq!(index.search_backward("an").count(), 2);
+    /// ```
+    pub fn from_spans<B: ArraySampler<S>>(
+        buffer: &[T],
+        spans: &[(usize, usize)],
+        converter: C,
+        sampler: B,
+    ) -> Self {
+        let sigma = converter.len() + 1;
+        let (bw, cs, doc, separators, sa) = {
+            let codes = SpanCodes::new(buffer, spans, &converter);
+            let sa = sais::sais(&codes, &IdConverter::new(sigma));
+            let (bw, cs, doc) = Self::encode_bwt(&codes, &codes.separators, &sa, sigma);
+            (bw, cs, doc, codes.separators, sa)
+        };
+        Self::from_parts(bw, cs, doc, separators, sa, converter, sampler)
+    }
+
+    /// Builds an index like [`FMIndexMultiPieces::new`], but stores only one copy of identical pieces.
+    ///
+    /// The deduplication is at the storage level: the index is that of the text of the distinct pieces,
+    /// stored in the order of their first occurrences, with a map back to the pieces of `text`.
+    /// Hence every other API, e.g. [`IndexWithPieces::piece_id`], [`Search::locate`],
+    /// [`FMIndexMultiPieces::piece_stats`] and [`FMIndexMultiPieces::piece_hash`], counts, identifies and
+    /// locates stored pieces, and an occurrence in a piece with copies is reported once.
+    /// Only the `original_*` methods, e.g. [`FMIndexMultiPieces::original_piece_ids`],
+    /// map a stored piece back to all the pieces of `text` equal to it.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::SuffixOrderSampler;
+    /// use fm_index::{BackwardSearchIndex, FMIndexMultiPieces, IndexWithPieces, PieceId};
+    ///
+    /// let text = b"apple\0banana\0apple\0cherry\0apple".to_vec();
+    /// let index = FMIndexMultiPieces::new_storage_deduplicated(text, RangeConverter::new(b'a', b'z'), SuffixOrderSampler::new());
+    /// assert_eq!(index.pieces_count(), 3);
+    /// assert_eq!(index.original_pieces_count(), 5);
+    /// assert_eq!(index.search_backward("pl").count(), 1);
+    /// let ids = index.search_backward("pl").original_piece_ids();
+    /// assert_eq!(ids, vec![PieceId::from(0), PieceId::from(2), PieceId::from(4)]);
+    /// assert_eq!(index.stored_piece_id(PieceId::from(3)), PieceId::from(2));
+    /// ```
+    pub fn new_storage_deduplicated<B: ArraySampler<S>>(
+        text: Vec<T>,
+        converter: C,
+        sampler: B,
+    ) -> Self {
+        let mut pieces = text.split(|c| c.is_zero()).collect::<Vec<_>>();
+        if text.last().is_some_and(|c| c.is_zero()) {
+            pieces.pop();
+        }
+        // Character is not hashable, so identical pieces are found by sorting
+        let mut order = (0..pieces.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| pieces[a].cmp(pieces[b]).then(a.cmp(&b)));
+        let mut first = vec![0; pieces.len()];
+        for (j, &i) in order.iter().enumerate() {
+            first[i] = if j > 0 && pieces[order[j - 1]] == pieces[i] {
+                first[order[j - 1]]
+            } else {
+                i
+            };
+        }
+
+        let mut stored = vec![0; pieces.len()];
+        let mut distinct = vec![];
+        for i in 0..pieces.len() {
+            stored[i] = if first[i] == i {
+                distinct.push(pieces[i]);
+                distinct.len() as u64 - 1
+            } else {
+                stored[first[i]]
+            };
+        }
+        let aliases = PieceAliases::new(stored, distinct.len() as u64);
+        let mut index = Self::new(distinct.join(&T::zero()), converter, sampler);
+        index.aliases = Some(aliases);
+        index
+    }
+}
+
+// The text encoded by `encode_pieces` for the pieces given by spans of a buffer, read without copying them.
+struct SpanCodes<'b, T, C> {
+    buffer: &'b [T],
+    spans: &'b [(usize, usize)],
+    converter: &'b C,
+    // positions of the separators preceding the pieces
+    separators: Vec<u64>,
+    len: usize,
+}
+
+impl<'b, T, C> SpanCodes<'b, T, C>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    fn new(buffer: &'b [T], spans: &'b [(usize, usize)], converter: &'b C) -> Self {
+        let sigma = converter.len() + 1;
+        assert!(
+            T::try_from_u64(sigma - 1).is_ok(),
+            "the converter leaves no room for separators"
+        );
+        assert!(
+            !spans.is_empty(),
+            "a multi-piece text has at least one piece"
+        );
+        let mut separators = Vec::with_capacity(spans.len());
+        let mut len = 0;
+        for &(offset, n) in spans {
+            assert!(n > 0, "pieces must not be empty");
+            let piece = &buffer[offset..offset + n];
+            assert!(
+                piece.iter().all(|c| !c.is_zero()),
+                "pieces must not contain zero"
+            );
+            separators.push(len as u64);
+            len += n + 1;
+        }
+        // the terminator
+        len += 1;
+        suffix_array::assert_text_len(len as u64);
+        SpanCodes {
+            buffer,
+            spans,
+            converter,
+            separators,
+            len,
+        }
+    }
+}
+
+impl<T, C> text::Text for SpanCodes<'_, T, C>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    type T = T;
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn get(&self, i: usize) -> T {
+        if i == self.len - 1 {
+            return T::from_u64(TERMINATOR);
+        }
+        let j = self.separators.partition_point(|&p| p <= i as u64) - 1;
+        let p = self.separators[j] as usize;
+        if p == i {
+            T::from_u64(SEPARATOR)
+        } else {
+            let c = self.buffer[self.spans[j].0 + i - p - 1];
+            T::from_u64(self.converter.convert(c).into() + 1)
+        }
+    }
+}
+
+/// How [`FMIndexMultiPieces::new_with_strategy`] sorts the suffixes of a text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConstructionStrategy {
+    /// Sorts the suffixes of the whole text by a single run of SA-IS, which is the default.
+    #[default]
+    Monolithic,
+    /// Sorts the suffixes of each piece by its own run of SA-IS on `threads` threads,
+    /// and merges them into the suffix array of the text.
+    ///
+    /// The runs are parallel, which suits corpora of many moderate pieces, but this does not lower
+    /// the peak memory: the suffix arrays of all pieces are kept until the merge, which builds
+    /// the suffix array of the text next to them, i.e. about twice as many words as [`ConstructionStrategy::Monolithic`].
+    /// The merge is sequential and compares suffixes by their characters,
+    /// so it is slow for pieces sharing long substrings.
+    Segmented { threads: usize },
+}
+
+// Builds the suffix array of `codes` encoded by `encode_pieces` from the suffix arrays of its pieces.
+//
+// The suffixes starting in pieces compare by the rest of their pieces, since the separator following
+// a piece is smaller than any character; ties between equal rests are broken by the suffixes starting
+// at the following separators, whose order is that of the suffixes of the sequence of piece ranks.
+// The suffix arrays of all pieces are alive during the merge, next to the output.
+fn segmented_suffix_array<T>(
+    codes: &[T],
+    separators: &[u64],
+    sigma: u64,
+    threads: usize,
+) -> Vec<u64>
+where
+    T: Character + Send + Sync,
+{
+    let n = codes.len();
+    let k = separators.len();
+    let start = |j: usize| separators[j] as usize + 1;
+    let piece = |j: usize| {
+        let end = separators.get(j + 1).map_or(n - 1, |&p| p as usize);
+        &codes[start(j)..end]
+    };
+
+    let chunk_size = k.div_ceil(threads);
+    let local = thread::scope(|scope| {
+        let handles = (0..k)
+            .step_by(chunk_size)
+            .map(|first| {
+                scope.spawn(move || {
+                    let id_converter = IdConverter::new(sigma);
+                    (first..k.min(first + chunk_size))
+                        .map(|j| {
+                            let mut text = piece(j).to_vec();
+                            text.push(T::from_u64(TERMINATOR));
+                            let mut sa = sais::sais(&text, &id_converter);
+                            // the suffix of the terminator alone
+                            sa.remove(0);
+                            sa
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    // ranks of the distinct pieces from 1, followed by 0 for the terminator
+    let mut order = (0..k).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| piece(a).cmp(piece(b)));
+    let mut ranks = vec![0u64; k + 1];
+    let mut rank = 0;
+    for (x, &j) in order.iter().enumerate() {
+        if x == 0 || piece(order[x - 1]) != piece(j) {
+            rank += 1;
+        }
+        ranks[j] = rank;
+    }
+    let rank_sa = sais::sais(&ranks, &IdConverter::new(rank + 1));
+    // separator_rows[j]: the row of the separator preceding the piece j, or of the terminator for j = k
+    let mut separator_rows = vec![0; k + 1];
+    let mut sa = Vec::with_capacity(n);
+    for (row, &j) in rank_sa.iter().enumerate() {
+        separator_rows[j as usize] = row as u64;
+        sa.push(separators.get(j as usize).map_or(n as u64 - 1, |&p| p));
+    }
+
+    let mut heap = BinaryHeap::with_capacity(k);
+    let entry = |j: usize, x: usize| {
+        let offset = local[j][x] as usize;
+        Reverse((&piece(j)[offset..], separator_rows[j + 1], j, x))
+    };
+    for j in 0..k {
+        heap.push(entry(j, 0));
+    }
+    while let Some(Reverse((_, _, j, x))) = heap.pop() {
+        sa.push((start(j) + local[j][x] as usize) as u64);
+        if x + 1 < local[j].len() {
+            heap.push(entry(j, x + 1));
+        }
+    }
+    sa
+}
+
+impl<T, C, S> FMIndexMultiPieces<T, C, S> {
+    pub fn len(&self) -> u64 {
+        self.bw.len()
+    }
+
+    fn size_without_sa(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.bw.size()
+            + self.cs.len() * std::mem::size_of::<u64>()
+            + self.boundary.size()
+            + self.starts.size()
+            + self.keys.as_ref().map_or(0, |keys| keys.size())
+            + self.aliases.as_ref().map_or(0, |aliases| aliases.size())
+            + self
+                .hashes
+                .as_ref()
+                .map_or(0, |hashes| hashes.len() * std::mem::size_of::<u64>())
+            + self
+                .piece_samples
+                .as_ref()
+                .map_or(0, |samples| samples.size())
+            + self.groups.as_ref().map_or(0, |groups| groups.size())
+            + self.qgram_filter.as_ref().map_or(0, |filter| filter.size())
+    }
+
+    pub(crate) fn boundary(&self) -> &PieceBoundary {
+        &self.boundary
+    }
+
+    /// Returns the ID of the piece containing position `p` of the original text.
+    ///
+    /// A separator belongs to the piece preceding it.
+    pub fn piece_id_at(&self, p: u64) -> PieceId {
+        strict_assert!(p < self.len() - 1);
+        PieceId::from(self.starts.search(p))
+    }
+
+    /// Returns the start position of a piece in the original text.
+    pub fn piece_start(&self, id: PieceId) -> u64 {
+        self.starts.get(id.into())
+    }
+
+    /// Returns the length of a piece, excluding the separator following it.
+    pub fn piece_len(&self, id: PieceId) -> u64 {
+        let id = u64::from(id);
+        let end = if id + 1 < self.boundary.pieces_count() {
+            self.starts.get(id + 1) - 1
+        } else {
+            self.len() - 2
+        };
+        end - self.starts.get(id)
+    }
+
+    /// Returns the lengths of all pieces in the order of IDs, e.g. for normalizing scores by piece length.
+    pub fn piece_lens(&self) -> Vec<u64> {
+        (0..self.boundary.pieces_count())
+            .map(|id| self.piece_len(PieceId::from(id)))
+            .collect()
+    }
+
+    /// Converts a position in the original text into the piece containing it and the offset in the piece.
+    ///
+    /// A separator belongs to the piece preceding it.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::NullSampler;
+    /// use fm_index::{FMIndexMultiPieces, GlobalPos, PieceId, PiecePos};
+    ///
+    /// let text = b"apple\0banana\0cherry".to_vec();
+    /// let index = FMIndexMultiPieces::new(text, RangeConverter::new(b'a', b'z'), NullSampler::new());
+    /// let p = index.piece_pos(GlobalPos::from(8));
+    /// assert_eq!(p, PiecePos::new(PieceId::from(1), 2));
+    /// assert_eq!(index.global_pos(p), GlobalPos::from(8));
+    /// ```
+    pub fn piece_pos(&self, p: GlobalPos) -> PiecePos {
+        let p = u64::from(p);
+        let piece = self.piece_id_at(p);
+        PiecePos::new(piece, p - self.piece_start(piece))
+    }
+
+    /// Converts a position in a piece into one in the original text.
+    pub fn global_pos(&self, p: PiecePos) -> GlobalPos {
+        GlobalPos::from(self.piece_start(p.piece) + p.offset)
+    }
+
+    /// Attaches an external key to each piece, given in the order of pieces,
+    /// which is serialized with the index.
+    ///
+    /// Piece IDs are positions of pieces and shift when pieces are removed before rebuilding an index,
+    /// whereas keys (e.g. document IDs of a database) identify pieces across rebuilds.
+    /// Keys must be unique.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::NullSampler;
+    /// use fm_index::{FMIndexMultiPieces, PieceId};
+    ///
+    /// // "banana" (key 20) has been deleted from "apple\0banana\0cherry"
+    /// let text = b"apple\0cherry".to_vec();
+    /// let index = FMIndexMultiPieces::new(text, RangeConverter::new(b'a', b'z'), NullSampler::new())
+    ///     .with_keys(vec![10, 30]);
+    /// assert_eq!(index.piece_id_by_key(30), Some(PieceId::from(1)));
+    /// assert_eq!(index.piece_id_by_key(20), None);
+    /// assert_eq!(index.key_by_piece_id(PieceId::from(0)), Some(10));
+    /// ```
+    pub fn with_keys(mut self, keys: Vec<u64>) -> Self {
+        assert_eq!(
+            keys.len(),
+            self.boundary.pieces_count() as usize,
+            "the number of keys must equal the number of pieces"
+        );
+        self.keys = Some(PieceKeys::new(keys));
+        self
+    }
+
+    /// Returns the number of pieces of the text given at construction,
+    /// which exceeds the number of stored pieces if identical pieces are deduplicated.
+    pub fn original_pieces_count(&self) -> u64 {
+        self.aliases
+            .as_ref()
+            .map_or(self.boundary.pieces_count(), |aliases| {
+                aliases.original_count()
+            })
+    }
+
+    /// Returns the IDs of the pieces of the original text equal to a stored piece, in ascending order.
+    ///
+    /// This is just `id` unless the index is built by [`FMIndexMultiPieces::new_storage_deduplicated`].
+    pub fn original_piece_ids(&self, id: PieceId) -> Vec<PieceId> {
+        match &self.aliases {
+            Some(aliases) => aliases.originals(id).collect(),
+            None => vec![id],
+        }
+    }
+
+    /// Returns the ID of the stored piece equal to a piece of the original text.
+    pub fn stored_piece_id(&self, original: PieceId) -> PieceId {
+        match &self.aliases {
+            Some(aliases) => aliases.stored(original),
+            None => original,
+        }
+    }
+
+    /// Returns the hash of a piece computed by [`crate::hash_piece`],
+    /// or `None` if hashes are not stored by [`FMIndexMultiPieces::with_piece_hashes`].
+    pub fn piece_hash(&self, id: PieceId) -> Option<u64> {
+        self.hashes
+            .as_ref()
+            .map(|hashes| hashes[u64::from(id) as usize])
+    }
+
+    /// Returns true if keys are attached by [`FMIndexMultiPieces::with_keys`].
+    pub fn has_keys(&self) -> bool {
+        self.keys.is_some()
+    }
+
+    /// Returns the ID of the piece with `key`, or `None` if there is no such piece or no keys are attached.
+    pub fn piece_id_by_key(&self, key: u64) -> Option<PieceId> {
+        self.keys.as_ref().and_then(|keys| keys.piece_id(key))
+    }
+
+    /// Returns the key of a piece, or `None` if no keys are attached.
+    pub fn key_by_piece_id(&self, id: PieceId) -> Option<u64> {
+        self.keys.as_ref().and_then(|keys| keys.key(id))
+    }
+
+    /// Assigns each piece to a group, given in the order of pieces, which is serialized with the index.
+    ///
+    /// Groups are numbered from 0 like pieces and map pieces to collections of them,
+    /// e.g. the files of chapters or the tenants of documents, so that occurrences are counted
+    /// and restricted by group in a single index.
+    /// A group may have no pieces.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::NullSampler;
+    /// use fm_index::{BackwardSearchIndex, FMIndexMultiPieces, PieceId};
+    ///
+    /// let text = b"red apple\0green apple\0red cherry\0apple pie".to_vec();
+    /// let index = FMIndexMultiPieces::new(text, RangeConverter::new(b' ', b'z'), NullSampler::new())
+    ///     .with_groups(vec![0, 1, 0, 2]);
+    /// assert_eq!(index.group_pieces(0), vec![PieceId::from(0), PieceId::from(2)]);
+    /// let search = index.search_backward("apple");
+    /// assert_eq!(search.distinct_groups(), vec![0, 1, 2]);
+    /// assert_eq!(search.count_in_group(0), 1);
+    /// assert_eq!(index.search_backward("red").group_counts(), vec![(0, 2)]);
+    /// ```
+    pub fn with_groups(mut self, groups: Vec<u64>) -> Self {
+        assert_eq!(
+            groups.len(),
+            self.boundary.pieces_count() as usize,
+            "the number of groups must equal the number of pieces"
+        );
+        self.groups = Some(PieceGroups::new(groups));
+        self
+    }
+
+    /// Returns the number of groups, i.e. one more than the largest group, or 0 if no groups are assigned.
+    pub fn groups_count(&self) -> u64 {
+        self.groups
+            .as_ref()
+            .map_or(0, |groups| groups.groups_count())
+    }
+
+    /// Returns the group of a piece, or `None` if no groups are assigned.
+    pub fn group_id(&self, id: PieceId) -> Option<u64> {
+        self.groups.as_ref().and_then(|groups| groups.group(id))
+    }
+
+    /// Returns the pieces of a group in ascending order of IDs.
+    pub fn group_pieces(&self, group: u64) -> Vec<PieceId> {
+        match &self.groups {
+            Some(groups) => groups.members(group).collect(),
+            None => vec![],
+        }
+    }
+}
+
+impl<T, C> FMIndexMultiPieces<T, C, ()> {
+    pub fn size(&self) -> usize {
+        self.size_without_sa()
+    }
+}
+
+impl<T, C, S> FMIndexMultiPieces<T, C, S>
+where
+    S: PartialArray,
+{
+    pub fn size(&self) -> usize {
+        self.size_without_sa() + self.suffix_array.size()
+    }
+}
+
+impl<T, C, S> FMIndexMultiPieces<T, C, S>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    /// Iterates over the pieces in ascending order of IDs, reconstructing the characters of each piece
+    /// from the index alone, e.g. to export the text for backup verification or migration.
+    ///
+    /// The characters of a piece are read by FL-mapping from the separator preceding it,
+    /// taking _O(log σ)_ time per character, and pieces can be skipped without reading them.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::NullSampler;
+    /// use fm_index::{FMIndexMultiPieces, PieceId};
+    ///
+    /// let text = b"apple\0banana\0cherry".to_vec();
+    /// let index = FMIndexMultiPieces::new(text, RangeConverter::new(b'a', b'z'), NullSampler::new());
+    /// let pieces = index
+    ///     .iter_pieces()
+    ///     .map(|(id, chars)| (id, chars.collect::<Vec<_>>()))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(pieces[1], (PieceId::from(1), b"banana".to_vec()));
+    /// assert_eq!(pieces.len(), 3);
+    /// ```
+    pub fn iter_pieces(
+        &self,
+    ) -> impl Iterator<Item = (PieceId, PieceIterator<ForwardIterator<'_, Self>>)> + '_ {
+        let mut rows = vec![0; self.boundary.pieces_count() as usize];
+        for i in self.boundary.separator_rows() {
+            rows[u64::from(self.boundary.piece_after(i).unwrap()) as usize] = i;
+        }
+        rows.into_iter().enumerate().map(move |(id, i)| {
+            (
+                PieceId::from(id as u64),
+                PieceIterator::new(self.iter_forward(self.fl_map(i))),
+            )
+        })
+    }
+
+    /// Scores the similarity of `query` to each piece with a substring kernel over `k`-mers,
+    /// and returns the `top` pieces in descending order of scores, breaking ties by piece IDs.
+    ///
+    /// The score of a piece is the sum of `a * b` over the `k`-mers occurring `a` times in `query`
+    /// and `b` times in the piece, so pieces sharing no `k`-mers are omitted.
+    /// Each distinct `k`-mer of `query` is searched once, and the pieces of its occurrences are found
+    /// like [`Search::piece_stats`], so this suits fuzzy retrieval by short queries
+    /// rather than comparing whole pieces.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::NullSampler;
+    /// use fm_index::{FMIndexMultiPieces, PieceId, PieceScore};
+    ///
+    /// let text = b"the lazy dog\0a crazy frog\0the cat".to_vec();
+    /// let index = FMIndexMultiPieces::new(text, RangeConverter::new(b' ', b'z'), NullSampler::new());
+    /// let scores = index.kmer_similarity("lazy frog", 3, 2);
+    /// assert_eq!(scores, vec![
+    ///     PieceScore { piece: PieceId::from(1), score: 6 },
+    ///     PieceScore { piece: PieceId::from(0), score: 3 },
+    /// ]);
+    /// ```
+    pub fn kmer_similarity<K: AsRef<[T]>>(
+        &self,
+        query: K,
+        k: usize,
+        top: usize,
+    ) -> Vec<PieceScore> {
+        assert!(k > 0, "k must be positive");
+        let query = query.as_ref();
+        let mut kmers = query
+            .windows(k)
+            .filter(|kmer| kmer.iter().all(|c| !c.is_zero()))
+            .collect::<Vec<_>>();
+        kmers.sort_unstable();
+        let mut scores = HashMap::new();
+        for group in kmers.chunk_by(|a, b| a == b) {
+            let weight = group.len() as u64;
+            for m in self.search_backward(group[0]).iter_matches() {
+                *scores.entry(m.piece_id()).or_insert(0) += weight;
+            }
+        }
+        let mut scores = scores
+            .into_iter()
+            .map(|(piece, score)| PieceScore { piece, score })
+            .collect::<Vec<_>>();
+        scores.sort_unstable_by_key(|s| (Reverse(s.score), s.piece));
+        scores.truncate(top);
+        scores
+    }
+
+    fn collect_stats<X: SuffixArrayStats>(&self, suffix_array: &X) -> IndexStats {
+        let n = self.bw.len();
+        let runs = stats::count_runs(n, |i| self.bw.access::<u64>(i));
+        let words = |v: Option<usize>| v.unwrap_or(0) * std::mem::size_of::<u64>();
+        IndexStats::new(n, self.converter.len() + 1, self.pieces_count(), runs).with_components(
+            std::mem::size_of::<Self>(),
+            vec![
+                ("bwt", self.bw.size()),
+                ("cs", words(Some(self.cs.len()))),
+                ("pieces", self.boundary.size() + self.starts.size()),
+                ("keys", self.keys.as_ref().map_or(0, |keys| keys.size())),
+                (
+                    "aliases",
+                    self.aliases.as_ref().map_or(0, |aliases| aliases.size()),
+                ),
+                (
+                    "hashes",
+                    words(self.hashes.as_ref().map(|hashes| hashes.len())),
+                ),
+                (
+                    "piece_samples",
+                    self.piece_samples
+                        .as_ref()
+                        .map_or(0, |samples| samples.size()),
+                ),
+                (
+                    "groups",
+                    self.groups.as_ref().map_or(0, |groups| groups.size()),
+                ),
+                (
+                    "qgram_filter",
+                    self.qgram_filter.as_ref().map_or(0, |filter| filter.size()),
+                ),
+            ],
+            suffix_array,
+        )
+    }
+}
+
+impl<T, C> FMIndexMultiPieces<T, C, ()>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    /// Summarizes the index, counting the runs of the BWT.
+    pub fn stats(&self) -> IndexStats {
+        self.collect_stats(&())
+    }
+}
+
+impl<T, C, S> FMIndexMultiPieces<T, C, S>
+where
+    T: Character,
+    C: Converter<T>,
+    S: PartialArray,
+{
+    /// Summarizes the index, counting the runs of the BWT.
+    pub fn stats(&self) -> IndexStats {
+        self.collect_stats(&self.suffix_array)
+    }
+}
+
+impl<T, C, S> FMIndexMultiPieces<T, C, S>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    fn encode(&self, c: T) -> u64 {
+        if c.is_zero() {
+            SEPARATOR
+        } else {
+            self.converter.convert(c).into() + 1
+        }
+    }
+
+    fn decode(&self, c: u64) -> T {
+        if c <= SEPARATOR {
+            T::zero()
+        } else {
+            T::from_u64(c - 1)
+        }
+    }
+
+    fn lf_map_code(&self, c: u64, i: u64) -> u64 {
+        if c <= SEPARATOR {
+            // The only row preceded by the terminator starts with the first separator,
+            // so it comes first among the rows preceded by zeros.
+            // Hence both are ranked together.
+            self.bw.rank(T::from_u64(TERMINATOR), i) + self.bw.rank(T::from_u64(SEPARATOR), i)
+        } else {
+            self.cs[c as usize] + self.bw.rank(T::from_u64(c), i)
+        }
+    }
+
+    /// Builds a [`QGramFilter`] of the `q`-grams of the text with a bitmap of `bits` bits and stores it with the index,
+    /// so that backward searches for patterns containing a q-gram absent from the text return nothing without rank queries.
+    ///
+    /// The filter hashes the characters converted by the converter of the index, as they are stored,
+    /// and a zero in a pattern is hashed as a separator, so it never rejects a pattern that the index matches.
+    /// The text is read by walking LF-mapping over the whole index once, which takes _n_ words of temporary memory.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::NullSampler;
+    /// use fm_index::{BackwardSearchIndex, FMIndexMultiPieces};
+    ///
+    /// let text = b"apple\0banana".to_vec();
+    /// let index = FMIndexMultiPieces::new(text, RangeConverter::new(b'a', b'z'), NullSampler::new())
+    ///     .with_qgram_filter(3, 1 << 10);
+    /// assert_eq!(index.search_backward("le\0ba").count(), 1);
+    /// assert_eq!(index.search_backward("apa").count(), 0);
+    /// ```
+    pub fn with_qgram_filter(mut self, q: usize, bits: usize) -> Self {
+        // `get_l` yields converted characters, which are stored shifted by the separator.
+        let codes =
+            filter::cyclic_text_codes(
+                &self,
+                |c| {
+                    if c.is_zero() {
+                        SEPARATOR
+                    } else {
+                        c.into() + 1
+                    }
+                },
+            );
+        self.qgram_filter = Some(QGramFilter::from_cyclic_codes(&codes, q, bits));
+        self
+    }
+
+    /// Returns the filter added by [`FMIndexMultiPieces::with_qgram_filter`], if any.
+    pub fn qgram_filter(&self) -> Option<&QGramFilter> {
+        self.qgram_filter.as_ref()
+    }
+
+    /// Computes the hash of each piece by [`crate::hash_piece`] and stores it with the index,
+    /// so that downstream systems can check that the index matches their versions of the documents
+    /// without extracting the text.
+    ///
+    /// The text is read by walking LF-mapping over the whole index once, keeping a single piece in memory,
+    /// so it is typically chained to the constructor.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::NullSampler;
+    /// use fm_index::{hash_piece, FMIndexMultiPieces, PieceId};
+    ///
+    /// let text = b"apple\0banana".to_vec();
+    /// let index = FMIndexMultiPieces::new(text, RangeConverter::new(b'a', b'z'), NullSampler::new())
+    ///     .with_piece_hashes();
+    /// assert_eq!(index.piece_hash(PieceId::from(1)), Some(hash_piece(b"banana")));
+    /// ```
+    pub fn with_piece_hashes(mut self) -> Self {
+        let k = self.boundary.pieces_count();
+        let mut hashes = vec![0; k as usize];
+        let mut piece = vec![];
+        // row 0 is the terminator following the last piece
+        let mut i = 0;
+        for id in (0..k).rev() {
+            loop {
+                let c = self.bw.access::<T>(i).into();
+                i = self.lf_map_code(c, i);
+                if c <= SEPARATOR {
+                    break;
+                }
+                piece.push(self.converter.convert_inv(T::from_u64(c - 1)));
+            }
+            piece.reverse();
+            hashes[id as usize] = piece::hash_piece(&piece);
+            piece.clear();
+        }
+        self.hashes = Some(hashes);
+        self
+    }
+
+    /// Samples the pieces of the suffixes at every `2^level`-th position of the text,
+    /// so that [`IndexWithPieces::piece_id`] takes at most `2^level` steps of LF-mapping
+    /// instead of the distance from the start of the piece, which matters for long pieces.
+    ///
+    /// The samples take _n + (n / 2^level) log k_ bits, and are computed by walking LF-mapping over the whole index once.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::NullSampler;
+    /// use fm_index::{BackwardSearchIndex, FMIndexMultiPieces, IndexWithPieces, PieceId};
+    ///
+    /// let text = b"apple\0banana\0cherry".to_vec();
+    /// let index = FMIndexMultiPieces::new(text, RangeConverter::new(b'a', b'z'), NullSampler::new())
+    ///     .with_piece_samples(2);
+    /// let search = index.search_backward("rr");
+    /// assert_eq!(search.iter_matches().next().unwrap().piece_id(), PieceId::from(2));
+    /// ```
+    pub fn with_piece_samples(mut self, level: usize) -> Self {
+        let n = self.bw.len();
+        let k = self.boundary.pieces_count();
+        let mask = (1 << level) - 1;
+        let mut samples = Vec::with_capacity((n >> level) as usize + 1);
+        // row 0 is the terminator at position n - 1, following the last piece
+        let mut i = 0;
+        let mut current = k - 1;
+        for p in (0..n).rev() {
+            let id = match self.boundary.piece_after(i) {
+                Some(id) => {
+                    current = u64::from(id).saturating_sub(1);
+                    u64::from(id)
+                }
+                None => current,
+            };
+            if p & mask == 0 {
+                samples.push((i, id));
+            }
+            i = self.lf_map_code(self.bw.access::<T>(i).into(), i);
+        }
+        self.piece_samples = Some(PieceSamples::new(level, n, k, samples));
+        self
+    }
+
+    /// Returns the level of the samples added by [`FMIndexMultiPieces::with_piece_samples`], if any.
+    pub fn piece_sample_level(&self) -> Option<usize> {
+        self.piece_samples.as_ref().map(|samples| samples.level())
+    }
+
+    /// Returns the ID of the piece equal to `pattern`, if any.
+    ///
+    /// An empty pattern or one containing zero equals no piece.
+    /// This does not walk the text, so it takes _O(m log σ)_ time for a pattern of length _m_.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::NullSampler;
+    /// use fm_index::{FMIndexMultiPieces, PieceId};
+    ///
+    /// let text = b"apple\0banana\0cherry".to_vec();
+    /// let index = FMIndexMultiPieces::new(text, RangeConverter::new(b'a', b'z'), NullSampler::new());
+    /// assert_eq!(index.contains_piece("banana"), Some(PieceId::from(1)));
+    /// assert_eq!(index.contains_piece("banan"), None);
+    /// ```
+    pub fn contains_piece<K>(&self, pattern: K) -> Option<PieceId>
+    where
+        K: AsRef<[T]>,
+    {
+        let mut state = SearchState::new(self);
+        self.contains_piece_in(&mut state, pattern.as_ref())
+    }
+
+    /// Returns the IDs of the pieces starting with `pattern` in ascending order.
+    ///
+    /// The rows of `"\0" + pattern` start with the separators preceding the pieces,
+    /// whose IDs are stored, so this needs no suffix array and works on count-only indexes.
+    /// It takes _O(m log σ + occ log occ)_ time for a pattern of length _m_ and _occ_ pieces.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::NullSampler;
+    /// use fm_index::{FMIndexMultiPieces, PieceId};
+    ///
+    /// let text = b"banana\0apple\0band\0ban".to_vec();
+    /// let index = FMIndexMultiPieces::new(text, RangeConverter::new(b'a', b'z'), NullSampler::new());
+    /// let ids = index.pieces_with_prefix("ban");
+    /// assert_eq!(ids, vec![PieceId::from(0), PieceId::from(2), PieceId::from(3)]);
+    /// assert!(index.pieces_with_prefix("nan").is_empty());
+    /// ```
+    pub fn pieces_with_prefix<K>(&self, pattern: K) -> Vec<PieceId>
+    where
+        K: AsRef<[T]>,
+    {
+        let range = self.search_prefix(pattern).get_range();
+        let mut ids = self
+            .boundary
+            .pieces_after(range.start..range.end)
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Looks up each of `patterns` with [`FMIndexMultiPieces::contains_piece`].
+    pub fn contains_pieces<I, K>(&self, patterns: I) -> Vec<Option<PieceId>>
+    where
+        I: IntoIterator<Item = K>,
+        K: AsRef<[T]>,
+    {
+        let mut state = SearchState::new(self);
+        patterns
+            .into_iter()
+            .map(|pattern| self.contains_piece_in(&mut state, pattern.as_ref()))
+            .collect()
+    }
+
+    fn contains_piece_in(&self, state: &mut SearchState, pattern: &[T]) -> Option<PieceId> {
+        // a pattern containing zero spans pieces, so it equals none of them
+        if pattern.is_empty() || pattern.iter().any(|c| c.is_zero()) {
+            return None;
+        }
+        let zero = [T::zero()];
+        state.reset(self);
+        self.search_in_place(state, zero);
+        self.search_in_place(state, pattern);
+        self.search_in_place(state, zero);
+        if state.count() > 0 {
+            self.boundary.piece_after(state.get_range().start)
+        } else {
+            None
+        }
+    }
+}
+
+/// A match of a pattern spanning pieces, returned by [`FMIndexMultiPieces::search_across_pieces`].
+pub struct SpanningSearch<'a, I>
+where
+    I: BackwardSearchIndex,
+{
+    /// The search for the pattern with separators inserted at `boundaries`.
+    pub search: Search<'a, I>,
+    /// The offsets `k` in the pattern such that a separator lies between its `k - 1`-th and `k`-th characters,
+    /// in ascending order.
+    pub boundaries: Vec<usize>,
+}
+
+impl<T, C, S> FMIndexMultiPieces<T, C, S>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    /// Searches for `pattern` allowing a separator between any two of its characters,
+    /// so that text split into pieces artificially (e.g. into sentences) can be matched as if it were joined.
+    ///
+    /// Returns a search for each way of inserting separators that occurs, including no separators,
+    /// or an error if more than `max_branches` of them are alive at some step,
+    /// as their number can grow exponentially with the length of `pattern`.
+    /// Positions located by the searches include the inserted separators,
+    /// so an occurrence spans `pattern.len() + boundaries.len()` characters of the original text.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::SuffixOrderSampler;
+    /// use fm_index::FMIndexMultiPieces;
+    ///
+    /// let text = b"the quick brown\0fox jumps\0over the lazy dog".to_vec();
+    /// let index = FMIndexMultiPieces::new(text, RangeConverter::new(b' ', b'z'), SuffixOrderSampler::new());
+    /// let results = index.search_across_pieces("brownfox", 16).unwrap();
+    /// assert_eq!(results.len(), 1);
+    /// assert_eq!(results[0].boundaries, vec![5]);
+    /// assert_eq!(results[0].search.locate(), vec![10]);
+    /// ```
+    pub fn search_across_pieces<K>(
+        &self,
+        pattern: K,
+        max_branches: usize,
+    ) -> Result<Vec<SpanningSearch<'_, Self>>, BranchLimitExceeded>
+    where
+        K: AsRef<[T]>,
+    {
+        let pattern = pattern.as_ref();
+        let zero = [T::zero()];
+        let mut branches = vec![(Search::new(self), vec![])];
+        for (k, &c) in pattern.iter().enumerate().rev() {
+            let mut next = vec![];
+            let mut push = |branch| {
+                if next.len() == max_branches {
+                    return Err(BranchLimitExceeded {
+                        limit: max_branches,
+                    });
+                }
+                next.push(branch);
+                Ok(())
+            };
+            for (search, boundaries) in &branches {
+                let search = search.search_backward([c]);
+                if search.count() == 0 {
+                    continue;
+                }
+                if k > 0 && !c.is_zero() && !pattern[k - 1].is_zero() {
+                    let joined = search.search_backward(zero);
+                    if joined.count() > 0 {
+                        let mut boundaries = boundaries.clone();
+                        boundaries.push(k);
+                        push((joined, boundaries))?;
+                    }
+                }
+                push((search, boundaries.clone()))?;
+            }
+            branches = next;
+        }
+        Ok(branches
+            .into_iter()
+            .map(|(search, mut boundaries)| {
+                boundaries.reverse();
+                SpanningSearch { search, boundaries }
+            })
+            .collect())
+    }
+}
+
+impl<'a, T, C, S> Search<'a, FMIndexMultiPieces<T, C, S>>
+where
+    T: Character,
+    C: Converter<T>,
+    S: PartialArray,
+{
+    /// Returns the IDs of the pieces of the original text containing the occurrences,
+    /// expanding deduplicated pieces into all of their copies, in ascending order.
+    pub fn original_piece_ids(&self) -> Vec<PieceId> {
+        let index = self.index();
+        let mut ids = self
+            .piece_ids_sorted()
+            .into_iter()
+            .flat_map(|id| index.original_piece_ids(id))
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Lists the positions of all occurrences in the pieces containing their first characters,
+    /// in the order of [`Search::iter_matches`].
+    pub fn locate_in_pieces(&self) -> Vec<PiecePos> {
+        self.iter_matches().map(|m| m.locate_in_piece()).collect()
+    }
+
+    /// Returns the ranges `[start, end)` of the occurrences in piece-local coordinates,
+    /// grouped by piece in ascending order of IDs, for highlighting them.
+    ///
+    /// The ranges of each piece are sorted, and overlapping or adjacent occurrences are merged.
+    /// An occurrence spanning separators is split into the pieces it covers, excluding the separators.
+    pub fn highlights(&self) -> Vec<(PieceId, Vec<(u64, u64)>)> {
+        let index = self.index();
+        let n = index.len() - 1;
+        let k = index.pieces_count();
+        let m = self.pattern_len() as u64;
+        let starts_with_zero = self.pattern().first().is_some_and(|c| c.is_zero());
+        let mut ranges = vec![];
+        for p in self.locate() {
+            // No piece starts with zero, so this is the occurrence at the leading separator,
+            // which is located at the start of the first piece.
+            let (mut p, mut rest) = if p == 0 && starts_with_zero {
+                (0, m - 1)
+            } else {
+                (p, m)
+            };
+            while rest > 0 {
+                p %= n;
+                let id = index.piece_id_at(p);
+                let start = index.piece_start(id);
+                let sep = if u64::from(id) + 1 < k {
+                    index.piece_start(PieceId::from(u64::from(id) + 1)) - 1
+                } else {
+                    n - 1
+                };
+                let end = (p + rest).min(sep);
+                if p < end {
+                    ranges.push((id, p - start, end - start));
+                }
+                let step = (p + rest).min(sep + 1) - p;
+                p += step;
+                rest -= step;
+            }
+        }
+        ranges.sort();
+
+        let mut highlights: Vec<(PieceId, Vec<(u64, u64)>)> = vec![];
+        for (id, s, e) in ranges {
+            match highlights.last_mut() {
+                Some((last, spans)) if *last == id => match spans.last_mut() {
+                    Some(span) if s <= span.1 => span.1 = span.1.max(e),
+                    _ => spans.push((s, e)),
+                },
+                _ => highlights.push((id, vec![(s, e)])),
+            }
+        }
+        highlights
+    }
+}
+
+/// The occurrences of a pattern in a piece, reported by [`Search::piece_stats`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PieceStat {
+    pub piece: PieceId,
+    /// The number of occurrences in the piece.
+    pub frequency: u64,
+    /// The length of the piece.
+    pub len: u64,
+}
+
+/// The similarity of a piece to a query, reported by [`FMIndexMultiPieces::kmer_similarity`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PieceScore {
+    pub piece: PieceId,
+    /// The number of pairs of equal `k`-mers in the query and the piece.
+    pub score: u64,
+}
+
+impl<'a, T, C, S> Search<'a, FMIndexMultiPieces<T, C, S>>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    /// Counts the occurrences in each piece containing them, in ascending order of piece IDs,
+    /// with the lengths of the pieces, which are the term and document statistics of ranking functions like BM25.
+    ///
+    /// The piece of each occurrence is found by walking LF-mapping back to the separator preceding it,
+    /// so this needs no suffix array and never resolves positions.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::NullSampler;
+    /// use fm_index::{BackwardSearchIndex, FMIndexMultiPieces, PieceId, PieceStat};
+    ///
+    /// let text = b"banana\0apple\0ananas".to_vec();
+    /// let index = FMIndexMultiPieces::new(text, RangeConverter::new(b'a', b'z'), NullSampler::new());
+    /// let stats = index.search_backward("an").piece_stats();
+    /// assert_eq!(stats, vec![
+    ///     PieceStat { piece: PieceId::from(0), frequency: 2, len: 6 },
+    ///     PieceStat { piece: PieceId::from(2), frequency: 2, len: 6 },
+    /// ]);
+    /// ```
+    pub fn piece_stats(&self) -> Vec<PieceStat> {
+        let index = self.index();
+        let mut ids = self
+            .iter_matches()
+            .map(|m| m.piece_id())
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        let mut stats: Vec<PieceStat> = vec![];
+        for id in ids {
+            match stats.last_mut() {
+                Some(stat) if stat.piece == id => stat.frequency += 1,
+                _ => stats.push(PieceStat {
+                    piece: id,
+                    frequency: 1,
+                    len: index.piece_len(id),
+                }),
+            }
+        }
+        stats
+    }
+
+    /// Returns the groups of the pieces containing the occurrences in ascending order,
+    /// which is empty if no groups are assigned by [`FMIndexMultiPieces::with_groups`].
+    pub fn distinct_groups(&self) -> Vec<u64> {
+        self.group_counts().into_iter().map(|(g, _)| g).collect()
+    }
+
+    /// Counts the occurrences in each group containing them, in ascending order of groups.
+    pub fn group_counts(&self) -> Vec<(u64, u64)> {
+        let mut groups = self
+            .iter_matches()
+            .filter_map(|m| m.group_id())
+            .collect::<Vec<_>>();
+        groups.sort_unstable();
+        let mut counts: Vec<(u64, u64)> = vec![];
+        for g in groups {
+            match counts.last_mut() {
+                Some((h, count)) if *h == g => *count += 1,
+                _ => counts.push((g, 1)),
+            }
+        }
+        counts
+    }
+
+    /// Iterates over the occurrences in the pieces of `group`, in the order of [`Search::iter_matches`].
+    ///
+    /// This filters all the occurrences of the search by the groups of their pieces,
+    /// so it takes time proportional to [`Search::count`] rather than to the occurrences in `group`.
+    pub fn iter_matches_in_group(
+        &self,
+        group: u64,
+    ) -> impl Iterator<Item = Match<'a, FMIndexMultiPieces<T, C, S>>> + '_ {
+        self.iter_matches()
+            .filter(move |m| m.group_id() == Some(group))
+    }
+
+    /// Counts the occurrences in the pieces of `group`, filtering them like [`Search::iter_matches_in_group`].
+    pub fn count_in_group(&self, group: u64) -> u64 {
+        self.iter_matches_in_group(group).count() as u64
+    }
+}
+
+impl<'a, T, C, S> Match<'a, FMIndexMultiPieces<T, C, S>>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    /// Returns the group of the piece containing the first character of the occurrence,
+    /// or `None` if no groups are assigned.
+    pub fn group_id(&self) -> Option<u64> {
+        self.index().group_id(self.piece_id())
+    }
+}
+
+impl<'a, T, C, S> Match<'a, FMIndexMultiPieces<T, C, S>>
+where
+    T: Character,
+    C: Converter<T>,
+    S: PartialArray,
+{
+    /// Returns the position of the occurrence in the piece containing its first character.
+    pub fn locate_in_piece(&self) -> PiecePos {
+        self.index().piece_pos(self.locate_global())
+    }
+}
+
+impl<T, C, S> FMIndexMultiPieces<T, C, S>
+where
+    T: Character,
+    C: Converter<T> + PartialEq,
+    S: PartialEq,
+{
+    /// Compares each component of this index with those of `other`.
+    pub fn diff(&self, other: &Self) -> IndexDiff {
+        let mut diff = IndexDiff::default();
+        diff.check_bwt(self, other, self.bw != other.bw);
+        diff.check("cs", self.cs != other.cs);
+        diff.check(
+            "pieces",
+            self.boundary != other.boundary || self.starts != other.starts,
+        );
+        diff.check("keys", self.keys != other.keys);
+        diff.check("aliases", self.aliases != other.aliases);
+        diff.check("hashes", self.hashes != other.hashes);
+        diff.check("piece_samples", self.piece_samples != other.piece_samples);
+        diff.check("groups", self.groups != other.groups);
+        diff.check("qgram_filter", self.qgram_filter != other.qgram_filter);
+        diff.check("converter", self.converter != other.converter);
+        diff.check("suffix_array", self.suffix_array != other.suffix_array);
+        diff
+    }
+}
+
+impl<T, C, S> IndexWithPieces for FMIndexMultiPieces<T, C, S>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    fn pieces_count(&self) -> u64 {
+        self.boundary.pieces_count()
+    }
+
+    fn piece_id(&self, mut i: u64) -> PieceId {
+        loop {
+            if let Some(id) = self.boundary.piece_after(i) {
+                return id;
+            }
+            if let Some(id) = self
+                .piece_samples
+                .as_ref()
+                .and_then(|samples| samples.get(i))
+            {
+                return id;
+            }
+            i = self.lf_map(i);
+        }
+    }
+}
+
+impl<T, C, S> BackwardIterableIndex for FMIndexMultiPieces<T, C, S>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    type T = T;
+
+    fn get_l(&self, i: u64) -> Self::T {
+        self.decode(self.bw.access::<T>(i).into())
+    }
+
+    fn lf_map(&self, i: u64) -> u64 {
+        self.lf_map_code(self.bw.access::<T>(i).into(), i)
+    }
+
+    fn lf_map2(&self, c: T, i: u64) -> u64 {
+        if !self.converter.contains(c) {
+            return 0;
+        }
+        self.lf_map_code(self.encode(c), i)
+    }
+
+    fn may_contain(&self, pattern: &[T]) -> bool {
+        match &self.qgram_filter {
+            Some(filter) => filter.may_contain_codes(pattern.iter().map(|&c| {
+                if self.converter.contains(c) {
+                    Some(self.encode(c))
+                } else {
+                    None
+                }
+            })),
+            None => true,
+        }
+    }
+
+    fn len(&self) -> u64 {
+        self.bw.len()
+    }
+
+    // The leading separator is not in the text either.
+    fn text_len_without_terminator(&self) -> u64 {
+        self.bw.len() - 2
+    }
+
+    // The leading separator is located at the start of the first piece already.
+    fn position_after_terminator(&self, p: u64) -> u64 {
+        if p == 0 {
+            0
+        } else {
+            p + 1
+        }
+    }
+}
+
+impl<T, C, S> ForwardIterableIndex for FMIndexMultiPieces<T, C, S>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    type T = T;
+
+    fn get_f(&self, i: u64) -> Self::T {
+        self.decode(self.f_code(i))
+    }
+
+    fn fl_map(&self, i: u64) -> u64 {
+        let c = self.f_code(i);
+        self.bw.select(T::from_u64(c), i - self.cs[c as usize])
+    }
+
+    fn fl_map2(&self, c: Self::T, i: u64) -> u64 {
+        let c = self.encode(c);
+        self.bw.select(T::from_u64(c), i - self.cs[c as usize])
+    }
+
+    fn len(&self) -> u64 {
+        self.bw.len()
+    }
+}
+
+impl<T, C, S> FMIndexMultiPieces<T, C, S> {
+    fn f_code(&self, i: u64) -> u64 {
+        self.cs.search(i)
+    }
+
+    // Converts a position in the stored text into one in the original text.
+    // The leading separator and the terminator have no counterpart there,
+    // so they are clamped to the first and the last positions of the original text respectively.
+    fn to_original(&self, p: u64) -> u64 {
+        let n = self.bw.len();
+        if p == 0 {
+            0
+        } else if p == n - 1 {
+            n - 3
+        } else {
+            p - 1
+        }
+    }
+}
+
+impl<T, C, S> FMIndexMultiPieces<T, C, S>
+where
+    S: PartialArray,
+{
+    // Returns the position in the stored text of the `i`-th row if it is sampled or starts with a separator,
+    // which is at the start position of the piece following it in the original text.
+    fn get_sa_sampled(&self, i: u64) -> Option<u64> {
+        self.suffix_array
+            .get(i)
+            .or_else(|| self.boundary.piece_after(i).map(|id| self.piece_start(id)))
+    }
+}
+
+impl<T, C, S> IndexWithSA for FMIndexMultiPieces<T, C, S>
+where
+    T: Character,
+    C: Converter<T>,
+    S: PartialArray,
+{
+    fn get_sa(&self, i: u64) -> u64 {
+        self.to_original(
+            suffix_array::resolve_sa(
+                i,
+                self.bw.len(),
+                None,
+                |k| self.get_sa_sampled(k),
+                |k| self.lf_map(k),
+            )
+            .unwrap(),
+        )
+    }
+
+    fn get_sa_with_budget(&self, i: u64, budget: &mut u64) -> Option<u64> {
+        suffix_array::resolve_sa(
+            i,
+            self.bw.len(),
+            Some(budget),
+            |k| self.get_sa_sampled(k),
+            |k| self.lf_map(k),
+        )
+        .map(|p| self.to_original(p))
+    }
+}
+
+impl<T, C, S> IndexWithConverter<T> for FMIndexMultiPieces<T, C, S>
+where
+    C: Converter<T>,
+{
+    type C = C;
+
+    fn get_converter(&self) -> &Self::C {
+        &self.converter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::suffix_array::{NullSampler, SuffixOrderSampledArray, SuffixOrderSampler};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn pieces() -> Vec<&'static str> {
+        vec!["mississippi", "ppi", "issi", "sip", "pi", "mis", "ssiss"]
+    }
+
+    fn build(text: &str) -> FMIndexMultiPieces<u8, RangeConverter<u8>, SuffixOrderSampledArray> {
+        FMIndexMultiPieces::new(
+            text.to_string().into_bytes(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(2),
+        )
+    }
+
+    fn naive_locate(text: &[u8], pattern: &[u8]) -> Vec<u64> {
+        (0..text.len())
+            .filter(|&i| text[i..].starts_with(pattern))
+            .map(|i| i as u64)
+            .collect()
+    }
+
+    #[test]
+    fn test_locate() {
+        let text = pieces().join("\0") + "\0";
+        let index = build(&text);
+        let text = text.into_bytes();
+        for pattern in &[
+            "i", "ssi", "pi", "ippi", "pi\0", "\0pi\0", "\0sip\0", "i\0", "s\0pi", "p",
+        ] {
+            let mut res = index.search_backward(pattern).locate();
+            res.sort();
+            assert_eq!(
+                res,
+                naive_locate(&text, pattern.as_bytes()),
+                "pattern {:?}",
+                pattern
+            );
+        }
+        // the occurrence at the leading separator is located at the start of the first piece
+        let mut res = index.search_backward("\0mis").locate();
+        res.sort();
+        assert_eq!(res, vec![0, 27]);
+    }
+
+    #[test]
+    fn test_locate_within_piece() {
+        let pieces = pieces();
+        let text = pieces.join("\0");
+        // samples almost no rows
+        let index = FMIndexMultiPieces::new(
+            text.clone().into_bytes(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(5),
+        );
+        let text = text.into_bytes();
+        let longest = pieces.iter().map(|p| p.len()).max().unwrap() as u64;
+        for pattern in &["i", "ss", "pi", "m", "\0p"] {
+            let search = index.search_backward(pattern);
+            let mut res = search.locate();
+            res.sort();
+            assert_eq!(
+                res,
+                naive_locate(&text, pattern.as_bytes()),
+                "pattern {:?}",
+                pattern
+            );
+            for i in search.get_range().rows() {
+                let mut budget = longest + 1;
+                assert!(index.get_sa_with_budget(i, &mut budget).is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn test_piece_id() {
+        let pieces = pieces();
+        let text = pieces.join("\0");
+        let index = build(&text);
+        let text = text.into_bytes();
+        assert_eq!(index.pieces_count(), pieces.len() as u64);
+
+        let mut starts = vec![0];
+        for piece in &pieces {
+            starts.push(starts.last().unwrap() + piece.len() as u64 + 1);
+        }
+        for i in 0..index.len() {
+            let p = index.get_sa(i);
+            if text.get(p as usize).is_none_or(|&c| c == 0) {
+                continue;
+            }
+            let expected = starts.iter().rposition(|&s| s <= p).unwrap() as u64;
+            assert_eq!(index.piece_id(i), PieceId::from(expected), "row {}", i);
+        }
+    }
+
+    #[test]
+    fn test_piece_id_at() {
+        let pieces = pieces();
+        let text = pieces.join("\0");
+        let index = build(&text);
+        let mut p = 0;
+        for (id, piece) in pieces.iter().enumerate() {
+            let id = PieceId::from(id as u64);
+            assert_eq!(index.piece_start(id), p);
+            for _ in 0..=piece.len() {
+                assert_eq!(index.piece_id_at(p), id);
+                p += 1;
+            }
+        }
+        let search = index.search_backward("ss");
+        for m in search.iter_matches() {
+            assert_eq!(m.piece_id(), index.piece_id_at(m.locate()));
+        }
+    }
+
+    #[test]
+    fn test_positions() {
+        let pieces = pieces();
+        let index = build(&pieces.join("\0"));
+        let mut p = 0;
+        for (id, piece) in pieces.iter().enumerate() {
+            for offset in 0..=piece.len() as u64 {
+                let pos = PiecePos::new(PieceId::from(id as u64), offset);
+                assert_eq!(index.piece_pos(GlobalPos::from(p)), pos);
+                assert_eq!(index.global_pos(pos), GlobalPos::from(p));
+                p += 1;
+            }
+        }
+
+        let search = index.search_backward("ss");
+        let global = search.locate_global();
+        assert_eq!(
+            global,
+            search
+                .locate()
+                .into_iter()
+                .map(GlobalPos::from)
+                .collect::<Vec<_>>()
+        );
+        let local = search.locate_in_pieces();
+        for (g, l) in global.iter().zip(&local) {
+            assert_eq!(index.global_pos(*l), *g);
+        }
+        let mut local = local
+            .into_iter()
+            .map(|p| (u64::from(p.piece), p.offset))
+            .collect::<Vec<_>>();
+        local.sort();
+        assert_eq!(local, vec![(0, 2), (0, 5), (2, 1), (6, 0), (6, 3)]);
+    }
+
+    #[test]
+    fn test_contains_piece() {
+        let pieces = pieces();
+        let index = FMIndexMultiPieces::new(
+            pieces.join("\0").into_bytes(),
+            RangeConverter::new(b'a', b'z'),
+            NullSampler::new(),
+        );
+        for (id, piece) in pieces.iter().enumerate() {
+            assert_eq!(index.contains_piece(piece), Some(PieceId::from(id as u64)));
+        }
+        assert_eq!(
+            index.contains_pieces(["ssi", "sip", "", "mississippis", "pi"]),
+            vec![
+                None,
+                Some(PieceId::from(3)),
+                None,
+                None,
+                Some(PieceId::from(4))
+            ]
+        );
+        // patterns spanning pieces equal none of them
+        assert_eq!(index.contains_piece("ppi\0issi"), None);
+        assert_eq!(index.contains_piece("\0ppi"), None);
+        assert_eq!(index.contains_piece("ppi\0"), None);
+    }
+
+    #[test]
+    fn test_locate_virtual_rows() {
+        let text = b"ab\0ba\0ab";
+        let index = FMIndexMultiPieces::new(
+            text.to_vec(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new(),
+        );
+        // the empty pattern matches every row, including the leading separator and the terminator
+        let positions = index.search_backward("").locate();
+        assert_eq!(positions.len(), text.len() + 2);
+        assert!(positions.iter().all(|&p| p < text.len() as u64));
+        let mut positions = index.search_backward("\0a").locate();
+        positions.sort();
+        assert_eq!(positions, vec![0, 5]);
+    }
+
+    #[test]
+    fn test_piece_boundary() {
+        let pieces = pieces();
+        let index = FMIndexMultiPieces::new(
+            pieces.join("\0").into_bytes(),
+            RangeConverter::new(b'a', b'z'),
+            NullSampler::new(),
+        );
+        let boundary = index.boundary();
+        let k = pieces.len() as u64;
+        assert_eq!(boundary.pieces_count(), k);
+        assert_eq!(boundary.separator_rows(), 1..k + 1);
+        assert_eq!(index.search_backward("\0").get_range(), (0, k + 1).into());
+        assert_eq!(boundary.piece_after(0), None);
+        assert_eq!(boundary.piece_after(k + 1), None);
+
+        let mut sorted = pieces.clone();
+        sorted.sort();
+        for i in 0..index.len() {
+            assert_eq!(index.get_f(i) == 0, i <= k, "row {}", i);
+            if let Some(id) = boundary.piece_after(i) {
+                // the separator rows are in the order of the pieces following them
+                let piece = index
+                    .iter_forward(i)
+                    .skip(1)
+                    .take_while(|&c| c != 0)
+                    .collect::<Vec<_>>();
+                assert_eq!(pieces[u64::from(id) as usize].as_bytes(), &piece[..]);
+                assert_eq!(sorted[i as usize - 1].as_bytes(), &piece[..]);
+                assert_eq!(index.piece_id(i), id);
+            }
+        }
+        assert_eq!(boundary.pieces_after(0..k + 5).count() as u64, k);
+        assert_eq!(
+            boundary.pieces_after(2..4).collect::<Vec<_>>(),
+            vec![
+                boundary.piece_after(2).unwrap(),
+                boundary.piece_after(3).unwrap()
+            ]
+        );
+        assert_eq!(boundary.pieces_after(0..1).count(), 0);
+        assert_eq!(boundary.pieces_after(k + 1..k + 3).count(), 0);
+    }
+
+    #[test]
+    fn test_pieces_with_prefix() {
+        let pieces = pieces();
+        let index = FMIndexMultiPieces::new(
+            pieces.join("\0").into_bytes(),
+            RangeConverter::new(b'a', b'z'),
+            NullSampler::new(),
+        );
+        for pattern in &["", "m", "mis", "ss", "pi", "p", "issi", "x", "mississippis"] {
+            let expected = pieces
+                .iter()
+                .enumerate()
+                .filter(|(_, piece)| piece.starts_with(pattern))
+                .map(|(id, _)| PieceId::from(id as u64))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                index.pieces_with_prefix(pattern),
+                expected,
+                "pattern {:?}",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn test_new_storage_deduplicated() {
+        let pieces = ["ppi", "mis", "ppi", "issi", "mis", "ppi", "sip"];
+        let index = FMIndexMultiPieces::new_storage_deduplicated(
+            format!("{}\0", pieces.join("\0")).into_bytes(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(2),
+        );
+        let distinct = ["ppi", "mis", "issi", "sip"];
+        assert_eq!(index.pieces_count(), distinct.len() as u64);
+        assert_eq!(index.original_pieces_count(), pieces.len() as u64);
+        assert_eq!(
+            index.diff(&build(&distinct.join("\0"))).components,
+            vec!["aliases"]
+        );
+
+        for (original, piece) in pieces.iter().enumerate() {
+            let stored = index.stored_piece_id(PieceId::from(original as u64));
+            assert_eq!(distinct[u64::from(stored) as usize], *piece);
+            assert!(index
+                .original_piece_ids(stored)
+                .contains(&PieceId::from(original as u64)));
+        }
+        for pattern in &["p", "pi", "is", "s", "mis", "x"] {
+            let expected = pieces
+                .iter()
+                .enumerate()
+                .filter(|(_, piece)| piece.contains(pattern))
+                .map(|(id, _)| PieceId::from(id as u64))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                index.search_backward(pattern).original_piece_ids(),
+                expected,
+                "pattern {:?}",
+                pattern
+            );
+        }
+
+        let index = build("ppi\0mis");
+        assert_eq!(index.original_pieces_count(), 2);
+        assert_eq!(
+            index.original_piece_ids(PieceId::from(1)),
+            vec![PieceId::from(1)]
+        );
+        assert_eq!(index.stored_piece_id(PieceId::from(1)), PieceId::from(1));
+    }
+
+    #[test]
+    fn test_piece_stats() {
+        let pieces = pieces();
+        let text = pieces.join("\0");
+        let index = FMIndexMultiPieces::new(
+            text.clone().into_bytes(),
+            RangeConverter::new(b'a', b'z'),
+            NullSampler::new(),
+        );
+        assert_eq!(
+            index.piece_lens(),
+            pieces.iter().map(|p| p.len() as u64).collect::<Vec<_>>()
+        );
+        for pattern in &["i", "ss", "ppi", "mis", "x"] {
+            let expected = pieces
+                .iter()
+                .enumerate()
+                .map(|(id, piece)| PieceStat {
+                    piece: PieceId::from(id as u64),
+                    frequency: naive_locate(piece.as_bytes(), pattern.as_bytes()).len() as u64,
+                    len: piece.len() as u64,
+                })
+                .filter(|stat| stat.frequency > 0)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                index.search_backward(pattern).piece_stats(),
+                expected,
+                "pattern {:?}",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn test_piece_hashes() {
+        let pieces = pieces();
+        let index = build(&pieces.join("\0"));
+        assert_eq!(index.piece_hash(PieceId::from(0)), None);
+        let size = index.size();
+        let index = index.with_piece_hashes();
+        assert!(index.size() > size);
+        for (id, piece) in pieces.iter().enumerate() {
+            assert_eq!(
+                index.piece_hash(PieceId::from(id as u64)),
+                Some(piece::hash_piece(piece.as_bytes()))
+            );
+        }
+
+        let text = vec![300u16, 301, 0, 301, 300];
+        let index =
+            FMIndexMultiPieces::new(text, RangeConverter::new(300, 310), NullSampler::new())
+                .with_piece_hashes();
+        assert_eq!(
+            index.piece_hash(PieceId::from(1)),
+            Some(piece::hash_piece(&[301u16, 300]))
+        );
+    }
+
+    #[test]
+    fn test_segmented_construction() {
+        let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+        let random = (0..300)
+            .map(|_| match rng.gen_range(0, 8) {
+                0 => 0,
+                _ => rng.gen_range(b'a', b'd'),
+            })
+            .collect::<Vec<_>>();
+        // pieces must not be empty
+        let random = random
+            .split(|&c| c == 0)
+            .filter(|p| !p.is_empty())
+            .collect::<Vec<_>>()
+            .join(&0);
+        let texts = vec![
+            pieces().join("\0").into_bytes(),
+            b"ab\0ab\0a\0abab\0ab\0b\0".to_vec(),
+            b"a".to_vec(),
+            random,
+        ];
+        for text in texts {
+            let expected = FMIndexMultiPieces::new(
+                text.clone(),
+                RangeConverter::new(b'a', b'z'),
+                SuffixOrderSampler::new(),
+            );
+            for threads in [1, 3] {
+                let index = FMIndexMultiPieces::new_with_strategy(
+                    text.clone(),
+                    RangeConverter::new(b'a', b'z'),
+                    SuffixOrderSampler::new(),
+                    ConstructionStrategy::Segmented { threads },
+                );
+                assert!(
+                    index.diff(&expected).is_identical(),
+                    "{:?}, threads = {}: {}",
+                    String::from_utf8_lossy(&text),
+                    threads,
+                    index.diff(&expected)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_spans() {
+        // length-prefixed records
+        let mut buffer = vec![];
+        let mut spans = vec![];
+        for p in pieces() {
+            buffer.push(p.len() as u8);
+            spans.push((buffer.len(), p.len()));
+            buffer.extend_from_slice(p.as_bytes());
+        }
+        // spans may overlap and need not be in order
+        spans.push((1, 4));
+        spans.push((spans[2].0 + 1, 3));
+        let text = spans
+            .iter()
+            .map(|&(offset, len)| &buffer[offset..offset + len])
+            .collect::<Vec<_>>()
+            .join(&0);
+
+        let expected = FMIndexMultiPieces::new(
+            text,
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new(),
+        );
+        let index = FMIndexMultiPieces::from_spans(
+            &buffer,
+            &spans,
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new(),
+        );
+        assert!(
+            index.diff(&expected).is_identical(),
+            "{}",
+            index.diff(&expected)
+        );
+        assert_eq!(index.search_backward("ssi").count(), 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "pieces must not be empty")]
+    fn test_from_spans_empty_piece() {
+        FMIndexMultiPieces::from_spans(
+            b"abc",
+            &[(0, 2), (2, 0)],
+            RangeConverter::new(b'a', b'z'),
+            NullSampler::new(),
+        );
+    }
+
+    #[test]
+    fn test_piece_samples() {
+        let pieces = pieces().iter().map(|p| p.repeat(10)).collect::<Vec<_>>();
+        let index = build(&pieces.join("\0"));
+        let expected = (0..index.len())
+            .map(|i| index.piece_id(i))
+            .collect::<Vec<_>>();
+        assert_eq!(index.piece_sample_level(), None);
+        let size = index.size();
+        let mut index = index;
+        for level in 0..4 {
+            index = index.with_piece_samples(level);
+            assert_eq!(index.piece_sample_level(), Some(level));
+            assert!(index.size() > size);
+            for (i, &id) in expected.iter().enumerate() {
+                assert_eq!(index.piece_id(i as u64), id, "level {}, row {}", level, i);
+            }
+        }
+    }
+
+    #[test]
+    fn test_keys() {
+        let pieces = pieces();
+        let keys = (0..pieces.len() as u64)
+            .map(|i| 1000 - i * 7)
+            .collect::<Vec<_>>();
+        let index = build(&pieces.join("\0"));
+        assert!(!index.has_keys());
+        assert_eq!(index.piece_id_by_key(keys[0]), None);
+        assert_eq!(index.key_by_piece_id(PieceId::from(0)), None);
+
+        let size = index.size();
+        let index = index.with_keys(keys.clone());
+        assert!(index.has_keys());
+        assert!(index.size() > size);
+        for (i, &key) in keys.iter().enumerate() {
+            let id = PieceId::from(i as u64);
+            assert_eq!(index.piece_id_by_key(key), Some(id));
+            assert_eq!(index.key_by_piece_id(id), Some(key));
+        }
+        assert_eq!(index.piece_id_by_key(1), None);
+        assert_eq!(
+            index.key_by_piece_id(PieceId::from(pieces.len() as u64)),
+            None
+        );
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&index).unwrap();
+            let index: FMIndexMultiPieces<u8, RangeConverter<u8>, SuffixOrderSampledArray> =
+                serde_json::from_str(&json).unwrap();
+            assert_eq!(index.piece_id_by_key(keys[3]), Some(PieceId::from(3)));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "keys must be unique")]
+    fn test_duplicate_keys() {
+        build("a\0b").with_keys(vec![1, 1]);
+    }
+
+    #[test]
+    fn test_iter_pieces() {
+        let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+        let pieces = (0..20)
+            .map(|_| {
+                let len = rng.gen_range(1, 12);
+                (0..len)
+                    .map(|_| rng.gen_range(b'a', b'd'))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let index = FMIndexMultiPieces::new(
+            pieces.join(&0),
+            RangeConverter::new(b'a', b'z'),
+            NullSampler::new(),
+        );
+        let actual = index
+            .iter_pieces()
+            .map(|(id, chars)| (u64::from(id), chars.collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        let expected = pieces
+            .into_iter()
+            .enumerate()
+            .map(|(id, piece)| (id as u64, piece))
+            .collect::<Vec<_>>();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_kmer_similarity() {
+        let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+        let pieces = (0..30)
+            .map(|_| {
+                let len = rng.gen_range(1, 40);
+                (0..len)
+                    .map(|_| rng.gen_range(b'a', b'd'))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let index = FMIndexMultiPieces::new(
+            pieces.join(&0),
+            RangeConverter::new(b'a', b'z'),
+            NullSampler::new(),
+        );
+        let count = |text: &[u8], kmer: &[u8]| {
+            text.windows(kmer.len()).filter(|&w| w == kmer).count() as u64
+        };
+        for k in 1..5 {
+            let query = (0..12)
+                .map(|_| rng.gen_range(b'a', b'd'))
+                .collect::<Vec<_>>();
+            let mut expected = pieces
+                .iter()
+                .enumerate()
+                .map(|(id, piece)| PieceScore {
+                    piece: PieceId::from(id as u64),
+                    score: query.windows(k).map(|kmer| count(piece, kmer)).sum(),
+                })
+                .filter(|s| s.score > 0)
+                .collect::<Vec<_>>();
+            expected.sort_by_key(|s| (Reverse(s.score), s.piece));
+            assert_eq!(index.kmer_similarity(&query, k, 100), expected);
+            expected.truncate(5);
+            assert_eq!(index.kmer_similarity(&query, k, 5), expected);
+        }
+        assert!(index.kmer_similarity("ab", 3, 5).is_empty());
+    }
+
+    #[test]
+    fn test_qgram_filter() {
+        use crate::converter::{AsciiLowercase, NormalizedConverter};
+
+        let text = pieces().join("\0").to_uppercase();
+        let converter =
+            || NormalizedConverter::new(RangeConverter::new(b'a', b'z'), AsciiLowercase);
+        let plain =
+            FMIndexMultiPieces::new(text.clone().into_bytes(), converter(), NullSampler::new());
+        let index =
+            FMIndexMultiPieces::new(text.clone().into_bytes(), converter(), NullSampler::new())
+                .with_qgram_filter(3, 1 << 8);
+        assert!(index.qgram_filter().is_some());
+        assert!(index.size() > plain.size());
+        assert!(!index.diff(&plain).is_identical());
+
+        // no false negatives on any substring, including separators and either case
+        let lower = text.to_lowercase().into_bytes();
+        for i in 0..lower.len() {
+            for j in i + 1..lower.len().min(i + 8) {
+                let pattern = &lower[i..j];
+                assert_eq!(
+                    index.search_backward(pattern).count(),
+                    plain.search_backward(pattern).count(),
+                    "{:?}",
+                    pattern
+                );
+            }
+        }
+        assert_eq!(index.search_prefix("mis").count(), 2);
+        assert_eq!(index.search_exact("PPI").count(), 1);
+        assert_eq!(index.search_backward("sss").count(), 0);
+        assert_eq!(index.search_backward("ab").count(), 0);
+    }
+
+    #[test]
+    fn test_groups() {
+        let pieces = pieces();
+        let groups = vec![2, 0, 2, 1, 0, 2, 4];
+        let index = build(&pieces.join("\0"));
+        assert_eq!(index.groups_count(), 0);
+        assert!(index.search_backward("ss").distinct_groups().is_empty());
+
+        let size = index.size();
+        let index = index.with_groups(groups.clone());
+        assert!(index.size() > size);
+        assert_eq!(index.groups_count(), 5);
+        assert!(index.group_pieces(3).is_empty());
+        assert!(index.group_pieces(5).is_empty());
+        assert_eq!(
+            index.group_pieces(2),
+            vec![PieceId::from(0), PieceId::from(2), PieceId::from(5)]
+        );
+        for pattern in &["i", "ss", "pi", "mis", "x"] {
+            let search = index.search_backward(pattern);
+            let mut expected = [0; 5];
+            for (id, piece) in pieces.iter().enumerate() {
+                expected[groups[id] as usize] += piece
+                    .as_bytes()
+                    .windows(pattern.len())
+                    .filter(|&w| w == pattern.as_bytes())
+                    .count() as u64;
+            }
+            let counts = search.group_counts();
+            assert_eq!(
+                counts,
+                (0..5)
+                    .filter(|&g| expected[g as usize] > 0)
+                    .map(|g| (g, expected[g as usize]))
+                    .collect::<Vec<_>>(),
+                "{}",
+                pattern
+            );
+            for g in 0..5 {
+                assert_eq!(search.count_in_group(g), expected[g as usize]);
+                assert!(search
+                    .iter_matches_in_group(g)
+                    .all(|m| groups[u64::from(m.piece_id()) as usize] == g));
+            }
+        }
+        assert_eq!(
+            index.diff(&build(&pieces.join("\0"))).components,
+            vec!["groups"]
+        );
+
+        // sparse groups do not allocate per group
+        let index = build(&pieces.join("\0")).with_groups(vec![u64::MAX - 1, 0, 0, 1, 0, 1, 1]);
+        assert_eq!(index.groups_count(), u64::MAX);
+        assert_eq!(index.group_pieces(u64::MAX - 1), vec![PieceId::from(0)]);
+        assert!(index.group_pieces(2).is_empty());
+    }
+
+    #[test]
+    fn test_search_across_pieces() {
+        let pieces = pieces();
+        let text = pieces.join("\0");
+        let index = build(&text);
+        let text = text.into_bytes();
+        for pattern in &["ippip", "ppiissi", "pis", "ssippi", "ississ", "x", "ii"] {
+            let mut results = index
+                .search_across_pieces(pattern, 1 << pattern.len())
+                .unwrap()
+                .into_iter()
+                .map(|r| {
+                    let mut positions = r.search.locate();
+                    positions.sort();
+                    (r.boundaries, positions)
+                })
+                .collect::<Vec<_>>();
+            results.sort();
+
+            // insert separators in every way
+            let m = pattern.len();
+            let mut expected = vec![];
+            for mask in 0..1u64 << (m - 1) {
+                let boundaries = (1..m)
+                    .filter(|k| mask >> (k - 1) & 1 > 0)
+                    .collect::<Vec<_>>();
+                let mut joined = vec![];
+                for (k, &c) in pattern.as_bytes().iter().enumerate() {
+                    if boundaries.contains(&k) {
+                        joined.push(0);
+                    }
+                    joined.push(c);
+                }
+                let positions = naive_locate(&text, &joined);
+                if !positions.is_empty() {
+                    expected.push((boundaries, positions));
+                }
+            }
+            expected.sort();
+            assert_eq!(results, expected, "pattern {:?}", pattern);
+        }
+
+        // "ss" occurs both within pieces and across them
+        assert_eq!(
+            index.search_across_pieces("ss", 1).err(),
+            Some(BranchLimitExceeded { limit: 1 })
+        );
+        assert!(index.search_across_pieces("ss", 2).is_ok());
+    }
+
+    #[test]
+    fn test_iter() {
+        let text = "abc\0de\0f".to_string();
+        let index = build(&text);
+        let search = index.search_backward("de");
+        let mut prev = search.iter_backward(0).take(4).collect::<Vec<_>>();
+        prev.reverse();
+        assert_eq!(prev, b"abc\0".to_owned());
+        let next = search.iter_forward(0).take(4).collect::<Vec<_>>();
+        assert_eq!(next, b"de\0f".to_owned());
+    }
+
+    #[test]
+    fn test_highlights() {
+        let index = build("banana\0ananas\0nab");
+        let spans = |pattern: &str| index.search_backward(pattern).highlights();
+        let id = PieceId::from;
+        assert_eq!(
+            spans("ana"),
+            vec![(id(0), vec![(1, 6)]), (id(1), vec![(0, 5)])]
+        );
+        assert_eq!(spans("nab"), vec![(id(2), vec![(0, 3)])]);
+        // spanning separators
+        assert_eq!(
+            spans("a\0a"),
+            vec![(id(0), vec![(5, 6)]), (id(1), vec![(0, 1)])]
+        );
+        assert_eq!(
+            spans("s\0nab"),
+            vec![(id(1), vec![(5, 6)]), (id(2), vec![(0, 3)])]
+        );
+        // the occurrence at the leading separator covers only the first piece
+        assert_eq!(spans("\0ban"), vec![(id(0), vec![(0, 3)])]);
+        assert_eq!(spans("x"), vec![]);
+    }
+
+    #[test]
+    fn test_iter_in_piece() {
+        let index = build("abc\0de\0fde");
+        let search = index.search_backward("d");
+        let mut results = search
+            .iter_matches()
+            .map(|m| {
+                let mut prev = m.iter_backward_in_piece().collect::<Vec<_>>();
+                prev.reverse();
+                let next = m.iter_forward_in_piece().collect::<Vec<_>>();
+                (prev, next)
+            })
+            .collect::<Vec<_>>();
+        results.sort();
+        assert_eq!(
+            results,
+            vec![
+                (b"".to_vec(), b"de".to_vec()),
+                (b"f".to_vec(), b"de".to_vec())
+            ]
+        );
+
+        // the first and last pieces are also enclosed
+        let search = index.search_backward("b");
+        assert_eq!(search.iter_backward_in_piece(0).collect::<Vec<_>>(), b"a");
+        assert_eq!(search.iter_forward_in_piece(0).collect::<Vec<_>>(), b"bc");
+        let search = index.search_backward("fd");
+        assert_eq!(search.iter_backward_in_piece(0).count(), 0);
+        assert_eq!(search.iter_forward_in_piece(0).collect::<Vec<_>>(), b"fde");
+    }
+}
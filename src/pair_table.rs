@@ -0,0 +1,162 @@
+use crate::character::Character;
+use crate::converter::{Converter, IndexWithConverter};
+use crate::search::{BackwardSearchIndex, SaInterval, Search};
+
+/// A table of the rows of every pair of characters, which saves the first two steps of backward searches.
+///
+/// Searches for patterns of one or two characters, whose intervals are typically huge,
+/// become table lookups, and longer patterns start from the range of their last two characters.
+/// It takes _16σ²_ bytes, e.g. 1 MiB for a byte alphabet, and _O(σ²)_ searches to build,
+/// so alphabets larger than [`PairTable::MAX_ALPHABET_SIZE`] are rejected.
+/// Single characters are looked up in the bucket array of the index without a table
+/// by [`crate::BackwardIterableIndex::char_range`].
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::SuffixOrderSampler;
+/// use fm_index::{BackwardSearchIndex, FMIndex, PairTable};
+///
+/// let index = FMIndex::new(b"mississippi".to_vec(), RangeConverter::new(b'a', b'z'), SuffixOrderSampler::new());
+/// let table = PairTable::new(&index);
+/// assert_eq!(table.search(&index, "ss").count(), 2);
+/// assert_eq!(table.search(&index, "issi").get_range(), index.search_backward("issi").get_range());
+/// ```
+pub struct PairTable {
+    sigma: u64,
+    // ranges[a * sigma + b]: the rows of the pair of the characters of codes `a` and `b`
+    ranges: Vec<SaInterval>,
+}
+
+impl PairTable {
+    /// The largest alphabet size, including the terminator, for which a table can be built.
+    ///
+    /// The table of this size takes 64 MiB.
+    pub const MAX_ALPHABET_SIZE: u64 = 1 << 11;
+
+    /// Builds the table of the pairs of characters of `index`.
+    ///
+    /// Panics if the alphabet of the converter of `index` is larger than [`PairTable::MAX_ALPHABET_SIZE`].
+    pub fn new<T, I>(index: &I) -> Self
+    where
+        T: Character,
+        I: BackwardSearchIndex<T = T> + IndexWithConverter<T>,
+    {
+        let converter = index.get_converter();
+        let sigma = converter.len();
+        assert!(
+            sigma <= Self::MAX_ALPHABET_SIZE,
+            "alphabet of {} characters is too large for a pair table (at most {})",
+            sigma,
+            Self::MAX_ALPHABET_SIZE
+        );
+        let mut ranges = Vec::with_capacity((sigma * sigma) as usize);
+        for a in 0..sigma {
+            let a = converter.convert_inv(T::from_u64(a));
+            for b in 0..sigma {
+                let b = converter.convert_inv(T::from_u64(b));
+                ranges.push(index.search_backward([a, b]).get_range());
+            }
+        }
+        PairTable { sigma, ranges }
+    }
+
+    /// Searches for `pattern` like [`BackwardSearchIndex::search_backward`].
+    ///
+    /// `index` must be the index the table was built from.
+    pub fn search<'a, T, I, K>(&self, index: &'a I, pattern: K) -> Search<'a, I>
+    where
+        T: Character,
+        I: BackwardSearchIndex<T = T> + IndexWithConverter<T>,
+        K: AsRef<[T]>,
+    {
+        let pattern = pattern.as_ref();
+        let m = pattern.len();
+        if m < 2 {
+            return index.search_backward(pattern);
+        }
+        let (a, b) = (pattern[m - 2], pattern[m - 1]);
+        let converter = index.get_converter();
+        let range = if converter.contains(a) && converter.contains(b) {
+            let (a, b) = (converter.convert(a).into(), converter.convert(b).into());
+            self.ranges[(a * self.sigma + b) as usize]
+        } else {
+            SaInterval::default()
+        };
+        Search::with_range(index, range.start, range.end, pattern[m - 2..].to_vec())
+            .search_backward(&pattern[..m - 2])
+    }
+
+    pub fn size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.ranges.len() * std::mem::size_of::<SaInterval>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::{IdConverter, RangeConverter};
+    use crate::iter::BackwardIterableIndex;
+    use crate::suffix_array::NullSampler;
+    use crate::{FMIndex, HybridFMIndex, RLFMIndex};
+
+    fn patterns() -> Vec<Vec<u8>> {
+        let mut patterns = vec![vec![]];
+        let mut last = vec![vec![]];
+        for _ in 0..4 {
+            let mut next = vec![];
+            for p in &last {
+                for &c in b"aimpsx" {
+                    let mut q = p.clone();
+                    q.push(c);
+                    next.push(q);
+                }
+            }
+            patterns.extend(next.iter().cloned());
+            last = next;
+        }
+        patterns
+    }
+
+    #[test]
+    fn test_char_range() {
+        let text = b"mississippi".to_vec();
+        let converter = RangeConverter::new(b'a', b'z');
+        let fm_index = FMIndex::new(text.clone(), converter.clone(), NullSampler::new());
+        let hybrid = HybridFMIndex::new(text, converter, NullSampler::new());
+        for c in 0..=255u8 {
+            let expected =
+                SaInterval::new(fm_index.lf_map2(c, 0), fm_index.lf_map2(c, fm_index.len()));
+            assert_eq!(fm_index.char_range(c), expected, "c = {}", c);
+            assert_eq!(hybrid.char_range(c), expected, "c = {}", c);
+        }
+    }
+
+    #[test]
+    fn test_pair_table() {
+        let text = b"mississippi".to_vec();
+        let converter = RangeConverter::new(b'a', b'z');
+        let fm_index = FMIndex::new(text.clone(), converter.clone(), NullSampler::new());
+        let rlfm_index = RLFMIndex::new(text, converter, NullSampler::new());
+        let fm_table = PairTable::new(&fm_index);
+        let rlfm_table = PairTable::new(&rlfm_index);
+        assert_eq!(fm_table.size(), rlfm_table.size());
+        for pattern in patterns() {
+            let expected = fm_index.search_backward(&pattern);
+            let search = fm_table.search(&fm_index, &pattern);
+            assert_eq!(search.get_range(), expected.get_range(), "{:?}", pattern);
+            assert_eq!(search.pattern(), pattern);
+            let search = rlfm_table.search(&rlfm_index, &pattern);
+            assert_eq!(search.count(), expected.count(), "{:?}", pattern);
+        }
+        assert_eq!(fm_table.search(&fm_index, "A").count(), 0);
+        assert_eq!(fm_table.search(&fm_index, "sA").count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "too large for a pair table")]
+    fn test_pair_table_large_alphabet() {
+        let converter = IdConverter::new(PairTable::MAX_ALPHABET_SIZE + 1);
+        let index = FMIndex::new(vec![1u16, 2, 3], converter, NullSampler::new());
+        PairTable::new(&index);
+    }
+}
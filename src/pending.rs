@@ -0,0 +1,347 @@
+use crate::character::Character;
+use crate::converter::Converter;
+use crate::multi_pieces::FMIndexMultiPieces;
+use crate::piece::{IndexWithPieces, PieceId};
+use crate::search::BackwardSearchIndex;
+use crate::suffix_array::{ArraySampler, PartialArray};
+
+use std::thread::{self, JoinHandle};
+
+/// Pieces which are not indexed yet, searched by scanning.
+///
+/// The pieces are stored as a text in which each piece is followed by zero,
+/// in the same way as the text of [`FMIndexMultiPieces`].
+pub struct PendingBuffer<T> {
+    text: Vec<T>,
+    // the start positions of pieces
+    starts: Vec<u64>,
+}
+
+impl<T> Default for PendingBuffer<T> {
+    fn default() -> Self {
+        PendingBuffer {
+            text: vec![],
+            starts: vec![],
+        }
+    }
+}
+
+impl<T> PendingBuffer<T>
+where
+    T: Character,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a piece, which must be non-empty and must not contain zero.
+    pub fn push<K: AsRef<[T]>>(&mut self, piece: K) {
+        let piece = piece.as_ref();
+        assert!(!piece.is_empty(), "pieces must not be empty");
+        assert!(
+            piece.iter().all(|c| !c.is_zero()),
+            "pieces must not contain zero"
+        );
+        self.starts.push(self.text.len() as u64);
+        self.text.extend_from_slice(piece);
+        self.text.push(T::zero());
+    }
+
+    pub fn pieces_count(&self) -> u64 {
+        self.starts.len() as u64
+    }
+
+    /// Returns the length of the text including separators.
+    pub fn len(&self) -> u64 {
+        self.text.len() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn text(&self) -> &[T] {
+        &self.text
+    }
+
+    pub fn count<K: AsRef<[T]>>(&self, pattern: K) -> u64 {
+        self.locate(pattern).len() as u64
+    }
+
+    /// Lists the positions of `pattern` in ascending order by scanning the text.
+    pub fn locate<K: AsRef<[T]>>(&self, pattern: K) -> Vec<u64> {
+        let pattern = pattern.as_ref();
+        if pattern.is_empty() {
+            return (0..self.len()).collect();
+        }
+        self.text
+            .windows(pattern.len())
+            .enumerate()
+            .filter(|(_, w)| *w == pattern)
+            .map(|(i, _)| i as u64)
+            .collect()
+    }
+
+    /// Returns the index of the pending piece containing position `p`.
+    pub fn piece_id(&self, p: u64) -> u64 {
+        debug_assert!(p < self.len());
+        (self.starts.partition_point(|&s| s <= p) - 1) as u64
+    }
+
+    // Removes the first `k` pieces.
+    fn drain(&mut self, k: usize) -> Vec<T> {
+        let end = self.starts.get(k).map_or(self.text.len(), |&s| s as usize);
+        self.starts.drain(..k);
+        for s in &mut self.starts {
+            *s -= end as u64;
+        }
+        self.text.drain(..end).collect()
+    }
+}
+
+/// A multi-piece index that accepts new pieces without rebuilding it on every insertion.
+///
+/// New pieces are kept in a [`PendingBuffer`] and searched by scanning,
+/// until [`LiveIndex::merge`] rebuilds the index over all pieces.
+/// Positions and piece IDs are those in the concatenation of the indexed and pending pieces,
+/// so they do not change by merging.
+/// Occurrences spanning the boundary between the indexed and pending pieces are not reported.
+pub struct LiveIndex<T, C, S, B> {
+    index: FMIndexMultiPieces<T, C, S>,
+    // the indexed text, in which every piece is followed by zero
+    text: Vec<T>,
+    pending: PendingBuffer<T>,
+    converter: C,
+    sampler: B,
+}
+
+/// A merge running in a background thread, started by [`LiveIndex::merge_in_background`].
+pub struct PendingMerge<T, C, S> {
+    handle: JoinHandle<FMIndexMultiPieces<T, C, S>>,
+    base_len: usize,
+    pieces: usize,
+}
+
+impl<T, C, S, B> LiveIndex<T, C, S, B>
+where
+    T: Character,
+    C: Converter<T> + Clone,
+    B: ArraySampler<S> + Clone,
+{
+    pub fn new(mut text: Vec<T>, converter: C, sampler: B) -> Self {
+        if !text.last().is_some_and(|c| c.is_zero()) {
+            text.push(T::zero());
+        }
+        let index = FMIndexMultiPieces::new(text.clone(), converter.clone(), sampler.clone());
+        LiveIndex {
+            index,
+            text,
+            pending: PendingBuffer::new(),
+            converter,
+            sampler,
+        }
+    }
+
+    pub fn index(&self) -> &FMIndexMultiPieces<T, C, S> {
+        &self.index
+    }
+
+    pub fn pending(&self) -> &PendingBuffer<T> {
+        &self.pending
+    }
+
+    pub fn pieces_count(&self) -> u64 {
+        self.index.pieces_count() + self.pending.pieces_count()
+    }
+
+    /// Adds a piece to the pending buffer and returns its ID.
+    pub fn add_piece<K: AsRef<[T]>>(&mut self, piece: K) -> PieceId {
+        self.pending.push(piece);
+        PieceId::from(self.pieces_count() - 1)
+    }
+
+    pub fn count<K: AsRef<[T]>>(&self, pattern: K) -> u64 {
+        let pattern = pattern.as_ref();
+        let virtual_count = self.matches_leading_separator(pattern) as u64;
+        self.index.search_backward(pattern).count() - virtual_count + self.pending.count(pattern)
+    }
+
+    /// Rebuilds the index over all pieces and empties the pending buffer.
+    pub fn merge(&mut self) {
+        let k = self.pending.pieces_count() as usize;
+        self.text.extend(self.pending.drain(k));
+        self.index = FMIndexMultiPieces::new(
+            self.text.clone(),
+            self.converter.clone(),
+            self.sampler.clone(),
+        );
+    }
+}
+
+impl<T, C, S, B> LiveIndex<T, C, S, B>
+where
+    T: Character,
+{
+    // Returns true if `pattern` occurs at the leading separator of the index,
+    // which precedes the first piece in the index but not in the text.
+    fn matches_leading_separator(&self, pattern: &[T]) -> bool {
+        match pattern.split_first() {
+            Some((c, rest)) => c.is_zero() && self.text.starts_with(rest),
+            None => false,
+        }
+    }
+}
+
+impl<T, C, S, B> LiveIndex<T, C, S, B>
+where
+    T: Character + Send + 'static,
+    C: Converter<T> + Clone + Send + 'static,
+    S: Send + 'static,
+    B: ArraySampler<S> + Clone + Send + 'static,
+{
+    /// Starts rebuilding the index over the current pieces in a background thread.
+    ///
+    /// Queries and insertions can continue meanwhile.
+    /// Pass the result to [`LiveIndex::finish_merge`] to replace the index.
+    pub fn merge_in_background(&self) -> PendingMerge<T, C, S> {
+        let mut text = self.text.clone();
+        text.extend_from_slice(self.pending.text());
+        let converter = self.converter.clone();
+        let sampler = self.sampler.clone();
+        PendingMerge {
+            handle: thread::spawn(move || FMIndexMultiPieces::new(text, converter, sampler)),
+            base_len: self.text.len(),
+            pieces: self.pending.pieces_count() as usize,
+        }
+    }
+
+    /// Waits for `merge` and replaces the index with its result.
+    ///
+    /// Pieces added after the merge started remain pending.
+    pub fn finish_merge(&mut self, merge: PendingMerge<T, C, S>) {
+        assert_eq!(
+            self.text.len(),
+            merge.base_len,
+            "the index has been merged after the merge started"
+        );
+        let index = merge.handle.join().expect("failed to build the index");
+        self.text.extend(self.pending.drain(merge.pieces));
+        self.index = index;
+    }
+}
+
+impl<T, C, S, B> LiveIndex<T, C, S, B>
+where
+    T: Character,
+    C: Converter<T>,
+    S: PartialArray,
+{
+    /// Lists the positions of `pattern` in the indexed pieces followed by those in the pending pieces.
+    pub fn locate<K: AsRef<[T]>>(&self, pattern: K) -> Vec<u64> {
+        let pattern = pattern.as_ref();
+        let base = self.text.len() as u64;
+        let mut results = self.index.search_backward(pattern).locate();
+        if pattern.first().is_some_and(|c| c.is_zero()) {
+            // The index locates its leading separator at the start of the first piece,
+            // and its terminator, which stands for the last zero of the text, at the character before it.
+            if self.matches_leading_separator(pattern) {
+                let i = results.iter().position(|&p| p == 0).unwrap();
+                results.swap_remove(i);
+            }
+            let last = base - 1;
+            for p in &mut results {
+                if *p == last - 1 {
+                    *p = last;
+                }
+            }
+        }
+        results.extend(self.pending.locate(pattern).into_iter().map(|p| base + p));
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::suffix_array::SuffixOrderSampler;
+
+    fn sorted(mut v: Vec<u64>) -> Vec<u64> {
+        v.sort();
+        v
+    }
+
+    #[test]
+    fn test_pending_buffer() {
+        let mut buffer = PendingBuffer::new();
+        buffer.push("abra");
+        buffer.push("cadabra");
+        assert_eq!(buffer.pieces_count(), 2);
+        assert_eq!(buffer.locate("bra"), vec![1, 9]);
+        assert_eq!(buffer.locate("a\0"), vec![3, 11]);
+        assert_eq!(buffer.count("\0c"), 1);
+        assert_eq!(buffer.piece_id(4), 0);
+        assert_eq!(buffer.piece_id(5), 1);
+    }
+
+    #[test]
+    fn test_live_index() {
+        let mut index = LiveIndex::new(
+            b"mississippi\0sip".to_vec(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(1),
+        );
+        assert_eq!(index.add_piece("missing"), PieceId::from(2));
+        assert_eq!(index.add_piece("pip"), PieceId::from(3));
+        assert_eq!(index.count("ssi"), 3);
+        let before = sorted(index.locate("ip"));
+        assert_eq!(before, vec![7, 13, 25]);
+
+        let merge = index.merge_in_background();
+        assert_eq!(index.add_piece("tip"), PieceId::from(4));
+        index.finish_merge(merge);
+        assert_eq!(index.index().pieces_count(), 4);
+        assert_eq!(index.pending().pieces_count(), 1);
+        assert_eq!(sorted(index.locate("ip")), vec![7, 13, 25, 29]);
+
+        index.merge();
+        assert_eq!(index.pending().pieces_count(), 0);
+        assert_eq!(index.pieces_count(), 5);
+        assert_eq!(sorted(index.locate("ip")), vec![7, 13, 25, 29]);
+        assert_eq!(index.count("ssi"), 3);
+    }
+
+    #[test]
+    fn test_live_index_leading_zero() {
+        let mut index = LiveIndex::new(
+            b"mississippi\0sip".to_vec(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(1),
+        );
+        index.add_piece("mist");
+        // the index has a separator before the first piece, which is not in the text,
+        // and occurrences spanning the indexed and pending pieces are not reported
+        assert_eq!(index.count("\0mis"), 0);
+        assert!(index.locate("\0mis").is_empty());
+        assert_eq!(index.count("\0"), 3);
+        assert_eq!(sorted(index.locate("\0")), vec![11, 15, 20]);
+        assert_eq!(sorted(index.locate("\0s")), vec![11]);
+        assert_eq!(index.count("\0si"), 1);
+        assert_eq!(sorted(index.locate("i\0")), vec![10]);
+        assert_eq!(index.locate("mis"), vec![0, 16]);
+
+        index.merge();
+        assert_eq!(index.count("\0mis"), 1);
+        assert_eq!(index.locate("\0mis"), vec![15]);
+
+        let index = LiveIndex::new(
+            b"a\0b".to_vec(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(1),
+        );
+        assert_eq!(index.count("\0"), 2);
+        assert_eq!(sorted(index.locate("\0")), vec![1, 3]);
+        assert!(index.locate("\0a").is_empty());
+        assert_eq!(index.locate("\0b"), vec![1]);
+    }
+}
@@ -0,0 +1,441 @@
+use crate::character::Character;
+use crate::succinct::{BitVector, RankSelect};
+use crate::util;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Range;
+
+/// An identifier of a piece of a multi-piece text.
+///
+/// Pieces are numbered from zero in the order they appear in the text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PieceId(u64);
+
+impl From<u64> for PieceId {
+    fn from(id: u64) -> Self {
+        PieceId(id)
+    }
+}
+
+impl From<PieceId> for u64 {
+    fn from(id: PieceId) -> Self {
+        id.0
+    }
+}
+
+/// An index over a text consisting of pieces delimited by zero.
+///
+/// Indexes of a single text implement it as well, regarding the whole text as the piece 0.
+pub trait IndexWithPieces {
+    fn pieces_count(&self) -> u64;
+
+    /// Returns the ID of the piece that contains the suffix of the `i`-th row.
+    ///
+    /// A suffix starting with a separator belongs to the piece following it.
+    fn piece_id(&self, i: u64) -> PieceId;
+}
+
+// External keys of pieces, which stay the same when the IDs of pieces shift after a rebuild.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct PieceKeys {
+    // keys in the order of piece IDs
+    keys: Vec<u64>,
+    // piece IDs in ascending order of keys
+    order: Vec<u64>,
+}
+
+impl PieceKeys {
+    pub(crate) fn new(keys: Vec<u64>) -> Self {
+        let mut order = (0..keys.len() as u64).collect::<Vec<_>>();
+        order.sort_by_key(|&id| keys[id as usize]);
+        assert!(
+            order
+                .windows(2)
+                .all(|w| keys[w[0] as usize] != keys[w[1] as usize]),
+            "keys must be unique"
+        );
+        PieceKeys { keys, order }
+    }
+
+    pub(crate) fn piece_id(&self, key: u64) -> Option<PieceId> {
+        self.order
+            .binary_search_by_key(&key, |&id| self.keys[id as usize])
+            .ok()
+            .map(|j| PieceId(self.order[j]))
+    }
+
+    pub(crate) fn key(&self, id: PieceId) -> Option<u64> {
+        self.keys.get(id.0 as usize).copied()
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        (self.keys.len() + self.order.len()) * std::mem::size_of::<u64>()
+    }
+}
+
+// Lists the indices of `keys`, each less than `count`, grouped by key:
+// `members[offsets[key]..offsets[key + 1]]` are the indices of `key` in ascending order.
+fn members_by_key(keys: &[u64], count: usize) -> (Vec<u64>, Vec<u64>) {
+    let mut offsets = vec![0; count + 1];
+    for &key in keys {
+        offsets[key as usize + 1] += 1;
+    }
+    for key in 0..count {
+        offsets[key + 1] += offsets[key];
+    }
+    let mut next = offsets.clone();
+    let mut members = vec![0; keys.len()];
+    for (j, &key) in keys.iter().enumerate() {
+        members[next[key as usize] as usize] = j as u64;
+        next[key as usize] += 1;
+    }
+    (offsets, members)
+}
+
+// The group of each piece attached by `with_groups`, and the pieces of each group.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct PieceGroups {
+    // the group of each piece
+    groups: Vec<u64>,
+    // the distinct groups in ascending order, so that sparse groups take no space
+    distinct: Vec<u64>,
+    // members[offsets[j]..offsets[j + 1]]: the pieces of the group `distinct[j]` in ascending order
+    offsets: Vec<u64>,
+    members: Vec<u64>,
+}
+
+impl PieceGroups {
+    pub(crate) fn new(groups: Vec<u64>) -> Self {
+        let mut distinct = groups.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+        let ranks = groups
+            .iter()
+            .map(|g| distinct.binary_search(g).unwrap() as u64)
+            .collect::<Vec<_>>();
+        let (offsets, members) = members_by_key(&ranks, distinct.len());
+        PieceGroups {
+            groups,
+            distinct,
+            offsets,
+            members,
+        }
+    }
+
+    pub(crate) fn groups_count(&self) -> u64 {
+        self.distinct.last().map_or(0, |&g| g + 1)
+    }
+
+    pub(crate) fn group(&self, id: PieceId) -> Option<u64> {
+        self.groups.get(id.0 as usize).copied()
+    }
+
+    pub(crate) fn members(&self, group: u64) -> impl Iterator<Item = PieceId> + '_ {
+        let (s, e) = match self.distinct.binary_search(&group) {
+            Ok(j) => (self.offsets[j], self.offsets[j + 1]),
+            Err(_) => (0, 0),
+        };
+        self.members[s as usize..e as usize]
+            .iter()
+            .map(|&id| PieceId(id))
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        (self.groups.len() + self.distinct.len() + self.offsets.len() + self.members.len())
+            * std::mem::size_of::<u64>()
+    }
+}
+
+// The pieces of the original text aliased to each of the distinct pieces stored in a deduplicated index.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct PieceAliases {
+    // the stored piece of each original piece
+    stored: Vec<u64>,
+    // originals[offsets[id]..offsets[id + 1]]: the original pieces of the stored piece `id` in ascending order
+    offsets: Vec<u64>,
+    originals: Vec<u64>,
+}
+
+impl PieceAliases {
+    pub(crate) fn new(stored: Vec<u64>, stored_count: u64) -> Self {
+        let (offsets, originals) = members_by_key(&stored, stored_count as usize);
+        PieceAliases {
+            stored,
+            offsets,
+            originals,
+        }
+    }
+
+    pub(crate) fn original_count(&self) -> u64 {
+        self.stored.len() as u64
+    }
+
+    pub(crate) fn stored(&self, original: PieceId) -> PieceId {
+        PieceId(self.stored[original.0 as usize])
+    }
+
+    pub(crate) fn originals(&self, id: PieceId) -> impl Iterator<Item = PieceId> + '_ {
+        let (s, e) = (self.offsets[id.0 as usize], self.offsets[id.0 as usize + 1]);
+        self.originals[s as usize..e as usize]
+            .iter()
+            .map(|&original| PieceId(original))
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        (self.stored.len() + self.offsets.len() + self.originals.len()) * std::mem::size_of::<u64>()
+    }
+}
+
+/// Hashes the characters of a piece as stored by [`crate::FMIndexMultiPieces::with_piece_hashes`],
+/// for computing the same hashes from a document store.
+///
+/// This is the 64-bit FNV-1a hash of the little-endian bytes of the characters,
+/// each taking `size_of::<T>()` bytes, which is stable across platforms and versions of this crate.
+///
+/// ```
+/// use fm_index::hash_piece;
+///
+/// assert_eq!(hash_piece(b""), 0xcbf2_9ce4_8422_2325);
+/// assert_eq!(hash_piece(b"a"), 0xaf63_dc4c_8601_ec8c);
+/// assert_ne!(hash_piece(b"ab"), hash_piece(b"ba"));
+/// ```
+pub fn hash_piece<T: Character>(piece: &[T]) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    let width = std::mem::size_of::<T>();
+    piece.iter().fold(OFFSET, |h, &c| {
+        c.into().to_le_bytes()[..width]
+            .iter()
+            .fold(h, |h, &b| (h ^ b as u64).wrapping_mul(PRIME))
+    })
+}
+
+// The rows of the suffixes starting with the zeros enclosing the pieces of `SEP p_0 SEP p_1 ... p_{k-1} $`.
+//
+// Since the terminator `$` is ranked together with the separators by LF-mapping, the following hold:
+// - row 0 is the suffix `$`, which follows the last piece;
+// - rows 1..=k are the suffixes starting with the k separators, each preceding a piece,
+//   in the lexicographic order of the pieces;
+// - a search for a pattern starting with zero yields rows in 0..=k, and in 1..=k if the pattern
+//   has more than one character, so a prefix or exact search finds pieces without walking the text.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub(crate) struct PieceBoundary {
+    // IDs of the pieces following the separators of rows 1..=k
+    doc: Vec<u64>,
+}
+
+impl PieceBoundary {
+    pub(crate) fn new(doc: Vec<u64>) -> Self {
+        assert!(!doc.is_empty(), "a multi-piece text has at least one piece");
+        debug_assert!(
+            {
+                let mut ids = doc.clone();
+                ids.sort_unstable();
+                ids.iter().enumerate().all(|(j, &id)| j as u64 == id)
+            },
+            "each piece must follow exactly one separator"
+        );
+        PieceBoundary { doc }
+    }
+
+    pub(crate) fn pieces_count(&self) -> u64 {
+        self.doc.len() as u64
+    }
+
+    pub(crate) fn separator_rows(&self) -> Range<u64> {
+        1..self.pieces_count() + 1
+    }
+
+    // Returns the piece following the separator at the start of row `i`,
+    // or `None` if the row does not start with a separator.
+    pub(crate) fn piece_after(&self, i: u64) -> Option<PieceId> {
+        if self.separator_rows().contains(&i) {
+            Some(PieceId(self.doc[i as usize - 1]))
+        } else {
+            None
+        }
+    }
+
+    // Returns the pieces following the separators of the rows in `rows`, which are clamped to the separator rows.
+    pub(crate) fn pieces_after(&self, rows: Range<u64>) -> impl Iterator<Item = PieceId> + '_ {
+        let end = rows.end.min(self.pieces_count() + 1);
+        let start = rows.start.clamp(1, end.max(1));
+        self.doc[start as usize - 1..end.max(1) as usize - 1]
+            .iter()
+            .map(|&id| PieceId(id))
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        self.doc.len() * std::mem::size_of::<u64>()
+    }
+}
+
+// The pieces of the rows of the suffixes at every `2^level`-th position of the text,
+// so that `piece_id` reaches a sampled row or a separator within `2^level` steps of LF-mapping.
+#[derive(PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct PieceSamples {
+    level: usize,
+    word_size: usize,
+    sampled: BitVector,
+    ids: fid::BitArray,
+}
+
+impl PieceSamples {
+    // Takes the sampled rows and their pieces, in any order, for a text of `n` rows and `k` pieces.
+    pub(crate) fn new(level: usize, n: u64, k: u64, mut samples: Vec<(u64, u64)>) -> Self {
+        samples.sort_unstable();
+        let word_size = util::word_size(k);
+        let mut sampled = BitVector::default();
+        let mut ids = fid::BitArray::with_word_size(word_size, samples.len());
+        let mut rows = samples.iter().peekable();
+        for i in 0..n {
+            let is_sampled = rows.next_if(|&&(row, _)| row == i).is_some();
+            sampled.push(is_sampled);
+        }
+        for (j, &(_, id)) in samples.iter().enumerate() {
+            ids.set_word(j, word_size, id);
+        }
+        PieceSamples {
+            level,
+            word_size,
+            sampled,
+            ids,
+        }
+    }
+
+    pub(crate) fn level(&self) -> usize {
+        self.level
+    }
+
+    pub(crate) fn get(&self, i: u64) -> Option<PieceId> {
+        if self.sampled.get(i) {
+            let j = self.sampled.rank1(i) as usize;
+            Some(PieceId(self.ids.get_word(j, self.word_size)))
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        self.sampled.size() + self.ids.size()
+    }
+}
+
+/// Diagnostics of a text to be indexed by [`crate::FMIndexMultiPieces`], returned by [`validate_multi_piece`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultiPieceDiagnostics {
+    /// The number of pieces, which does not count the empty piece after a trailing separator.
+    pub pieces_count: u64,
+    /// The positions of separators which end an empty piece,
+    /// i.e. a separator at the beginning of the text or following another separator.
+    pub empty_pieces: Vec<u64>,
+    /// Whether the text ends with a separator.
+    pub trailing_separator: bool,
+}
+
+impl MultiPieceDiagnostics {
+    /// Returns true if an index can be built over the text.
+    pub fn is_valid(&self) -> bool {
+        self.pieces_count > 0 && self.empty_pieces.is_empty()
+    }
+}
+
+impl fmt::Display for MultiPieceDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.pieces_count == 0 {
+            return write!(f, "the text has no pieces");
+        }
+        write!(f, "{} pieces", self.pieces_count)?;
+        if !self.empty_pieces.is_empty() {
+            write!(
+                f,
+                "; {} empty pieces ended by separators at {:?}",
+                self.empty_pieces.len(),
+                self.empty_pieces
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks `text`, whose pieces are delimited by zero, before building [`crate::FMIndexMultiPieces`] over it.
+///
+/// ```
+/// use fm_index::validate_multi_piece;
+///
+/// let diagnostics = validate_multi_piece(b"apple\0\0banana\0");
+/// assert!(!diagnostics.is_valid());
+/// assert_eq!(diagnostics.pieces_count, 3);
+/// assert_eq!(diagnostics.empty_pieces, vec![6]);
+/// assert!(diagnostics.trailing_separator);
+/// ```
+pub fn validate_multi_piece<T: Character>(text: &[T]) -> MultiPieceDiagnostics {
+    let mut pieces_count = 0;
+    let mut empty_pieces = vec![];
+    let mut piece_len = 0;
+    for (i, c) in text.iter().enumerate() {
+        if c.is_zero() {
+            if piece_len == 0 {
+                empty_pieces.push(i as u64);
+            }
+            pieces_count += 1;
+            piece_len = 0;
+        } else {
+            piece_len += 1;
+        }
+    }
+    if piece_len > 0 {
+        pieces_count += 1;
+    }
+    MultiPieceDiagnostics {
+        pieces_count,
+        empty_pieces,
+        trailing_separator: text.last().is_some_and(|c| c.is_zero()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_multi_piece() {
+        let cases: Vec<(&str, u64, Vec<u64>, bool)> = vec![
+            ("abc", 1, vec![], false),
+            ("abc\0", 1, vec![], true),
+            ("abc\0de", 2, vec![], false),
+            ("\0abc", 2, vec![0], false),
+            ("a\0\0\0b\0", 4, vec![2, 3], true),
+            ("", 0, vec![], false),
+        ];
+        for (text, pieces_count, empty_pieces, trailing_separator) in cases {
+            let diagnostics = validate_multi_piece(text.as_bytes());
+            assert_eq!(
+                diagnostics,
+                MultiPieceDiagnostics {
+                    pieces_count,
+                    empty_pieces: empty_pieces.clone(),
+                    trailing_separator
+                },
+                "text {:?}",
+                text
+            );
+            assert_eq!(
+                diagnostics.is_valid(),
+                pieces_count > 0 && empty_pieces.is_empty()
+            );
+        }
+        assert_eq!(
+            validate_multi_piece(b"a\0\0b").to_string(),
+            "3 pieces; 1 empty pieces ended by separators at [2]"
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use crate::piece::PieceId;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A position in the whole text, as returned by `locate`.
+///
+/// For a multi-piece text it counts the pieces before and the separators between them;
+/// convert it with [`crate::FMIndexMultiPieces::piece_pos`] to an offset within a piece.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GlobalPos(u64);
+
+impl From<u64> for GlobalPos {
+    fn from(p: u64) -> Self {
+        GlobalPos(p)
+    }
+}
+
+impl From<GlobalPos> for u64 {
+    fn from(p: GlobalPos) -> Self {
+        p.0
+    }
+}
+
+impl fmt::Display for GlobalPos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A position in a piece of a multi-piece text, which is an offset from the start of the piece.
+///
+/// The separator following a piece is at the offset equal to the length of the piece.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PiecePos {
+    pub piece: PieceId,
+    pub offset: u64,
+}
+
+impl PiecePos {
+    pub fn new(piece: PieceId, offset: u64) -> Self {
+        PiecePos { piece, offset }
+    }
+}
+
+impl fmt::Display for PiecePos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", u64::from(self.piece), self.offset)
+    }
+}
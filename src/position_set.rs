@@ -0,0 +1,311 @@
+use std::iter::{self, Copied, FromIterator, Peekable};
+use std::slice;
+
+// The positions of a set in ascending order.
+type Positions<'a> = Copied<slice::Iter<'a, u64>>;
+
+/// A sorted set of positions of occurrences, saved from a search by [`crate::Search::position_set`]
+/// to combine the results of several queries.
+///
+/// Set operations merge the sorted positions lazily, so their results can be consumed
+/// partially or collected into another set.
+/// It may hold any sorted values, such as piece IDs for co-occurrences within the same piece.
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::SuffixOrderSampler;
+/// use fm_index::{BackwardSearchIndex, FMIndex, PositionSet};
+///
+/// let text = b"abcab cab bca".to_vec();
+/// let index = FMIndex::new(text, RangeConverter::new(b' ', b'z'), SuffixOrderSampler::new());
+/// let ab = index.search_backward("ab").position_set();
+/// let ca = index.search_backward("ca").position_set();
+/// assert_eq!(ab.iter().collect::<Vec<_>>(), vec![0, 3, 7]);
+/// assert_eq!(ca.iter().collect::<Vec<_>>(), vec![2, 6, 11]);
+/// // "ab" right after "ca"
+/// let shifted = ca.iter().map(|p| p + 1).collect::<PositionSet>();
+/// assert_eq!(ab.intersection(&shifted).collect::<Vec<_>>(), vec![3, 7]);
+/// assert_eq!(ab.difference(&shifted).collect::<Vec<_>>(), vec![0]);
+/// // "ab" within 1 character of "ca"
+/// assert_eq!(ab.near(&ca, 1).collect::<Vec<_>>(), vec![3, 7]);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PositionSet {
+    // sorted and deduplicated
+    positions: Vec<u64>,
+}
+
+impl PositionSet {
+    pub fn new() -> Self {
+        PositionSet::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn contains(&self, p: u64) -> bool {
+        self.positions.binary_search(&p).is_ok()
+    }
+
+    /// Iterates over the positions in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.positions.iter().copied()
+    }
+
+    pub fn as_slice(&self) -> &[u64] {
+        &self.positions
+    }
+
+    pub fn union<'a>(&'a self, other: &'a PositionSet) -> SetMerge<Positions<'a>, Positions<'a>> {
+        SetMerge::new(self.positions(), other.positions(), SetOp::Union)
+    }
+
+    pub fn intersection<'a>(
+        &'a self,
+        other: &'a PositionSet,
+    ) -> SetMerge<Positions<'a>, Positions<'a>> {
+        SetMerge::new(self.positions(), other.positions(), SetOp::Intersection)
+    }
+
+    /// Iterates over the positions in this set but not in `other`.
+    pub fn difference<'a>(
+        &'a self,
+        other: &'a PositionSet,
+    ) -> SetMerge<Positions<'a>, Positions<'a>> {
+        SetMerge::new(self.positions(), other.positions(), SetOp::Difference)
+    }
+
+    fn positions(&self) -> Positions<'_> {
+        self.positions.iter().copied()
+    }
+
+    /// Iterates over the positions in this set within distance `d` of some position in `other`.
+    ///
+    /// Each position takes a binary search on `other`.
+    pub fn near<'a>(&'a self, other: &'a PositionSet, d: u64) -> impl Iterator<Item = u64> + 'a {
+        self.iter().filter(move |&p| {
+            let j = other
+                .positions
+                .partition_point(|&q| q < p.saturating_sub(d));
+            other
+                .positions
+                .get(j)
+                .is_some_and(|&q| q <= p.saturating_add(d))
+        })
+    }
+}
+
+impl FromIterator<u64> for PositionSet {
+    fn from_iter<I: IntoIterator<Item = u64>>(iter: I) -> Self {
+        PositionSet::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl From<Vec<u64>> for PositionSet {
+    fn from(mut positions: Vec<u64>) -> Self {
+        positions.sort_unstable();
+        positions.dedup();
+        PositionSet { positions }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SetOp {
+    Union,
+    Intersection,
+    Difference,
+}
+
+/// A lazy merge of two [`PositionSet`]s, or iterators over positions in strictly ascending order,
+/// yielding positions in ascending order.
+pub struct SetMerge<A, B>
+where
+    A: Iterator<Item = u64>,
+    B: Iterator<Item = u64>,
+{
+    a: Peekable<A>,
+    b: Peekable<B>,
+    op: SetOp,
+}
+
+impl<A, B> SetMerge<A, B>
+where
+    A: Iterator<Item = u64>,
+    B: Iterator<Item = u64>,
+{
+    fn new(a: A, b: B, op: SetOp) -> Self {
+        SetMerge {
+            a: a.peekable(),
+            b: b.peekable(),
+            op,
+        }
+    }
+}
+
+impl<A, B> Iterator for SetMerge<A, B>
+where
+    A: Iterator<Item = u64>,
+    B: Iterator<Item = u64>,
+{
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        loop {
+            let (a, b) = (self.a.peek().copied(), self.b.peek().copied());
+            match (a, b, self.op) {
+                (None, None, _) => return None,
+                (Some(_), None, SetOp::Union) | (Some(_), None, SetOp::Difference) => {
+                    return self.a.next()
+                }
+                (None, Some(_), SetOp::Union) => return self.b.next(),
+                (_, None, _) | (None, _, _) => return None,
+                (Some(x), Some(y), op) if x < y => {
+                    self.a.next();
+                    if op != SetOp::Intersection {
+                        return Some(x);
+                    }
+                }
+                (Some(x), Some(y), op) if x > y => {
+                    self.b.next();
+                    if op == SetOp::Union {
+                        return Some(y);
+                    }
+                }
+                (Some(x), Some(_), op) => {
+                    self.a.next();
+                    self.b.next();
+                    if op != SetOp::Difference {
+                        return Some(x);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Merges iterators over positions in strictly ascending order into one in ascending order without duplicates,
+/// e.g. the results of several related searches by [`crate::Search::iter_positions_sorted`].
+///
+/// Positions are merged lazily by a balanced tree of the unions of [`PositionSet::union`],
+/// taking _O(log k)_ time per position for _k_ iterators.
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::SuffixOrderSampler;
+/// use fm_index::{merge_unique, BackwardSearchIndex, FMIndex};
+///
+/// let text = b"abcab cab bca".to_vec();
+/// let index = FMIndex::new(text, RangeConverter::new(b' ', b'z'), SuffixOrderSampler::new());
+/// // "ab" and its extensions share the positions of their occurrences
+/// let searches = vec![index.search_backward("ab"), index.search_backward("abc"), index.search_backward("ab ")];
+/// let positions = merge_unique(searches.iter().map(|s| s.iter_positions_sorted()));
+/// assert_eq!(positions.collect::<Vec<_>>(), vec![0, 3, 7]);
+/// ```
+pub fn merge_unique<'a, I, J>(iters: I) -> MergeUnique<'a>
+where
+    I: IntoIterator<Item = J>,
+    J: Iterator<Item = u64> + 'a,
+{
+    MergeUnique {
+        merged: union_all(iters.into_iter().collect()),
+    }
+}
+
+// Merges `iters` by unions of its halves.
+fn union_all<'a, J>(mut iters: Vec<J>) -> Box<dyn Iterator<Item = u64> + 'a>
+where
+    J: Iterator<Item = u64> + 'a,
+{
+    if iters.len() <= 1 {
+        return match iters.pop() {
+            Some(iter) => Box::new(iter),
+            None => Box::new(iter::empty()),
+        };
+    }
+    let right = iters.split_off(iters.len() / 2);
+    Box::new(SetMerge::new(
+        union_all(iters),
+        union_all(right),
+        SetOp::Union,
+    ))
+}
+
+/// A lazy merge of sorted iterators over positions, returned by [`merge_unique`].
+pub struct MergeUnique<'a> {
+    merged: Box<dyn Iterator<Item = u64> + 'a>,
+}
+
+impl Iterator for MergeUnique<'_> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        self.merged.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_set_operations() {
+        let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+        for _ in 0..20 {
+            let a = (0..rng.gen_range(0, 30))
+                .map(|_| rng.gen_range(0, 50))
+                .collect::<BTreeSet<u64>>();
+            let b = (0..rng.gen_range(0, 30))
+                .map(|_| rng.gen_range(0, 50))
+                .collect::<BTreeSet<u64>>();
+            let x = a.iter().copied().collect::<PositionSet>();
+            let y = b.iter().copied().collect::<PositionSet>();
+            assert_eq!(x.len(), a.len());
+            assert_eq!(
+                x.union(&y).collect::<Vec<_>>(),
+                a.union(&b).copied().collect::<Vec<_>>()
+            );
+            assert_eq!(
+                x.intersection(&y).collect::<Vec<_>>(),
+                a.intersection(&b).copied().collect::<Vec<_>>()
+            );
+            assert_eq!(
+                x.difference(&y).collect::<Vec<_>>(),
+                a.difference(&b).copied().collect::<Vec<_>>()
+            );
+            for d in 0..3 {
+                let expected = a
+                    .iter()
+                    .copied()
+                    .filter(|&p| b.iter().any(|&q| p.max(q) - p.min(q) <= d))
+                    .collect::<Vec<_>>();
+                assert_eq!(x.near(&y, d).collect::<Vec<_>>(), expected);
+            }
+        }
+        for _ in 0..20 {
+            let sets = (0..rng.gen_range(0, 5))
+                .map(|_| {
+                    (0..rng.gen_range(0, 30))
+                        .map(|_| rng.gen_range(0, 50))
+                        .collect::<BTreeSet<u64>>()
+                })
+                .collect::<Vec<_>>();
+            let expected = sets.iter().flatten().copied().collect::<BTreeSet<_>>();
+            assert_eq!(
+                merge_unique(sets.iter().map(|set| set.iter().copied())).collect::<Vec<_>>(),
+                expected.into_iter().collect::<Vec<_>>()
+            );
+        }
+        let set = PositionSet::from(vec![5, 1, 5, 3]);
+        assert_eq!(set.as_slice(), &[1, 3, 5]);
+        assert!(set.contains(3) && !set.contains(4));
+        assert!(PositionSet::new().is_empty());
+    }
+}
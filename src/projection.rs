@@ -0,0 +1,196 @@
+use crate::character::Character;
+use crate::converter::Converter;
+use crate::search::{BackwardSearchIndex, Search};
+use crate::succinct::{BitVector, RankSelect};
+use crate::suffix_array::{ArraySampler, IndexWithSA, PartialArray};
+use crate::FMIndex;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A mapping of characters into classes for [`ProjectedIndex`].
+///
+/// Characters mapped to `None` are ignored, and characters mapped to the same class match each other.
+/// It is implemented for closures returning an `Option`.
+pub trait Projection<T> {
+    fn project(&self, c: T) -> Option<T>;
+}
+
+impl<T, F> Projection<T> for F
+where
+    F: Fn(T) -> Option<T>,
+{
+    fn project(&self, c: T) -> Option<T> {
+        self(c)
+    }
+}
+
+/// An index over a text projected by a character-class mapping declared at construction,
+/// e.g. to search ignoring punctuation or case.
+///
+/// Patterns are projected in the same way, so an occurrence may contain any number of ignored characters
+/// between those of the pattern, and the text has no ignored characters to branch over at query time.
+/// A bit vector of the kept characters maps positions in the projected text back to the original text,
+/// taking _n_ bits beside the index of the projected text.
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::SuffixOrderSampler;
+/// use fm_index::ProjectedIndex;
+///
+/// let text = b"Don't stop; don't!".to_vec();
+/// let projection = |c: u8| c.is_ascii_alphabetic().then(|| c.to_ascii_lowercase());
+/// let index = ProjectedIndex::new(text, projection, RangeConverter::new(b'a', b'z'), SuffixOrderSampler::new());
+/// assert_eq!(index.search("dont").count(), 2);
+/// assert_eq!(index.search("t-s").count(), 1);
+/// let mut spans = index.locate("DONT");
+/// spans.sort();
+/// assert_eq!(spans, vec![(0, 5), (12, 17)]);
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ProjectedIndex<T, C, S, P> {
+    index: FMIndex<T, C, S>,
+    // whether each character of the original text is kept in the projected text
+    kept: BitVector,
+    projection: P,
+}
+
+impl<T, C, S, P> ProjectedIndex<T, C, S, P>
+where
+    T: Character,
+    C: Converter<T>,
+    P: Projection<T>,
+{
+    /// Builds an index over `text` projected by `projection`, whose classes must be in the range of `converter`.
+    ///
+    /// `text` may or may not end with zero, which is never projected.
+    pub fn new<B: ArraySampler<S>>(text: Vec<T>, projection: P, converter: C, sampler: B) -> Self {
+        let mut kept = BitVector::default();
+        let mut projected = Vec::with_capacity(text.len() + 1);
+        for &c in text.iter().take_while(|c| !c.is_zero()) {
+            let class = projection.project(c);
+            kept.push(class.is_some());
+            if let Some(class) = class {
+                assert!(!class.is_zero(), "characters must not be projected to zero");
+                projected.push(class);
+            }
+        }
+        projected.push(T::zero());
+        ProjectedIndex {
+            index: FMIndex::new(projected, converter, sampler),
+            kept,
+            projection,
+        }
+    }
+
+    /// Searches for the projection of `pattern`, ignoring its characters which the projection ignores.
+    pub fn search<K: AsRef<[T]>>(&self, pattern: K) -> Search<'_, FMIndex<T, C, S>> {
+        let pattern = pattern
+            .as_ref()
+            .iter()
+            .filter_map(|&c| self.projection.project(c))
+            .collect::<Vec<_>>();
+        self.index.search_backward(pattern)
+    }
+
+    /// Returns the index over the projected text, whose positions are converted by [`ProjectedIndex::to_original`].
+    pub fn index(&self) -> &FMIndex<T, C, S> {
+        &self.index
+    }
+
+    /// Converts a position in the projected text into the position of its character in the original text.
+    ///
+    /// The end of the projected text is converted into the end of the original text.
+    pub fn to_original(&self, p: u64) -> u64 {
+        if p < self.index.len() - 1 {
+            self.kept.select1(p)
+        } else {
+            self.kept.len()
+        }
+    }
+}
+
+impl<T, C, S, P> ProjectedIndex<T, C, S, P>
+where
+    T: Character,
+    C: Converter<T>,
+    S: PartialArray,
+    P: Projection<T>,
+{
+    /// Lists the occurrences of the projection of `pattern` as ranges `[start, end)` of the original text,
+    /// which start and end with kept characters and may contain ignored ones.
+    pub fn locate<K: AsRef<[T]>>(&self, pattern: K) -> Vec<(u64, u64)> {
+        let search = self.search(pattern);
+        let m = search.pattern_len() as u64;
+        search
+            .iter_matches()
+            .map(|o| {
+                let p = self.index.get_sa(o.match_id());
+                let start = self.to_original(p);
+                if m == 0 {
+                    (start, start)
+                } else {
+                    (start, self.to_original(p + m - 1) + 1)
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the size of the index, excluding the heap memory of the projection.
+    pub fn size(&self) -> usize {
+        std::mem::size_of::<Self>() - std::mem::size_of::<FMIndex<T, C, S>>()
+            + self.index.size()
+            + self.kept.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::suffix_array::SuffixOrderSampler;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn project(c: u8) -> Option<u8> {
+        match c {
+            b'-' | b' ' => None,
+            b'A'..=b'C' => Some(c.to_ascii_lowercase()),
+            _ => Some(c),
+        }
+    }
+
+    #[test]
+    fn test_projected_index() {
+        let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+        let alphabet = b"abcAB- ";
+        let text = (0..200)
+            .map(|_| alphabet[rng.gen_range(0, alphabet.len())])
+            .collect::<Vec<_>>();
+        let index = ProjectedIndex::new(
+            text.clone(),
+            project,
+            RangeConverter::new(b'a', b'c'),
+            SuffixOrderSampler::new().level(2),
+        );
+        let positions = (0..text.len())
+            .filter(|&p| project(text[p]).is_some())
+            .collect::<Vec<_>>();
+        let projected = positions
+            .iter()
+            .map(|&p| project(text[p]).unwrap())
+            .collect::<Vec<_>>();
+        for pattern in &["a", "ab", "a-B c", "bca", "cc", "x"] {
+            let q = pattern.bytes().filter_map(project).collect::<Vec<_>>();
+            let mut expected = (0..projected.len())
+                .filter(|&i| projected[i..].starts_with(&q))
+                .map(|i| (positions[i] as u64, positions[i + q.len() - 1] as u64 + 1))
+                .collect::<Vec<_>>();
+            let mut res = index.locate(pattern);
+            res.sort();
+            expected.sort();
+            assert_eq!(res, expected, "pattern {:?}", pattern);
+        }
+        assert_eq!(index.to_original(projected.len() as u64), text.len() as u64);
+    }
+}
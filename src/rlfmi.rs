@@ -1,23 +1,37 @@
+use crate::bucket::{BucketArray, EliasFanoBucketArray};
 use crate::character::Character;
 use crate::converter::{Converter, IndexWithConverter};
+use crate::diff::IndexDiff;
+use crate::filter::{self, QGramFilter};
+use crate::piece::{IndexWithPieces, PieceId};
 use crate::sais;
-use crate::suffix_array::{ArraySampler, IndexWithSA, PartialArray};
+use crate::search::SaInterval;
+use crate::stats::{IndexStats, SuffixArrayStats};
+use crate::succinct::{BitVector, RankSelect};
+use crate::suffix_array::{
+    self, ArraySampler, IndexWithExtrema, IndexWithISA, IndexWithSA, InvalidText, PartialArray,
+    PartialExtremaArray, PartialInverseArray, Scratch,
+};
 use crate::util;
 use crate::wavelet_matrix::WaveletMatrix;
 use crate::{BackwardIterableIndex, ForwardIterableIndex};
 
-use fid::FID;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
-pub struct RLFMIndex<T, C, S> {
+#[derive(PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RLFMIndex<T, C, S, B = Vec<u64>> {
     converter: C,
     suffix_array: S,
     s: WaveletMatrix,
-    b: fid::BitVector,
-    bp: fid::BitVector,
-    cs: Vec<u64>,
+    b: BitVector,
+    bp: BitVector,
+    cs: B,
     len: u64,
+    // q-grams of the text added by `with_qgram_filter`
+    #[cfg_attr(feature = "serde", serde(default))]
+    qgram_filter: Option<QGramFilter>,
     _t: std::marker::PhantomData<T>,
 }
 
@@ -30,18 +44,68 @@ where
         if !text[text.len() - 1].is_zero() {
             text.push(T::zero());
         }
+        let sa = sais::sais(&text, &converter);
+        Self::from_suffix_array(text, sa, converter, sampler)
+    }
+
+    /// Builds an index like [`RLFMIndex::new`], or returns an error if `text` cannot be indexed.
+    pub fn try_new<B: ArraySampler<S>>(
+        mut text: Vec<T>,
+        converter: C,
+        sampler: B,
+    ) -> Result<Self, InvalidText> {
+        if text.last().is_some_and(|c| !c.is_zero()) {
+            text.push(T::zero());
+        }
+        let sa = suffix_array::try_build_suffix_array(&text, &converter)?;
+        Ok(Self::from_suffix_array(text, sa, converter, sampler))
+    }
+
+    /// Builds an index like [`RLFMIndex::new`], computing the suffix array in the buffer of `scratch`.
+    pub fn new_with_scratch<B: ArraySampler<S>>(
+        mut text: Vec<T>,
+        converter: C,
+        sampler: B,
+        scratch: &mut Scratch,
+    ) -> Self {
+        if !text[text.len() - 1].is_zero() {
+            text.push(T::zero());
+        }
+        let sa = scratch.build(&text, &converter);
+        Self::build(&text, sa, converter, sampler)
+    }
+
+    /// Builds an index from `text` and its suffix array computed elsewhere, skipping SA-IS.
+    ///
+    /// `text` must end with zero, and `sa` must be the suffix array of the whole `text` including it.
+    /// The suffix array is not verified; use [`crate::suffix_array::validate`] to check it beforehand.
+    pub fn from_suffix_array<B: ArraySampler<S>>(
+        text: Vec<T>,
+        sa: Vec<u64>,
+        converter: C,
+        sampler: B,
+    ) -> Self {
+        Self::build(&text, &sa, converter, sampler)
+    }
+
+    fn build<B: ArraySampler<S>>(text: &[T], sa: &[u64], converter: C, sampler: B) -> Self {
+        assert!(
+            text.last().is_some_and(|c| c.is_zero()),
+            "text must end with zero"
+        );
+        assert_eq!(sa.len(), text.len(), "sa must be as long as text");
+        suffix_array::assert_text_len(text.len() as u64);
         let n = text.len();
         let m = converter.len();
-        let sa = sais::sais(&text, &converter);
 
         let mut c0 = T::zero();
         // sequence of run heads
         let mut s = Vec::new();
         // sequence of run lengths
         // run length `l` is encoded as 10^{l-1}
-        let mut b = fid::BitVector::new();
+        let mut b = BitVector::default();
         let mut runs_by_char: Vec<Vec<usize>> = vec![vec![]; m as usize];
-        for &k in &sa {
+        for &k in sa {
             let k = k as usize;
             let c = converter.convert(if k > 0 { text[k - 1] } else { text[n - 1] });
             // We do not allow consecutive occurrences of zeroes,
@@ -60,7 +124,7 @@ where
             c0 = c;
         }
         let s = WaveletMatrix::new_with_size(s, util::log2(m - 1) + 1);
-        let mut bp = fid::BitVector::new();
+        let mut bp = BitVector::default();
         let mut cs = vec![0u64; m as usize];
         let mut c = 0;
         for (rs, ci) in runs_by_char.into_iter().zip(&mut cs) {
@@ -76,16 +140,37 @@ where
 
         RLFMIndex {
             converter,
-            suffix_array: sampler.sample(sa),
+            suffix_array: sampler.sample_slice(sa),
             s,
             b,
             bp,
             cs,
             len: n as u64,
+            qgram_filter: None,
             _t: std::marker::PhantomData::<T>,
         }
     }
 
+    /// Replaces the array of run bucket positions with its Elias-Fano representation.
+    ///
+    /// This reduces the _O(σ)_ term of the index size for large alphabets
+    /// at the cost of slower LF-mapping.
+    pub fn compress_cs(self) -> RLFMIndex<T, C, S, EliasFanoBucketArray> {
+        RLFMIndex {
+            cs: EliasFanoBucketArray::new(&self.cs),
+            converter: self.converter,
+            suffix_array: self.suffix_array,
+            s: self.s,
+            b: self.b,
+            bp: self.bp,
+            len: self.len,
+            qgram_filter: self.qgram_filter,
+            _t: self._t,
+        }
+    }
+}
+
+impl<T, C, S, B> RLFMIndex<T, C, S, B> {
     pub fn runs(&self) -> u64 {
         self.s.len()
     }
@@ -94,39 +179,142 @@ where
         self.len
     }
 
+    /// Returns true if the text consists of the terminator only.
     pub fn is_empty(&self) -> bool {
-        self.len == 0
+        self.len <= 1
     }
 }
 
-impl<T, C> RLFMIndex<T, C, ()> {
+impl<T, C, B> RLFMIndex<T, C, (), B>
+where
+    B: BucketArray,
+{
     pub fn size(&self) -> usize {
         std::mem::size_of::<Self>()
             + self.s.size()
             + self.b.size()
             + self.bp.size()
-            + self.cs.len() * std::mem::size_of::<Vec<u64>>()
+            + self.cs.size()
+            + self.qgram_filter.as_ref().map_or(0, |filter| filter.size())
     }
 }
 
-impl<T, C, S> RLFMIndex<T, C, S>
+impl<T, C, S, B> RLFMIndex<T, C, S, B>
 where
     S: PartialArray,
+    B: BucketArray,
 {
     pub fn size(&self) -> usize {
         std::mem::size_of::<Self>()
             + self.s.size()
             + self.b.size()
             + self.bp.size()
-            + self.cs.len() * std::mem::size_of::<Vec<u64>>()
+            + self.cs.size()
+            + self.qgram_filter.as_ref().map_or(0, |filter| filter.size())
             + self.suffix_array.size()
     }
 }
 
-impl<T, C, S> BackwardIterableIndex for RLFMIndex<T, C, S>
+impl<T, C, S, B> RLFMIndex<T, C, S, B>
+where
+    T: Character,
+    C: Converter<T>,
+    B: BucketArray,
+{
+    fn collect_stats<X: SuffixArrayStats>(&self, suffix_array: &X) -> IndexStats {
+        let mut components = vec![
+            ("bwt", self.s.size()),
+            ("runs", self.b.size() + self.bp.size()),
+            ("cs", self.cs.size()),
+        ];
+        if let Some(filter) = &self.qgram_filter {
+            components.push(("qgram_filter", filter.size()));
+        }
+        IndexStats::new(self.len, self.converter.len(), 1, self.runs()).with_components(
+            std::mem::size_of::<Self>(),
+            components,
+            suffix_array,
+        )
+    }
+}
+
+impl<T, C, B> RLFMIndex<T, C, (), B>
+where
+    T: Character,
+    C: Converter<T>,
+    B: BucketArray,
+{
+    /// Summarizes the index, counting the runs of the BWT.
+    pub fn stats(&self) -> IndexStats {
+        self.collect_stats(&())
+    }
+}
+
+impl<T, C, S, B> RLFMIndex<T, C, S, B>
+where
+    T: Character,
+    C: Converter<T>,
+    B: BucketArray,
+    S: PartialArray,
+{
+    /// Summarizes the index, counting the runs of the BWT.
+    pub fn stats(&self) -> IndexStats {
+        self.collect_stats(&self.suffix_array)
+    }
+}
+
+impl<T, C, S, B> RLFMIndex<T, C, S, B>
+where
+    T: Character,
+    C: Converter<T> + PartialEq,
+    S: PartialEq,
+    B: BucketArray + PartialEq,
+{
+    /// Compares each component of this index with those of `other`.
+    pub fn diff(&self, other: &Self) -> IndexDiff {
+        let mut diff = IndexDiff::default();
+        diff.check_bwt(
+            self,
+            other,
+            self.len != other.len || self.s != other.s || self.b != other.b || self.bp != other.bp,
+        );
+        diff.check("cs", self.cs != other.cs);
+        diff.check("converter", self.converter != other.converter);
+        diff.check("suffix_array", self.suffix_array != other.suffix_array);
+        diff.check("qgram_filter", self.qgram_filter != other.qgram_filter);
+        diff
+    }
+}
+
+impl<T, C, S, B> RLFMIndex<T, C, S, B>
+where
+    T: Character,
+    C: Converter<T>,
+    B: BucketArray,
+{
+    /// Builds a [`QGramFilter`] of the `q`-grams of the text with a bitmap of `bits` bits and stores it with the index,
+    /// so that backward searches for patterns containing a q-gram absent from the text return nothing without rank queries.
+    ///
+    /// The filter hashes the characters converted by the converter of the index, as they are stored,
+    /// so it never rejects a pattern that the index matches.
+    /// The text is read by walking LF-mapping over the whole index once, which takes _n_ words of temporary memory.
+    pub fn with_qgram_filter(mut self, q: usize, bits: usize) -> Self {
+        let codes = filter::cyclic_text_codes(&self, |c| c.into());
+        self.qgram_filter = Some(QGramFilter::from_cyclic_codes(&codes, q, bits));
+        self
+    }
+
+    /// Returns the filter added by [`RLFMIndex::with_qgram_filter`], if any.
+    pub fn qgram_filter(&self) -> Option<&QGramFilter> {
+        self.qgram_filter.as_ref()
+    }
+}
+
+impl<T, C, S, B> BackwardIterableIndex for RLFMIndex<T, C, S, B>
 where
     T: Character,
     C: Converter<T>,
+    B: BucketArray,
 {
     type T = T;
 
@@ -143,48 +331,49 @@ where
         let c = self.get_l(i);
         let j = self.b.rank1(i);
         let nr = self.s.rank(c, j);
-        self.bp.select1(self.cs[c.into() as usize] + nr) + i - self.b.select1(j)
+        self.bp.select1(self.cs.get(c.into()) + nr) + i - self.b.select1(j)
     }
 
     fn lf_map2(&self, c: T, i: u64) -> u64 {
+        if !self.converter.contains(c) {
+            return 0;
+        }
         let c = self.converter.convert(c);
         let j = self.b.rank1(i);
         let nr = self.s.rank(c, j);
         if self.get_l(i) != c {
-            self.bp.select1(self.cs[c.into() as usize] + nr)
+            self.bp.select1(self.cs.get(c.into()) + nr)
         } else {
-            self.bp.select1(self.cs[c.into() as usize] + nr) + i - self.b.select1(j)
+            self.bp.select1(self.cs.get(c.into()) + nr) + i - self.b.select1(j)
+        }
+    }
+
+    fn may_contain(&self, pattern: &[T]) -> bool {
+        match &self.qgram_filter {
+            Some(filter) => filter.may_contain(pattern, &self.converter),
+            None => true,
         }
     }
 }
 
-impl<T, C, S> ForwardIterableIndex for RLFMIndex<T, C, S>
+impl<T, C, S, B> ForwardIterableIndex for RLFMIndex<T, C, S, B>
 where
     T: Character,
     C: Converter<T>,
+    B: BucketArray,
 {
     type T = T;
 
     fn get_f(&self, i: u64) -> Self::T {
-        let mut s = 0;
-        let mut e = self.cs.len() as usize;
         let r = self.bp.rank1(i + 1) - 1;
-        while e - s > 1 {
-            let m = s + (e - s) / 2;
-            if self.cs[m] <= r {
-                s = m;
-            } else {
-                e = m;
-            }
-        }
-        T::from_u64(s as u64)
+        T::from_u64(self.cs.search(r))
     }
 
     fn fl_map(&self, i: u64) -> u64 {
         let c = self.get_f(i);
         let j = self.bp.rank1(i + 1) - 1;
         let p = self.bp.select1(j);
-        let m = self.s.select(c, j - self.cs[c.into() as usize]);
+        let m = self.s.select(c, j - self.cs.get(c.into()));
         let n = self.b.select1(m);
         n + i - p
     }
@@ -193,7 +382,7 @@ where
         let c = self.converter.convert(c);
         let j = self.bp.rank1(i + 1) - 1;
         let p = self.bp.select1(j);
-        let m = self.s.select(c, j - self.cs[c.into() as usize]);
+        let m = self.s.select(c, j - self.cs.get(c.into()));
         let n = self.b.select1(m);
         n + i - p
     }
@@ -203,29 +392,64 @@ where
     }
 }
 
-impl<T, C, S> IndexWithSA for RLFMIndex<T, C, S>
+impl<T, C, S, B> IndexWithSA for RLFMIndex<T, C, S, B>
 where
     T: Character,
     C: Converter<T>,
     S: PartialArray,
+    B: BucketArray,
 {
-    fn get_sa(&self, mut i: u64) -> u64 {
-        let mut steps = 0;
-        loop {
-            match self.suffix_array.get(i) {
-                Some(sa) => {
-                    return (sa + steps) % self.len();
-                }
-                None => {
-                    i = self.lf_map(i);
-                    steps += 1;
-                }
-            }
-        }
+    fn get_sa(&self, i: u64) -> u64 {
+        suffix_array::resolve_sa(
+            i,
+            self.len(),
+            None,
+            |k| self.suffix_array.get(k),
+            |k| self.lf_map(k),
+        )
+        .unwrap()
+    }
+
+    fn get_sa_with_budget(&self, i: u64, budget: &mut u64) -> Option<u64> {
+        suffix_array::resolve_sa(
+            i,
+            self.len(),
+            Some(budget),
+            |k| self.suffix_array.get(k),
+            |k| self.lf_map(k),
+        )
+    }
+}
+
+impl<T, C, S, B> IndexWithISA for RLFMIndex<T, C, S, B>
+where
+    T: Character,
+    C: Converter<T>,
+    S: PartialInverseArray,
+    B: BucketArray,
+{
+    fn get_isa(&self, p: u64) -> u64 {
+        suffix_array::get_isa(self, &self.suffix_array, p)
     }
 }
 
-impl<T, C, S> IndexWithConverter<T> for RLFMIndex<T, C, S>
+impl<T, C, S, B> IndexWithExtrema for RLFMIndex<T, C, S, B>
+where
+    T: Character,
+    C: Converter<T>,
+    S: PartialExtremaArray,
+    B: BucketArray,
+{
+    fn min_sa(&self, interval: SaInterval) -> u64 {
+        suffix_array::get_extrema(self, &self.suffix_array, interval).0
+    }
+
+    fn max_sa(&self, interval: SaInterval) -> u64 {
+        suffix_array::get_extrema(self, &self.suffix_array, interval).1
+    }
+}
+
+impl<T, C, S, B> IndexWithConverter<T> for RLFMIndex<T, C, S, B>
 where
     C: Converter<T>,
 {
@@ -236,6 +460,16 @@ where
     }
 }
 
+impl<T, C, S, B> IndexWithPieces for RLFMIndex<T, C, S, B> {
+    fn pieces_count(&self) -> u64 {
+        1
+    }
+
+    fn piece_id(&self, _i: u64) -> PieceId {
+        PieceId::from(0)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -243,8 +477,6 @@ mod tests {
     use crate::search::BackwardSearchIndex;
     use crate::suffix_array::{NullSampler, SuffixOrderSampler};
 
-    use fid::FID;
-
     #[test]
     fn test_count() {
         let text = "mississippi".to_string().into_bytes();
@@ -363,6 +595,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_from_suffix_array() {
+        let text = "mississippi\0".to_string().into_bytes();
+        let converter = RangeConverter::new(b'a', b'z');
+        let sa = sais::sais(&text, &converter);
+        let index =
+            RLFMIndex::from_suffix_array(text, sa, converter, SuffixOrderSampler::new().level(1));
+        let mut res = index.search_backward("issi").locate();
+        res.sort();
+        assert_eq!(res, vec![1, 4]);
+        assert_eq!(index.search_backward("ppi").locate(), vec![8]);
+    }
+
+    #[test]
+    fn test_compress_cs() {
+        let text = "mississippi".to_string().into_bytes();
+        let plain = RLFMIndex::new(
+            text.clone(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(1),
+        );
+        let compressed = RLFMIndex::new(
+            text,
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(1),
+        )
+        .compress_cs();
+        for pattern in &["m", "i", "iss", "ppi", "z"] {
+            let expected = plain.search_backward(pattern);
+            let actual = compressed.search_backward(pattern);
+            assert_eq!(actual.get_range(), expected.get_range());
+            assert_eq!(actual.locate(), expected.locate());
+        }
+        for i in 0..plain.len() {
+            assert_eq!(compressed.get_f(i), plain.get_f(i));
+            assert_eq!(compressed.fl_map(i), plain.fl_map(i));
+        }
+    }
+
     #[test]
     fn test_get_l() {
         let text = "mississippi".to_string().into_bytes();
@@ -371,7 +642,7 @@ mod tests {
 
         for (i, a) in ans.into_iter().enumerate() {
             let l = rlfmi.get_l(i as u64);
-            assert_eq!(rlfmi.converter.convert_inv(l as u8), a);
+            assert_eq!(rlfmi.converter.convert_inv(l), a);
         }
     }
 
@@ -429,7 +700,7 @@ mod tests {
 
         for (s, r) in ans {
             let search = rlfmi.search_backward(s);
-            assert_eq!(search.get_range(), r);
+            assert_eq!(search.get_range(), SaInterval::from(r));
         }
     }
 
@@ -462,7 +733,7 @@ mod tests {
 
         for (i, a) in ans.into_iter().enumerate() {
             let f = rlfmi.get_f(i as u64);
-            assert_eq!(rlfmi.converter.convert_inv(f as u8), a);
+            assert_eq!(rlfmi.converter.convert_inv(f), a);
         }
     }
 
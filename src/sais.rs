@@ -3,20 +3,20 @@
 //!    IEEE Transactions on Computers, 60(10), 1471–1484. https://doi.org/10.1109/tc.2010.188
 
 use fid::BitArray;
-use std::fmt::Debug;
+use num_traits::Zero;
 
 use crate::converter::{Converter, IdConverter};
+use crate::text::Text;
+use crate::util::strict_assert;
 
-pub fn count_chars<T, C, K>(text: K, converter: &C) -> Vec<u64>
+pub fn count_chars<X, C>(text: &X, converter: &C) -> Vec<u64>
 where
-    T: Copy + Clone + Into<u64>,
-    K: AsRef<[T]>,
-    C: Converter<T>,
+    X: Text + ?Sized,
+    C: Converter<X::T>,
 {
-    let text = text.as_ref();
     let mut occs = vec![0; converter.len() as usize];
-    for &c in text.iter() {
-        let c = converter.convert(c).into() as usize;
+    for i in 0..text.len() {
+        let c = converter.convert(text.get(i)).into() as usize;
         occs[c] += 1;
     }
 
@@ -43,12 +43,10 @@ pub fn get_bucket_end_pos(occs: &[u64]) -> Vec<u64> {
     buckets
 }
 
-fn get_types<T, K>(text: K) -> (BitArray, Vec<usize>)
+fn get_types<X>(text: &X) -> (BitArray, Vec<usize>)
 where
-    T: Copy + Clone + Ord,
-    K: AsRef<[T]>,
+    X: Text + ?Sized,
 {
-    let text = text.as_ref();
     let n = text.len();
     // true => S-Type, false => L-Type
     let mut types = BitArray::new(n);
@@ -63,7 +61,8 @@ where
     let mut lms = vec![n - 1];
     let mut prev_is_s_type = false;
     for i in (0..(n - 1)).rev() {
-        let is_s_type = text[i] < text[i + 1] || (text[i] == text[i + 1] && prev_is_s_type);
+        let (c, d) = (text.get(i), text.get(i + 1));
+        let is_s_type = c < d || (c == d && prev_is_s_type);
         if is_s_type {
             types.set_bit(i, true);
         } else if prev_is_s_type {
@@ -75,33 +74,31 @@ where
 }
 
 fn is_lms(types: &BitArray, i: u64) -> bool {
-    i > 0 && i < u64::max_value() && types.get_bit(i as usize) && !types.get_bit(i as usize - 1)
+    i > 0 && i < u64::MAX && types.get_bit(i as usize) && !types.get_bit(i as usize - 1)
 }
 
-fn induced_sort<T, K, C>(text: K, converter: &C, types: &BitArray, occs: &[u64], sa: &mut [u64])
+fn induced_sort<X, C>(text: &X, converter: &C, types: &BitArray, occs: &[u64], sa: &mut [u64])
 where
-    T: Into<u64> + Copy + Clone + Ord,
-    K: AsRef<[T]>,
-    C: Converter<T>,
+    X: Text + ?Sized,
+    C: Converter<X::T>,
 {
-    let text = text.as_ref();
     let n = text.len();
     let mut bucket_start_pos = get_bucket_start_pos(occs);
     for i in 0..n {
         let j = sa[i];
-        if 0 < j && j < u64::max_value() && !types.get_bit(j as usize - 1) {
-            let c = converter.convert(text[j as usize - 1]).into() as usize;
+        if 0 < j && j < u64::MAX && !types.get_bit(j as usize - 1) {
+            let c = converter.convert(text.get(j as usize - 1)).into() as usize;
             let p = bucket_start_pos[c] as usize;
             sa[p] = j - 1;
             bucket_start_pos[c] += 1;
         }
     }
 
-    let mut bucket_end_pos = get_bucket_end_pos(&occs);
+    let mut bucket_end_pos = get_bucket_end_pos(occs);
     for i in (0..n).rev() {
         let j = sa[i];
-        if j != 0 && j != u64::max_value() && types.get_bit(j as usize - 1) {
-            let c = converter.convert(text[j as usize - 1]).into() as usize;
+        if j != 0 && j != u64::MAX && types.get_bit(j as usize - 1) {
+            let c = converter.convert(text.get(j as usize - 1)).into() as usize;
             let p = bucket_end_pos[c] as usize - 1;
             sa[p] = j - 1;
             bucket_end_pos[c] -= 1;
@@ -109,37 +106,44 @@ where
     }
 }
 
-pub fn sais<T, C, K>(text: K, converter: &C) -> Vec<u64>
+pub fn sais<X, C>(text: &X, converter: &C) -> Vec<u64>
 where
-    T: Into<u64> + Copy + Clone + Ord + Debug,
-    K: AsRef<[T]>,
-    C: Converter<T>,
+    X: Text + ?Sized,
+    C: Converter<X::T>,
 {
-    let n = text.as_ref().len();
+    let mut sa = vec![];
+    sais_into(text, converter, &mut sa);
+    sa
+}
+
+/// Builds the suffix array into `sa`, reusing its capacity.
+pub fn sais_into<X, C>(text: &X, converter: &C, sa: &mut Vec<u64>)
+where
+    X: Text + ?Sized,
+    C: Converter<X::T>,
+{
+    let n = text.len();
+    sa.clear();
     match n {
-        0 => vec![],
-        1 => vec![0],
+        0 => {}
+        1 => sa.push(0),
         _ => {
-            debug_assert!(
-                text.as_ref().last().map(|&c| c.into()) == Some(0u64),
+            strict_assert!(
+                text.get(n - 1).is_zero(),
                 "expected: the last char in text should be zero"
             );
-            let mut sa = vec![u64::max_value(); n];
-            sais_sub(&text, &mut sa, converter);
-            sa
+            sa.resize(n, u64::MAX);
+            sais_sub(text, sa, converter);
         }
     }
 }
 
 #[allow(clippy::cognitive_complexity)]
-fn sais_sub<T, C, K>(text: K, sa: &mut [u64], converter: &C)
+fn sais_sub<X, C>(text: &X, sa: &mut [u64], converter: &C)
 where
-    T: Into<u64> + Copy + Clone + Ord + Debug,
-    K: AsRef<[T]>,
-    C: Converter<T>,
+    X: Text + ?Sized,
+    C: Converter<X::T>,
 {
-    let text = text.as_ref();
-
     let n = text.len();
     let (types, lms) = get_types(text);
     let lms_len = lms.len();
@@ -149,7 +153,7 @@ where
     // Step 1.
     for &i in lms.iter().rev() {
         // TODO: refactor
-        let c = converter.convert(text[i]).into();
+        let c = converter.convert(text.get(i)).into();
         let k = bucket_end_pos[c as usize] as usize - 1;
         sa[k] = i as u64;
         bucket_end_pos[c as usize] = k as u64;
@@ -186,7 +190,7 @@ where
 
         let (sa_lms, names) = sa.split_at_mut(lms_len);
         for n in names.iter_mut() {
-            *n = u64::max_value();
+            *n = u64::MAX;
         }
         names[sa_lms[0] as usize / 2] = 0; // name of the sentinel
         if lms_len <= 1 {
@@ -197,9 +201,11 @@ where
                 let p = sa_lms[i - 1] as usize;
                 let q = sa_lms[i] as usize;
                 let mut d = 1;
-                let mut same = text[p] == text[q] && types.get_bit(p) == types.get_bit(q);
+                let mut same = text.get(p) == text.get(q) && types.get_bit(p) == types.get_bit(q);
                 while same {
-                    if text[p + d] != text[q + d] || types.get_bit(p + d) != types.get_bit(q + d) {
+                    if text.get(p + d) != text.get(q + d)
+                        || types.get_bit(p + d) != types.get_bit(q + d)
+                    {
                         same = false;
                         break;
                     } else if is_lms(&types, (p + d) as u64) && is_lms(&types, (p + d) as u64) {
@@ -214,13 +220,13 @@ where
             }
         }
         for s in sa_lms.iter_mut() {
-            *s = u64::max_value();
+            *s = u64::MAX;
         }
     }
     let mut i = sa.len() - 1;
     let mut j = 0;
     while j < lms_len {
-        if sa[i] < u64::max_value() {
+        if sa[i] < u64::MAX {
             sa[sa.len() - 1 - j] = sa[i];
             j += 1;
         }
@@ -230,7 +236,7 @@ where
     {
         let (sa1, s1) = sa.split_at_mut(sa.len() - lms_len);
         if name < lms_len as u64 {
-            sais_sub(&s1, sa1, &IdConverter::new(name + 1 as u64));
+            sais_sub(&*s1, sa1, &IdConverter::new(name + 1));
         } else {
             for (i, &s) in s1.iter().enumerate() {
                 sa1[s as usize] = i as u64
@@ -245,17 +251,17 @@ where
     }
 
     for i in &mut sa[lms_len..] {
-        *i = u64::max_value();
+        *i = u64::MAX;
     }
 
     let mut bucket_end_pos = get_bucket_end_pos(&occs);
     for i in (0..lms_len).rev() {
         let j = sa[i] as usize;
-        sa[i] = u64::max_value();
+        sa[i] = u64::MAX;
         let c = if j == n {
             0
         } else {
-            converter.convert(text[j]).into()
+            converter.convert(text.get(j)).into()
         };
         let k = bucket_end_pos[c as usize] as usize - 1;
         sa[k] = j as u64;
@@ -287,7 +293,7 @@ mod tests {
         let n = text.len();
         let types_expected = "LLSSLLSSLLSSLLLLS";
         let lms_expected = marks_to_lms("  *   *   *     *");
-        let (types, lms) = get_types(text);
+        let (types, lms) = get_types(text.as_bytes());
         let types_actual = (0..n)
             .map(|i| if types.get_bit(i) { 'S' } else { 'L' })
             .collect::<String>();
@@ -302,7 +308,7 @@ mod tests {
         let n = text.len();
         let types_expected = "LSSLS".to_string();
         let lms_expected = marks_to_lms(" *  *");
-        let (types, lms) = get_types(text);
+        let (types, lms) = get_types(text.as_bytes());
         let types_actual = (0..n)
             .map(|i| if types.get_bit(i) { 'S' } else { 'L' })
             .collect::<String>();
@@ -315,7 +321,7 @@ mod tests {
     fn test_get_bucket_start_pos() {
         let text = "mmiissiissiippii\0";
         let converter = RangeConverter::new(b'a', b'z');
-        let occs = count_chars(text, &converter);
+        let occs = count_chars(text.as_bytes(), &converter);
         let bucket_start_pos = get_bucket_start_pos(&occs);
         let ans = vec![(b'\0', 0), (b'i', 1), (b'm', 9), (b'p', 11), (b's', 13)];
         for (c, expected) in ans {
@@ -333,7 +339,7 @@ mod tests {
         let text = "mmiissiissiippii\0";
         let ans = vec![(b'\0', 1), (b'i', 9), (b'm', 11), (b'p', 13), (b's', 17)];
         let converter = RangeConverter::new(b'a', b'z');
-        let occs = count_chars(text, &converter);
+        let occs = count_chars(text.as_bytes(), &converter);
         let bucket_end_pos = get_bucket_end_pos(&occs);
         for (c, expected) in ans {
             let actual = bucket_end_pos[converter.convert(c) as usize];
@@ -355,24 +361,24 @@ mod tests {
 
     #[test]
     fn test_sais_1() {
-        let text = &[0u8];
-        let sa = sais(&text, &IdConverter::new(4));
+        let text: &[u8] = &[0u8];
+        let sa = sais(text, &IdConverter::new(4));
         let expected = get_suffix_array(text);
         assert_eq!(sa, expected);
     }
 
     #[test]
     fn test_sais_2() {
-        let text = &[3u8, 0];
-        let sa = sais(&text, &IdConverter::new(4));
+        let text: &[u8] = &[3u8, 0];
+        let sa = sais(text, &IdConverter::new(4));
         let expected = get_suffix_array(text);
         assert_eq!(sa, expected);
     }
 
     #[test]
     fn test_sais_4() {
-        let text = &[3u8, 2, 1, 0];
-        let sa = sais(&text, &IdConverter::new(4));
+        let text: &[u8] = &[3u8, 2, 1, 0];
+        let sa = sais(text, &IdConverter::new(4));
         let expected = get_suffix_array(text);
         assert_eq!(sa, expected);
     }
@@ -403,7 +409,7 @@ mod tests {
         let ans = get_suffix_array(text);
 
         assert_eq!(sa.len(), ans.len());
-        for (i, (actual, expected)) in sa.into_iter().zip(ans.into_iter()).enumerate() {
+        for (i, (actual, expected)) in sa.into_iter().zip(ans).enumerate() {
             assert_eq!(
                 actual, expected,
                 "wrong at {}-th pos: expected {}, but actual {}",
@@ -426,7 +432,7 @@ mod tests {
         let sa = sais(&text, &converter);
         let ans = get_suffix_array(&text);
         assert_eq!(sa.len(), ans.len());
-        for (i, (actual, expected)) in sa.into_iter().zip(ans.into_iter()).enumerate() {
+        for (i, (actual, expected)) in sa.into_iter().zip(ans).enumerate() {
             assert_eq!(
                 actual, expected,
                 "wrong at {}-th pos: expected {}, but actual {}",
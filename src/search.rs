@@ -1,17 +1,773 @@
-use crate::iter::{BackwardIterableIndex, BackwardIterator, ForwardIterableIndex, ForwardIterator};
-use crate::suffix_array::IndexWithSA;
+use crate::ambiguity::{Ambiguity, BranchLimitExceeded};
+use crate::anchor::{AnchoredSearch, Exact, Prefix, Suffix};
+use crate::budget::{BudgetLimit, PartialResult, QueryBudget};
+use crate::character::Character;
+use crate::converter::{Converter, IndexWithConverter};
+use crate::iter::{
+    BackwardIterableIndex, BackwardIterator, ForwardIterableIndex, ForwardIterator, PieceIterator,
+};
+use crate::matching_stats::MatchingStatistic;
+use crate::piece::{IndexWithPieces, PieceId};
+use crate::position::GlobalPos;
+use crate::position_set::PositionSet;
+use crate::suffix_array::{IndexWithExtrema, IndexWithISA, IndexWithSA, StepLimitExceeded};
+use crate::suggest::Suggestion;
+use crate::text_store::{BlockCodec, TextStore};
+use crate::util::strict_assert;
+use crate::visit::{Visit, VisitStep};
 
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::Arc;
+
+/// An index that supports backward search.
+///
+/// This is implemented for every [`BackwardIterableIndex`].
 pub trait BackwardSearchIndex: BackwardIterableIndex {
-    fn search_backward<K>(&self, pattern: K) -> Search<Self>
+    fn search_backward<K>(&self, pattern: K) -> Search<'_, Self>
     where
         K: AsRef<[Self::T]>,
     {
         Search::new(self).search_backward(pattern)
     }
+
+    /// Searches for `pattern` like [`BackwardSearchIndex::search_backward`],
+    /// or returns an error without searching if it is longer than `max_len`.
+    ///
+    /// This guards services against pathologically long patterns from untrusted input,
+    /// and [`Search::try_search_backward`] bounds the whole pattern of chained refinements likewise.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::NullSampler;
+    /// use fm_index::{BackwardSearchIndex, FMIndex, PatternTooLong};
+    ///
+    /// let index = FMIndex::new(b"mississippi".to_vec(), RangeConverter::new(b'a', b'z'), NullSampler::new());
+    /// let search = index.try_search_backward("ssi", 4).unwrap();
+    /// assert_eq!(search.count(), 2);
+    /// assert_eq!(search.try_search_backward("is", 4).err(), Some(PatternTooLong { len: 5, max_len: 4 }));
+    /// ```
+    fn try_search_backward<K>(
+        &self,
+        pattern: K,
+        max_len: usize,
+    ) -> Result<Search<'_, Self>, PatternTooLong>
+    where
+        K: AsRef<[Self::T]>,
+    {
+        Search::new(self).try_search_backward(pattern, max_len)
+    }
+
+    /// Checks that `pattern` is a well-formed query for this index without searching for it,
+    /// so that invalid queries can be told apart from ones with no occurrences.
+    ///
+    /// A pattern is well-formed if it is not empty, has no zeros, which are the terminator or separators of pieces,
+    /// and every character is in the alphabet of the converter.
+    /// The first problem in the pattern is reported.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::NullSampler;
+    /// use fm_index::{BackwardSearchIndex, FMIndex, InvalidPattern};
+    ///
+    /// let index = FMIndex::new(b"mississippi".to_vec(), RangeConverter::new(b'a', b'z'), NullSampler::new());
+    /// assert_eq!(index.validate_pattern("xyz"), Ok(()));
+    /// assert_eq!(index.validate_pattern("is it"), Err(InvalidPattern::OutOfRange { position: 2 }));
+    /// assert_eq!(index.validate_pattern("a\0b"), Err(InvalidPattern::Separator { position: 1 }));
+    /// assert_eq!(index.validate_pattern_with_separators("a\0b"), Ok(()));
+    /// assert_eq!(index.validate_pattern(""), Err(InvalidPattern::Empty));
+    /// ```
+    fn validate_pattern<K>(&self, pattern: K) -> Result<(), InvalidPattern>
+    where
+        K: AsRef<[Self::T]>,
+        Self: IndexWithConverter<Self::T>,
+        Self::T: Character,
+    {
+        check_pattern(self.get_converter(), pattern.as_ref(), false)
+    }
+
+    /// Checks `pattern` like [`BackwardSearchIndex::validate_pattern`] but allows zeros,
+    /// e.g. for patterns spanning pieces of [`crate::FMIndexMultiPieces`].
+    fn validate_pattern_with_separators<K>(&self, pattern: K) -> Result<(), InvalidPattern>
+    where
+        K: AsRef<[Self::T]>,
+        Self: IndexWithConverter<Self::T>,
+        Self::T: Character,
+    {
+        check_pattern(self.get_converter(), pattern.as_ref(), true)
+    }
+
+    /// Returns true if `pattern` occurs at least once.
+    ///
+    /// This neither copies the pattern nor builds a [`Search`], and stops as soon as no suffix matches,
+    /// so it is the cheapest query for filtering.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::NullSampler;
+    /// use fm_index::{BackwardSearchIndex, FMIndex};
+    ///
+    /// let index = FMIndex::new(b"mississippi".to_vec(), RangeConverter::new(b'a', b'z'), NullSampler::new());
+    /// assert!(index.contains("ssip"));
+    /// assert!(!index.contains("spi"));
+    /// assert!(index.contains(""));
+    /// ```
+    fn contains<K>(&self, pattern: K) -> bool
+    where
+        K: AsRef<[Self::T]>,
+    {
+        let (s, e) = backward_range(self, 0, self.len(), pattern.as_ref());
+        s < e
+    }
+
+    /// Searches for `pattern` at the start of the text.
+    ///
+    /// Since the text is regarded as cyclic, the terminator precedes the start of the text,
+    /// so this searches for `"\0" + pattern`, and the occurrence is located at the start of the text.
+    /// For [`crate::FMIndexMultiPieces`], it finds `pattern` at the start of pieces.
+    /// The search can only be refined by appending to `pattern`; see [`crate::anchor`].
+    fn search_prefix<K>(&self, pattern: K) -> AnchoredSearch<'_, Self, Prefix>
+    where
+        K: AsRef<[Self::T]>,
+        Self::T: Character,
+    {
+        AnchoredSearch::new(
+            self.search_backward(pattern)
+                .search_backward([<Self::T as Character>::from_u64(0)]),
+        )
+    }
+
+    /// Searches for `pattern` at the end of the text, i.e. followed by the terminator.
+    ///
+    /// For [`crate::FMIndexMultiPieces`], it finds `pattern` at the end of pieces.
+    /// The search can only be refined by prepending to `pattern`; see [`crate::anchor`].
+    fn search_suffix<K>(&self, pattern: K) -> AnchoredSearch<'_, Self, Suffix>
+    where
+        K: AsRef<[Self::T]>,
+        Self::T: Character,
+    {
+        AnchoredSearch::new(
+            self.search_backward([<Self::T as Character>::from_u64(0)])
+                .search_backward(pattern),
+        )
+    }
+
+    /// Searches for `pattern` equal to the whole text,
+    /// located at the start of the text like [`BackwardSearchIndex::search_prefix`].
+    ///
+    /// For [`crate::FMIndexMultiPieces`], it finds pieces equal to `pattern`.
+    /// The search cannot be refined.
+    fn search_exact<K>(&self, pattern: K) -> AnchoredSearch<'_, Self, Exact>
+    where
+        K: AsRef<[Self::T]>,
+        Self::T: Character,
+    {
+        self.search_suffix(pattern).anchor_start()
+    }
+
+    /// Searches for the concrete patterns matched by `pattern`, whose characters may stand for
+    /// sets of characters given by `ambiguity`, e.g. [`crate::Iupac`] codes.
+    ///
+    /// Branches are expanded character by character during the backward search and pruned as soon as
+    /// they have no occurrences, instead of searching every concrete pattern.
+    /// Returns one search per concrete pattern that occurs, whose occurrences are disjoint,
+    /// or an error if more than `max_branches` of them are alive at some step.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::SuffixOrderSampler;
+    /// use fm_index::{BackwardSearchIndex, FMIndex, Iupac};
+    ///
+    /// let text = b"GATTACAGATCACA".to_vec();
+    /// let index = FMIndex::new(text, RangeConverter::new(b'A', b'T'), SuffixOrderSampler::new());
+    /// let searches = index.search_ambiguous("GATYA", &Iupac, 16).unwrap();
+    /// let mut positions = searches.iter().flat_map(|s| s.locate()).collect::<Vec<_>>();
+    /// positions.sort();
+    /// assert_eq!(positions, vec![0, 7]);
+    /// assert!(index.search_ambiguous("NN", &Iupac, 4).is_err());
+    /// ```
+    fn search_ambiguous<K, A>(
+        &self,
+        pattern: K,
+        ambiguity: &A,
+        max_branches: usize,
+    ) -> Result<Vec<Search<'_, Self>>, BranchLimitExceeded>
+    where
+        K: AsRef<[Self::T]>,
+        A: Ambiguity<Self::T>,
+    {
+        let mut branches = vec![Search::new(self)];
+        for &c in pattern.as_ref().iter().rev() {
+            let chars = ambiguity.expand(c);
+            let mut next = vec![];
+            for branch in &branches {
+                for &d in chars.iter() {
+                    let search = branch.search_backward([d]);
+                    if search.count() == 0 {
+                        continue;
+                    }
+                    if next.len() == max_branches {
+                        return Err(BranchLimitExceeded {
+                            limit: max_branches,
+                        });
+                    }
+                    next.push(search);
+                }
+            }
+            branches = next;
+            if branches.is_empty() {
+                break;
+            }
+        }
+        Ok(branches)
+    }
+
+    /// Suggests patterns that occur in the text and differ from `pattern`
+    /// by substituting or deleting a single character, e.g. for "did you mean" when `pattern` has no occurrences.
+    ///
+    /// Candidates are explored during the backward search: the suffix of `pattern` after each position
+    /// is searched once, and only the characters preceding its occurrences are tried as substitutes.
+    /// Returns at most `limit` suggestions in descending order of occurrence counts.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::NullSampler;
+    /// use fm_index::{BackwardSearchIndex, FMIndex};
+    ///
+    /// let text = b"the cat sat on the mat with the hat".to_vec();
+    /// let index = FMIndex::new(text, RangeConverter::new(b' ', b'z'), NullSampler::new());
+    /// let suggestions = index
+    ///     .suggest("xat", 3)
+    ///     .into_iter()
+    ///     .map(|s| (String::from_utf8(s.pattern).unwrap(), s.count))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(
+    ///     suggestions,
+    ///     vec![("at".to_string(), 4), ("cat".to_string(), 1), ("hat".to_string(), 1)]
+    /// );
+    /// ```
+    fn suggest<K>(&self, pattern: K, limit: usize) -> Vec<Suggestion<Self::T>>
+    where
+        K: AsRef<[Self::T]>,
+        Self: IndexWithConverter<Self::T>,
+        Self::T: Character,
+    {
+        let pattern = pattern.as_ref();
+        let m = pattern.len();
+        let mut suggestions = vec![];
+        // the search for `pattern[j + 1..]`
+        let mut suffix = Search::new(self);
+        for j in (0..m).rev() {
+            if suffix.count() == 0 {
+                break;
+            }
+            let prefix = &pattern[..j];
+            // deletion of `pattern[j]`
+            if m > 1 {
+                let count = suffix.search_backward(prefix).count();
+                if count > 0 {
+                    let mut candidate = prefix.to_vec();
+                    candidate.extend_from_slice(&pattern[j + 1..]);
+                    suggestions.push(Suggestion {
+                        pattern: candidate,
+                        count,
+                    });
+                }
+            }
+            // substitutions of `pattern[j]`
+            for (c, _) in suffix.left_extension_counts() {
+                if c == pattern[j] {
+                    continue;
+                }
+                let count = suffix.search_backward([c]).search_backward(prefix).count();
+                if count > 0 {
+                    let mut candidate = pattern.to_vec();
+                    candidate[j] = c;
+                    suggestions.push(Suggestion {
+                        pattern: candidate,
+                        count,
+                    });
+                }
+            }
+            suffix = suffix.search_backward([pattern[j]]);
+        }
+        // deleting either of repeated characters gives the same pattern
+        suggestions.sort_by(|a, b| a.pattern.cmp(&b.pattern));
+        suggestions.dedup_by(|a, b| a.pattern == b.pattern);
+        suggestions.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.pattern.cmp(&b.pattern))
+        });
+        suggestions.truncate(limit);
+        suggestions
+    }
+
+    /// Returns the positions of the occurrences of `a` with an occurrence of `b` starting within
+    /// distance `d` of it, in ascending order.
+    ///
+    /// The rarer of the two patterns is located, and the text around each of its occurrences
+    /// is read by iterating backward and forward to find the other pattern,
+    /// which takes _O(min(occ_a, occ_b) (d + m))_ steps of LF- or FL-mapping plus the cost of locating.
+    /// Neighborhoods do not extend across zeros, so both patterns must occur in the same piece.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::SuffixOrderSampler;
+    /// use fm_index::{BackwardSearchIndex, FMIndex};
+    ///
+    /// let text = b"red apple, green pear, red pear, apple".to_vec();
+    /// let index = FMIndex::new(text, RangeConverter::new(b' ', b'z'), SuffixOrderSampler::new());
+    /// assert_eq!(index.search_near("red", "pear", 4), vec![23]);
+    /// assert_eq!(index.search_near("pear", "red", 4), vec![27]);
+    /// assert_eq!(index.search_near("apple", "red", 4), vec![4]);
+    /// assert!(index.search_near("apple", "pear", 3).is_empty());
+    /// ```
+    fn search_near<K>(&self, a: K, b: K, d: u64) -> Vec<u64>
+    where
+        K: AsRef<[<Self as BackwardIterableIndex>::T]>,
+        Self: ForwardIterableIndex<T = <Self as BackwardIterableIndex>::T>
+            + IndexWithConverter<<Self as BackwardIterableIndex>::T>
+            + IndexWithSA,
+        <Self as BackwardIterableIndex>::T: Character,
+    {
+        let (a, b) = (a.as_ref(), b.as_ref());
+        let (search_a, search_b) = (self.search_backward(a), self.search_backward(b));
+        let mut positions = vec![];
+        if search_a.count() == 0 || search_b.count() == 0 {
+            return positions;
+        }
+        let a_is_rarer = search_a.count() <= search_b.count();
+        let (rare, other) = if a_is_rarer {
+            (search_a, b)
+        } else {
+            (search_b, a)
+        };
+        let m = other.len() as u64;
+        for occurrence in rare.iter_matches() {
+            let p = occurrence.locate();
+            let mut window = occurrence
+                .iter_backward()
+                .take(d as usize)
+                .take_while(|&c| c.into() != 0)
+                .collect::<Vec<_>>();
+            let start = p - window.len() as u64;
+            window.reverse();
+            window.extend(
+                occurrence
+                    .iter_forward()
+                    .take((d + m) as usize)
+                    .take_while(|&c| c.into() != 0),
+            );
+            let mut nearby = (0..window.len())
+                .filter(|&j| window[j..].starts_with(other))
+                .map(|j| start + j as u64)
+                .filter(|&q| q.max(p) - q.min(p) <= d);
+            if a_is_rarer {
+                if nearby.next().is_some() {
+                    positions.push(p);
+                }
+            } else {
+                positions.extend(nearby);
+            }
+        }
+        positions.sort_unstable();
+        positions.dedup();
+        positions
+    }
+
+    /// Computes the matching statistics of `query`: for each position of `query`,
+    /// the longest substring starting there that occurs in the text and the rows of its occurrences.
+    ///
+    /// `query` is scanned backward, extending the match of the following position by one character.
+    /// When the extension fails, the match is shortened by one character and searched again from scratch,
+    /// so this takes _O(m ℓ)_ steps of LF-mapping in the worst case, where _ℓ_ is the longest match,
+    /// and _O(m)_ steps when most extensions succeed.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::SuffixOrderSampler;
+    /// use fm_index::{BackwardSearchIndex, FMIndex};
+    ///
+    /// let index = FMIndex::new(b"mississippi".to_vec(), RangeConverter::new(b'a', b'z'), SuffixOrderSampler::new());
+    /// let stats = index.matching_statistics("sipping");
+    /// let lens = stats.iter().map(|s| s.len).collect::<Vec<_>>();
+    /// assert_eq!(lens, vec![5, 4, 3, 2, 1, 0, 0]);
+    /// assert_eq!(stats[0].locate_one(&index), Some(6));
+    /// assert_eq!(stats[6].locate_one(&index), None);
+    /// ```
+    fn matching_statistics<K>(&self, query: K) -> Vec<MatchingStatistic>
+    where
+        K: AsRef<[Self::T]>,
+    {
+        let query = query.as_ref();
+        let n = self.len();
+        let mut stats = vec![
+            MatchingStatistic {
+                len: 0,
+                range: SaInterval { start: 0, end: n },
+            };
+            query.len()
+        ];
+        // the match `query[i + 1..i + 1 + len]` and its rows
+        let (mut len, mut s, mut e) = (0, 0, n);
+        for i in (0..query.len()).rev() {
+            loop {
+                let (s2, e2) = backward_range(self, s, e, &query[i..=i]);
+                if s2 < e2 {
+                    len += 1;
+                    s = s2;
+                    e = e2;
+                    break;
+                }
+                if len == 0 {
+                    s = 0;
+                    e = n;
+                    break;
+                }
+                len -= 1;
+                (s, e) = backward_range(self, 0, n, &query[i + 1..i + 1 + len]);
+            }
+            stats[i] = MatchingStatistic {
+                len: len as u64,
+                range: SaInterval { start: s, end: e },
+            };
+        }
+        stats
+    }
+
+    /// Explores the patterns occurring in the text depth-first, extending each of them backward
+    /// by the characters preceding its occurrences, and lets `visitor` decide after each step
+    /// whether to continue, prune or emit the pattern.
+    ///
+    /// This is a building block for searches outside the crate, such as approximate or wildcard matching
+    /// and top-k exploration. Extensions are tried in ascending order of characters, skipping the terminator,
+    /// and each step takes _O(σ)_ rank queries. Returns the searches emitted in the order of the exploration.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::SuffixOrderSampler;
+    /// use fm_index::{BackwardSearchIndex, FMIndex, Visit};
+    ///
+    /// let index = FMIndex::new(b"mississippi".to_vec(), RangeConverter::new(b'a', b'z'), SuffixOrderSampler::new());
+    /// // "s?i" with a wildcard in the middle, searched from the last character
+    /// let pattern = b"s?i";
+    /// let searches = index.visit_backward(|step| {
+    ///     let c = pattern[pattern.len() - step.depth];
+    ///     if c != b'?' && c != step.c {
+    ///         Visit::Prune
+    ///     } else if step.depth == pattern.len() {
+    ///         Visit::Emit
+    ///     } else {
+    ///         Visit::Continue
+    ///     }
+    /// });
+    /// let patterns = searches.iter().map(|s| s.pattern()).collect::<Vec<_>>();
+    /// assert_eq!(patterns, vec![b"ssi".to_vec()]);
+    /// assert_eq!(searches[0].count(), 2);
+    /// ```
+    fn visit_backward<F>(&self, mut visitor: F) -> Vec<Search<'_, Self>>
+    where
+        F: FnMut(&VisitStep<'_, Self::T>) -> Visit,
+        Self: IndexWithConverter<Self::T>,
+        Self::T: Character,
+    {
+        let converter = self.get_converter();
+        let chars = (1..converter.len())
+            .map(|code| (code, converter.convert_inv(Self::T::from_u64(code))))
+            // skip characters that are merged into another one by the converter
+            .filter(|&(code, c)| converter.convert(c).into() == code)
+            .map(|(_, c)| c)
+            .collect::<Vec<_>>();
+        let mut emitted = vec![];
+        let mut path = vec![];
+        // (character, rows, depth) of the patterns to visit, the next one last
+        let mut stack = vec![];
+        let push_extensions = |stack: &mut Vec<_>, s: u64, e: u64, depth: usize| {
+            for &c in chars.iter().rev() {
+                let (s2, e2) = (self.lf_map2(c, s), self.lf_map2(c, e));
+                if s2 < e2 {
+                    stack.push((c, s2, e2, depth + 1));
+                }
+            }
+        };
+        push_extensions(&mut stack, 0, self.len(), 0);
+        while let Some((c, s, e, depth)) = stack.pop() {
+            path.truncate(depth - 1);
+            path.push(c);
+            let step = VisitStep {
+                c,
+                depth,
+                range: SaInterval::new(s, e),
+                path: &path,
+            };
+            match visitor(&step) {
+                Visit::Continue => push_extensions(&mut stack, s, e, depth),
+                Visit::Prune => {}
+                Visit::Emit => {
+                    emitted.push(Search::with_range(self, s, e, step.pattern()));
+                }
+                Visit::Stop => break,
+            }
+        }
+        emitted
+    }
+
+    /// Narrows `state` by `pattern` without allocating.
+    fn search_in_place<K>(&self, state: &mut SearchState, pattern: K)
+    where
+        K: AsRef<[Self::T]>,
+    {
+        let (s, e) = backward_range(self, state.s, state.e, pattern.as_ref());
+        state.s = s;
+        state.e = e;
+    }
+
+    /// Calls `f` with every `k`-gram (`1 <= k <= max_n`) occurring in `corpus` and its number of occurrences in the index.
+    ///
+    /// The `k`-grams ending at the same position of `corpus` share a single backward search,
+    /// so this takes _O(|corpus| max_n)_ rank queries in total.
+    /// An n-gram is visited as many times as it occurs in `corpus`.
+    fn for_each_ngram_count<F>(&self, corpus: &[Self::T], max_n: usize, mut f: F)
+    where
+        F: FnMut(&[Self::T], u64),
+    {
+        for j in 1..=corpus.len() {
+            let mut s = 0;
+            let mut e = self.len();
+            for k in 1..=max_n.min(j) {
+                if s < e {
+                    let c = corpus[j - k];
+                    s = self.lf_map2(c, s);
+                    e = self.lf_map2(c, e);
+                }
+                f(&corpus[j - k..j], e - s);
+            }
+        }
+    }
+
+    /// Counts the occurrences in the index of every `n`-gram in `corpus`.
+    fn ngram_counts(&self, corpus: &[Self::T], n: usize) -> HashMap<Vec<Self::T>, u64>
+    where
+        Self::T: Eq + Hash,
+    {
+        let mut counts = HashMap::new();
+        self.for_each_ngram_count(corpus, n, |gram, count| {
+            if gram.len() == n && !counts.contains_key(gram) {
+                counts.insert(gram.to_vec(), count);
+            }
+        });
+        counts
+    }
 }
 
 impl<I: BackwardIterableIndex> BackwardSearchIndex for I {}
 
+fn backward_range<I>(index: &I, mut s: u64, mut e: u64, mut pattern: &[I::T]) -> (u64, u64)
+where
+    I: BackwardIterableIndex,
+{
+    if s < e && !pattern.is_empty() && !index.may_contain(pattern) {
+        return (s, s);
+    }
+    if s == 0 && e == index.len() {
+        if let Some((&c, rest)) = pattern.split_last() {
+            (s, e) = index.char_range(c).into();
+            pattern = rest;
+        }
+    }
+    for &c in pattern.iter().rev() {
+        if s == e {
+            break;
+        }
+        s = index.lf_map2(c, s);
+        e = index.lf_map2(c, e);
+    }
+    (s, e)
+}
+
+fn check_pattern<T, C>(converter: &C, pattern: &[T], allow_zero: bool) -> Result<(), InvalidPattern>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    if pattern.is_empty() {
+        return Err(InvalidPattern::Empty);
+    }
+    for (position, &c) in pattern.iter().enumerate() {
+        if c.is_zero() {
+            if !allow_zero {
+                return Err(InvalidPattern::Separator { position });
+            }
+        } else if !converter.contains(c) {
+            return Err(InvalidPattern::OutOfRange { position });
+        }
+    }
+    Ok(())
+}
+
+/// An error returned by [`BackwardSearchIndex::validate_pattern`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidPattern {
+    /// The pattern has no characters, so it would match at every position.
+    Empty,
+    /// The character at `position` is zero, i.e. the terminator or a separator of pieces.
+    Separator { position: usize },
+    /// The character at `position` is not in the range of the converter.
+    OutOfRange { position: usize },
+}
+
+impl fmt::Display for InvalidPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvalidPattern::Empty => write!(f, "pattern is empty"),
+            InvalidPattern::Separator { position } => {
+                write!(f, "pattern contains a separator at {}", position)
+            }
+            InvalidPattern::OutOfRange { position } => {
+                write!(
+                    f,
+                    "character at {} is out of the converter's range",
+                    position
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidPattern {}
+
+/// An error returned when an occurrence is requested by an index not less than the number of occurrences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OccurrenceOutOfRange {
+    pub index: u64,
+    pub count: u64,
+}
+
+impl fmt::Display for OccurrenceOutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "occurrence {} is out of range for {} occurrences",
+            self.index, self.count
+        )
+    }
+}
+
+impl std::error::Error for OccurrenceOutOfRange {}
+
+/// An error returned when a pattern is longer than the maximum length given to a search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PatternTooLong {
+    /// The length of the whole pattern including the refinements so far.
+    pub len: usize,
+    pub max_len: usize,
+}
+
+impl fmt::Display for PatternTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "the pattern of length {} exceeds the maximum length {}",
+            self.len, self.max_len
+        )
+    }
+}
+
+impl std::error::Error for PatternTooLong {}
+
+/// A half-open interval `[start, end)` of rows of the suffix array.
+///
+/// Rows are not text positions; use [`Search::locate`] to get the positions of a search result.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SaInterval {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl SaInterval {
+    pub fn new(start: u64, end: u64) -> Self {
+        debug_assert!(start <= end);
+        SaInterval { start, end }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn contains(&self, i: u64) -> bool {
+        self.start <= i && i < self.end
+    }
+
+    /// Returns the rows in both intervals, which is empty if they do not overlap.
+    pub fn intersect(&self, other: SaInterval) -> SaInterval {
+        let start = self.start.max(other.start);
+        SaInterval::new(start, self.end.min(other.end).max(start))
+    }
+
+    pub fn rows(&self) -> std::ops::Range<u64> {
+        self.start..self.end
+    }
+}
+
+impl From<(u64, u64)> for SaInterval {
+    fn from((start, end): (u64, u64)) -> Self {
+        SaInterval::new(start, end)
+    }
+}
+
+impl From<SaInterval> for (u64, u64) {
+    fn from(interval: SaInterval) -> Self {
+        (interval.start, interval.end)
+    }
+}
+
+/// A reusable search result which, unlike [`Search`], does not own the pattern.
+///
+/// Together with [`BackwardSearchIndex::search_in_place`] and [`SearchState::locate_into`],
+/// queries can be answered without allocating.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchState {
+    s: u64,
+    e: u64,
+}
+
+impl SearchState {
+    /// Creates a state matching every suffix of the text of `index`.
+    pub fn new<I: BackwardIterableIndex>(index: &I) -> Self {
+        SearchState {
+            s: 0,
+            e: index.len(),
+        }
+    }
+
+    pub fn reset<I: BackwardIterableIndex>(&mut self, index: &I) {
+        *self = SearchState::new(index);
+    }
+
+    pub fn get_range(&self) -> SaInterval {
+        SaInterval::new(self.s, self.e)
+    }
+
+    pub fn count(&self) -> u64 {
+        self.e - self.s
+    }
+
+    /// Replaces the contents of `results` with the positions of all occurrences.
+    pub fn locate_into<I: IndexWithSA>(&self, index: &I, results: &mut Vec<u64>) {
+        results.clear();
+        results.extend((self.s..self.e).map(|k| index.get_sa(k)));
+    }
+}
+
+/// A search result, represented as a range of the suffix array.
+///
+/// Available operations depend on the traits implemented by the index.
 pub struct Search<'a, I>
 where
     I: BackwardSearchIndex,
@@ -19,89 +775,1300 @@ where
     index: &'a I,
     s: u64,
     e: u64,
-    pattern: Vec<I::T>,
+    pattern: Option<Arc<PatternSegment<I::T>>>,
+    pattern_len: usize,
+}
+
+// The pattern of a search is stored as a list of the segments given to each refinement,
+// from the first character, so that refining a search does not copy the pattern searched so far.
+struct PatternSegment<T> {
+    chars: Vec<T>,
+    next: Option<Arc<PatternSegment<T>>>,
+}
+
+// Drops a chain of segments iteratively, since dropping it recursively would overflow the stack
+// for a long chain of refinements.
+impl<T> Drop for PatternSegment<T> {
+    fn drop(&mut self) {
+        let mut next = self.next.take();
+        while let Some(segment) = next {
+            next = match Arc::try_unwrap(segment) {
+                Ok(mut segment) => segment.next.take(),
+                // shared with another search, which drops the rest
+                Err(_) => None,
+            };
+        }
+    }
 }
 
 impl<'a, I> Search<'a, I>
 where
     I: BackwardSearchIndex,
 {
-    fn new(index: &'a I) -> Search<I> {
+    pub(crate) fn new(index: &'a I) -> Search<'a, I> {
         Search {
             index,
             s: 0,
             e: index.len(),
-            pattern: vec![],
+            pattern: None,
+            pattern_len: 0,
         }
     }
 
-    pub fn search_backward<K: AsRef<[I::T]>>(&self, pattern: K) -> Self {
-        let mut s = self.s;
-        let mut e = self.e;
-        let mut pattern = pattern.as_ref().to_vec();
-        for &c in pattern.iter().rev() {
-            s = self.index.lf_map2(c, s);
-            e = self.index.lf_map2(c, e);
-            if s == e {
-                break;
-            }
+    // Creates a search for `pattern` whose rows are known to be `[s, e)`.
+    pub(crate) fn with_range(index: &'a I, s: u64, e: u64, pattern: Vec<I::T>) -> Search<'a, I> {
+        Search {
+            index,
+            s,
+            e,
+            pattern_len: pattern.len(),
+            pattern: Some(Arc::new(PatternSegment {
+                chars: pattern,
+                next: None,
+            })),
         }
-        pattern.extend_from_slice(&self.pattern);
+    }
+
+    pub fn search_backward<K: AsRef<[I::T]>>(&self, pattern: K) -> Self {
+        let chars = pattern.as_ref().to_vec();
+        let (s, e) = backward_range(self.index, self.s, self.e, &chars);
+        let pattern_len = self.pattern_len + chars.len();
+        let pattern = if chars.is_empty() {
+            self.pattern.clone()
+        } else {
+            Some(Arc::new(PatternSegment {
+                chars,
+                next: self.pattern.clone(),
+            }))
+        };
 
         Search {
             index: self.index,
             s,
             e,
             pattern,
+            pattern_len,
         }
     }
 
-    pub fn get_range(&self) -> (u64, u64) {
-        (self.s, self.e)
+    /// Prepends `pattern` like [`Search::search_backward`],
+    /// or returns an error without searching if the whole pattern becomes longer than `max_len`.
+    pub fn try_search_backward<K: AsRef<[I::T]>>(
+        &self,
+        pattern: K,
+        max_len: usize,
+    ) -> Result<Self, PatternTooLong> {
+        let len = self.pattern_len + pattern.as_ref().len();
+        if len > max_len {
+            return Err(PatternTooLong { len, max_len });
+        }
+        Ok(self.search_backward(pattern))
+    }
+
+    pub fn get_range(&self) -> SaInterval {
+        SaInterval::new(self.s, self.e)
     }
 
     pub fn count(&self) -> u64 {
         self.e - self.s
     }
+
+    pub(crate) fn index(&self) -> &'a I {
+        self.index
+    }
+
+    /// Returns the length of the whole pattern searched so far.
+    pub fn pattern_len(&self) -> usize {
+        self.pattern_len
+    }
+
+    /// Returns the whole pattern searched so far.
+    ///
+    /// The pattern is kept in the segments given to each refinement, so this copies them into one.
+    pub fn pattern(&self) -> Vec<I::T> {
+        let mut pattern = Vec::with_capacity(self.pattern_len);
+        let mut segment = self.pattern.as_deref();
+        while let Some(PatternSegment { chars, next }) = segment {
+            pattern.extend_from_slice(chars);
+            segment = next.as_deref();
+        }
+        pattern
+    }
+
+    /// Iterates over the occurrences in ascending order of their rows in the suffix array,
+    /// i.e. in lexicographic order of the suffixes starting at them.
+    ///
+    /// This order is the same for every index type and does not change across versions.
+    pub fn iter_matches(&self) -> impl Iterator<Item = Match<'a, I>> + '_ {
+        let index = self.index;
+        let len = self.pattern_len as u64;
+        (self.s..self.e).map(move |i| Match { index, i, len })
+    }
+
+    /// Iterates over the occurrences like [`Search::iter_matches`], but over at most as many as `budget` allows.
+    ///
+    /// Only the limit on matches applies, since no results are buffered.
+    pub fn iter_matches_within(
+        &self,
+        budget: &QueryBudget,
+    ) -> PartialResult<impl Iterator<Item = Match<'a, I>> + '_> {
+        let (n, exhausted) = budget.admit(self.count(), 0, &[BudgetLimit::Matches]);
+        PartialResult {
+            result: self.iter_matches().take(n as usize),
+            exhausted,
+        }
+    }
+
+    /// Returns the number of occurrences after each step of the backward search.
+    ///
+    /// The `k`-th element is the number of occurrences of the last `k + 1` characters of the pattern.
+    /// This is useful for estimating the selectivity of a query.
+    pub fn selectivity_trace(&self) -> Vec<u64> {
+        let mut s = 0;
+        let mut e = self.index.len();
+        let mut trace = Vec::with_capacity(self.pattern_len);
+        for &c in self.pattern().iter().rev() {
+            if s < e {
+                s = self.index.lf_map2(c, s);
+                e = self.index.lf_map2(c, e);
+            }
+            trace.push(e - s);
+        }
+        trace
+    }
 }
 
 impl<'a, I> Search<'a, I>
 where
     I: BackwardIterableIndex,
 {
-    pub fn iter_backward(&self, i: u64) -> BackwardIterator<I> {
+    pub fn iter_backward(&self, i: u64) -> BackwardIterator<'_, I> {
         let m = self.count();
 
-        debug_assert!(m > 0, "cannot iterate from empty search result");
-        debug_assert!(i < m, "{} is out of range", i);
+        strict_assert!(m > 0, "cannot iterate from empty search result");
+        strict_assert!(i < m, "{} is out of range", i);
 
         self.index.iter_backward(self.s + i)
     }
+
+    /// Iterates like [`Search::iter_backward`], or returns an error if there is no `i`-th occurrence.
+    pub fn try_iter_backward(
+        &self,
+        i: u64,
+    ) -> Result<BackwardIterator<'_, I>, OccurrenceOutOfRange> {
+        self.check_occurrence(i)?;
+        Ok(self.index.iter_backward(self.s + i))
+    }
+
+    fn check_occurrence(&self, i: u64) -> Result<(), OccurrenceOutOfRange> {
+        if i < self.count() {
+            Ok(())
+        } else {
+            Err(OccurrenceOutOfRange {
+                index: i,
+                count: self.count(),
+            })
+        }
+    }
 }
 
 impl<'a, I> Search<'a, I>
 where
     I: BackwardSearchIndex + ForwardIterableIndex,
 {
-    pub fn iter_forward(&self, i: u64) -> ForwardIterator<I> {
+    pub fn iter_forward(&self, i: u64) -> ForwardIterator<'_, I> {
         let m = self.count();
 
-        debug_assert!(m > 0, "cannot iterate from empty search result");
-        debug_assert!(i < m, "{} is out of range", i);
+        strict_assert!(m > 0, "cannot iterate from empty search result");
+        strict_assert!(i < m, "{} is out of range", i);
 
         self.index.iter_forward(self.s + i)
     }
+
+    /// Iterates like [`Search::iter_forward`], or returns an error if there is no `i`-th occurrence.
+    pub fn try_iter_forward(&self, i: u64) -> Result<ForwardIterator<'_, I>, OccurrenceOutOfRange> {
+        self.check_occurrence(i)?;
+        Ok(self.index.iter_forward(self.s + i))
+    }
+}
+
+impl<'a, I> Search<'a, I>
+where
+    I: BackwardSearchIndex + IndexWithExtrema,
+{
+    /// Returns the leftmost position of the occurrences without locating all of them,
+    /// or `None` if there are none.
+    pub fn min_position(&self) -> Option<u64> {
+        (self.s < self.e).then(|| self.index.min_sa(self.get_range()))
+    }
+
+    /// Returns the rightmost position of the occurrences without locating all of them,
+    /// or `None` if there are none.
+    pub fn max_position(&self) -> Option<u64> {
+        (self.s < self.e).then(|| self.index.max_sa(self.get_range()))
+    }
+}
+
+impl<'a, I> Search<'a, I>
+where
+    I: BackwardIterableIndex + IndexWithPieces,
+{
+    /// Iterates characters backward like [`Search::iter_backward`],
+    /// but stops at the start of the piece containing the `i`-th occurrence.
+    pub fn iter_backward_in_piece(&self, i: u64) -> PieceIterator<BackwardIterator<'_, I>> {
+        PieceIterator::new(self.iter_backward(i))
+    }
+}
+
+impl<'a, I> Search<'a, I>
+where
+    I: BackwardSearchIndex + IndexWithPieces,
+{
+    /// Returns the IDs of the pieces containing the occurrences, sorted in ascending order and deduplicated.
+    ///
+    /// Occurrences are visited in the order of rows, which is unrelated to the order of pieces.
+    /// For many occurrences the IDs are marked in a bitmap of the pieces and read out in order,
+    /// which is cheaper than sorting them.
+    pub fn piece_ids_sorted(&self) -> Vec<PieceId> {
+        let k = self.index.pieces_count();
+        let ids = self.iter_matches().map(|m| u64::from(m.piece_id()));
+        if self.count() < k / 64 {
+            let mut ids = ids.collect::<Vec<_>>();
+            ids.sort_unstable();
+            ids.dedup();
+            return ids.into_iter().map(PieceId::from).collect();
+        }
+        let mut seen = vec![0u64; (k as usize).div_ceil(64)];
+        for id in ids {
+            seen[id as usize / 64] |= 1 << (id % 64);
+        }
+        let mut sorted = vec![];
+        for (w, &word) in seen.iter().enumerate() {
+            let mut word = word;
+            while word != 0 {
+                sorted.push(PieceId::from(w as u64 * 64 + word.trailing_zeros() as u64));
+                word &= word - 1;
+            }
+        }
+        sorted
+    }
+
+    /// Iterates over the occurrences grouped by the pieces containing them,
+    /// in ascending order of piece IDs and then of rows.
+    ///
+    /// The occurrences are sorted by their pieces instead of being collected into a map.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::SuffixOrderSampler;
+    /// use fm_index::{BackwardSearchIndex, FMIndexMultiPieces, PieceId};
+    ///
+    /// let text = b"banana\0apple\0ananas".to_vec();
+    /// let index = FMIndexMultiPieces::new(text, RangeConverter::new(b'a', b'z'), SuffixOrderSampler::new());
+    /// let groups = index
+    ///     .search_backward("ana")
+    ///     .iter_matches_by_piece()
+    ///     .map(|(id, matches)| {
+    ///         let mut positions = matches.map(|m| m.locate()).collect::<Vec<_>>();
+    ///         positions.sort();
+    ///         (id, positions)
+    ///     })
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(groups, vec![(PieceId::from(0), vec![1, 3]), (PieceId::from(2), vec![13, 15])]);
+    /// ```
+    pub fn iter_matches_by_piece(
+        &self,
+    ) -> impl Iterator<Item = (PieceId, impl Iterator<Item = Match<'a, I>>)> {
+        let index = self.index;
+        let len = self.pattern_len as u64;
+        let mut rows = self
+            .iter_matches()
+            .map(|m| (m.piece_id(), m.i))
+            .collect::<Vec<_>>();
+        rows.sort_unstable();
+        let mut groups: Vec<(PieceId, Vec<u64>)> = vec![];
+        for (id, i) in rows {
+            match groups.last_mut() {
+                Some((last, group)) if *last == id => group.push(i),
+                _ => groups.push((id, vec![i])),
+            }
+        }
+        groups
+            .into_iter()
+            .map(move |(id, group)| (id, group.into_iter().map(move |i| Match { index, i, len })))
+    }
+}
+
+impl<'a, I> Search<'a, I>
+where
+    I: BackwardSearchIndex + ForwardIterableIndex + IndexWithPieces,
+{
+    /// Iterates characters forward like [`Search::iter_forward`],
+    /// but stops at the end of the piece containing the `i`-th occurrence.
+    pub fn iter_forward_in_piece(&self, i: u64) -> PieceIterator<ForwardIterator<'_, I>> {
+        PieceIterator::new(self.iter_forward(i))
+    }
+}
+
+impl<'a, I> Search<'a, I>
+where
+    I: BackwardSearchIndex + IndexWithConverter<<I as BackwardIterableIndex>::T>,
+    I::T: Character,
+{
+    /// Counts the occurrences of each character preceding the occurrences,
+    /// i.e. the left extensions of the pattern by one character, which backward search can take next.
+    ///
+    /// Characters which do not occur and the terminator are omitted.
+    /// This takes _O(σ)_ rank queries and does not enumerate the occurrences.
+    /// For the characters following the occurrences (right extensions),
+    /// see [`crate::BidirectionalIndex::right_extension_counts`].
+    pub fn left_extension_counts(&self) -> Vec<(I::T, u64)> {
+        let converter = self.index.get_converter();
+        let mut counts = vec![];
+        if self.s == self.e {
+            return counts;
+        }
+        for code in 1..converter.len() {
+            let c = converter.convert_inv(I::T::from_u64(code));
+            // skip characters that are merged into another one by the converter
+            if converter.convert(c).into() != code {
+                continue;
+            }
+            let n = self.index.lf_map2(c, self.e) - self.index.lf_map2(c, self.s);
+            if n > 0 {
+                counts.push((c, n));
+            }
+        }
+        counts
+    }
 }
 
 impl<'a, I> Search<'a, I>
 where
     I: BackwardSearchIndex + IndexWithSA,
 {
+    /// Lists the positions of all occurrences in the order of [`Search::iter_matches`].
     pub fn locate(&self) -> Vec<u64> {
+        let mut results: Vec<u64> = Vec::with_capacity((self.e - self.s) as usize);
+        self.locate_into(&mut results);
+        results
+    }
+
+    /// Saves the positions of all occurrences as a sorted set to combine them with those of other searches.
+    pub fn position_set(&self) -> PositionSet {
+        PositionSet::from(self.locate())
+    }
+
+    /// Iterates over the positions of all occurrences in ascending order,
+    /// to merge them with those of other searches by [`crate::merge_unique`].
+    ///
+    /// It is eager: all the positions are located and sorted when this is called,
+    /// so only the merge of the iterators is lazy.
+    pub fn iter_positions_sorted(&self) -> std::vec::IntoIter<u64> {
+        let mut positions = self.locate();
+        positions.sort_unstable();
+        positions.into_iter()
+    }
+
+    /// Lists the positions of all occurrences like [`Search::locate`], typed as positions in the whole text.
+    pub fn locate_global(&self) -> Vec<GlobalPos> {
+        self.iter_matches().map(|m| m.locate_global()).collect()
+    }
+
+    /// Replaces the contents of `results` with the positions of all occurrences.
+    ///
+    /// No allocation happens if `results` has enough capacity.
+    pub fn locate_into(&self, results: &mut Vec<u64>) {
+        SearchState {
+            s: self.s,
+            e: self.e,
+        }
+        .locate_into(self.index, results);
+    }
+
+    /// Counts the occurrences in each bucket of `bucket_size` text positions,
+    /// without collecting their positions.
+    ///
+    /// The `k`-th element is the number of occurrences at positions `[k * bucket_size, (k + 1) * bucket_size)`,
+    /// and there is a bucket for every position of the text including the terminator.
+    pub fn position_histogram(&self, bucket_size: u64) -> Vec<u64> {
+        assert!(bucket_size > 0, "bucket_size must be positive");
+        let mut histogram = vec![0; self.index.len().div_ceil(bucket_size) as usize];
+        for k in self.s..self.e {
+            histogram[(self.index.get_sa(k) / bucket_size) as usize] += 1;
+        }
+        histogram
+    }
+
+    /// Lists the positions of the occurrences like [`Search::locate`], but of at most as many as `budget` allows.
+    ///
+    /// Each position counts as a resolved position and an iterated match, and takes 8 bytes of the result buffer.
+    pub fn locate_within(&self, budget: &QueryBudget) -> PartialResult<Vec<u64>> {
+        let (n, exhausted) = budget.admit(
+            self.count(),
+            std::mem::size_of::<u64>(),
+            &[
+                BudgetLimit::Positions,
+                BudgetLimit::Matches,
+                BudgetLimit::Bytes,
+            ],
+        );
+        PartialResult {
+            result: (self.s..self.s + n).map(|k| self.index.get_sa(k)).collect(),
+            exhausted,
+        }
+    }
+
+    /// Lists the positions of all occurrences with at most `limit` steps of LF-mapping in total.
+    ///
+    /// Locating an occurrence takes up to `2^L` steps for sampling level `L`,
+    /// so this bounds the worst-case latency of a query.
+    pub fn locate_with_budget(&self, limit: u64) -> Result<Vec<u64>, StepLimitExceeded> {
+        let mut budget = limit;
         let mut results: Vec<u64> = Vec::with_capacity((self.e - self.s) as usize);
         for k in self.s..self.e {
-            results.push(self.index.get_sa(k));
+            match self.index.get_sa_with_budget(k, &mut budget) {
+                Some(sa) => results.push(sa),
+                None => return Err(StepLimitExceeded { limit }),
+            }
+        }
+        Ok(results)
+    }
+
+    /// Lists the positions of `k` occurrences chosen uniformly at random without replacement,
+    /// or of all occurrences if there are at most `k`, in the order of [`Search::iter_matches`].
+    ///
+    /// Only the chosen rows are located, so this takes _O(k)_ locate operations however many occurrences there are,
+    /// e.g. for estimating statistics over a huge result set.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::SuffixOrderSampler;
+    /// use fm_index::{BackwardSearchIndex, FMIndex};
+    /// use rand::SeedableRng;
+    ///
+    /// let text = b"abracadabra".repeat(100);
+    /// let index = FMIndex::new(text, RangeConverter::new(b'a', b'z'), SuffixOrderSampler::new().level(2));
+    /// let mut rng = rand::rngs::StdRng::from_seed([0; 32]);
+    /// let positions = index.search_backward("cad").sample_positions(10, &mut rng);
+    /// assert_eq!(positions.len(), 10);
+    /// assert!(positions.iter().all(|p| p % 11 == 4));
+    /// ```
+    #[cfg(feature = "rand")]
+    pub fn sample_positions<R: rand::Rng + ?Sized>(&self, k: usize, rng: &mut R) -> Vec<u64> {
+        let m = self.count();
+        let k = (k as u64).min(m);
+        // Floyd's algorithm chooses k distinct rows out of m with k random numbers
+        let mut rows = std::collections::BTreeSet::new();
+        for j in m - k..m {
+            let t = rng.gen_range(0, j + 1);
+            if !rows.insert(t) {
+                rows.insert(j);
+            }
+        }
+        rows.into_iter()
+            .map(|i| self.index.get_sa(self.s + i))
+            .collect()
+    }
+}
+
+impl<'a, I> Search<'a, I>
+where
+    I: BackwardSearchIndex + IndexWithISA,
+{
+    /// Returns true if the pattern occurs at position `p`.
+    ///
+    /// Instead of matching the pattern again, this finds the row of `p` and checks that it is in the range.
+    /// It takes at most `2^L` steps of LF-mapping for sampling level `L`
+    /// regardless of the number of occurrences.
+    pub fn verify(&self, p: u64) -> bool {
+        if p >= self.index.len() || self.s == self.e {
+            return false;
+        }
+        let i = self.index.get_isa(p);
+        self.s <= i && i < self.e
+    }
+
+    /// Filters candidate positions (e.g. from an external filter) to those where the pattern occurs.
+    pub fn verify_candidates<K>(&self, candidates: K) -> impl Iterator<Item = u64> + '_
+    where
+        K: IntoIterator<Item = u64>,
+        K::IntoIter: 'a,
+    {
+        candidates.into_iter().filter(move |&p| self.verify(p))
+    }
+}
+
+/// An occurrence of a pattern.
+pub struct Match<'a, I> {
+    index: &'a I,
+    i: u64,
+    // the length of the pattern
+    len: u64,
+}
+
+impl<'a, I> Match<'a, I>
+where
+    I: BackwardIterableIndex,
+{
+    /// Returns the row of the suffix array of the occurrence.
+    ///
+    /// It identifies the occurrence within the index,
+    /// and sorting by it gives the order of [`Search::iter_matches`].
+    pub fn match_id(&self) -> u64 {
+        self.i
+    }
+
+    pub(crate) fn index(&self) -> &'a I {
+        self.index
+    }
+
+    /// Returns the length of the occurrence, i.e. of the pattern searched.
+    pub fn pattern_len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn iter_backward(&self) -> BackwardIterator<'a, I> {
+        self.index.iter_backward(self.i)
+    }
+}
+
+impl<'a, I> Match<'a, I>
+where
+    I: ForwardIterableIndex,
+{
+    pub fn iter_forward(&self) -> ForwardIterator<'a, I> {
+        self.index.iter_forward(self.i)
+    }
+}
+
+impl<'a, I> Match<'a, I>
+where
+    I: IndexWithSA,
+{
+    pub fn locate(&self) -> u64 {
+        self.index.get_sa(self.i)
+    }
+
+    pub fn locate_global(&self) -> GlobalPos {
+        GlobalPos::from(self.locate())
+    }
+
+    /// Extracts the occurrence with up to `before` and `after` characters around it from `store`,
+    /// decompressing only the blocks needed.
+    pub fn context<C: BlockCodec>(&self, store: &TextStore<C>, before: u64, after: u64) -> Vec<u8> {
+        let p = self.locate();
+        store.extract(p.saturating_sub(before)..p + self.len + after)
+    }
+}
+
+impl<'a, I> Match<'a, I>
+where
+    I: IndexWithPieces,
+{
+    pub fn piece_id(&self) -> PieceId {
+        self.index.piece_id(self.i)
+    }
+}
+
+impl<'a, I> Match<'a, I>
+where
+    I: BackwardIterableIndex + IndexWithPieces,
+{
+    /// Iterates characters backward from the occurrence to the start of its piece.
+    pub fn iter_backward_in_piece(&self) -> PieceIterator<BackwardIterator<'a, I>> {
+        PieceIterator::new(self.iter_backward())
+    }
+}
+
+impl<'a, I> Match<'a, I>
+where
+    I: ForwardIterableIndex + IndexWithPieces,
+{
+    /// Iterates characters forward from the occurrence to the end of its piece.
+    pub fn iter_forward_in_piece(&self) -> PieceIterator<ForwardIterator<'a, I>> {
+        PieceIterator::new(self.iter_forward())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ambiguity::Iupac;
+    use crate::converter::RangeConverter;
+    use crate::suffix_array::{ExtremaSampler, NullSampler, SuffixOrderSampler, TextOrderSampler};
+    use crate::{FMIndex, FMIndexMultiPieces, HybridFMIndex, RLFMIndex};
+
+    #[test]
+    fn test_search_in_place() {
+        let text = "mississippi".to_string().into_bytes();
+        let index = FMIndex::new(
+            text,
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(1),
+        );
+        let mut state = SearchState::new(&index);
+        let mut results = Vec::with_capacity(16);
+        for pattern in &["ssi", "i", "mi", "pp", "x", ""] {
+            state.reset(&index);
+            index.search_in_place(&mut state, pattern);
+            let search = index.search_backward(pattern);
+            assert_eq!(state.get_range(), search.get_range());
+            assert_eq!(state.count(), search.count());
+
+            state.locate_into(&index, &mut results);
+            assert_eq!(results, search.locate());
+            search.locate_into(&mut results);
+            assert_eq!(results, search.locate());
+        }
+
+        // chaining
+        state.reset(&index);
+        index.search_in_place(&mut state, "si");
+        index.search_in_place(&mut state, "is");
+        assert_eq!(state.get_range(), index.search_backward("issi").get_range());
+    }
+
+    #[test]
+    fn test_sa_interval() {
+        let a = SaInterval::new(2, 6);
+        assert_eq!(a.len(), 4);
+        assert!(a.contains(2) && !a.contains(6));
+        assert_eq!(a.intersect(SaInterval::new(4, 9)), SaInterval::new(4, 6));
+        assert!(a.intersect(SaInterval::new(7, 9)).is_empty());
+        assert_eq!(a.rows().collect::<Vec<_>>(), vec![2, 3, 4, 5]);
+        assert_eq!(<(u64, u64)>::from(a), (2, 6));
+    }
+
+    #[test]
+    fn test_verify() {
+        let text = "mississippi".to_string().into_bytes();
+        let fm_index = FMIndex::new(
+            text.clone(),
+            RangeConverter::new(b'a', b'z'),
+            TextOrderSampler::new().level(2),
+        );
+        let rlfm_index = RLFMIndex::new(
+            text,
+            RangeConverter::new(b'a', b'z'),
+            TextOrderSampler::new().level(1),
+        );
+        for i in 0..fm_index.len() {
+            assert_eq!(fm_index.get_isa(fm_index.get_sa(i)), i);
+            assert_eq!(rlfm_index.get_isa(rlfm_index.get_sa(i)), i);
+        }
+
+        let search = fm_index.search_backward("ssi");
+        assert!(search.verify(2));
+        assert!(!search.verify(3));
+        assert!(!search.verify(100));
+        assert_eq!(
+            search.verify_candidates(0..12).collect::<Vec<_>>(),
+            vec![2, 5]
+        );
+        let search = rlfm_index.search_backward("i");
+        assert_eq!(
+            search
+                .verify_candidates(vec![10, 0, 7, 3])
+                .collect::<Vec<_>>(),
+            vec![10, 7]
+        );
+    }
+
+    #[test]
+    fn test_iter_matches() {
+        let text = "mississippi".to_string().into_bytes();
+        let index = FMIndex::new(
+            text,
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(1),
+        );
+        let search = index.search_backward("i");
+        let (s, e) = search.get_range().into();
+        let matches = search.iter_matches().collect::<Vec<_>>();
+        assert_eq!(
+            matches.iter().map(|m| m.match_id()).collect::<Vec<_>>(),
+            (s..e).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            matches.iter().map(|m| m.locate()).collect::<Vec<_>>(),
+            search.locate()
+        );
+        // suffixes are in lexicographic order
+        assert_eq!(search.locate(), vec![10, 7, 4, 1]);
+        assert_eq!(
+            matches[3].iter_forward().take(4).collect::<Vec<_>>(),
+            b"issi".to_owned()
+        );
+        assert_eq!(matches[0].iter_backward().next(), Some(b'p'));
+    }
+
+    #[test]
+    fn test_pattern() {
+        let text = "mississippi".to_string().into_bytes();
+        let index = FMIndex::new(
+            text,
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new(),
+        );
+        let search = index.search_backward("");
+        assert_eq!(search.pattern_len(), 0);
+        assert_eq!(search.pattern(), b"");
+        let search = search
+            .search_backward("i")
+            .search_backward("")
+            .search_backward("ss");
+        let refined = search.search_backward("mi");
+        assert_eq!(search.pattern_len(), 3);
+        assert_eq!(search.pattern(), b"ssi");
+        assert_eq!(refined.pattern_len(), 5);
+        assert_eq!(refined.pattern(), b"missi");
+        assert_eq!(refined.count(), 1);
+    }
+
+    #[test]
+    fn test_search_prefix_suffix_exact() {
+        fn check<I>(index: &I)
+        where
+            I: BackwardSearchIndex<T = u8> + IndexWithSA,
+        {
+            assert_eq!(index.search_prefix("miss").locate(), vec![0]);
+            assert_eq!(index.search_prefix("iss").count(), 0);
+            assert_eq!(index.search_prefix("").count(), 1);
+            assert_eq!(index.search_suffix("ppi").locate(), vec![8]);
+            assert_eq!(index.search_suffix("pp").count(), 0);
+            assert_eq!(index.search_suffix("").locate(), vec![11]);
+            assert_eq!(index.search_exact("mississippi").locate(), vec![0]);
+            assert_eq!(index.search_exact("mississipp").count(), 0);
+            assert_eq!(index.search_exact("ississippi").count(), 0);
+        }
+        let text = "mississippi".to_string().into_bytes();
+        let converter = RangeConverter::new(b'a', b'z');
+        check(&FMIndex::new(
+            text.clone(),
+            converter.clone(),
+            SuffixOrderSampler::new(),
+        ));
+        check(&RLFMIndex::new(text, converter, SuffixOrderSampler::new()));
+
+        let text = "ab\0ba\0ab".to_string().into_bytes();
+        let index = FMIndexMultiPieces::new(
+            text,
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new(),
+        );
+        assert_eq!(index.search_prefix("a").count(), 2);
+        assert_eq!(index.search_suffix("a").locate(), vec![4]);
+        assert_eq!(index.search_exact("ab").count(), 2);
+        assert_eq!(index.search_exact("a").count(), 0);
+    }
+
+    #[test]
+    fn test_piece_ids_sorted() {
+        let mut pieces = (0..200).map(|i| format!("p{}", i)).collect::<Vec<_>>();
+        pieces[3] = "aaa".to_string();
+        pieces[150] = "baa".to_string();
+        pieces[70] = "aab".to_string();
+        let text = pieces.join("\0").into_bytes();
+        let index = FMIndexMultiPieces::new(
+            text,
+            RangeConverter::new(b'0', b'z'),
+            SuffixOrderSampler::new(),
+        );
+        let ids = |ids: &[u64]| ids.iter().map(|&i| PieceId::from(i)).collect::<Vec<_>>();
+        assert_eq!(
+            index.search_backward("aa").piece_ids_sorted(),
+            ids(&[3, 70, 150])
+        );
+        let expected = (0..200)
+            .filter(|&i| pieces[i].contains('1'))
+            .map(|i| i as u64)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            index.search_backward("1").piece_ids_sorted(),
+            ids(&expected)
+        );
+        assert_eq!(index.search_backward("ba").piece_ids_sorted(), ids(&[150]));
+        assert_eq!(index.search_backward("x").piece_ids_sorted(), vec![]);
+    }
+
+    #[test]
+    fn test_suggest() {
+        let text = b"the cat sat on the mat with the hat".to_vec();
+        let index = FMIndex::new(
+            text.clone(),
+            RangeConverter::new(b' ', b'z'),
+            NullSampler::new(),
+        );
+        let count = |p: &[u8]| {
+            (0..=text.len() - p.len())
+                .filter(|&i| text[i..].starts_with(p))
+                .count() as u64
+        };
+        for pattern in &["thw", "xat", "caat", "th", "q", "with"] {
+            let pattern = pattern.as_bytes();
+            let mut expected = vec![];
+            for j in 0..pattern.len() {
+                if pattern.len() > 1 {
+                    let mut p = pattern.to_vec();
+                    p.remove(j);
+                    expected.push(p);
+                }
+                for c in b' '..=b'z' {
+                    if c != pattern[j] {
+                        let mut p = pattern.to_vec();
+                        p[j] = c;
+                        expected.push(p);
+                    }
+                }
+            }
+            expected.sort();
+            expected.dedup();
+            let mut expected = expected
+                .into_iter()
+                .map(|p| Suggestion {
+                    count: count(&p),
+                    pattern: p,
+                })
+                .filter(|s| s.count > 0)
+                .collect::<Vec<_>>();
+            expected.sort_by(|a, b| {
+                b.count
+                    .cmp(&a.count)
+                    .then_with(|| a.pattern.cmp(&b.pattern))
+            });
+            assert_eq!(
+                index.suggest(pattern, usize::MAX),
+                expected,
+                "{:?}",
+                pattern
+            );
+            expected.truncate(2);
+            assert_eq!(index.suggest(pattern, 2), expected);
+        }
+        assert!(index.suggest("qqq", 10).is_empty());
+    }
+
+    #[test]
+    fn test_iter_matches_by_piece() {
+        let pieces = ["banana", "apple", "ananas", "cabana", "nan"];
+        let index = FMIndexMultiPieces::new(
+            pieces.join("\0").into_bytes(),
+            RangeConverter::new(b'a', b'z'),
+            NullSampler::new(),
+        );
+        for pattern in &["a", "an", "na", "p", "x", "a\0a"] {
+            let search = index.search_backward(pattern);
+            let mut expected: HashMap<PieceId, Vec<u64>> = HashMap::new();
+            for m in search.iter_matches() {
+                expected.entry(m.piece_id()).or_default().push(m.match_id());
+            }
+            let mut expected = expected.into_iter().collect::<Vec<_>>();
+            expected.sort();
+            let groups = search
+                .iter_matches_by_piece()
+                .map(|(id, matches)| (id, matches.map(|m| m.match_id()).collect::<Vec<_>>()))
+                .collect::<Vec<_>>();
+            assert_eq!(groups, expected, "pattern {:?}", pattern);
+        }
+
+        let index = FMIndex::new(
+            b"banana".to_vec(),
+            RangeConverter::new(b'a', b'z'),
+            NullSampler::new(),
+        );
+        let groups = index
+            .search_backward("an")
+            .iter_matches_by_piece()
+            .map(|(id, matches)| (id, matches.map(|m| m.pattern_len()).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        assert_eq!(groups, vec![(PieceId::from(0), vec![2, 2])]);
+    }
+
+    #[test]
+    fn test_search_ambiguous() {
+        let text = b"ACGTTGCANNACGGTACCAGTRTA".to_vec();
+        let index = FMIndex::new(
+            text.clone(),
+            RangeConverter::new(b'A', b'Z'),
+            SuffixOrderSampler::new(),
+        );
+        let locate = |searches: Vec<Search<_>>| {
+            let mut positions = searches.iter().flat_map(|s| s.locate()).collect::<Vec<_>>();
+            positions.sort();
+            positions
+        };
+        for pattern in &["N", "AC", "RYN", "GTNA", "NNNN", "ACW", "NR", "X"] {
+            let expected = (0..=text.len() - pattern.len())
+                .filter(|&i| {
+                    pattern
+                        .bytes()
+                        .enumerate()
+                        .all(|(k, c)| Iupac.expand(c).contains(&text[i + k]))
+                })
+                .map(|i| i as u64)
+                .collect::<Vec<_>>();
+            let searches = index.search_ambiguous(pattern, &Iupac, 1000).unwrap();
+            assert_eq!(locate(searches), expected, "pattern = {:?}", pattern);
+        }
+
+        // a literal N in the text is matched only by a closure that maps N to itself
+        let searches = index.search_ambiguous("NN", &|c| vec![c], 1).unwrap();
+        assert_eq!(locate(searches), vec![8]);
+        assert_eq!(
+            index.search_ambiguous("NNN", &Iupac, 10).err(),
+            Some(BranchLimitExceeded { limit: 10 })
+        );
+    }
+
+    #[test]
+    fn test_len_and_pieces_count() {
+        fn check<I>(index: &I, text_len: u64, pieces_count: u64)
+        where
+            I: BackwardSearchIndex<T = u8> + IndexWithPieces,
+        {
+            assert_eq!(index.text_len_without_terminator(), text_len);
+            assert!(!BackwardIterableIndex::is_empty(index));
+            assert_eq!(index.pieces_count(), pieces_count);
+            assert_eq!(
+                index.search_backward("b").piece_ids_sorted().len() as u64,
+                pieces_count
+            );
+        }
+        let text = "ab\0ba\0ab".to_string().into_bytes();
+        let converter = RangeConverter::new(b'a', b'z');
+        check(
+            &FMIndex::new(text.clone(), converter.clone(), SuffixOrderSampler::new()),
+            8,
+            1,
+        );
+        check(
+            &RLFMIndex::new(text.clone(), converter.clone(), SuffixOrderSampler::new()),
+            8,
+            1,
+        );
+        check(
+            &HybridFMIndex::new(text.clone(), converter.clone(), SuffixOrderSampler::new()),
+            8,
+            1,
+        );
+        check(
+            &FMIndexMultiPieces::new(text, converter.clone(), SuffixOrderSampler::new()),
+            8,
+            3,
+        );
+
+        let index = FMIndex::new(vec![0u8], converter, NullSampler::new());
+        assert!(BackwardIterableIndex::is_empty(&index));
+        assert_eq!(index.text_len_without_terminator(), 0);
+    }
+
+    #[test]
+    fn test_pattern_out_of_alphabet() {
+        fn check<I: BackwardSearchIndex<T = u8>>(index: &I) {
+            assert_eq!(index.search_backward("ssi").count(), 2);
+            for pattern in ["ssX", "Xssi", "s~i", "{", "ss\u{7f}"] {
+                let search = index.search_backward(pattern);
+                assert_eq!(search.count(), 0, "pattern = {:?}", pattern);
+                assert!(search.get_range().is_empty());
+            }
+        }
+        let text = "mississippi".to_string().into_bytes();
+        let converter = RangeConverter::new(b'a', b'z');
+        check(&FMIndex::new(
+            text.clone(),
+            converter.clone(),
+            SuffixOrderSampler::new(),
+        ));
+        check(&RLFMIndex::new(
+            text.clone(),
+            converter.clone(),
+            SuffixOrderSampler::new(),
+        ));
+        check(&HybridFMIndex::new(
+            text.clone(),
+            converter.clone(),
+            SuffixOrderSampler::new(),
+        ));
+        check(&FMIndexMultiPieces::new(
+            text,
+            converter,
+            SuffixOrderSampler::new(),
+        ));
+    }
+
+    #[test]
+    fn test_min_max_position() {
+        let text = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua.".to_string().into_bytes();
+        for level in 0..4 {
+            let index = FMIndex::new(
+                text.clone(),
+                RangeConverter::new(b' ', b'~'),
+                ExtremaSampler::new(SuffixOrderSampler::new().level(2)).level(level),
+            );
+            for pattern in &["o", "e", "or", "dolor", "t", " ", "x", ""] {
+                let search = index.search_backward(pattern);
+                let positions = search.locate();
+                assert_eq!(search.min_position(), positions.iter().min().copied());
+                assert_eq!(search.max_position(), positions.iter().max().copied());
+            }
+        }
+    }
+
+    #[test]
+    fn test_position_histogram() {
+        let text = "mississippi".to_string().into_bytes();
+        let index = FMIndex::new(
+            text,
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(2),
+        );
+        let search = index.search_backward("i");
+        assert_eq!(search.position_histogram(4), vec![1, 2, 1]);
+        assert_eq!(search.position_histogram(100), vec![4]);
+        assert_eq!(
+            index.search_backward("s").position_histogram(1),
+            vec![0, 0, 1, 1, 0, 1, 1, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(index.search_backward("x").position_histogram(5), vec![0; 3]);
+    }
+
+    #[test]
+    fn test_contains() {
+        let text = b"abracadabra mississippi".to_vec();
+        let converter = RangeConverter::new(b' ', b'z');
+        let fmi = FMIndex::new(text.clone(), converter.clone(), NullSampler::new());
+        let rlfmi = RLFMIndex::new(text.clone(), converter.clone(), NullSampler::new());
+        let pieces = FMIndexMultiPieces::new(
+            b"abracadabra\0mississippi".to_vec(),
+            converter,
+            NullSampler::new(),
+        );
+        for pattern in [
+            "a",
+            "cad",
+            "ra m",
+            "ssippi",
+            "",
+            "x",
+            "zz",
+            "abracadabrab",
+            "a\0m",
+        ] {
+            let expected = fmi.search_backward(pattern).count() > 0;
+            assert_eq!(fmi.contains(pattern), expected, "{:?}", pattern);
+            assert_eq!(rlfmi.contains(pattern), expected, "{:?}", pattern);
+            assert_eq!(
+                pieces.contains(pattern),
+                pieces.search_backward(pattern).count() > 0,
+                "{:?}",
+                pattern
+            );
+        }
+        assert!(pieces.contains("a\0m"));
+        assert!(!pieces.contains("ra m"));
+    }
+
+    #[test]
+    fn test_try_search_backward() {
+        let text = "ab".repeat(5000).into_bytes();
+        let index = FMIndex::new(
+            text.clone(),
+            RangeConverter::new(b'a', b'b'),
+            NullSampler::new(),
+        );
+        // a long chain of refinements, each bounded by the length of the text
+        let mut search = index.try_search_backward("b", text.len()).unwrap();
+        for k in 1..text.len() {
+            let c = if k % 2 == 1 { "a" } else { "b" };
+            search = search.try_search_backward(c, text.len()).unwrap();
+        }
+        assert_eq!(search.pattern_len(), text.len());
+        assert_eq!(search.count(), 1);
+        assert_eq!(search.pattern(), text);
+        assert_eq!(
+            search.try_search_backward("b", text.len()).err(),
+            Some(PatternTooLong {
+                len: text.len() + 1,
+                max_len: text.len()
+            })
+        );
+        assert!(search.try_search_backward("", text.len()).is_ok());
+        assert!(index.try_search_backward("abab", 3).is_err());
+    }
+
+    #[test]
+    fn test_validate_pattern() {
+        let converter = RangeConverter::new(b'a', b'z');
+        let pieces = FMIndexMultiPieces::new(
+            b"apple\0banana".to_vec(),
+            converter.clone(),
+            NullSampler::new(),
+        );
+        let rlfmi = RLFMIndex::new(b"banana".to_vec(), converter, NullSampler::new());
+        for (pattern, expected) in [
+            ("nana", Ok(())),
+            ("", Err(InvalidPattern::Empty)),
+            ("Banana", Err(InvalidPattern::OutOfRange { position: 0 })),
+            ("e\0b", Err(InvalidPattern::Separator { position: 1 })),
+            ("\0{", Err(InvalidPattern::Separator { position: 0 })),
+        ] {
+            assert_eq!(pieces.validate_pattern(pattern), expected, "{:?}", pattern);
+            assert_eq!(rlfmi.validate_pattern(pattern), expected, "{:?}", pattern);
+        }
+        assert_eq!(pieces.validate_pattern_with_separators("e\0b"), Ok(()));
+        assert!(pieces.search_backward("e\0b").count() > 0);
+        assert_eq!(
+            pieces.validate_pattern_with_separators("\0{"),
+            Err(InvalidPattern::OutOfRange { position: 1 })
+        );
+        assert_eq!(
+            pieces.validate_pattern_with_separators(""),
+            Err(InvalidPattern::Empty)
+        );
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_sample_positions() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let text = b"abcab".repeat(40);
+        let index = RLFMIndex::new(
+            text,
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(2),
+        );
+        let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+        let search = index.search_backward("ab");
+        let all = search.locate();
+        let mut hits = vec![0; all.len()];
+        for _ in 0..200 {
+            let positions = search.sample_positions(5, &mut rng);
+            assert_eq!(positions.len(), 5);
+            let mut ks = positions
+                .iter()
+                .map(|p| all.iter().position(|q| q == p).unwrap())
+                .collect::<Vec<_>>();
+            // distinct occurrences in the order of rows
+            assert!(ks.windows(2).all(|w| w[0] < w[1]));
+            for k in ks.drain(..) {
+                hits[k] += 1;
+            }
+        }
+        // every occurrence is chosen about 200 * 5 / 80 = 12.5 times
+        assert!(hits.iter().all(|&h| h > 0));
+        assert_eq!(search.sample_positions(1000, &mut rng), all);
+        assert!(index
+            .search_backward("x")
+            .sample_positions(3, &mut rng)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_ngram_counts() {
+        let text = "mississippi".to_string().into_bytes();
+        let index = FMIndex::new(
+            text,
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(1),
+        );
+        let corpus = b"missing".to_vec();
+        let counts = index.ngram_counts(&corpus, 3);
+        assert_eq!(counts.len(), 5);
+        assert_eq!(counts[&b"mis".to_vec()], 1);
+        assert_eq!(counts[&b"iss".to_vec()], 2);
+        assert_eq!(counts[&b"ssi".to_vec()], 2);
+        assert_eq!(counts[&b"sin".to_vec()], 0);
+        assert_eq!(counts[&b"ing".to_vec()], 0);
+
+        let mut visited = vec![];
+        index.for_each_ngram_count(b"sip", 2, |gram, count| {
+            visited.push((gram.to_vec(), count))
+        });
+        assert_eq!(
+            visited,
+            vec![
+                (b"s".to_vec(), 4),
+                (b"i".to_vec(), 4),
+                (b"si".to_vec(), 2),
+                (b"p".to_vec(), 2),
+                (b"ip".to_vec(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_search_near() {
+        let text = b"abcab cab bca abab c".to_vec();
+        let index = FMIndex::new(
+            text.clone(),
+            RangeConverter::new(b' ', b'z'),
+            SuffixOrderSampler::new().level(1),
+        );
+        let occurrences = |p: &[u8]| {
+            (0..text.len())
+                .filter(|&i| text[i..].starts_with(p))
+                .map(|i| i as u64)
+                .collect::<Vec<_>>()
+        };
+        let patterns: Vec<&[u8]> = vec![b"ab", b"c", b"ca", b"b c", b"x"];
+        for a in &patterns {
+            for b in &patterns {
+                for d in 0..6 {
+                    let expected = occurrences(a)
+                        .into_iter()
+                        .filter(|&p| occurrences(b).iter().any(|&q| q.max(p) - q.min(p) <= d))
+                        .collect::<Vec<_>>();
+                    assert_eq!(
+                        index.search_near(a, b, d),
+                        expected,
+                        "{:?} near {:?} within {}",
+                        a,
+                        b,
+                        d
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_matching_statistics() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+        let text = (0..200)
+            .map(|_| rng.gen_range(b'a', b'e'))
+            .collect::<Vec<_>>();
+        let index = FMIndex::new(
+            text.clone(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(1),
+        );
+        let occurs = |p: &[u8]| p.is_empty() || text.windows(p.len()).any(|w| w == p);
+        for _ in 0..10 {
+            let query = (0..30)
+                .map(|_| rng.gen_range(b'a', b'f'))
+                .collect::<Vec<_>>();
+            let stats = index.matching_statistics(&query);
+            for (i, stat) in stats.iter().enumerate() {
+                let expected = (0..=query.len() - i)
+                    .rev()
+                    .find(|&l| occurs(&query[i..i + l]))
+                    .unwrap();
+                assert_eq!(stat.len, expected as u64, "{:?} at {}", query, i);
+                assert_eq!(
+                    stat.range,
+                    index.search_backward(&query[i..i + expected]).get_range()
+                );
+                if let Some(p) = stat.locate_one(&index) {
+                    let p = p as usize;
+                    assert_eq!(&text[p..p + expected], &query[i..i + expected]);
+                }
+            }
         }
-        results
     }
 }
@@ -0,0 +1,177 @@
+use crate::search::{BackwardSearchIndex, Search};
+
+use std::collections::VecDeque;
+use std::thread;
+
+/// A pull-based stream of query results over a stream of patterns.
+///
+/// Patterns are taken from the underlying iterator in batches only when the results
+/// are pulled, so a slow consumer never lets patterns pile up (backpressure).
+/// A batch may be processed by several threads, and the results are returned
+/// in the order of the patterns.
+///
+/// In an async service, a session can be driven from a blocking task,
+/// e.g. pulling a batch of results per `spawn_blocking` call.
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::SuffixOrderSampler;
+/// use fm_index::{FMIndex, QuerySession};
+///
+/// let index = FMIndex::new(
+///     b"mississippi".to_vec(),
+///     RangeConverter::new(b'a', b'z'),
+///     SuffixOrderSampler::new().level(2),
+/// );
+/// let patterns = vec!["ssi", "i", "pp", "x"].into_iter();
+/// let counts = QuerySession::new(&index, patterns, |search| search.count())
+///     .batch_size(2)
+///     .threads(2)
+///     .collect::<Vec<_>>();
+/// assert_eq!(counts, vec![2, 4, 1, 0]);
+/// ```
+pub struct QuerySession<'a, I, P, F, R> {
+    index: &'a I,
+    patterns: P,
+    f: F,
+    batch_size: usize,
+    threads: usize,
+    results: VecDeque<R>,
+}
+
+impl<'a, I, P, F, R> QuerySession<'a, I, P, F, R>
+where
+    I: BackwardSearchIndex + Sync,
+    I::T: Send + Sync,
+    P: Iterator,
+    P::Item: AsRef<[I::T]> + Sync,
+    F: Fn(Search<'a, I>) -> R + Sync,
+    R: Send,
+{
+    /// Creates a session which applies `f` to the search of each pattern.
+    pub fn new(index: &'a I, patterns: P, f: F) -> Self {
+        QuerySession {
+            index,
+            patterns,
+            f,
+            batch_size: 64,
+            threads: 1,
+            results: VecDeque::new(),
+        }
+    }
+
+    /// Sets the number of patterns taken at a time, which is 64 by default.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch_size must be positive");
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Sets the number of threads processing a batch, which is 1 by default.
+    pub fn threads(mut self, threads: usize) -> Self {
+        assert!(threads > 0, "threads must be positive");
+        self.threads = threads;
+        self
+    }
+
+    /// Takes the next batch of patterns and processes them.
+    /// Returns false if there are no more patterns.
+    fn fill(&mut self) -> bool {
+        let batch = self
+            .patterns
+            .by_ref()
+            .take(self.batch_size)
+            .collect::<Vec<_>>();
+        if batch.is_empty() {
+            return false;
+        }
+        let index = self.index;
+        let f = &self.f;
+        let run = |patterns: &[P::Item]| {
+            patterns
+                .iter()
+                .map(|pattern| f(index.search_backward(pattern)))
+                .collect::<Vec<_>>()
+        };
+        if self.threads == 1 || batch.len() == 1 {
+            self.results.extend(run(&batch));
+        } else {
+            let chunk_size = batch.len().div_ceil(self.threads);
+            let results = thread::scope(|scope| {
+                let handles = batch
+                    .chunks(chunk_size)
+                    .map(|chunk| scope.spawn(move || run(chunk)))
+                    .collect::<Vec<_>>();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().unwrap())
+                    .collect::<Vec<_>>()
+            });
+            self.results.extend(results.into_iter().flatten());
+        }
+        true
+    }
+}
+
+impl<'a, I, P, F, R> Iterator for QuerySession<'a, I, P, F, R>
+where
+    I: BackwardSearchIndex + Sync,
+    I::T: Send + Sync,
+    P: Iterator,
+    P::Item: AsRef<[I::T]> + Sync,
+    F: Fn(Search<'a, I>) -> R + Sync,
+    R: Send,
+{
+    type Item = R;
+
+    fn next(&mut self) -> Option<R> {
+        if self.results.is_empty() && !self.fill() {
+            return None;
+        }
+        self.results.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::suffix_array::SuffixOrderSampler;
+    use crate::FMIndex;
+
+    use std::cell::Cell;
+
+    #[test]
+    fn test_session() {
+        let index = FMIndex::new(
+            b"mississippi".to_vec(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(1),
+        );
+        let patterns = ["i", "s", "ss", "ssi", "p", "pi", "m", "x", "issi", "sip"];
+        for &threads in &[1, 3] {
+            let pulled = Cell::new(0);
+            let mut session = QuerySession::new(
+                &index,
+                patterns.iter().inspect(|_| pulled.set(pulled.get() + 1)),
+                |search| {
+                    let mut positions = search.locate();
+                    positions.sort();
+                    positions
+                },
+            )
+            .batch_size(4)
+            .threads(threads);
+
+            assert_eq!(pulled.get(), 0);
+            assert_eq!(session.next(), Some(vec![1, 4, 7, 10]));
+            assert_eq!(pulled.get(), 4);
+            let rest = session.collect::<Vec<_>>();
+            assert_eq!(pulled.get(), patterns.len());
+            assert_eq!(rest.len(), patterns.len() - 1);
+            assert_eq!(rest[2], vec![2, 5]);
+            assert!(rest[6].is_empty());
+            assert_eq!(rest[7], vec![1, 4]);
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use crate::suffix_array::PartialArray;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::fmt;
+
+/// A summary of an index returned by its `stats` method, e.g. to be logged when a service starts
+/// and compared between builds.
+///
+/// Counting the runs of the BWT takes _O(n log σ)_ time for indexes other than [`crate::RLFMIndex`],
+/// which store them.
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::SuffixOrderSampler;
+/// use fm_index::FMIndex;
+///
+/// let index = FMIndex::new(b"mississippi".to_vec(), RangeConverter::new(b'a', b'z'), SuffixOrderSampler::new().level(2));
+/// let stats = index.stats();
+/// assert_eq!(stats.len, 12);
+/// assert_eq!(stats.alphabet_bits, 5);
+/// assert_eq!(stats.sample_level, Some(2));
+/// assert_eq!(stats.size, index.size());
+/// println!("{}", stats);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct IndexStats {
+    /// The length of the text including the terminator and the separators of pieces.
+    pub len: u64,
+    /// The number of bits per character of the BWT, i.e. of the levels of its wavelet matrix.
+    pub alphabet_bits: u64,
+    /// The number of pieces, which is 1 for indexes of a single text.
+    pub pieces: u64,
+    /// The number of runs of equal characters in the BWT.
+    pub runs: u64,
+    /// The suffix array is sampled at every `2^sample_level` rows or text positions,
+    /// or `None` if it is not stored or sampled irregularly.
+    pub sample_level: Option<usize>,
+    /// The total size in bytes as returned by the `size` method.
+    pub size: usize,
+    /// The sizes in bytes of the components, which add up to `size` but for the struct itself.
+    pub components: Vec<(&'static str, usize)>,
+}
+
+impl IndexStats {
+    pub(crate) fn new(len: u64, sigma: u64, pieces: u64, runs: u64) -> Self {
+        IndexStats {
+            len,
+            alphabet_bits: crate::util::log2(sigma - 1) + 1,
+            pieces,
+            runs,
+            sample_level: None,
+            size: 0,
+            components: vec![],
+        }
+    }
+
+    // Sets the size as the sum of the components and the struct of the index.
+    pub(crate) fn with_components<S: SuffixArrayStats>(
+        mut self,
+        header: usize,
+        mut components: Vec<(&'static str, usize)>,
+        suffix_array: &S,
+    ) -> Self {
+        if let Some(size) = suffix_array.sa_size() {
+            components.push(("suffix_array", size));
+        }
+        self.sample_level = suffix_array.sa_level();
+        self.size = header + components.iter().map(|&(_, size)| size).sum::<usize>();
+        self.components = components;
+        self
+    }
+
+    /// Returns the size of a component, or `None` if the index has no such component.
+    pub fn component(&self, name: &str) -> Option<usize> {
+        self.components
+            .iter()
+            .find(|&&(component, _)| component == name)
+            .map(|&(_, size)| size)
+    }
+}
+
+impl fmt::Display for IndexStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "length {}, {} bits per character, {} pieces, {} runs, ",
+            self.len, self.alphabet_bits, self.pieces, self.runs
+        )?;
+        match self.sample_level {
+            Some(level) => write!(f, "sample level {}, ", level)?,
+            None => write!(f, "no sample level, ")?,
+        }
+        write!(f, "{} bytes (", self.size)?;
+        for (k, (name, size)) in self.components.iter().enumerate() {
+            if k > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} {}", name, size)?;
+        }
+        write!(f, ")")
+    }
+}
+
+// The suffix array of an index, which is `()` if it is not stored.
+pub(crate) trait SuffixArrayStats {
+    fn sa_size(&self) -> Option<usize>;
+    fn sa_level(&self) -> Option<usize>;
+}
+
+impl SuffixArrayStats for () {
+    fn sa_size(&self) -> Option<usize> {
+        None
+    }
+
+    fn sa_level(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl<S: PartialArray> SuffixArrayStats for S {
+    fn sa_size(&self) -> Option<usize> {
+        Some(self.size())
+    }
+
+    fn sa_level(&self) -> Option<usize> {
+        self.sample_level()
+    }
+}
+
+// Counts the runs of a BWT of length `n` given by `access`.
+pub(crate) fn count_runs<F: Fn(u64) -> u64>(n: u64, access: F) -> u64 {
+    let mut runs = 0;
+    let mut prev = None;
+    for i in 0..n {
+        let c = access(i);
+        if prev != Some(c) {
+            runs += 1;
+            prev = Some(c);
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::converter::RangeConverter;
+    use crate::suffix_array::{NullSampler, SuffixOrderSampler, TextOrderSampler};
+    use crate::{FMIndex, FMIndexMultiPieces, HybridFMIndex, RLFMIndex};
+
+    #[test]
+    fn test_stats() {
+        let text = b"abracadabra mississippi abracadabra".to_vec();
+        let converter = RangeConverter::new(b' ', b'z');
+        let rlfmi = RLFMIndex::new(
+            text.clone(),
+            converter.clone(),
+            SuffixOrderSampler::new().level(2),
+        );
+        let expected = rlfmi.stats();
+        assert_eq!(expected.len, 36);
+        assert_eq!(expected.alphabet_bits, 7);
+        assert_eq!(expected.pieces, 1);
+        assert_eq!(expected.runs, rlfmi.runs());
+        assert_eq!(expected.size, rlfmi.size());
+
+        let fmi = FMIndex::new(
+            text.clone(),
+            converter.clone(),
+            TextOrderSampler::new().level(3),
+        );
+        let stats = fmi.stats();
+        assert_eq!(
+            (stats.len, stats.runs, stats.sample_level),
+            (36, expected.runs, Some(3))
+        );
+        assert_eq!(stats.size, fmi.size());
+        let fmi = FMIndex::new(text.clone(), converter.clone(), NullSampler::new());
+        let stats = fmi.stats();
+        assert_eq!((stats.sample_level, stats.size), (None, fmi.size()));
+        assert_eq!(stats.component("suffix_array"), None);
+        assert_eq!(
+            stats.to_string(),
+            format!(
+                "length 36, 7 bits per character, 1 pieces, {} runs, no sample level, {} bytes (bwt {}, cs {})",
+                expected.runs,
+                fmi.size(),
+                stats.component("bwt").unwrap(),
+                stats.component("cs").unwrap()
+            )
+        );
+
+        let hybrid = HybridFMIndex::new(
+            text.clone(),
+            converter.clone(),
+            SuffixOrderSampler::new().level(2),
+        );
+        let stats = hybrid.stats();
+        assert_eq!((stats.runs, stats.size), (expected.runs, hybrid.size()));
+
+        let pieces = FMIndexMultiPieces::new(
+            b"apple\0banana\0cherry".to_vec(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(1),
+        )
+        .with_keys(vec![3, 1, 2]);
+        let stats = pieces.stats();
+        assert_eq!((stats.len, stats.pieces), (21, 3));
+        assert_eq!(stats.alphabet_bits, 5);
+        assert_eq!(stats.size, pieces.size());
+        assert_eq!(stats.component("hashes"), Some(0));
+        assert!(stats.component("keys").unwrap() > 0);
+    }
+}
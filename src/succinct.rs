@@ -0,0 +1,107 @@
+//! The bit vectors with rank and select support that back the indexes.
+//!
+//! Indexes use [`BitVector`] only through [`RankSelect`],
+//! so another succinct data structure library can be plugged in by implementing the trait
+//! without changing the index implementations.
+//! It is currently backed by the [`fid`] crate.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The bit vector of the backend.
+///
+/// It is serialized as the backend's own type.
+#[derive(Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub(crate) struct BitVector(fid::BitVector);
+
+/// A growable bit vector supporting rank and select in constant or near-constant time.
+pub(crate) trait RankSelect: Default {
+    fn push(&mut self, b: bool);
+
+    fn len(&self) -> u64;
+
+    fn get(&self, i: u64) -> bool;
+
+    /// Returns the number of ones in `[0, i)`.
+    fn rank1(&self, i: u64) -> u64;
+
+    /// Returns the position of the `r`-th one (0-origin).
+    fn select1(&self, r: u64) -> u64;
+
+    /// Returns the position of the `r`-th zero (0-origin).
+    fn select0(&self, r: u64) -> u64;
+
+    /// Returns the size of the vector in bytes.
+    fn size(&self) -> usize;
+
+    fn rank0(&self, i: u64) -> u64 {
+        i - self.rank1(i)
+    }
+
+    fn rank(&self, b: bool, i: u64) -> u64 {
+        if b {
+            self.rank1(i)
+        } else {
+            self.rank0(i)
+        }
+    }
+}
+
+impl RankSelect for BitVector {
+    fn push(&mut self, b: bool) {
+        self.0.push(b)
+    }
+
+    fn len(&self) -> u64 {
+        fid::FID::len(&self.0)
+    }
+
+    fn get(&self, i: u64) -> bool {
+        fid::FID::get(&self.0, i)
+    }
+
+    fn rank1(&self, i: u64) -> u64 {
+        fid::FID::rank1(&self.0, i)
+    }
+
+    fn select1(&self, r: u64) -> u64 {
+        fid::FID::select1(&self.0, r)
+    }
+
+    fn select0(&self, r: u64) -> u64 {
+        fid::FID::select0(&self.0, r)
+    }
+
+    fn size(&self) -> usize {
+        self.0.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank_select() {
+        let bits = [true, false, false, true, true, false, true, false];
+        let mut bv = BitVector::default();
+        for &b in &bits {
+            bv.push(b);
+        }
+        assert_eq!(bv.len(), bits.len() as u64);
+        let mut ones = 0;
+        for (i, &b) in bits.iter().enumerate() {
+            let i = i as u64;
+            assert_eq!(bv.get(i), b);
+            assert_eq!(bv.rank1(i), ones);
+            assert_eq!(bv.rank0(i), i - ones);
+            if b {
+                assert_eq!(bv.select1(ones), i);
+                ones += 1;
+            } else {
+                assert_eq!(bv.select0(i - ones), i);
+            }
+        }
+    }
+}
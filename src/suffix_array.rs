@@ -1,18 +1,339 @@
+use crate::bucket::{BucketArray, EliasFanoBucketArray};
+use crate::character::Character;
+use crate::converter::Converter;
+use crate::iter::BackwardIterableIndex;
+use crate::sais;
+use crate::search::SaInterval;
+use crate::succinct::{BitVector, RankSelect};
 use crate::util;
+use crate::util::strict_assert;
+use crate::warm;
 use std::fmt;
 
+pub use crate::disk_array::{DiskSampledArray, DiskSampler};
+pub use crate::warm::WarmRegion;
+
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 pub trait IndexWithSA {
     fn get_sa(&self, i: u64) -> u64;
+
+    /// Computes `SA[i]` with at most `budget` steps of LF-mapping.
+    ///
+    /// The number of steps taken is subtracted from `budget`.
+    /// Returns `None` if the budget runs out before reaching a sampled entry.
+    ///
+    /// The default implementation ignores `budget` and always computes the entry with [`IndexWithSA::get_sa`].
+    fn get_sa_with_budget(&self, i: u64, budget: &mut u64) -> Option<u64> {
+        let _ = budget;
+        Some(self.get_sa(i))
+    }
+}
+
+// Computes `SA[i]` of a text of length `n` by LF-mapping from row `i` until `sampled` gives the position of a row,
+// taking at most `budget` steps if given.
+pub(crate) fn resolve_sa<F, G>(
+    mut i: u64,
+    n: u64,
+    mut budget: Option<&mut u64>,
+    sampled: F,
+    lf_map: G,
+) -> Option<u64>
+where
+    F: Fn(u64) -> Option<u64>,
+    G: Fn(u64) -> u64,
+{
+    let mut steps = 0;
+    loop {
+        if let Some(p) = sampled(i) {
+            return Some((p + steps) % n);
+        }
+        match budget {
+            Some(ref b) if **b == 0 => return None,
+            Some(ref mut b) => **b -= 1,
+            None => {}
+        }
+        i = lf_map(i);
+        steps += 1;
+    }
+}
+
+/// An error returned when a query takes more steps than its budget allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepLimitExceeded {
+    pub limit: u64,
+}
+
+impl fmt::Display for StepLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "query exceeded the limit of {} steps", self.limit)
+    }
+}
+
+impl std::error::Error for StepLimitExceeded {}
+
+/// Builds the suffix array of `text` with SA-IS in _O(n)_ time.
+///
+/// `text` must end with zero and must not contain consecutive zeros,
+/// and `converter` must map every character into `0..converter.len()`.
+/// These requirements are not checked; see [`try_build_suffix_array`].
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::build_suffix_array;
+///
+/// let sa = build_suffix_array(b"banana\0", &RangeConverter::new(b'a', b'z'));
+/// assert_eq!(sa, vec![6, 5, 3, 1, 0, 4, 2]);
+/// ```
+pub fn build_suffix_array<T, C>(text: &[T], converter: &C) -> Vec<u64>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    sais::sais(text, converter)
+}
+
+/// Checks the requirements of [`build_suffix_array`] and builds the suffix array of `text`.
+pub fn try_build_suffix_array<T, C>(text: &[T], converter: &C) -> Result<Vec<u64>, InvalidText>
+where
+    T: Character,
+    C: Converter<T>,
+{
+    if let Err(TextTooLarge { len }) = check_text_len(text.len() as u64) {
+        return Err(InvalidText::TooLarge { len });
+    }
+    match text.last() {
+        None => return Err(InvalidText::Empty),
+        Some(c) if !c.is_zero() => return Err(InvalidText::NotTerminated),
+        _ => {}
+    }
+    for (i, &c) in text.iter().enumerate() {
+        if !converter.contains(c) || converter.convert(c).into() >= converter.len() {
+            return Err(InvalidText::OutOfRange { position: i as u64 });
+        }
+        if i > 0 && c.is_zero() && text[i - 1].is_zero() {
+            return Err(InvalidText::ConsecutiveZeros {
+                position: i as u64 - 1,
+            });
+        }
+    }
+    Ok(sais::sais(text, converter))
+}
+
+/// Reusable buffers for the temporaries of index construction,
+/// passed to [`crate::FMIndex::new_with_scratch`] and [`crate::RLFMIndex::new_with_scratch`].
+///
+/// A `Vec` cannot take a custom allocator on stable Rust, so instead the suffix array,
+/// the largest temporary of _n_ words, is built in a buffer owned by the caller and kept across builds.
+/// The buffer can be reserved up front (e.g. with [`Scratch::with_capacity`] while huge pages are enabled,
+/// or handed over with `From<Vec<u64>>`), and reusing it avoids fragmenting the heap in long-lived services.
+#[derive(Debug, Default)]
+pub struct Scratch {
+    sa: Vec<u64>,
+}
+
+impl Scratch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates buffers for texts of up to `len` characters including the terminator.
+    pub fn with_capacity(len: usize) -> Self {
+        Scratch {
+            sa: Vec::with_capacity(len),
+        }
+    }
+
+    /// Returns the length of the longest text the buffers hold without reallocation.
+    pub fn capacity(&self) -> usize {
+        self.sa.capacity()
+    }
+
+    pub fn into_inner(self) -> Vec<u64> {
+        self.sa
+    }
+
+    pub(crate) fn build<T, C>(&mut self, text: &[T], converter: &C) -> &[u64]
+    where
+        T: Character,
+        C: Converter<T>,
+    {
+        sais::sais_into(text, converter, &mut self.sa);
+        &self.sa
+    }
+}
+
+impl From<Vec<u64>> for Scratch {
+    fn from(sa: Vec<u64>) -> Self {
+        Scratch { sa }
+    }
+}
+
+/// An error returned when a text is too large to be indexed on the target platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextTooLarge {
+    pub len: u64,
+}
+
+impl fmt::Display for TextTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "text of length {} is too large to be indexed on a {}-bit target",
+            self.len,
+            usize::BITS
+        )
+    }
+}
+
+impl std::error::Error for TextTooLarge {}
+
+/// Checks that an index over a text of length `len` (including the terminator) is addressable.
+///
+/// The suffix array is built in memory, and packed arrays of `len` words of `log2(len) + 1` bits
+/// are addressed in bits by `usize`. Both limits matter only on 32-bit targets.
+pub fn check_text_len(len: u64) -> Result<(), TextTooLarge> {
+    let word_size = util::word_size(len.max(1)) as u64;
+    let fits = len <= (isize::MAX as u64) / std::mem::size_of::<u64>() as u64
+        && len
+            .checked_mul(word_size)
+            .is_some_and(|bits| bits <= usize::MAX as u64);
+    if fits {
+        Ok(())
+    } else {
+        Err(TextTooLarge { len })
+    }
+}
+
+// Panics with the message of `TextTooLarge` in constructors.
+pub(crate) fn assert_text_len(len: u64) {
+    if let Err(e) = check_text_len(len) {
+        panic!("{}", e);
+    }
+}
+
+/// An error returned by [`try_build_suffix_array`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidText {
+    Empty,
+    /// The text does not end with zero.
+    NotTerminated,
+    /// The text contains zeros at `position` and `position + 1`.
+    ConsecutiveZeros {
+        position: u64,
+    },
+    /// The character at `position` is not in the range of the converter.
+    OutOfRange {
+        position: u64,
+    },
+    /// The text is too large for the target; see [`check_text_len`].
+    TooLarge {
+        len: u64,
+    },
+    /// A Burrows-Wheeler transform contains another zero at `position`.
+    ExtraTerminator {
+        position: u64,
+    },
+}
+
+impl fmt::Display for InvalidText {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvalidText::Empty => write!(f, "text is empty"),
+            InvalidText::NotTerminated => write!(f, "text does not end with zero"),
+            InvalidText::ConsecutiveZeros { position } => {
+                write!(f, "text contains consecutive zeros at {}", position)
+            }
+            InvalidText::OutOfRange { position } => {
+                write!(
+                    f,
+                    "character at {} is out of the converter's range",
+                    position
+                )
+            }
+            InvalidText::TooLarge { len } => TextTooLarge { len: *len }.fmt(f),
+            InvalidText::ExtraTerminator { position } => {
+                write!(f, "transform contains another zero at {}", position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidText {}
+
+/// An error returned by [`validate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidSuffixArray {
+    /// The array is not as long as the text.
+    Length { expected: u64, actual: u64 },
+    /// The array is not a permutation; `sa[index]` is out of range or duplicated.
+    NotPermutation { index: u64 },
+    /// The suffixes at `sa[index]` and `sa[index + 1]` are not in order.
+    Unsorted { index: u64 },
+}
+
+impl fmt::Display for InvalidSuffixArray {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvalidSuffixArray::Length { expected, actual } => write!(
+                f,
+                "suffix array has {} elements but the text has {} characters",
+                actual, expected
+            ),
+            InvalidSuffixArray::NotPermutation { index } => {
+                write!(f, "suffix array is not a permutation at {}", index)
+            }
+            InvalidSuffixArray::Unsorted { index } => {
+                write!(f, "suffixes at {} and {} are not sorted", index, index + 1)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidSuffixArray {}
+
+/// Checks that `sa` is the suffix array of `text` in _O(n)_ time.
+///
+/// Adjacent suffixes are compared by their first characters and the ranks of the suffixes following them,
+/// which is sufficient for the whole array to be sorted.
+pub fn validate<T: Character>(text: &[T], sa: &[u64]) -> Result<(), InvalidSuffixArray> {
+    let n = text.len();
+    if sa.len() != n {
+        return Err(InvalidSuffixArray::Length {
+            expected: n as u64,
+            actual: sa.len() as u64,
+        });
+    }
+    // rank[p] = 1 + the index of the suffix at p; the empty suffix has rank 0
+    let mut rank = vec![0u64; n + 1];
+    for (i, &p) in sa.iter().enumerate() {
+        if p as usize >= n || rank[p as usize] != 0 {
+            return Err(InvalidSuffixArray::NotPermutation { index: i as u64 });
+        }
+        rank[p as usize] = i as u64 + 1;
+    }
+    for (i, w) in sa.windows(2).enumerate() {
+        let (a, b) = (w[0] as usize, w[1] as usize);
+        if (text[a], rank[a + 1]) >= (text[b], rank[b + 1]) {
+            return Err(InvalidSuffixArray::Unsorted { index: i as u64 });
+        }
+    }
+    Ok(())
 }
 
 pub trait PartialArray {
     fn get(&self, i: u64) -> Option<u64>;
     fn size(&self) -> usize;
+
+    /// Returns `L` if the entries are sampled at every `2^L` rows or text positions.
+    fn sample_level(&self) -> Option<usize> {
+        None
+    }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SuffixOrderSampledArray {
     level: usize,
     word_size: usize,
@@ -22,7 +343,7 @@ pub struct SuffixOrderSampledArray {
 
 impl PartialArray for SuffixOrderSampledArray {
     fn get(&self, i: u64) -> Option<u64> {
-        debug_assert!(i < self.len as u64);
+        strict_assert!(i < self.len as u64);
         if i & ((1 << self.level) - 1) == 0 {
             Some(self.sa.get_word(i as usize >> self.level, self.word_size))
         } else {
@@ -33,6 +354,10 @@ impl PartialArray for SuffixOrderSampledArray {
     fn size(&self) -> usize {
         std::mem::size_of::<Self>() + self.sa.size()
     }
+
+    fn sample_level(&self) -> Option<usize> {
+        Some(self.level)
+    }
 }
 
 impl fmt::Debug for SuffixOrderSampledArray {
@@ -47,11 +372,290 @@ impl fmt::Debug for SuffixOrderSampledArray {
     }
 }
 
+/// An index that can find the row of the suffix starting at a given text position.
+pub trait IndexWithISA {
+    fn get_isa(&self, p: u64) -> u64;
+
+    /// Materializes the suffix array entries of the text positions in `range`,
+    /// taking a walk of LF-mapping over the range.
+    ///
+    /// ```
+    /// use fm_index::converter::RangeConverter;
+    /// use fm_index::suffix_array::{IndexWithISA, TextOrderSampler};
+    /// use fm_index::{BackwardSearchIndex, FMIndex};
+    ///
+    /// let text = b"mississippi mississippi".to_vec();
+    /// let index = FMIndex::new(text, RangeConverter::new(b' ', b'z'), TextOrderSampler::new().level(4));
+    /// let region = index.warm_range(12..23);
+    /// let search = index.search_backward("ssi");
+    /// assert_eq!(region.locate(&search), search.locate());
+    /// ```
+    fn warm_range(&self, range: std::ops::Range<u64>) -> WarmRegion<'_, Self>
+    where
+        Self: BackwardIterableIndex + IndexWithSA + Sized,
+    {
+        warm::warm_range(self, range)
+    }
+}
+
+/// A sampled suffix array that also stores the rows of the suffixes at sampled text positions.
+pub trait PartialInverseArray: PartialArray {
+    /// Returns the distance between sampled text positions.
+    fn interval(&self) -> u64;
+
+    /// Returns the row of the suffix starting at `p`, which must be a multiple of [`PartialInverseArray::interval`].
+    fn get_inverse(&self, p: u64) -> u64;
+}
+
+// Walks LF-mapping from the nearest sampled position after `p`.
+pub(crate) fn get_isa<I, S>(index: &I, suffix_array: &S, p: u64) -> u64
+where
+    I: BackwardIterableIndex,
+    S: PartialInverseArray,
+{
+    let n = index.len();
+    debug_assert!(p < n);
+    let interval = suffix_array.interval();
+    let q = p.div_ceil(interval).saturating_mul(interval).min(n);
+    let mut i = suffix_array.get_inverse(q % n);
+    for _ in p..q {
+        i = index.lf_map(i);
+    }
+    i
+}
+
+/// A suffix array sampled at every `2^level`-th text position,
+/// which also supports finding the row of a text position.
+#[derive(PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TextOrderSampledArray {
+    level: usize,
+    word_size: usize,
+    sampled: BitVector,
+    sa: fid::BitArray,
+    isa: fid::BitArray,
+    len: usize,
+}
+
+impl PartialArray for TextOrderSampledArray {
+    fn get(&self, i: u64) -> Option<u64> {
+        strict_assert!(i < self.len as u64);
+        if self.sampled.get(i) {
+            let k = self.sampled.rank1(i) as usize;
+            Some(self.sa.get_word(k, self.word_size) << self.level)
+        } else {
+            None
+        }
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.sampled.size() + self.sa.size() + self.isa.size()
+    }
+
+    fn sample_level(&self) -> Option<usize> {
+        Some(self.level)
+    }
+}
+
+impl PartialInverseArray for TextOrderSampledArray {
+    fn interval(&self) -> u64 {
+        1 << self.level
+    }
+
+    fn get_inverse(&self, p: u64) -> u64 {
+        debug_assert!(p & ((1 << self.level) - 1) == 0);
+        self.isa.get_word(p as usize >> self.level, self.word_size)
+    }
+}
+
+/// An index that finds the smallest and largest suffix array entries in a range of rows.
+pub trait IndexWithExtrema {
+    /// Returns the smallest suffix array entry in a non-empty interval.
+    fn min_sa(&self, interval: SaInterval) -> u64;
+
+    /// Returns the largest suffix array entry in a non-empty interval.
+    fn max_sa(&self, interval: SaInterval) -> u64;
+}
+
+/// A sampled suffix array that also stores the extrema of the suffix array entries in blocks of rows.
+pub trait PartialExtremaArray: PartialArray {
+    /// Returns the number of rows in a block.
+    fn block_size(&self) -> u64;
+
+    /// Returns the smallest and largest entries in the blocks `[s, e)`, where `s < e`.
+    fn block_extrema(&self, s: u64, e: u64) -> (u64, u64);
+}
+
+// Resolves the rows outside of whole blocks, and looks up the whole blocks.
+pub(crate) fn get_extrema<I, S>(index: &I, suffix_array: &S, interval: SaInterval) -> (u64, u64)
+where
+    I: IndexWithSA,
+    S: PartialExtremaArray,
+{
+    debug_assert!(!interval.is_empty());
+    let SaInterval { start: s, end: e } = interval;
+    let b = suffix_array.block_size();
+    let (bs, be) = (s.div_ceil(b), e / b);
+    let extrema = |init, rows: &mut dyn Iterator<Item = u64>| {
+        rows.map(|i| index.get_sa(i))
+            .fold(init, |(min, max): (u64, u64), p| (min.min(p), max.max(p)))
+    };
+    if bs < be {
+        let init = suffix_array.block_extrema(bs, be);
+        extrema(init, &mut (s..bs * b).chain(be * b..e))
+    } else {
+        extrema((u64::MAX, 0), &mut (s..e))
+    }
+}
+
+/// A sampled suffix array with a sparse table of the extrema of each block of rows,
+/// built by [`ExtremaSampler`].
+#[derive(PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExtremaSampledArray<S> {
+    inner: S,
+    level: usize,
+    word_size: usize,
+    // `min[j][k]` and `max[j][k]` hold the extrema of the blocks `[k, k + 2^j)`.
+    min: Vec<fid::BitArray>,
+    max: Vec<fid::BitArray>,
+}
+
+impl<S> ExtremaSampledArray<S> {
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: PartialArray> PartialArray for ExtremaSampledArray<S> {
+    fn get(&self, i: u64) -> Option<u64> {
+        self.inner.get(i)
+    }
+
+    fn size(&self) -> usize {
+        let table = |t: &Vec<fid::BitArray>| t.iter().map(|a| a.size()).sum::<usize>();
+        std::mem::size_of::<Self>() - std::mem::size_of::<S>()
+            + self.inner.size()
+            + table(&self.min)
+            + table(&self.max)
+    }
+
+    fn sample_level(&self) -> Option<usize> {
+        self.inner.sample_level()
+    }
+}
+
+impl<S: PartialInverseArray> PartialInverseArray for ExtremaSampledArray<S> {
+    fn interval(&self) -> u64 {
+        self.inner.interval()
+    }
+
+    fn get_inverse(&self, p: u64) -> u64 {
+        self.inner.get_inverse(p)
+    }
+}
+
+impl<S: PartialArray> PartialExtremaArray for ExtremaSampledArray<S> {
+    fn block_size(&self) -> u64 {
+        1 << self.level
+    }
+
+    fn block_extrema(&self, s: u64, e: u64) -> (u64, u64) {
+        debug_assert!(s < e);
+        let j = util::log2(e - s) as usize;
+        let (k1, k2) = (s as usize, (e - (1 << j)) as usize);
+        let ws = self.word_size;
+        let min = self.min[j]
+            .get_word(k1, ws)
+            .min(self.min[j].get_word(k2, ws));
+        let max = self.max[j]
+            .get_word(k1, ws)
+            .max(self.max[j].get_word(k2, ws));
+        (min, max)
+    }
+}
+
+/// A sampler which wraps another sampler and additionally stores the extrema
+/// of the suffix array in every block of `2^level` rows, enabling [`IndexWithExtrema`].
+///
+/// The extra space is _O((n / 2^level) log(n / 2^level) log n)_ bits,
+/// and a query resolves at most `2^(level + 1)` entries by LF-mapping.
+#[derive(Clone, Default)]
+pub struct ExtremaSampler<B> {
+    inner: B,
+    level: usize,
+}
+
+impl<B> ExtremaSampler<B> {
+    pub fn new(inner: B) -> Self {
+        ExtremaSampler { inner, level: 6 }
+    }
+
+    pub fn level(mut self, level: usize) -> Self {
+        self.level = level;
+        self
+    }
+}
+
+impl<S, B> ArraySampler<ExtremaSampledArray<S>> for ExtremaSampler<B>
+where
+    B: ArraySampler<S>,
+{
+    fn sample(&self, sa: Vec<u64>) -> ExtremaSampledArray<S> {
+        self.sample_slice(&sa)
+    }
+
+    fn sample_slice(&self, sa: &[u64]) -> ExtremaSampledArray<S> {
+        let n = sa.len();
+        debug_assert!(n > 0);
+        let word_size = util::word_size(n as u64);
+        let blocks: Vec<(u64, u64)> = sa
+            .chunks_exact(1 << self.level)
+            .map(|c| (*c.iter().min().unwrap(), *c.iter().max().unwrap()))
+            .collect();
+        let mut min = vec![];
+        let mut max = vec![];
+        let mut level = blocks;
+        let mut width = 1;
+        while !level.is_empty() {
+            let mut min_words = fid::BitArray::with_word_size(word_size, level.len());
+            let mut max_words = fid::BitArray::with_word_size(word_size, level.len());
+            for (k, &(lo, hi)) in level.iter().enumerate() {
+                min_words.set_word(k, word_size, lo);
+                max_words.set_word(k, word_size, hi);
+            }
+            min.push(min_words);
+            max.push(max_words);
+            level = (0..level.len().saturating_sub(width))
+                .map(|k| {
+                    let (a, b) = (level[k], level[k + width]);
+                    (a.0.min(b.0), a.1.max(b.1))
+                })
+                .collect();
+            width *= 2;
+        }
+        ExtremaSampledArray {
+            inner: self.inner.sample_slice(sa),
+            level: self.level,
+            word_size,
+            min,
+            max,
+        }
+    }
+}
+
 pub trait ArraySampler<S> {
     fn sample(&self, sa: Vec<u64>) -> S;
+
+    /// Samples a borrowed suffix array, so that the caller can keep its buffer.
+    ///
+    /// The default implementation copies `sa`; the samplers of this crate do not.
+    fn sample_slice(&self, sa: &[u64]) -> S {
+        self.sample(sa.to_vec())
+    }
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct NullSampler {}
 
 impl NullSampler {
@@ -62,9 +666,11 @@ impl NullSampler {
 
 impl ArraySampler<()> for NullSampler {
     fn sample(&self, _sa: Vec<u64>) {}
+
+    fn sample_slice(&self, _sa: &[u64]) {}
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct SuffixOrderSampler {
     level: usize,
 }
@@ -82,8 +688,12 @@ impl SuffixOrderSampler {
 
 impl ArraySampler<SuffixOrderSampledArray> for SuffixOrderSampler {
     fn sample(&self, sa: Vec<u64>) -> SuffixOrderSampledArray {
+        self.sample_slice(&sa)
+    }
+
+    fn sample_slice(&self, sa: &[u64]) -> SuffixOrderSampledArray {
         let n = sa.len();
-        let word_size = (util::log2(n as u64) + 1) as usize;
+        let word_size = util::word_size(n as u64);
         debug_assert!(n > 0);
         debug_assert!(
             n > (1 << self.level),
@@ -94,7 +704,7 @@ impl ArraySampler<SuffixOrderSampledArray> for SuffixOrderSampler {
         let sa_samples_len = ((n - 1) >> self.level) + 1;
         let mut sa_samples = fid::BitArray::with_word_size(word_size, sa_samples_len);
         for i in 0..sa_samples_len {
-            sa_samples.set_word(i, word_size, sa[i << self.level] as u64);
+            sa_samples.set_word(i, word_size, sa[i << self.level]);
         }
         SuffixOrderSampledArray {
             level: self.level,
@@ -105,9 +715,416 @@ impl ArraySampler<SuffixOrderSampledArray> for SuffixOrderSampler {
     }
 }
 
+/// A sampler which keeps the suffix array entries of every `2^level`-th text position.
+///
+/// Unlike [`SuffixOrderSampler`], the number of LF-mapping steps to locate an occurrence
+/// is bounded by `2^level` regardless of the text, and the resulting array supports [`IndexWithISA`].
+#[derive(Clone, Default)]
+pub struct TextOrderSampler {
+    level: usize,
+}
+
+impl TextOrderSampler {
+    pub fn new() -> Self {
+        TextOrderSampler { level: 0 }
+    }
+
+    pub fn level(mut self, level: usize) -> Self {
+        self.level = level;
+        self
+    }
+}
+
+impl ArraySampler<TextOrderSampledArray> for TextOrderSampler {
+    fn sample(&self, sa: Vec<u64>) -> TextOrderSampledArray {
+        self.sample_slice(&sa)
+    }
+
+    fn sample_slice(&self, sa: &[u64]) -> TextOrderSampledArray {
+        let n = sa.len();
+        let word_size = util::word_size(n as u64);
+        debug_assert!(n > 0);
+        let mask = (1 << self.level) - 1;
+        let samples_len = ((n - 1) >> self.level) + 1;
+        let mut sampled = BitVector::default();
+        let mut sa_samples = fid::BitArray::with_word_size(word_size, samples_len);
+        let mut isa_samples = fid::BitArray::with_word_size(word_size, samples_len);
+        let mut k = 0;
+        for (i, &p) in sa.iter().enumerate() {
+            let is_sampled = p & mask == 0;
+            sampled.push(is_sampled);
+            if is_sampled {
+                let j = p as usize >> self.level;
+                sa_samples.set_word(k, word_size, j as u64);
+                isa_samples.set_word(j, word_size, i as u64);
+                k += 1;
+            }
+        }
+        TextOrderSampledArray {
+            level: self.level,
+            word_size,
+            sampled,
+            sa: sa_samples,
+            isa: isa_samples,
+            len: n,
+        }
+    }
+}
+
+/// A suffix array sampled at every `2^level`-th text position like [`TextOrderSampledArray`],
+/// whose sampled rows are stored with Elias-Fano encoding instead of a bit vector over all rows.
+///
+/// The sampled rows take _(n / 2^level)(2 + level)_ bits instead of _n_ bits,
+/// which is smaller for `level >= 3`, at the cost of a select query and a short scan in `get`.
+#[derive(PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EliasFanoSampledArray {
+    level: usize,
+    word_size: usize,
+    rows: EliasFanoBucketArray,
+    sa: fid::BitArray,
+    isa: fid::BitArray,
+    len: usize,
+}
+
+impl PartialArray for EliasFanoSampledArray {
+    fn get(&self, i: u64) -> Option<u64> {
+        strict_assert!(i < self.len as u64);
+        let k = self.rows.rank(i);
+        if k < self.rows.len() && self.rows.get(k) == i {
+            Some(self.sa.get_word(k as usize, self.word_size) << self.level)
+        } else {
+            None
+        }
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.rows.size() + self.sa.size() + self.isa.size()
+    }
+
+    fn sample_level(&self) -> Option<usize> {
+        Some(self.level)
+    }
+}
+
+impl PartialInverseArray for EliasFanoSampledArray {
+    fn interval(&self) -> u64 {
+        1 << self.level
+    }
+
+    fn get_inverse(&self, p: u64) -> u64 {
+        debug_assert!(p & ((1 << self.level) - 1) == 0);
+        self.isa.get_word(p as usize >> self.level, self.word_size)
+    }
+}
+
+/// A sampler which builds an [`EliasFanoSampledArray`].
+#[derive(Clone, Default)]
+pub struct EliasFanoSampler {
+    level: usize,
+}
+
+impl EliasFanoSampler {
+    pub fn new() -> Self {
+        EliasFanoSampler { level: 0 }
+    }
+
+    pub fn level(mut self, level: usize) -> Self {
+        self.level = level;
+        self
+    }
+}
+
+impl ArraySampler<EliasFanoSampledArray> for EliasFanoSampler {
+    fn sample(&self, sa: Vec<u64>) -> EliasFanoSampledArray {
+        self.sample_slice(&sa)
+    }
+
+    fn sample_slice(&self, sa: &[u64]) -> EliasFanoSampledArray {
+        let n = sa.len();
+        let word_size = util::word_size(n as u64);
+        debug_assert!(n > 0);
+        let mask = (1 << self.level) - 1;
+        let samples_len = ((n - 1) >> self.level) + 1;
+        let mut rows = Vec::with_capacity(samples_len);
+        let mut sa_samples = fid::BitArray::with_word_size(word_size, samples_len);
+        let mut isa_samples = fid::BitArray::with_word_size(word_size, samples_len);
+        for (i, &p) in sa.iter().enumerate() {
+            if p & mask == 0 {
+                let j = p as usize >> self.level;
+                sa_samples.set_word(rows.len(), word_size, j as u64);
+                isa_samples.set_word(j, word_size, i as u64);
+                rows.push(i as u64);
+            }
+        }
+        EliasFanoSampledArray {
+            level: self.level,
+            word_size,
+            rows: EliasFanoBucketArray::new(&rows),
+            sa: sa_samples,
+            isa: isa_samples,
+            len: n,
+        }
+    }
+}
+
+/// A suffix array sampled at text positions, more densely in designated regions.
+#[derive(PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RegionSampledArray {
+    word_size: usize,
+    sampled: BitVector,
+    sa: fid::BitArray,
+    len: usize,
+}
+
+impl PartialArray for RegionSampledArray {
+    fn get(&self, i: u64) -> Option<u64> {
+        strict_assert!(i < self.len as u64);
+        if self.sampled.get(i) {
+            let k = self.sampled.rank1(i) as usize;
+            Some(self.sa.get_word(k, self.word_size))
+        } else {
+            None
+        }
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.sampled.size() + self.sa.size()
+    }
+}
+
+/// A sampler which keeps the suffix array entries of every `2^level`-th text position,
+/// and of every `2^level'`-th position in each hot region given by [`RegionSampler::hot`].
+///
+/// Locating an occurrence in a hot region then takes fewer steps of LF-mapping,
+/// while the rest of the text is sampled sparsely.
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::RegionSampler;
+/// use fm_index::{BackwardSearchIndex, FMIndex};
+///
+/// let text = b"mississippi mississippi".to_vec();
+/// let n = text.len() as u64;
+/// // sample every 16th position, but every position of the last 12 characters
+/// let sampler = RegionSampler::new().level(4).hot(n - 12..n + 1, 0);
+/// let index = FMIndex::new(text, RangeConverter::new(b' ', b'z'), sampler);
+/// let mut positions = index.search_backward("ssi").locate();
+/// positions.sort();
+/// assert_eq!(positions, vec![2, 5, 14, 17]);
+/// ```
+#[derive(Clone, Default)]
+pub struct RegionSampler {
+    level: usize,
+    hot: Vec<(std::ops::Range<u64>, usize)>,
+}
+
+impl RegionSampler {
+    pub fn new() -> Self {
+        RegionSampler {
+            level: 0,
+            hot: vec![],
+        }
+    }
+
+    /// Sets the sampling level outside of hot regions.
+    pub fn level(mut self, level: usize) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Samples text positions in `range` at `level`.
+    ///
+    /// If regions overlap, the densest level applies.
+    pub fn hot(mut self, range: std::ops::Range<u64>, level: usize) -> Self {
+        self.hot.push((range, level));
+        self
+    }
+
+    fn is_sampled(&self, p: u64) -> bool {
+        let level = self
+            .hot
+            .iter()
+            .filter(|(range, _)| range.contains(&p))
+            .map(|&(_, level)| level)
+            .fold(self.level, usize::min);
+        p & ((1 << level) - 1) == 0
+    }
+}
+
+impl ArraySampler<RegionSampledArray> for RegionSampler {
+    fn sample(&self, sa: Vec<u64>) -> RegionSampledArray {
+        self.sample_slice(&sa)
+    }
+
+    fn sample_slice(&self, sa: &[u64]) -> RegionSampledArray {
+        let n = sa.len();
+        let word_size = util::word_size(n as u64);
+        debug_assert!(n > 0);
+        let mut sampled = BitVector::default();
+        let mut samples = vec![];
+        for &p in sa {
+            let is_sampled = self.is_sampled(p);
+            sampled.push(is_sampled);
+            if is_sampled {
+                samples.push(p);
+            }
+        }
+        let mut sa_samples = fid::BitArray::with_word_size(word_size, samples.len().max(1));
+        for (k, &p) in samples.iter().enumerate() {
+            sa_samples.set_word(k, word_size, p);
+        }
+        RegionSampledArray {
+            word_size,
+            sampled,
+            sa: sa_samples,
+            len: n,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::converter::RangeConverter;
+    use crate::sais;
+
+    #[test]
+    fn test_text_order() {
+        let sa = vec![11, 10, 7, 4, 1, 0, 9, 8, 6, 3, 5, 2];
+        for level in 0..4 {
+            let ssa = TextOrderSampler::new().level(level).sample(sa.clone());
+            for (i, &p) in sa.iter().enumerate() {
+                if p % (1 << level) == 0 {
+                    assert_eq!(ssa.get(i as u64), Some(p));
+                    assert_eq!(ssa.get_inverse(p), i as u64);
+                } else {
+                    assert_eq!(ssa.get(i as u64), None);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_build_suffix_array() {
+        let converter = RangeConverter::new(b'a', b'z');
+        for text in &["mississippi\0", "abra\0cadabra\0", "a\0", "\0"] {
+            let text = text.as_bytes();
+            let sa = try_build_suffix_array(text, &converter).unwrap();
+            let mut expected = (0..text.len() as u64).collect::<Vec<_>>();
+            expected.sort_by_key(|&i| &text[i as usize..]);
+            assert_eq!(sa, expected);
+            assert_eq!(validate(text, &sa), Ok(()));
+        }
+        assert_eq!(
+            try_build_suffix_array(b"", &converter),
+            Err(InvalidText::Empty)
+        );
+        assert_eq!(
+            try_build_suffix_array(b"abc", &converter),
+            Err(InvalidText::NotTerminated)
+        );
+        assert_eq!(
+            try_build_suffix_array(b"ab\0\0c\0", &converter),
+            Err(InvalidText::ConsecutiveZeros { position: 2 })
+        );
+        assert_eq!(
+            try_build_suffix_array(b"ab{\0", &converter),
+            Err(InvalidText::OutOfRange { position: 2 })
+        );
+    }
+
+    #[test]
+    fn test_elias_fano_sampler() {
+        let sa = vec![11, 10, 7, 4, 1, 0, 9, 8, 6, 3, 5, 2];
+        for level in 0..4 {
+            let expected = TextOrderSampler::new().level(level).sample(sa.clone());
+            let ssa = EliasFanoSampler::new().level(level).sample(sa.clone());
+            for (i, &p) in sa.iter().enumerate() {
+                assert_eq!(ssa.get(i as u64), expected.get(i as u64));
+                if p % (1 << level) == 0 {
+                    assert_eq!(ssa.get_inverse(p), i as u64);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_region_sampler() {
+        let sa = vec![11, 10, 7, 4, 1, 0, 9, 8, 6, 3, 5, 2];
+        let ssa = RegionSampler::new()
+            .level(3)
+            .hot(8..12, 0)
+            .hot(2..6, 1)
+            .sample(sa.clone());
+        for (i, &p) in sa.iter().enumerate() {
+            let expected = [0, 2, 4, 8, 9, 10, 11].contains(&p);
+            assert_eq!(ssa.get(i as u64), if expected { Some(p) } else { None });
+        }
+    }
+
+    #[test]
+    fn test_extrema_sampler() {
+        let sa = vec![11, 10, 7, 4, 1, 0, 9, 8, 6, 3, 5, 2];
+        for level in 0..3 {
+            let ssa = ExtremaSampler::new(SuffixOrderSampler::new())
+                .level(level)
+                .sample(sa.clone());
+            let blocks = sa.len() >> level;
+            for s in 0..blocks {
+                for e in s + 1..=blocks {
+                    let rows = &sa[s << level..e << level];
+                    assert_eq!(
+                        ssa.block_extrema(s as u64, e as u64),
+                        (*rows.iter().min().unwrap(), *rows.iter().max().unwrap())
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_check_text_len() {
+        assert_eq!(check_text_len(1), Ok(()));
+        assert_eq!(check_text_len(1 << 20), Ok(()));
+        assert_eq!(
+            check_text_len(u64::MAX),
+            Err(TextTooLarge { len: u64::MAX })
+        );
+        if cfg!(target_pointer_width = "64") {
+            assert_eq!(check_text_len(1 << 40), Ok(()));
+        } else {
+            assert!(check_text_len(1 << 28).is_err());
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let text = "mississippi\0".to_string().into_bytes();
+        let sa = sais::sais(&text, &RangeConverter::new(b'a', b'z'));
+        assert_eq!(validate(&text, &sa), Ok(()));
+
+        let mut swapped = sa.clone();
+        swapped.swap(3, 4);
+        assert_eq!(
+            validate(&text, &swapped),
+            Err(InvalidSuffixArray::Unsorted { index: 3 })
+        );
+        let mut duplicated = sa.clone();
+        duplicated[5] = duplicated[6];
+        assert_eq!(
+            validate(&text, &duplicated),
+            Err(InvalidSuffixArray::NotPermutation { index: 6 })
+        );
+        assert_eq!(
+            validate(&text, &sa[1..]),
+            Err(InvalidSuffixArray::Length {
+                expected: 12,
+                actual: 11
+            })
+        );
+    }
 
     #[test]
     fn test_regular() {
@@ -0,0 +1,9 @@
+/// A pattern similar to a searched one that occurs in the text,
+/// returned by [`crate::BackwardSearchIndex::suggest`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Suggestion<T> {
+    /// The suggested pattern, which occurs in the text.
+    pub pattern: Vec<T>,
+    /// The number of occurrences of the pattern.
+    pub count: u64,
+}
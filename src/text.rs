@@ -0,0 +1,173 @@
+use crate::character::Character;
+use crate::util::strict_assert;
+
+use std::marker::PhantomData;
+
+/// A sequence of characters from which an index is built.
+///
+/// It is implemented for slices and vectors, and by [`PackedText`] for small alphabets.
+pub trait Text {
+    type T: Character;
+
+    fn len(&self) -> usize;
+
+    fn get(&self, i: usize) -> Self::T;
+}
+
+impl<T: Character> Text for [T] {
+    type T = T;
+
+    fn len(&self) -> usize {
+        <[T]>::len(self)
+    }
+
+    fn get(&self, i: usize) -> T {
+        self[i]
+    }
+}
+
+impl<T: Character> Text for Vec<T> {
+    type T = T;
+
+    fn len(&self) -> usize {
+        <[T]>::len(self)
+    }
+
+    fn get(&self, i: usize) -> T {
+        self[i]
+    }
+}
+
+/// A text whose characters are packed into `bits` bits each, e.g. 2 bits for DNA bases.
+///
+/// Characters range over `1..=2^bits` and are stored as `c - 1`,
+/// and the text is terminated by an implicit zero.
+/// Thus a genome with bases encoded as 1 to 4 takes a quarter of the memory of a byte text,
+/// though construction by [`crate::FMIndex::from_text`] still allocates a suffix array of _n_ words,
+/// which dominates its peak memory.
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::SuffixOrderSampler;
+/// use fm_index::{BackwardSearchIndex, FMIndex, PackedText};
+///
+/// let base = |c| match c {
+///     b'A' => 1,
+///     b'C' => 2,
+///     b'G' => 3,
+///     _ => 4,
+/// };
+/// let mut text = PackedText::new(2);
+/// for &c in b"GATTACAGATTACA" {
+///     text.push(base(c));
+/// }
+/// let index = FMIndex::from_text(&text, RangeConverter::new(1u8, 4), SuffixOrderSampler::new());
+/// let pattern = b"TAC".iter().map(|&c| base(c)).collect::<Vec<_>>();
+/// assert_eq!(index.search_backward(pattern).count(), 2);
+/// ```
+pub struct PackedText<T> {
+    bits: usize,
+    len: usize,
+    words: Vec<u64>,
+    _t: PhantomData<T>,
+}
+
+impl<T: Character> PackedText<T> {
+    /// Creates an empty text of characters of `bits` bits, which must be less than 64 and divide 64.
+    pub fn new(bits: usize) -> Self {
+        assert!(
+            0 < bits && bits < 64 && 64 % bits == 0,
+            "bits must be less than 64 and divide 64 (got {})",
+            bits
+        );
+        PackedText {
+            bits,
+            len: 0,
+            words: vec![],
+            _t: PhantomData,
+        }
+    }
+
+    pub fn bits(&self) -> usize {
+        self.bits
+    }
+
+    /// Appends a character in `1..=2^bits`.
+    pub fn push(&mut self, c: T) {
+        let code = c.into();
+        assert!(
+            code >= 1 && (code - 1) >> self.bits == 0,
+            "character {} does not fit in {} bits",
+            code,
+            self.bits
+        );
+        let (k, offset) = self.locate(self.len);
+        if k == self.words.len() {
+            self.words.push(0);
+        }
+        self.words[k] |= (code - 1) << offset;
+        self.len += 1;
+    }
+
+    /// Returns the size of the packed characters in bytes.
+    pub fn size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.words.len() * std::mem::size_of::<u64>()
+    }
+
+    fn locate(&self, i: usize) -> (usize, usize) {
+        let per_word = 64 / self.bits;
+        (i / per_word, i % per_word * self.bits)
+    }
+}
+
+impl<T: Character> Text for PackedText<T> {
+    type T = T;
+
+    /// Returns the number of characters including the terminator.
+    fn len(&self) -> usize {
+        self.len + 1
+    }
+
+    fn get(&self, i: usize) -> T {
+        strict_assert!(i <= self.len);
+        if i == self.len {
+            return T::zero();
+        }
+        let (k, offset) = self.locate(i);
+        let mask = (1 << self.bits) - 1;
+        T::from_u64(((self.words[k] >> offset) & mask) + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packed_text() {
+        for &bits in &[1, 2, 4, 8] {
+            let chars = (0..100)
+                .map(|i| (i * 7 % (1 << bits) + 1) as u16)
+                .collect::<Vec<_>>();
+            let mut text = PackedText::new(bits);
+            for &c in &chars {
+                text.push(c);
+            }
+            assert_eq!(Text::len(&text), chars.len() + 1);
+            for (i, &c) in chars.iter().enumerate() {
+                assert_eq!(text.get(i), c);
+            }
+            assert_eq!(text.get(chars.len()), 0);
+        }
+
+        let mut text = PackedText::new(32);
+        text.push(u64::from(u32::MAX) + 1);
+        assert_eq!(text.get(0), 1 << 32);
+    }
+
+    #[test]
+    #[should_panic(expected = "bits must be less than 64")]
+    fn test_packed_text_64_bits() {
+        PackedText::<u64>::new(64);
+    }
+}
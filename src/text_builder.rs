@@ -0,0 +1,252 @@
+use crate::character::Character;
+use crate::piece::PieceId;
+
+/// A builder of multi-piece texts for [`crate::FMIndexMultiPieces`] from streaming sources,
+/// with caps on the length of each piece and of the whole text.
+///
+/// Pieces are read from iterators of characters and delimited by zero.
+/// A piece is read only up to the cap, so unbounded or untrusted inputs take bounded memory,
+/// and the outcome of each piece tells whether it is added, truncated or skipped.
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::NullSampler;
+/// use fm_index::{FMIndexMultiPieces, PieceId, PieceOutcome, SkipReason, TextBuilder};
+///
+/// let mut builder = TextBuilder::new().max_piece_len(5).max_total_len(14);
+/// assert_eq!(builder.push_piece(b"apple".iter().copied()), PieceOutcome::Added(PieceId::from(0)));
+/// assert_eq!(builder.push_piece(b"".iter().copied()), PieceOutcome::Skipped(SkipReason::Empty));
+/// // an endless piece is truncated to the cap
+/// assert_eq!(builder.push_piece(std::iter::repeat(b'b')), PieceOutcome::Truncated(PieceId::from(1)));
+/// assert_eq!(builder.push_piece(b"cherry".iter().copied()), PieceOutcome::Truncated(PieceId::from(2)));
+/// assert!(builder.is_full());
+/// let text = builder.finish();
+/// assert_eq!(text, b"apple\0bbbbb\0c\0".to_vec());
+/// let index = FMIndexMultiPieces::new(text, RangeConverter::new(b'a', b'z'), NullSampler::new());
+/// assert_eq!(index.piece_len(PieceId::from(1)), 5);
+/// ```
+#[derive(Clone, Debug)]
+pub struct TextBuilder<T> {
+    text: Vec<T>,
+    max_piece_len: usize,
+    max_total_len: usize,
+    pieces: u64,
+}
+
+/// The outcome of reading a piece by [`TextBuilder`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PieceOutcome {
+    /// The piece is added as a whole.
+    Added(PieceId),
+    /// The piece is added, but cut at the cap on the length of pieces or of the text.
+    Truncated(PieceId),
+    /// The piece is not added.
+    Skipped(SkipReason),
+}
+
+/// The reason why [`TextBuilder`] skips a piece.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The piece has no characters.
+    Empty,
+    /// The piece contains zero, which delimits pieces.
+    ContainsZero,
+    /// The text has reached its cap, so the piece is not read.
+    TextFull,
+}
+
+/// The outcome of a piece read by [`TextBuilder::extend_interleaved`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PieceReport {
+    /// The index of the source of the piece.
+    pub source: usize,
+    /// The index of the piece in its source.
+    pub ordinal: usize,
+    pub outcome: PieceOutcome,
+}
+
+impl<T: Character> Default for TextBuilder<T> {
+    fn default() -> Self {
+        TextBuilder::new()
+    }
+}
+
+impl<T: Character> TextBuilder<T> {
+    /// Creates a builder without caps.
+    pub fn new() -> Self {
+        TextBuilder {
+            text: vec![],
+            max_piece_len: usize::MAX,
+            max_total_len: usize::MAX,
+            pieces: 0,
+        }
+    }
+
+    /// Sets the maximum number of characters of a piece, beyond which pieces are truncated.
+    pub fn max_piece_len(mut self, max_piece_len: usize) -> Self {
+        assert!(max_piece_len > 0, "max_piece_len must be positive");
+        self.max_piece_len = max_piece_len;
+        self
+    }
+
+    /// Sets the maximum length of the text including the zero following each piece.
+    pub fn max_total_len(mut self, max_total_len: usize) -> Self {
+        self.max_total_len = max_total_len;
+        self
+    }
+
+    /// Returns the length of the text built so far.
+    pub fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn pieces_count(&self) -> u64 {
+        self.pieces
+    }
+
+    /// Returns true if no more pieces can be added, i.e. the text has no room for a character and a zero.
+    pub fn is_full(&self) -> bool {
+        self.max_total_len.saturating_sub(self.text.len()) < 2
+    }
+
+    /// Reads a piece and appends it to the text.
+    ///
+    /// At most one character beyond the cap is read to tell whether the piece is truncated,
+    /// and nothing is read if the text is full.
+    pub fn push_piece<I: IntoIterator<Item = T>>(&mut self, piece: I) -> PieceOutcome {
+        if self.is_full() {
+            return PieceOutcome::Skipped(SkipReason::TextFull);
+        }
+        let cap = self
+            .max_piece_len
+            .min(self.max_total_len - self.text.len() - 1);
+        let start = self.text.len();
+        let mut truncated = false;
+        for c in piece {
+            if c.is_zero() {
+                self.text.truncate(start);
+                return PieceOutcome::Skipped(SkipReason::ContainsZero);
+            }
+            if self.text.len() - start == cap {
+                truncated = true;
+                break;
+            }
+            self.text.push(c);
+        }
+        if self.text.len() == start {
+            return PieceOutcome::Skipped(SkipReason::Empty);
+        }
+        self.text.push(T::zero());
+        let id = PieceId::from(self.pieces);
+        self.pieces += 1;
+        if truncated {
+            PieceOutcome::Truncated(id)
+        } else {
+            PieceOutcome::Added(id)
+        }
+    }
+
+    /// Reads pieces from `sources` in turn, one piece from each source at a time,
+    /// until all of them are exhausted or the text is full.
+    ///
+    /// Pieces left in the sources when the text becomes full are not read, so sources may be unbounded.
+    pub fn extend_interleaved<S, P>(&mut self, sources: Vec<S>) -> Vec<PieceReport>
+    where
+        S: Iterator<Item = P>,
+        P: IntoIterator<Item = T>,
+    {
+        let mut sources = sources.into_iter().map(Some).collect::<Vec<_>>();
+        let mut ordinals = vec![0; sources.len()];
+        let mut reports = vec![];
+        while !self.is_full() && sources.iter().any(|source| source.is_some()) {
+            for (i, slot) in sources.iter_mut().enumerate() {
+                if self.is_full() {
+                    break;
+                }
+                let piece = match slot.as_mut().and_then(|source| source.next()) {
+                    Some(piece) => piece,
+                    None => {
+                        *slot = None;
+                        continue;
+                    }
+                };
+                reports.push(PieceReport {
+                    source: i,
+                    ordinal: ordinals[i],
+                    outcome: self.push_piece(piece),
+                });
+                ordinals[i] += 1;
+            }
+        }
+        reports
+    }
+
+    /// Returns the text, which is empty if no pieces are added.
+    pub fn finish(self) -> Vec<T> {
+        self.text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_builder() {
+        let mut builder = TextBuilder::new().max_piece_len(3).max_total_len(20);
+        type Source = Box<dyn Iterator<Item = Box<dyn Iterator<Item = u8>>>>;
+        let source = |pieces: Vec<&'static [u8]>| -> Source {
+            Box::new(
+                pieces
+                    .into_iter()
+                    .map(|p| Box::new(p.iter().copied()) as Box<dyn Iterator<Item = u8>>),
+            )
+        };
+        // endless pieces of endless characters
+        let endless: Source = Box::new(
+            std::iter::repeat(())
+                .map(|_| Box::new(std::iter::repeat(b'z')) as Box<dyn Iterator<Item = u8>>),
+        );
+        let reports = builder.extend_interleaved(vec![
+            source(vec![b"ab", b"cdefg", b""]),
+            source(vec![b"h\0i", b"jk"]),
+            endless,
+        ]);
+        let outcomes = reports
+            .iter()
+            .map(|r| (r.source, r.ordinal, r.outcome))
+            .collect::<Vec<_>>();
+        let id = PieceId::from;
+        assert_eq!(
+            outcomes,
+            vec![
+                (0, 0, PieceOutcome::Added(id(0))),
+                (1, 0, PieceOutcome::Skipped(SkipReason::ContainsZero)),
+                (2, 0, PieceOutcome::Truncated(id(1))),
+                (0, 1, PieceOutcome::Truncated(id(2))),
+                (1, 1, PieceOutcome::Added(id(3))),
+                (2, 1, PieceOutcome::Truncated(id(4))),
+                (0, 2, PieceOutcome::Skipped(SkipReason::Empty)),
+                (2, 2, PieceOutcome::Truncated(id(5))),
+            ]
+        );
+        assert!(builder.is_full());
+        assert_eq!(builder.pieces_count(), 6);
+        assert_eq!(
+            builder.push_piece(vec![b'x']),
+            PieceOutcome::Skipped(SkipReason::TextFull)
+        );
+        assert_eq!(builder.finish(), b"ab\0zzz\0cde\0jk\0zzz\0z\0".to_vec());
+
+        let mut builder = TextBuilder::<u8>::new();
+        assert_eq!(
+            builder.extend_interleaved(Vec::<std::vec::IntoIter<Vec<u8>>>::new()),
+            vec![]
+        );
+        assert!(builder.is_empty());
+    }
+}
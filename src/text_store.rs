@@ -0,0 +1,180 @@
+use std::ops::Range;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A compression scheme for blocks of a [`TextStore`].
+///
+/// This crate does not depend on a compression library; implement it for one such as zstd:
+///
+/// ```ignore
+/// struct Zstd(i32);
+///
+/// impl BlockCodec for Zstd {
+///     fn compress(&self, block: &[u8]) -> Vec<u8> {
+///         zstd::bulk::compress(block, self.0).unwrap()
+///     }
+///
+///     fn decompress(&self, data: &[u8], len: usize) -> Vec<u8> {
+///         zstd::bulk::decompress(data, len).unwrap()
+///     }
+/// }
+/// ```
+pub trait BlockCodec {
+    fn compress(&self, block: &[u8]) -> Vec<u8>;
+
+    /// Restores a block of `len` bytes compressed by [`BlockCodec::compress`].
+    fn decompress(&self, data: &[u8], len: usize) -> Vec<u8>;
+}
+
+/// A codec that stores blocks as they are.
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Uncompressed;
+
+impl BlockCodec for Uncompressed {
+    fn compress(&self, block: &[u8]) -> Vec<u8> {
+        block.to_vec()
+    }
+
+    fn decompress(&self, data: &[u8], _len: usize) -> Vec<u8> {
+        data.to_vec()
+    }
+}
+
+/// The original text stored in independently compressed blocks of a fixed size,
+/// kept beside an index to extract the text around occurrences, e.g. with [`crate::Match::context`].
+///
+/// Extracting a range decompresses only the blocks overlapping it,
+/// so an index and a store make a searchable archive of the text.
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::SuffixOrderSampler;
+/// use fm_index::text_store::{TextStore, Uncompressed};
+/// use fm_index::{BackwardSearchIndex, FMIndex};
+///
+/// let text = b"The quick brown fox jumps over the lazy dog".to_vec();
+/// let store = TextStore::new(&text, 8, Uncompressed);
+/// let index = FMIndex::new(text, RangeConverter::new(b' ', b'z'), SuffixOrderSampler::new());
+/// let search = index.search_backward("fox");
+/// let m = search.iter_matches().next().unwrap();
+/// assert_eq!(m.context(&store, 6, 6), b"brown fox jumps");
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TextStore<C> {
+    blocks: Vec<Vec<u8>>,
+    block_size: usize,
+    len: u64,
+    codec: C,
+}
+
+impl<C: BlockCodec> TextStore<C> {
+    pub fn new(text: &[u8], block_size: usize, codec: C) -> Self {
+        assert!(block_size > 0, "block_size must be positive");
+        let blocks = text
+            .chunks(block_size)
+            .map(|block| codec.compress(block))
+            .collect();
+        TextStore {
+            blocks,
+            block_size,
+            len: text.len() as u64,
+            codec,
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn block(&self, k: usize) -> Vec<u8> {
+        let len = (self.len as usize - k * self.block_size).min(self.block_size);
+        self.codec.decompress(&self.blocks[k], len)
+    }
+
+    /// Returns the characters of the text in `range`, which is clamped to the text.
+    pub fn extract(&self, range: Range<u64>) -> Vec<u8> {
+        let start = range.start.min(self.len) as usize;
+        let end = range.end.min(self.len) as usize;
+        let mut text = Vec::with_capacity(end.saturating_sub(start));
+        if start >= end {
+            return text;
+        }
+        for k in start / self.block_size..=(end - 1) / self.block_size {
+            let offset = k * self.block_size;
+            let block = self.block(k);
+            let s = start.max(offset) - offset;
+            let e = end.min(offset + block.len()) - offset;
+            text.extend_from_slice(&block[s..e]);
+        }
+        text
+    }
+
+    /// Returns the size of the compressed blocks.
+    pub fn size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self
+                .blocks
+                .iter()
+                .map(|block| block.len() + std::mem::size_of::<Vec<u8>>())
+                .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A run-length codec to check that blocks are decompressed with their lengths.
+    struct RunLength;
+
+    impl BlockCodec for RunLength {
+        fn compress(&self, block: &[u8]) -> Vec<u8> {
+            let mut data: Vec<u8> = vec![];
+            for &c in block {
+                match data.len() {
+                    n if n >= 2 && data[n - 1] == c && data[n - 2] < u8::MAX => data[n - 2] += 1,
+                    _ => data.extend_from_slice(&[1, c]),
+                }
+            }
+            data
+        }
+
+        fn decompress(&self, data: &[u8], len: usize) -> Vec<u8> {
+            let mut block = Vec::with_capacity(len);
+            for run in data.chunks(2) {
+                block.extend(std::iter::repeat_n(run[1], run[0] as usize));
+            }
+            assert_eq!(block.len(), len);
+            block
+        }
+    }
+
+    #[test]
+    fn test_extract() {
+        let text = b"aaaaabbbbbbbbbbcdddddddddeeeeeeeeeeeeeeef".to_vec();
+        for block_size in 1..=text.len() + 1 {
+            let store = TextStore::new(&text, block_size, RunLength);
+            assert_eq!(store.len(), text.len() as u64);
+            for s in 0..=text.len() {
+                for e in s..=text.len() {
+                    assert_eq!(store.extract(s as u64..e as u64), &text[s..e]);
+                }
+            }
+            assert_eq!(store.extract(30..100), &text[30..]);
+            assert!(store.extract(100..200).is_empty());
+        }
+        let store = TextStore::new(&text, 16, RunLength);
+        assert!(store.size() < TextStore::new(&text, 16, Uncompressed).size());
+        assert!(TextStore::new(b"", 4, Uncompressed).is_empty());
+    }
+}
@@ -0,0 +1,182 @@
+use crate::converter::RangeConverter;
+use crate::search::{BackwardSearchIndex, Search};
+use crate::suffix_array::{ArraySampler, PartialArray};
+use crate::FMIndex;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A transformation of strings into sequences of token IDs, e.g. words or byte pairs.
+///
+/// Tokens in the vocabulary have IDs in `1..=vocabulary_size()`; zero is reserved for the terminator.
+pub trait Tokenizer {
+    /// Splits `s` into token IDs. Tokens out of the vocabulary get IDs out of the range,
+    /// so that patterns containing them match nothing.
+    fn tokenize(&self, s: &str) -> Vec<u32>;
+
+    fn vocabulary_size(&self) -> u32;
+}
+
+/// A tokenizer which splits strings at whitespace and numbers the distinct words in lexicographic order.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WordTokenizer {
+    // sorted and deduplicated
+    words: Vec<String>,
+}
+
+impl WordTokenizer {
+    /// Builds the vocabulary of the words of `text`.
+    pub fn train(text: &str) -> Self {
+        let mut words = text
+            .split_whitespace()
+            .map(|w| w.to_string())
+            .collect::<Vec<_>>();
+        words.sort_unstable();
+        words.dedup();
+        WordTokenizer { words }
+    }
+
+    /// Returns the word of a token ID, or `None` if it is out of the vocabulary.
+    pub fn word(&self, id: u32) -> Option<&str> {
+        let i = (id as usize).checked_sub(1)?;
+        self.words.get(i).map(|w| w.as_str())
+    }
+}
+
+impl Tokenizer for WordTokenizer {
+    fn tokenize(&self, s: &str) -> Vec<u32> {
+        s.split_whitespace()
+            .map(
+                |w| match self.words.binary_search_by(|v| v.as_str().cmp(w)) {
+                    Ok(i) => i as u32 + 1,
+                    Err(_) => self.vocabulary_size() + 1,
+                },
+            )
+            .collect()
+    }
+
+    fn vocabulary_size(&self) -> u32 {
+        self.words.len() as u32
+    }
+}
+
+/// An index over the tokens of a text, which stores its tokenizer to tokenize queries in the same way.
+///
+/// Positions returned by `locate` count tokens, not bytes.
+///
+/// ```
+/// use fm_index::suffix_array::SuffixOrderSampler;
+/// use fm_index::tokenizer::{TokenizedIndex, WordTokenizer};
+///
+/// let text = "the cat sat on the mat and the cat ran";
+/// let index = TokenizedIndex::new(text, WordTokenizer::train(text), SuffixOrderSampler::new());
+/// assert_eq!(index.search("the cat").count(), 2);
+/// assert_eq!(index.search("the   cat  sat").locate(), vec![0]);
+/// assert_eq!(index.search("at").count(), 0);
+/// assert_eq!(index.search("the dog").count(), 0);
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TokenizedIndex<K, S> {
+    index: FMIndex<u32, RangeConverter<u32>, S>,
+    tokenizer: K,
+}
+
+impl<K, S> TokenizedIndex<K, S>
+where
+    K: Tokenizer,
+{
+    /// Builds an index over the tokens of `text`, all of which must be in the vocabulary of `tokenizer`.
+    pub fn new<B: ArraySampler<S>>(text: &str, tokenizer: K, sampler: B) -> Self {
+        let max = tokenizer.vocabulary_size().max(1);
+        let mut tokens = tokenizer.tokenize(text);
+        assert!(
+            tokens.iter().all(|&t| 1 <= t && t <= max),
+            "the text contains tokens out of the vocabulary"
+        );
+        tokens.push(0);
+        let index = FMIndex::new(tokens, RangeConverter::new(1, max), sampler);
+        TokenizedIndex { index, tokenizer }
+    }
+
+    /// Searches for the tokens of `query`.
+    pub fn search(&self, query: &str) -> Search<'_, FMIndex<u32, RangeConverter<u32>, S>> {
+        self.index.search_backward(self.tokenizer.tokenize(query))
+    }
+
+    pub fn tokenizer(&self) -> &K {
+        &self.tokenizer
+    }
+
+    /// Returns the index over token IDs, e.g. to search for tokenized patterns directly.
+    pub fn index(&self) -> &FMIndex<u32, RangeConverter<u32>, S> {
+        &self.index
+    }
+}
+
+impl<K, S> TokenizedIndex<K, S>
+where
+    S: PartialArray,
+{
+    /// Returns the size of the index, excluding the heap memory of the tokenizer.
+    pub fn size(&self) -> usize {
+        std::mem::size_of::<K>() + self.index.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::suffix_array::{NullSampler, SuffixOrderSampler};
+
+    // A tokenizer of characters in pairs, standing in for byte-pair encoding.
+    struct Pairs;
+
+    impl Tokenizer for Pairs {
+        fn tokenize(&self, s: &str) -> Vec<u32> {
+            s.as_bytes()
+                .chunks(2)
+                .map(|p| match p {
+                    [a, b] => (*a - b'a') as u32 * 26 + (*b - b'a') as u32 + 1,
+                    _ => self.vocabulary_size() + 1,
+                })
+                .collect()
+        }
+
+        fn vocabulary_size(&self) -> u32 {
+            26 * 26
+        }
+    }
+
+    #[test]
+    fn test_word_tokenizer() {
+        let tokenizer = WordTokenizer::train("b a  c\na b");
+        assert_eq!(tokenizer.vocabulary_size(), 3);
+        assert_eq!(tokenizer.tokenize("a b c d"), vec![1, 2, 3, 4]);
+        assert_eq!(tokenizer.word(2), Some("b"));
+        assert_eq!(tokenizer.word(0), None);
+        assert_eq!(tokenizer.word(4), None);
+    }
+
+    #[test]
+    fn test_tokenized_index() {
+        let text = "abcdabcdefab";
+        let index = TokenizedIndex::new(text, Pairs, SuffixOrderSampler::new());
+        let mut res = index.search("ab").locate();
+        res.sort();
+        assert_eq!(res, vec![0, 2, 5]);
+        assert_eq!(index.search("cdab").count(), 1);
+        // "bc" is not a token of the text since pairs are aligned
+        assert_eq!(index.search("bc").count(), 0);
+        assert_eq!(index.search("abc").count(), 0);
+
+        let index = TokenizedIndex::new("", WordTokenizer::train(""), NullSampler::new());
+        assert_eq!(index.search("a").count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "the text contains tokens out of the vocabulary")]
+    fn test_out_of_vocabulary() {
+        TokenizedIndex::new("a b", WordTokenizer::train("a"), NullSampler::new());
+    }
+}
@@ -1,7 +1,22 @@
-pub fn log2(x: u64) -> u64 {
-    ((std::mem::size_of::<u64>() * 8) as u64) - u64::from(x.leading_zeros()) - 1
+pub const fn log2(x: u64) -> u64 {
+    ((std::mem::size_of::<u64>() * 8) as u64) - x.leading_zeros() as u64 - 1
 }
 
+/// Returns the number of bits to store values in `[0, n]`.
+pub const fn word_size(n: u64) -> usize {
+    (log2(n) + 1) as usize
+}
+
+// Asserts a precondition of a public method in debug builds, and in release builds with `release-asserts`.
+macro_rules! strict_assert {
+    ($($arg:tt)*) => {
+        if cfg!(any(debug_assertions, feature = "release-asserts")) {
+            assert!($($arg)*);
+        }
+    };
+}
+pub(crate) use strict_assert;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -15,4 +30,11 @@ mod tests {
         assert_eq!(log2(7u64), 2);
         assert_eq!(log2(8u64), 3);
     }
+
+    #[test]
+    #[cfg_attr(not(any(debug_assertions, feature = "release-asserts")), ignore)]
+    #[should_panic(expected = "3 is out of range")]
+    fn test_strict_assert() {
+        strict_assert!(3 < 2, "{} is out of range", 3);
+    }
 }
@@ -0,0 +1,212 @@
+//! Checking a built index against its source text.
+//!
+//! [`quickcheck`] searches random patterns drawn from the text and compares the results
+//! with a naive scan of the text.
+//! It is deterministic for a given seed, so it can be run in CI to catch construction bugs
+//! specific to an environment or a text.
+//!
+//! ```
+//! use fm_index::converter::RangeConverter;
+//! use fm_index::suffix_array::SuffixOrderSampler;
+//! use fm_index::verify::{self, Quickcheck};
+//! use fm_index::FMIndex;
+//!
+//! let text = b"mississippi".to_vec();
+//! let index = FMIndex::new(
+//!     text.clone(),
+//!     RangeConverter::new(b'a', b'z'),
+//!     SuffixOrderSampler::new().level(2),
+//! );
+//! assert!(verify::quickcheck(&index, &text).is_ok());
+//! assert!(Quickcheck::new().seed(42).patterns(10).run(&index, &text).is_ok());
+//! ```
+
+use crate::character::Character;
+use crate::search::BackwardSearchIndex;
+use crate::suffix_array::IndexWithSA;
+
+use std::fmt;
+
+/// An error returned when the index disagrees with the text on a pattern.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch<T> {
+    pub pattern: Vec<T>,
+    /// The sorted positions of the pattern in the text.
+    pub expected: Vec<u64>,
+    /// The sorted positions located by the index.
+    pub actual: Vec<u64>,
+}
+
+impl<T: fmt::Debug> fmt::Display for Mismatch<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "pattern {:?} occurs at {:?}, but the index located {:?}",
+            self.pattern, self.expected, self.actual
+        )
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for Mismatch<T> {}
+
+/// A configuration of random self-tests.
+#[derive(Clone, Debug)]
+pub struct Quickcheck {
+    seed: u64,
+    patterns: usize,
+    max_len: usize,
+}
+
+impl Default for Quickcheck {
+    fn default() -> Self {
+        Quickcheck {
+            seed: 0,
+            patterns: 100,
+            max_len: 16,
+        }
+    }
+}
+
+impl Quickcheck {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets the number of patterns searched, which is 100 by default.
+    pub fn patterns(mut self, patterns: usize) -> Self {
+        self.patterns = patterns;
+        self
+    }
+
+    /// Sets the maximum length of patterns, which is 16 by default.
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        assert!(max_len > 0, "max_len must be positive");
+        self.max_len = max_len;
+        self
+    }
+
+    /// Searches random patterns with `index` built from `text`, which excludes the terminator,
+    /// and returns the first pattern on which it disagrees with a naive scan of `text`.
+    ///
+    /// Most patterns are substrings of the text, and some of them have a character replaced
+    /// so that absent patterns are also tested.
+    /// Patterns do not contain zero, so multi-piece texts can be checked as well.
+    pub fn run<T, I>(&self, index: &I, text: &[T]) -> Result<(), Mismatch<T>>
+    where
+        T: Character,
+        I: BackwardSearchIndex<T = T> + IndexWithSA,
+    {
+        if text.is_empty() {
+            return Ok(());
+        }
+        let mut rng = XorShift::new(self.seed);
+        for _ in 0..self.patterns {
+            let p = rng.below(text.len() as u64) as usize;
+            let len = 1 + rng.below(self.max_len.min(text.len() - p) as u64) as usize;
+            let mut pattern = text[p..p + len]
+                .iter()
+                .copied()
+                .take_while(|c| !c.is_zero())
+                .collect::<Vec<_>>();
+            if pattern.is_empty() {
+                continue;
+            }
+            if rng.below(4) == 0 {
+                let k = rng.below(pattern.len() as u64) as usize;
+                let c = text[rng.below(text.len() as u64) as usize];
+                if !c.is_zero() {
+                    pattern[k] = c;
+                }
+            }
+
+            let expected = (0..=text.len() - pattern.len())
+                .filter(|&i| text[i..].starts_with(&pattern))
+                .map(|i| i as u64)
+                .collect::<Vec<_>>();
+            let search = index.search_backward(&pattern);
+            let mut actual = search.locate();
+            actual.sort_unstable();
+            if search.count() != expected.len() as u64 || actual != expected {
+                return Err(Mismatch {
+                    pattern,
+                    expected,
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs [`Quickcheck`] with the default configuration.
+pub fn quickcheck<T, I>(index: &I, text: &[T]) -> Result<(), Mismatch<T>>
+where
+    T: Character,
+    I: BackwardSearchIndex<T = T> + IndexWithSA,
+{
+    Quickcheck::new().run(index, text)
+}
+
+// A small deterministic generator, so that the results do not depend on the version of a dependency.
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        // the state must not be zero
+        XorShift(seed ^ 0x9e37_79b9_7f4a_7c15)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::suffix_array::SuffixOrderSampler;
+    use crate::{FMIndex, FMIndexMultiPieces, RLFMIndex};
+
+    #[test]
+    fn test_quickcheck() {
+        let text = b"The quick brown fox jumps over the lazy dog.".to_vec();
+        let converter = RangeConverter::new(b' ', b'z');
+        let index = FMIndex::new(
+            text.clone(),
+            converter.clone(),
+            SuffixOrderSampler::new().level(1),
+        );
+        assert_eq!(quickcheck(&index, &text), Ok(()));
+        let index = RLFMIndex::new(text.clone(), converter.clone(), SuffixOrderSampler::new());
+        assert_eq!(Quickcheck::new().seed(7).run(&index, &text), Ok(()));
+
+        let pieces = b"apple\0banana\0cherry".to_vec();
+        let index = FMIndexMultiPieces::new(
+            pieces.clone(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new(),
+        );
+        assert_eq!(quickcheck(&index, &pieces), Ok(()));
+
+        // an index of another text
+        let other = FMIndex::new(
+            b"The quick brown fox jumps over the lazy cat.".to_vec(),
+            converter,
+            SuffixOrderSampler::new(),
+        );
+        let err = Quickcheck::new()
+            .patterns(1000)
+            .run(&other, &text)
+            .unwrap_err();
+        assert_ne!(err.expected, err.actual);
+    }
+}
@@ -0,0 +1,275 @@
+use serde::de::{self, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeStruct, Serializer};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::{FMIndex, FMIndexMultiPieces, RLFMIndex};
+
+/// The version of the serialized layout of indexes.
+///
+/// It is incremented whenever a change to an index type alters its serialized form.
+///
+/// - 1: the initial layout.
+/// - 2: the layout of [`crate::FMIndexMultiPieces`] with piece start positions and optional piece structures,
+///   and q-gram filters of indexes, which are absent from indexes of version 1.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// A type serialized in a [`Versioned`], which tells the format versions whose layout of it can still be read.
+///
+/// Implement it with the default for other types to be wrapped in a [`Versioned`].
+pub trait VersionedFormat {
+    /// The oldest format version in which `Self` is serialized in a layout this version of the crate reads.
+    ///
+    /// It is [`FORMAT_VERSION`] by default, i.e. only the current layout is read.
+    const OLDEST_READABLE_VERSION: u32 = FORMAT_VERSION;
+}
+
+// The layout of version 1 lacks only the q-gram filter, which is read as absent.
+impl<T, C, S, B> VersionedFormat for FMIndex<T, C, S, B> {
+    const OLDEST_READABLE_VERSION: u32 = 1;
+}
+
+impl<T, C, S, B> VersionedFormat for RLFMIndex<T, C, S, B> {
+    const OLDEST_READABLE_VERSION: u32 = 1;
+}
+
+// The layout of version 1 has no piece start positions.
+impl<T, C, S> VersionedFormat for FMIndexMultiPieces<T, C, S> {}
+
+/// An index tagged with [`FORMAT_VERSION`] for serialization.
+///
+/// Deserialization reads the version before the index. An index written by an older version
+/// whose layout is still read, as told by [`VersionedFormat::OLDEST_READABLE_VERSION`], is upgraded
+/// by reading it in the current layout. Otherwise it fails with a clear error,
+/// instead of failing on (or silently accepting) a changed layout.
+///
+/// ```
+/// use fm_index::converter::RangeConverter;
+/// use fm_index::suffix_array::NullSampler;
+/// use fm_index::{FMIndex, Versioned};
+///
+/// let index = FMIndex::new(b"mississippi".to_vec(), RangeConverter::new(b'a', b'z'), NullSampler::new());
+/// let versioned = Versioned::new(index);
+/// // Serialize `versioned` with any serde format, and deserialize it as `Versioned<FMIndex<...>>`.
+/// let index = versioned.into_inner();
+/// ```
+pub struct Versioned<I> {
+    index: I,
+}
+
+impl<I> Versioned<I> {
+    pub fn new(index: I) -> Self {
+        Versioned { index }
+    }
+
+    pub fn get(&self) -> &I {
+        &self.index
+    }
+
+    pub fn into_inner(self) -> I {
+        self.index
+    }
+}
+
+const FIELDS: &[&str] = &["version", "index"];
+
+impl<I: Serialize> Serialize for Versioned<I> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Versioned", 2)?;
+        state.serialize_field("version", &FORMAT_VERSION)?;
+        state.serialize_field("index", &self.index)?;
+        state.end()
+    }
+}
+
+impl<'de, I: Deserialize<'de> + VersionedFormat> Deserialize<'de> for Versioned<I> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_struct("Versioned", FIELDS, VersionedVisitor(PhantomData))
+    }
+}
+
+struct VersionedVisitor<I>(PhantomData<I>);
+
+fn check_version<I: VersionedFormat, E: de::Error>(version: u32) -> Result<(), E> {
+    let oldest = I::OLDEST_READABLE_VERSION;
+    if (oldest..=FORMAT_VERSION).contains(&version) {
+        Ok(())
+    } else if oldest == FORMAT_VERSION {
+        Err(E::custom(format_args!(
+            "unsupported index format version {} (expected {})",
+            version, FORMAT_VERSION
+        )))
+    } else {
+        Err(E::custom(format_args!(
+            "unsupported index format version {} (expected {} to {})",
+            version, oldest, FORMAT_VERSION
+        )))
+    }
+}
+
+impl<'de, I: Deserialize<'de> + VersionedFormat> Visitor<'de> for VersionedVisitor<I> {
+    type Value = Versioned<I>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a versioned index")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let version: u32 = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        check_version::<I, _>(version)?;
+        let index = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        Ok(Versioned { index })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        match map.next_key::<String>()? {
+            Some(ref key) if key == "version" => {}
+            _ => return Err(de::Error::missing_field("version")),
+        }
+        check_version::<I, _>(map.next_value()?)?;
+        match map.next_key::<String>()? {
+            Some(ref key) if key == "index" => {}
+            _ => return Err(de::Error::missing_field("index")),
+        }
+        let index = map.next_value()?;
+        Ok(Versioned { index })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::search::BackwardSearchIndex;
+    use crate::suffix_array::{SuffixOrderSampledArray, SuffixOrderSampler};
+    use crate::{FMIndex, FMIndexMultiPieces, RLFMIndex};
+
+    type Index = FMIndex<u8, RangeConverter<u8>, SuffixOrderSampledArray>;
+    type RLIndex = RLFMIndex<u8, RangeConverter<u8>, SuffixOrderSampledArray>;
+    type MultiIndex = FMIndexMultiPieces<u8, RangeConverter<u8>, SuffixOrderSampledArray>;
+
+    // written by `write_fixtures` at each format version rather than by a released crate; see fixtures/README.md
+    const FM_INDEX_V1: &str = include_str!("../fixtures/fm_index_v1.json");
+    const RLFM_INDEX_V1: &str = include_str!("../fixtures/rlfm_index_v1.json");
+    const FM_INDEX_V2: &str = include_str!("../fixtures/fm_index_v2.json");
+    const RLFM_INDEX_V2: &str = include_str!("../fixtures/rlfm_index_v2.json");
+    const MULTI_PIECES_V2: &str = include_str!("../fixtures/multi_pieces_v2.json");
+
+    fn build_fm_index() -> Index {
+        FMIndex::new(
+            b"mississippi".to_vec(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(1),
+        )
+    }
+
+    fn build_rlfm_index() -> RLIndex {
+        RLFMIndex::new(
+            b"mississippi".to_vec(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(1),
+        )
+    }
+
+    fn build_multi_pieces() -> MultiIndex {
+        FMIndexMultiPieces::new(
+            b"mississ\0ppi".to_vec(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(1),
+        )
+        .with_keys(vec![10, 20])
+    }
+
+    fn assert_searchable<I: BackwardSearchIndex<T = u8> + crate::suffix_array::IndexWithSA>(
+        index: &I,
+    ) {
+        let mut res = index.search_backward("iss").locate();
+        res.sort();
+        assert_eq!(res, vec![1, 4]);
+        assert_eq!(index.search_backward("ppi").locate(), vec![8]);
+    }
+
+    #[test]
+    fn test_fixtures_v2() {
+        let index: Versioned<Index> = serde_json::from_str(FM_INDEX_V2).unwrap();
+        assert_searchable(index.get());
+        let index: Versioned<RLIndex> = serde_json::from_str(RLFM_INDEX_V2).unwrap();
+        assert_searchable(index.get());
+        let index: Versioned<MultiIndex> = serde_json::from_str(MULTI_PIECES_V2).unwrap();
+        assert_searchable(index.get());
+        assert_eq!(
+            index.get().key_by_piece_id(crate::PieceId::from(1)),
+            Some(20)
+        );
+    }
+
+    #[test]
+    fn test_upgrade_v1() {
+        let index: Versioned<Index> = serde_json::from_str(FM_INDEX_V1).unwrap();
+        assert_searchable(index.get());
+        assert!(index.get().qgram_filter().is_none());
+        let index: Versioned<RLIndex> = serde_json::from_str(RLFM_INDEX_V1).unwrap();
+        assert_searchable(index.get());
+        assert!(index.get().qgram_filter().is_none());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let json = serde_json::to_string(&Versioned::new(build_fm_index())).unwrap();
+        let index: Versioned<Index> = serde_json::from_str(&json).unwrap();
+        assert_searchable(index.get());
+    }
+
+    #[test]
+    fn test_reject_other_versions() {
+        // the layout of multi-piece indexes changed in version 2
+        let v1 = MULTI_PIECES_V2.replacen("\"version\":2", "\"version\":1", 1);
+        let err = serde_json::from_str::<Versioned<MultiIndex>>(&v1)
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string()
+                .starts_with("unsupported index format version 1 (expected 2)"),
+            "{}",
+            err
+        );
+
+        let v3 = FM_INDEX_V2.replacen("\"version\":2", "\"version\":3", 1);
+        let err = serde_json::from_str::<Versioned<Index>>(&v3).err().unwrap();
+        assert!(
+            err.to_string()
+                .starts_with("unsupported index format version 3 (expected 1 to 2)"),
+            "{}",
+            err
+        );
+    }
+
+    /// Writes fixtures for the current format version.
+    /// Run this with `--ignored` after incrementing `FORMAT_VERSION`,
+    /// and keep the fixtures of older versions.
+    #[test]
+    #[ignore]
+    fn write_fixtures() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+        std::fs::write(
+            dir.join(format!("fm_index_v{}.json", FORMAT_VERSION)),
+            serde_json::to_string(&Versioned::new(build_fm_index())).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join(format!("rlfm_index_v{}.json", FORMAT_VERSION)),
+            serde_json::to_string(&Versioned::new(build_rlfm_index())).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join(format!("multi_pieces_v{}.json", FORMAT_VERSION)),
+            serde_json::to_string(&Versioned::new(build_multi_pieces())).unwrap(),
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+use crate::search::SaInterval;
+
+/// The decision of a visitor of [`crate::BackwardSearchIndex::visit_backward`] on a pattern.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Visit {
+    /// Extends the pattern by the characters preceding its occurrences.
+    Continue,
+    /// Skips the extensions of the pattern.
+    Prune,
+    /// Returns the search for the pattern and skips its extensions.
+    Emit,
+    /// Ends the exploration, returning the searches emitted so far.
+    Stop,
+}
+
+/// A pattern reached by [`crate::BackwardSearchIndex::visit_backward`] after a step of the backward search.
+#[derive(Clone, Copy, Debug)]
+pub struct VisitStep<'a, T> {
+    /// The character prepended to the pattern in this step.
+    pub c: T,
+    /// The length of the pattern.
+    pub depth: usize,
+    /// The rows of the occurrences of the pattern, which is not empty.
+    pub range: SaInterval,
+    /// The characters of the pattern in the order they were searched, i.e. from the last one,
+    /// ending with `c`.
+    pub path: &'a [T],
+}
+
+impl<T: Copy> VisitStep<'_, T> {
+    pub fn count(&self) -> u64 {
+        self.range.len()
+    }
+
+    /// Returns the pattern in the order of the text.
+    pub fn pattern(&self) -> Vec<T> {
+        self.path.iter().rev().copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::RangeConverter;
+    use crate::search::BackwardSearchIndex;
+    use crate::suffix_array::SuffixOrderSampler;
+    use crate::FMIndex;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_visit_backward() {
+        let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+        let text = (0..300)
+            .map(|_| rng.gen_range(b'a', b'e'))
+            .collect::<Vec<_>>();
+        let index = FMIndex::new(
+            text.clone(),
+            RangeConverter::new(b'a', b'z'),
+            SuffixOrderSampler::new().level(2),
+        );
+
+        // occurrences of "abcd" with at most one mismatch
+        let pattern = b"abcd";
+        let searches = index.visit_backward(|step| {
+            let mismatches = step
+                .pattern()
+                .iter()
+                .zip(&pattern[pattern.len() - step.depth..])
+                .filter(|(a, b)| a != b)
+                .count();
+            if mismatches > 1 {
+                Visit::Prune
+            } else if step.depth == pattern.len() {
+                Visit::Emit
+            } else {
+                Visit::Continue
+            }
+        });
+        let mut positions = searches
+            .iter()
+            .flat_map(|search| search.locate())
+            .collect::<Vec<_>>();
+        positions.sort();
+        let expected = (0..=text.len() - pattern.len())
+            .filter(|&i| {
+                text[i..i + pattern.len()]
+                    .iter()
+                    .zip(pattern)
+                    .filter(|(a, b)| a != b)
+                    .count()
+                    <= 1
+            })
+            .map(|i| i as u64)
+            .collect::<Vec<_>>();
+        assert_eq!(positions, expected);
+
+        // the first three characters in ascending order, stopping the exploration
+        let mut visited = vec![];
+        let searches = index.visit_backward(|step| {
+            visited.push((step.c, step.depth, step.count()));
+            if visited.len() == 3 {
+                Visit::Stop
+            } else {
+                Visit::Emit
+            }
+        });
+        let count = |c: u8| text.iter().filter(|&&d| d == c).count() as u64;
+        assert_eq!(
+            visited,
+            vec![
+                (b'a', 1, count(b'a')),
+                (b'b', 1, count(b'b')),
+                (b'c', 1, count(b'c'))
+            ]
+        );
+        assert_eq!(searches.len(), 2);
+    }
+}
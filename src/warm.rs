@@ -0,0 +1,143 @@
+use crate::iter::BackwardIterableIndex;
+use crate::search::{BackwardSearchIndex, Search};
+use crate::suffix_array::{self, IndexWithISA, IndexWithSA};
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// The suffix array entries of every position in a range of the text,
+/// materialized by [`IndexWithISA::warm_range`] to locate occurrences there without LF-mapping.
+///
+/// It takes _O(r)_ words for a range of _r_ positions, and is meant to be kept only while
+/// a portion of a huge index with a high sampling level is queried repeatedly.
+/// Occurrences outside of the range are located through the samples of the index,
+/// or through the range if it is reached first.
+pub struct WarmRegion<'a, I> {
+    index: &'a I,
+    range: Range<u64>,
+    // row -> text position
+    rows: HashMap<u64, u64>,
+}
+
+pub(crate) fn warm_range<I>(index: &I, range: Range<u64>) -> WarmRegion<'_, I>
+where
+    I: BackwardIterableIndex + IndexWithSA + IndexWithISA,
+{
+    let end = range.end.min(index.len());
+    let start = range.start.min(end);
+    let mut rows = HashMap::with_capacity((end - start) as usize);
+    if start < end {
+        let mut p = end - 1;
+        let mut i = index.get_isa(p);
+        loop {
+            rows.insert(i, p);
+            if p == start {
+                break;
+            }
+            i = index.lf_map(i);
+            p -= 1;
+        }
+    }
+    WarmRegion {
+        index,
+        range: start..end,
+        rows,
+    }
+}
+
+impl<'a, I> WarmRegion<'a, I>
+where
+    I: BackwardIterableIndex + IndexWithSA,
+{
+    /// Returns the range of text positions materialized, clamped to the text.
+    pub fn range(&self) -> Range<u64> {
+        self.range.clone()
+    }
+
+    pub fn size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.rows.capacity() * 2 * std::mem::size_of::<u64>()
+    }
+
+    /// Lists the positions of all occurrences of `search` like [`Search::locate`].
+    pub fn locate(&self, search: &Search<'_, I>) -> Vec<u64>
+    where
+        I: BackwardSearchIndex,
+    {
+        search.get_range().rows().map(|i| self.get_sa(i)).collect()
+    }
+}
+
+impl<'a, I> IndexWithSA for WarmRegion<'a, I>
+where
+    I: BackwardIterableIndex + IndexWithSA,
+{
+    fn get_sa(&self, i: u64) -> u64 {
+        suffix_array::resolve_sa(
+            i,
+            self.index.len(),
+            None,
+            |k| {
+                let row = self.rows.get(&k).copied();
+                row.or_else(|| self.index.get_sa_with_budget(k, &mut 0))
+            },
+            |k| self.index.lf_map(k),
+        )
+        .unwrap()
+    }
+
+    fn get_sa_with_budget(&self, i: u64, budget: &mut u64) -> Option<u64> {
+        suffix_array::resolve_sa(
+            i,
+            self.index.len(),
+            Some(budget),
+            |k| {
+                let row = self.rows.get(&k).copied();
+                row.or_else(|| self.index.get_sa_with_budget(k, &mut 0))
+            },
+            |k| self.index.lf_map(k),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::converter::RangeConverter;
+    use crate::search::BackwardSearchIndex;
+    use crate::suffix_array::{IndexWithISA, IndexWithSA, TextOrderSampler};
+    use crate::FMIndex;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_warm_range() {
+        let mut rng: StdRng = SeedableRng::from_seed([0; 32]);
+        let text = (0..200)
+            .map(|_| rng.gen_range(b'a', b'e'))
+            .collect::<Vec<_>>();
+        let index = FMIndex::new(
+            text,
+            RangeConverter::new(b'a', b'z'),
+            TextOrderSampler::new().level(4),
+        );
+        let region = index.warm_range(50..120);
+        assert_eq!(region.range(), 50..120);
+        for i in 0..index.len() {
+            let p = index.get_sa(i);
+            assert_eq!(region.get_sa(i), p);
+            let mut budget = 0;
+            let warm = region.get_sa_with_budget(i, &mut budget);
+            if (50..120).contains(&p) {
+                assert_eq!(warm, Some(p));
+            }
+        }
+        for pattern in &["ab", "dca", "bbc"] {
+            let search = index.search_backward(pattern);
+            assert_eq!(region.locate(&search), search.locate());
+        }
+
+        let region = index.warm_range(150..1000);
+        assert_eq!(region.range(), 150..index.len());
+        assert_eq!(region.get_sa(0), index.len() - 1);
+        assert!(index.warm_range(10..10).range().is_empty());
+    }
+}
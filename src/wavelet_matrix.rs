@@ -1,11 +1,13 @@
 use crate::character::Character;
 
-use fid::{BitVector, FID};
+use crate::succinct::{BitVector, RankSelect};
 use std::fmt;
 
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+#[derive(PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct WaveletMatrix {
     rows: Vec<BitVector>,
     size: u64,
@@ -24,7 +26,7 @@ impl WaveletMatrix {
         let mut ones: Vec<T> = Vec::new();
         let mut partitions: Vec<u64> = Vec::new();
         for r in 0..size {
-            let mut bv = BitVector::new();
+            let mut bv = BitVector::default();
             let mut new_zeros: Vec<T> = Vec::new();
             let mut new_ones: Vec<T> = Vec::new();
             for arr in &[zeros, ones] {
@@ -117,6 +119,71 @@ impl WaveletMatrix {
         e
     }
 
+    /// Returns the `k`-th smallest value (from zero) in `[s, e)`.
+    pub fn quantile(&self, mut s: u64, mut e: u64, mut k: u64) -> u64 {
+        debug_assert!(k < e - s);
+        let mut n = 0u64;
+        for (r, bv) in self.rows.iter().enumerate() {
+            let (s0, e0) = (bv.rank0(s), bv.rank0(e));
+            if k < e0 - s0 {
+                s = s0;
+                e = e0;
+            } else {
+                k -= e0 - s0;
+                let z = self.partitions[r];
+                s = z + bv.rank1(s);
+                e = z + bv.rank1(e);
+                n |= 1 << (self.size - (r as u64) - 1);
+            }
+        }
+        n
+    }
+
+    /// Counts the values less than `x` in `[s, e)`.
+    pub fn count_less(&self, mut s: u64, mut e: u64, x: u64) -> u64 {
+        if self.size < 64 && x >> self.size > 0 {
+            return e - s;
+        }
+        let mut count = 0;
+        for (r, bv) in self.rows.iter().enumerate() {
+            let (s0, e0) = (bv.rank0(s), bv.rank0(e));
+            if (x >> (self.size - (r as u64) - 1)) & 1 > 0 {
+                count += e0 - s0;
+                let z = self.partitions[r];
+                s = z + bv.rank1(s);
+                e = z + bv.rank1(e);
+            } else {
+                s = s0;
+                e = e0;
+            }
+        }
+        count
+    }
+
+    /// Returns the distinct values in `[s, e)` in ascending order, with their frequencies.
+    ///
+    /// It takes _O(d log σ)_ time for _d_ distinct values.
+    pub fn distinct(&self, s: u64, e: u64) -> Vec<(u64, u64)> {
+        let mut values = vec![];
+        self.distinct_rec(0, s, e, 0, &mut values);
+        values
+    }
+
+    fn distinct_rec(&self, r: usize, s: u64, e: u64, n: u64, values: &mut Vec<(u64, u64)>) {
+        if s == e {
+            return;
+        }
+        if r == self.rows.len() {
+            values.push((n, e - s));
+            return;
+        }
+        let bv = &self.rows[r];
+        self.distinct_rec(r + 1, bv.rank0(s), bv.rank0(e), n, values);
+        let z = self.partitions[r];
+        let bit = 1 << (self.size - (r as u64) - 1);
+        self.distinct_rec(r + 1, z + bv.rank1(s), z + bv.rank1(e), n | bit, values);
+    }
+
     pub fn len(&self) -> u64 {
         self.len
     }
@@ -155,13 +222,7 @@ mod tests {
         for i in 0..(1 << size) {
             let mut r = 0;
             for (k, &n) in numbers.iter().enumerate() {
-                assert!(
-                    wm.rank(i as u8, k as u64) == r,
-                    "wm.rank({}, {}) == {}",
-                    i,
-                    k,
-                    r
-                );
+                assert!(wm.rank(i, k as u64) == r, "wm.rank({}, {}) == {}", i, k, r);
                 if n == i {
                     r += 1;
                 }
@@ -205,6 +266,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn range_queries_small() {
+        let numbers = vec![4u8, 7, 6, 5, 3, 2, 1, 0, 1, 4, 1, 7];
+        let wm = WaveletMatrix::new_with_size(numbers.clone(), 3);
+        for s in 0..numbers.len() {
+            for e in s..=numbers.len() {
+                let mut sorted = numbers[s..e].iter().map(|&n| n as u64).collect::<Vec<_>>();
+                sorted.sort();
+                for (k, &n) in sorted.iter().enumerate() {
+                    assert_eq!(wm.quantile(s as u64, e as u64, k as u64), n);
+                }
+                for x in 0..10 {
+                    let less = sorted.iter().filter(|&&n| n < x).count() as u64;
+                    assert_eq!(wm.count_less(s as u64, e as u64, x), less);
+                }
+                let mut distinct: Vec<(u64, u64)> = vec![];
+                for &n in &sorted {
+                    match distinct.last_mut() {
+                        Some((m, f)) if *m == n => *f += 1,
+                        _ => distinct.push((n, 1)),
+                    }
+                }
+                assert_eq!(wm.distinct(s as u64, e as u64), distinct);
+            }
+        }
+    }
+
     #[test]
     fn empty() {
         let empty_vec: Vec<u8> = vec![];